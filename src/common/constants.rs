@@ -29,9 +29,11 @@ pub const OP_LEA    : u32 = 0b0100_0001_1100_0000;
 pub const OP_LINK   : u32 = 0b0100_1110_0101_0000;
 pub const OP_MOVE   : u32 = 0b0000_0000_0000_0000;
 pub const OP_MOVE2  : u32 = 0b0100_0000_0000_0000;
+pub const OP_MOVEC  : u32 = 0b0100_1110_0111_1010; // Only 010+
 pub const OP_MOVEM  : u32 = 0b0100_1000_1000_0000;
 pub const OP_MOVEP  : u32 = 0b0000_0000_0000_1000;
 pub const OP_MOVEQ  : u32 = 0b0111_0000_0000_0000;
+pub const OP_MOVES  : u32 = 0b0000_1110_0000_0000; // Only 010+
 pub const OP_MULS   : u32 = 0b1100_0001_1100_0000;
 pub const OP_MULU   : u32 = 0b1100_0000_1100_0000;
 pub const OP_NBCD   : u32 = 0b0100_1000_0000_0000;
@@ -126,7 +128,7 @@ pub const ROTX_MEM_SHIFT  : u32 = 0xC0 | (ROTX_REG_SHIFT << 6);
 pub const ROTA_MEM_SHIFT  : u32 = 0xC0 | (ROTA_REG_SHIFT << 6);
 
 pub const MOVE_FROM_SR : u32 = 0x0c0;
-// pub const MOVE_FROM_CCR : u32 = 0x2c0; // Only 010+
+pub const MOVE_FROM_CCR: u32 = 0x2c0; // Only 010+
 pub const MOVE_TO_CCR  : u32 = 0x4c0;
 pub const MOVE_TO_SR   : u32 = 0x6c0;
 
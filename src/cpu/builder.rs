@@ -0,0 +1,152 @@
+//! Building a core in a given state.
+//!
+//! A [`CoreBuilder`] creates a core that is ready to run, with the stack
+//! pointers, PC, status register and CPU model chosen up front rather than
+//! read from the reset vectors or set by executing code. This keeps test
+//! setup short, e.g. for a core already in user mode:
+//!
+//! ```rust
+//! use r68k::cpu::builder::CoreBuilder;
+//!
+//! let cpu = CoreBuilder::new()
+//!     .pc(0x1000)
+//!     .ssp(0x8000)
+//!     .usp(0x4000)
+//!     .status_register(0x0000) // user mode, all interrupts unmasked
+//!     .build();
+//! assert_eq!(0x1000, cpu.pc);
+//! assert_eq!(0x4000, cpu.dar[15]);
+//! assert_eq!(0x8000, cpu.ssp());
+//! assert_eq!(0x0000, cpu.status_register());
+//! ```
+
+use super::{ConfiguredCore, Cpu, CpuModel};
+use crate::interrupts::{AutoInterruptController, InterruptController};
+use crate::ram::{AddressBus, PagedMem};
+
+/// Options for a new core, applied by [`build`](Self::build) or
+/// [`build_with`](Self::build_with).
+///
+/// The defaults give the state after [`reset_to(0, 0)`](ConfiguredCore::reset_to):
+/// supervisor mode with interrupts masked (SR `0x2700`), both stack
+/// pointers and PC at 0, emulating the 68000.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoreBuilder {
+    pc: u32,
+    ssp: u32,
+    usp: u32,
+    sr: u16,
+    model: CpuModel,
+}
+
+impl Default for CoreBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CoreBuilder {
+    /// Returns a builder with the default options.
+    pub fn new() -> CoreBuilder {
+        CoreBuilder { pc: 0, ssp: 0, usp: 0, sr: 0x2700, model: CpuModel::M68000 }
+    }
+
+    /// Sets the program counter.
+    pub fn pc(mut self, pc: u32) -> Self {
+        self.pc = pc;
+        self
+    }
+
+    /// Sets the supervisor stack pointer.
+    pub fn ssp(mut self, ssp: u32) -> Self {
+        self.ssp = ssp;
+        self
+    }
+
+    /// Sets the user stack pointer.
+    pub fn usp(mut self, usp: u32) -> Self {
+        self.usp = usp;
+        self
+    }
+
+    /// Sets the status register, including the supervisor bit and the
+    /// interrupt mask. A7 is the stack pointer of the mode it selects.
+    pub fn status_register(mut self, sr: u16) -> Self {
+        self.sr = sr;
+        self
+    }
+
+    /// Clears the supervisor bit of the status register, keeping the rest,
+    /// so that the core starts in user mode.
+    pub fn user_mode(mut self) -> Self {
+        self.sr &= !0x2000;
+        self
+    }
+
+    /// Sets the CPU model, see [`ConfiguredCore::set_model`].
+    pub fn model(mut self, model: CpuModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Builds a core with paged memory and autovectored interrupts, like
+    /// [`Cpu::new`].
+    pub fn build(self) -> Cpu {
+        self.build_with(AutoInterruptController::new(), PagedMem::new(0))
+    }
+
+    /// Builds a core with the given interrupt controller and memory.
+    pub fn build_with<T: InterruptController, A: AddressBus>(self, int_ctrl: T, memory: A) -> ConfiguredCore<T, A> {
+        let mut core = ConfiguredCore::new_with(self.pc, int_ctrl, memory);
+        if self.model != CpuModel::M68000 {
+            core.set_model(self.model);
+        }
+        core.reset_to(self.ssp, self.pc);
+        core.inactive_usp = self.usp;
+        core.sr_to_flags(self.sr);
+        core
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoreBuilder;
+    use crate::cpu::{Cycles, CpuModel};
+    use crate::ram::{AddressBus, SUPERVISOR_DATA, SUPERVISOR_PROGRAM};
+
+    #[test]
+    fn user_mode_core_takes_interrupts_on_the_supervisor_stack() {
+        let mut cpu = CoreBuilder::new()
+            .pc(0x40)
+            .ssp(0x400)
+            .usp(0x300)
+            .status_register(0x2000)
+            .user_mode()
+            .build();
+        assert_eq!(0x0000, cpu.status_register());
+        assert_eq!(0x300, cpu.dar[15]);
+        // 0x4e71 is NOP
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x40, 0x4e71);
+        cpu.mem.write_long(SUPERVISOR_DATA, 26 * 4, 0x180);
+        cpu.int_ctrl.request_interrupt(2);
+
+        assert_eq!(Cycles(44), cpu.execute1());
+        assert_eq!(0x180, cpu.pc);
+        assert_eq!(0x2200, cpu.status_register());
+        assert_eq!(0x400 - 6, cpu.dar[15]);
+        assert_eq!(0x0000, cpu.mem.read_word(SUPERVISOR_DATA, 0x400 - 6));
+        assert_eq!(0x300, cpu.usp());
+    }
+
+    #[test]
+    fn defaults_match_reset_to_and_model_is_applied() {
+        let cpu = CoreBuilder::default().build();
+        assert_eq!(0x2700, cpu.status_register());
+        assert_eq!(CpuModel::M68000, cpu.model());
+
+        let cpu = CoreBuilder::new().model(CpuModel::M68010).build();
+        assert_eq!(CpuModel::M68010, cpu.model());
+        // MOVE from CCR is legal on the 68010 only
+        assert!(crate::cpu::Core::is_legal_opcode(&cpu, 0x42c0));
+    }
+}
@@ -0,0 +1,439 @@
+//! Instruction disassembly.
+//!
+//! [`disassemble`] turns the instruction at an address into Motorola
+//! syntax text, e.g. `MOVE.W (A0)+, D1`. The instruction is identified by
+//! the same table the interpreter dispatches through, so the text always
+//! names the handler that executes it; operands are decoded with
+//! [`decode_ea`]. Branch targets are shown as absolute addresses, and
+//! opcodes that are not legal as `DC.W $xxxx`.
+//!
+//! [`disassemble_with`] writes the same text in another [`DisasmStyle`],
+//! for tools that expect e.g. `0x` numbers or lower case registers.
+
+use crate::ram::{AddressBus, AddressSpace};
+use super::ops;
+use super::operand::{decode_ea, Operand};
+
+const CONDITIONS: [&str; 16] = ["t", "f", "hi", "ls", "cc", "cs", "ne", "eq", "vc", "vs", "pl", "mi", "ge", "lt", "gt", "le"];
+
+/// How [`disassemble_with`] writes hexadecimal numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberStyle {
+    /// `$1234`, the Motorola convention
+    Motorola,
+    /// `0x1234`
+    C,
+    /// `4660`, in decimal
+    Raw,
+}
+
+/// Where [`disassemble_with`] puts the size of an operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeSuffix {
+    /// `MOVE.W`
+    Dotted,
+    /// `MOVEW`
+    Attached,
+    /// `MOVE`, leaving the size to the operands
+    Omitted,
+}
+
+/// Output conventions of [`disassemble_with`].
+///
+/// The default is the Motorola syntax of [`disassemble`]: `$` numbers,
+/// dotted size suffixes and upper case.
+///
+/// # Example
+///
+/// ```rust
+/// use r68k::cpu::disassembler::{disassemble_with, DisasmStyle, NumberStyle, SizeSuffix};
+/// use r68k::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM};
+///
+/// let mut mem = PagedMem::new(0);
+/// // MOVE.L #$12345678, -(A7)
+/// for (offset, word) in [0x2f3c, 0x1234, 0x5678].iter().enumerate() {
+///     mem.write_word(SUPERVISOR_PROGRAM, 0x1000 + 2 * offset as u32, *word);
+/// }
+/// let style = DisasmStyle { numbers: NumberStyle::C, size_suffix: SizeSuffix::Attached, lowercase: true };
+/// assert_eq!(("movel #0x12345678, -(a7)".to_string(), 6), disassemble_with(&mem, SUPERVISOR_PROGRAM, 0x1000, style));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DisasmStyle {
+    /// How hexadecimal numbers are written.
+    pub numbers: NumberStyle,
+    /// Where the operation size goes.
+    pub size_suffix: SizeSuffix,
+    /// True for lower case mnemonics and registers, e.g. `move.w d0, d1`.
+    pub lowercase: bool,
+}
+
+impl Default for DisasmStyle {
+    fn default() -> DisasmStyle {
+        DisasmStyle { numbers: NumberStyle::Motorola, size_suffix: SizeSuffix::Dotted, lowercase: false }
+    }
+}
+
+impl DisasmStyle {
+    /// Rewrites Motorola syntax `text` in this style.
+    fn apply(&self, text: String) -> String {
+        let text = if self.numbers == NumberStyle::Motorola {
+            text
+        } else {
+            let mut styled = String::with_capacity(text.len() + 8);
+            let mut rest = text.as_str();
+            while let Some(dollar) = rest.find('$') {
+                styled.push_str(&rest[..dollar]);
+                let digits = &rest[dollar + 1..];
+                let length = digits.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(digits.len());
+                let (digits, after) = digits.split_at(length);
+                match self.numbers {
+                    NumberStyle::C => styled.push_str(&format!("0x{digits}")),
+                    _ => styled.push_str(&u32::from_str_radix(digits, 16).unwrap_or(0).to_string()),
+                }
+                rest = after;
+            }
+            styled.push_str(rest);
+            styled
+        };
+        if self.lowercase { text.to_lowercase() } else { text }
+    }
+
+    fn suffix<'a>(&self, suffix: &'a str) -> &'a str {
+        match self.size_suffix {
+            SizeSuffix::Dotted => suffix,
+            SizeSuffix::Attached => suffix.trim_start_matches('.'),
+            SizeSuffix::Omitted => "",
+        }
+    }
+}
+
+/// Disassembles the instruction at `pc` in `address_space`.
+///
+/// Returns the text and the length of the instruction in bytes,
+/// including extension words.
+///
+/// # Example
+///
+/// ```rust
+/// use r68k::cpu::disassembler::disassemble;
+/// use r68k::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM};
+///
+/// let mut mem = PagedMem::new(0);
+/// // ADDI.L #$10000, (8,A0)
+/// for (offset, word) in [0x06a8, 0x0001, 0x0000, 0x0008].iter().enumerate() {
+///     mem.write_word(SUPERVISOR_PROGRAM, 0x1000 + 2 * offset as u32, *word);
+/// }
+/// assert_eq!(("ADDI.L #$10000, (8,A0)".to_string(), 8), disassemble(&mem, SUPERVISOR_PROGRAM, 0x1000));
+/// ```
+pub fn disassemble<A: AddressBus>(mem: &A, address_space: AddressSpace, pc: u32) -> (String, u32) {
+    disassemble_with(mem, address_space, pc, DisasmStyle::default())
+}
+
+/// Disassembles the instruction at `pc` in `address_space`, in `style`.
+///
+/// Like [`disassemble`], which is this with the default style.
+pub fn disassemble_with<A: AddressBus>(mem: &A, address_space: AddressSpace, pc: u32, style: DisasmStyle) -> (String, u32) {
+    let opcode = mem.read_word(address_space, pc) as u16;
+    disassemble_opcode(mem, address_space, pc, opcode, style)
+}
+
+/// Disassembles `opcode` as if it were at `pc`, reading only its extension
+/// words from memory.
+fn disassemble_opcode<A: AddressBus>(mem: &A, address_space: AddressSpace, pc: u32, opcode: u16, style: DisasmStyle) -> (String, u32) {
+    let mut decoder = Decoder { mem, address_space, pc, next: pc.wrapping_add(2), opcode, style };
+    let text = decoder.instruction(ops::handler_name(opcode));
+    (style.apply(text), decoder.next.wrapping_sub(pc))
+}
+
+/// Disassembles `opcode` as if it were at `pc`, taking its extension words
+/// from `fetched`, the (address, word) pairs the interpreter read, rather
+/// than from memory. Words the interpreter skipped, like the displacement
+/// of a branch not taken, are read from memory.
+pub(super) fn disassemble_fetched<A: AddressBus>(mem: &A, address_space: AddressSpace, pc: u32, opcode: u16, fetched: &[(u32, u16)], style: DisasmStyle) -> String {
+    disassemble_opcode(&Fetched { mem, fetched }, address_space, pc, opcode, style).0
+}
+
+/// Memory as seen by the instruction being executed.
+struct Fetched<'a, A: AddressBus> {
+    mem: &'a A,
+    fetched: &'a [(u32, u16)],
+}
+
+impl<A: AddressBus> AddressBus for Fetched<'_, A> {
+    fn copy_from(&mut self, _other: &Self) {}
+
+    fn read_byte(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.mem.read_byte(address_space, address)
+    }
+
+    fn read_word(&self, address_space: AddressSpace, address: u32) -> u32 {
+        match self.fetched.iter().find(|(fetched, _)| *fetched == address) {
+            Some((_, word)) => u32::from(*word),
+            None => self.mem.read_word(address_space, address),
+        }
+    }
+
+    fn read_long(&self, address_space: AddressSpace, address: u32) -> u32 {
+        (self.read_word(address_space, address) << 16) | self.read_word(address_space, address.wrapping_add(2))
+    }
+
+    fn write_byte(&mut self, _address_space: AddressSpace, _address: u32, _value: u32) {}
+
+    fn write_word(&mut self, _address_space: AddressSpace, _address: u32, _value: u32) {}
+
+    fn write_long(&mut self, _address_space: AddressSpace, _address: u32, _value: u32) {}
+}
+
+struct Decoder<'a, A: AddressBus> {
+    mem: &'a A,
+    address_space: AddressSpace,
+    pc: u32,
+    /// Address of the next extension word
+    next: u32,
+    opcode: u16,
+    style: DisasmStyle,
+}
+
+impl<A: AddressBus> Decoder<'_, A> {
+    fn word(&mut self) -> u16 {
+        let word = self.mem.read_word(self.address_space, self.next) as u16;
+        self.next = self.next.wrapping_add(2);
+        word
+    }
+
+    fn ea(&mut self, mode: u16, reg: u16, size: u8) -> Operand {
+        let (operand, length) = decode_ea(self.mem, self.address_space, self.next, mode as u8, reg as u8, size);
+        self.next = self.next.wrapping_add(length);
+        operand
+    }
+
+    /// The effective address in the low six bits of the opcode
+    fn source(&mut self, size: u8) -> Operand {
+        self.ea((self.opcode >> 3) & 7, self.opcode & 7, size)
+    }
+
+    fn immediate(&mut self, size: u8) -> Operand {
+        self.ea(7, 4, size)
+    }
+
+    /// A branch target relative to the address after the opcode word
+    fn target(&self, displacement: i32) -> String {
+        format!("${:x}", self.pc.wrapping_add(2).wrapping_add(displacement as u32))
+    }
+
+    fn instruction(&mut self, name: &str) -> String {
+        let mut parts = name.split('_');
+        let mnemonic = parts.next().unwrap_or("");
+        let size_part = parts.next().unwrap_or("");
+        let form = parts.next().unwrap_or("");
+        let size = match size_part { "8" => 1, "32" => 4, _ => 2 };
+        let suffix = match size { 1 => ".B", 4 => ".L", _ => ".W" };
+        let op = self.opcode;
+        let (dx, ry) = ((op >> 9) & 7, op & 7);
+        let condition = |prefix: &str| mnemonic.strip_prefix(prefix).is_some_and(|cc| CONDITIONS.contains(&cc));
+
+        let (suffix, operands) = match mnemonic {
+            "add" | "sub" | "and" | "or" if form == "er" => (suffix, format!("{}, D{}", self.source(size), dx)),
+            "add" | "sub" | "and" | "or" | "eor" => (suffix, format!("D{}, {}", dx, self.source(size))),
+            "cmp" => (suffix, format!("{}, D{}", self.source(size), dx)),
+            "adda" | "suba" | "cmpa" => (suffix, format!("{}, A{}", self.source(size), dx)),
+            "addi" | "subi" | "andi" | "ori" | "eori" | "cmpi" => {
+                let data = self.immediate(size);
+                let destination = match form {
+                    "toc" => "CCR".to_string(),
+                    "tos" => "SR".to_string(),
+                    _ => self.source(size).to_string(),
+                };
+                (suffix, format!("{data}, {destination}"))
+            }
+            "addq" | "subq" => (suffix, format!("#{}, {}", if dx == 0 { 8 } else { dx }, self.source(size))),
+            "addx" | "subx" | "abcd" | "sbcd" => {
+                let suffix = if mnemonic.ends_with("bcd") { "" } else { suffix };
+                if form == "rr" {
+                    (suffix, format!("D{ry}, D{dx}"))
+                } else {
+                    (suffix, format!("-(A{ry}), -(A{dx})"))
+                }
+            }
+            "cmpm" => (suffix, format!("(A{ry})+, (A{dx})+")),
+            "asl" | "asr" | "lsl" | "lsr" | "rol" | "ror" | "roxl" | "roxr" => match form {
+                "r" => (suffix, format!("D{dx}, D{ry}")),
+                "s" => (suffix, format!("#{}, D{}", if dx == 0 { 8 } else { dx }, ry)),
+                _ => (suffix, self.source(2).to_string()),
+            },
+            "btst" | "bchg" | "bclr" | "bset" => {
+                let bit = if form == "s" { format!("#{}", self.word() & 0xff) } else { format!("D{dx}") };
+                ("", format!("{}, {}", bit, self.source(1)))
+            }
+            _ if mnemonic == "bra" || mnemonic == "bsr" || (condition("b") && mnemonic != "bt" && mnemonic != "bf") => {
+                if size == 1 {
+                    (".S", self.target(i32::from(op as u8 as i8)))
+                } else {
+                    let displacement = i32::from(self.word() as i16);
+                    (".W", self.target(displacement))
+                }
+            }
+            _ if condition("db") => {
+                let displacement = i32::from(self.word() as i16);
+                ("", format!("D{}, {}", ry, self.target(displacement)))
+            }
+            _ if condition("s") => ("", self.source(1).to_string()),
+            "clr" | "neg" | "negx" | "not" | "tst" => (suffix, self.source(size).to_string()),
+            "nbcd" | "tas" | "jmp" | "jsr" | "pea" => ("", self.source(size).to_string()),
+            "lea" => ("", format!("{}, A{}", self.source(4), dx)),
+            "chk" | "divs" | "divu" | "muls" | "mulu" => (suffix, format!("{}, D{}", self.source(2), dx)),
+            "move" => match form {
+                "frs" => ("", format!("SR, {}", self.source(2))),
+                "toc" => ("", format!("{}, CCR", self.source(2))),
+                "tos" => ("", format!("{}, SR", self.source(2))),
+                "fru" => ("", format!("USP, A{ry}")),
+                "tou" => ("", format!("A{ry}, USP")),
+                _ => {
+                    let source = self.source(size);
+                    let destination = self.ea((op >> 6) & 7, dx, size);
+                    (suffix, format!("{source}, {destination}"))
+                }
+            },
+            "movea" => (suffix, format!("{}, A{}", self.source(size), dx)),
+            "moveq" => ("", format!("#{}, D{}", op as u8 as i8, dx)),
+            "movem" => {
+                let mask = self.word();
+                let list = register_list(if (op >> 3) & 7 == 4 { mask.reverse_bits() } else { mask });
+                if form == "re" {
+                    (suffix, format!("{}, {}", list, self.source(size)))
+                } else {
+                    (suffix, format!("{}, {}", self.source(size), list))
+                }
+            }
+            "movep" => {
+                let memory = Operand::Disp16 { reg: ry as u8, displacement: self.word() as i16 };
+                if form == "er" {
+                    (suffix, format!("{memory}, D{dx}"))
+                } else {
+                    (suffix, format!("D{dx}, {memory}"))
+                }
+            }
+            "exg" => match form {
+                "dd" => ("", format!("D{dx}, D{ry}")),
+                "aa" => ("", format!("A{dx}, A{ry}")),
+                _ => ("", format!("D{dx}, A{ry}")),
+            },
+            "ext" => (if size_part == "bw" { ".W" } else { ".L" }, format!("D{ry}")),
+            "swap" => ("", format!("D{ry}")),
+            "unlk" => ("", format!("A{ry}")),
+            "link" => ("", format!("A{}, #{}", ry, self.word() as i16)),
+            "trap" => ("", format!("#{}", op & 15)),
+            "stop" => ("", format!("#${:04x}", self.word())),
+            "nop" | "reset" | "rte" | "rtr" | "rts" | "trapv" => ("", String::new()),
+            _ => return format!("DC.W ${op:04x}"),
+        };
+        let mnemonic = format!("{}{}", mnemonic.to_uppercase(), self.style.suffix(suffix));
+        if operands.is_empty() {
+            mnemonic
+        } else {
+            format!("{mnemonic} {operands}")
+        }
+    }
+}
+
+/// Formats a MOVEM register mask, bit 0 being D0, e.g. `D0-D2/A6`.
+fn register_list(mask: u16) -> String {
+    let mut groups = Vec::new();
+    let mut reg = 0;
+    while reg < 16 {
+        if mask & (1 << reg) == 0 {
+            reg += 1;
+            continue;
+        }
+        // runs don't extend from D7 into A0
+        let mut last = reg;
+        while last % 8 != 7 && mask & (1 << (last + 1)) != 0 {
+            last += 1;
+        }
+        let name = |r: u16| format!("{}{}", if r < 8 { 'D' } else { 'A' }, r & 7);
+        groups.push(if last == reg { name(reg) } else { format!("{}-{}", name(reg), name(last)) });
+        reg = last + 1;
+    }
+    if groups.is_empty() {
+        "#0".to_string()
+    } else {
+        groups.join("/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disassemble, disassemble_with, register_list, DisasmStyle, NumberStyle, SizeSuffix};
+    use crate::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM};
+
+    fn memory(words: &[u16]) -> PagedMem {
+        let mut mem = PagedMem::new(0);
+        for (offset, word) in words.iter().enumerate() {
+            mem.write_word(SUPERVISOR_PROGRAM, 0x1000 + 2 * offset as u32, u32::from(*word));
+        }
+        mem
+    }
+
+    fn text(words: &[u16]) -> (String, u32) {
+        disassemble(&memory(words), SUPERVISOR_PROGRAM, 0x1000)
+    }
+
+    fn styled(words: &[u16], numbers: NumberStyle, size_suffix: SizeSuffix, lowercase: bool) -> String {
+        let style = DisasmStyle { numbers, size_suffix, lowercase };
+        disassemble_with(&memory(words), SUPERVISOR_PROGRAM, 0x1000, style).0
+    }
+
+    #[test]
+    fn operands_are_in_motorola_order() {
+        assert_eq!(("MOVE.W (A0)+, D1".to_string(), 2), text(&[0x3218]));
+        assert_eq!(("MOVE.L #$12345678, -(A7)".to_string(), 6), text(&[0x2f3c, 0x1234, 0x5678]));
+        assert_eq!(("ADD.B D1, D0".to_string(), 2), text(&[0xd001]));
+        assert_eq!(("SUB.L D0, (A1)".to_string(), 2), text(&[0x9191]));
+        assert_eq!(("ANDI.B #$1f, CCR".to_string(), 4), text(&[0x023c, 0x001f]));
+        assert_eq!(("LSL.W #1, D0".to_string(), 2), text(&[0xe348]));
+        assert_eq!(("BTST #3, (4,A0)".to_string(), 6), text(&[0x0828, 0x0003, 0x0004]));
+        assert_eq!(("MOVEQ #-1, D7".to_string(), 2), text(&[0x7eff]));
+        assert_eq!(("ABCD -(A1), -(A0)".to_string(), 2), text(&[0xc109]));
+        assert_eq!(("RTS".to_string(), 2), text(&[0x4e75]));
+        assert_eq!(("DC.W $4afc".to_string(), 2), text(&[0x4afc]));
+    }
+
+    #[test]
+    fn branch_targets_are_absolute() {
+        assert_eq!(("BEQ.S $1006".to_string(), 2), text(&[0x6704]));
+        assert_eq!(("BRA.W $ff2".to_string(), 4), text(&[0x6000, 0xfff0]));
+        assert_eq!(("DBF D0, $1000".to_string(), 4), text(&[0x51c8, 0xfffe]));
+        assert_eq!(("JSR (4660,PC)".to_string(), 4), text(&[0x4eba, 0x1234]));
+    }
+
+    #[test]
+    fn movem_lists_registers_in_ascending_order() {
+        assert_eq!("D0-D2/A6", register_list(0x4007));
+        assert_eq!("D7/A0", register_list(0x0180));
+        assert_eq!(("MOVEM.L D0-D1/A6, -(A7)".to_string(), 4), text(&[0x48e7, 0xc002]));
+        assert_eq!(("MOVEM.W (A7)+, D0/A0-A1".to_string(), 4), text(&[0x4c9f, 0x0301]));
+    }
+    #[test]
+    fn default_style_is_motorola_syntax() {
+        let mem = memory(&[0x2f3c, 0x1234, 0x5678]);
+        assert_eq!(text(&[0x2f3c, 0x1234, 0x5678]), disassemble_with(&mem, SUPERVISOR_PROGRAM, 0x1000, DisasmStyle::default()));
+    }
+
+    #[test]
+    fn styles_change_numbers_case_and_suffixes() {
+        use super::NumberStyle::{Motorola, C, Raw};
+        use super::SizeSuffix::{Attached, Dotted, Omitted};
+        // MOVE.L #$12345678, -(A7)
+        let words = [0x2f3c, 0x1234, 0x5678];
+        assert_eq!("MOVE.L #0x12345678, -(A7)", styled(&words, C, Dotted, false));
+        assert_eq!("MOVE.L #305419896, -(A7)", styled(&words, Raw, Dotted, false));
+        assert_eq!("movel #$12345678, -(a7)", styled(&words, Motorola, Attached, true));
+        assert_eq!("MOVE #$12345678, -(A7)", styled(&words, Motorola, Omitted, false));
+        // BEQ.S $1006 and absolute short addresses
+        assert_eq!("beqs 0x1006", styled(&[0x6704], C, Attached, true));
+        assert_eq!("BEQ 4102", styled(&[0x6704], Raw, Omitted, false));
+        assert_eq!("MOVE.W D0, 0x0042.W", styled(&[0x31c0, 0x0042], C, Dotted, false));
+        // DC.W is a directive, its size stays
+        assert_eq!("dc.w 19196", styled(&[0x4afc], Raw, Omitted, true));
+    }
+}
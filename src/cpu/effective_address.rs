@@ -2,88 +2,121 @@ use super::{Core, Result};
 use std::num::Wrapping;
 
 pub fn absolute_word<T: Core>(core: &mut T) -> Result<u32> {
-    core.read_imm_i16().map(|res| res as u32)
+    let ea = (core.read_imm_i16())? as u32;
+    Ok(computed(core, 7, 0, ea))
 }
 pub fn absolute_long<T: Core>(core: &mut T) -> Result<u32> {
-    core.read_imm_u32()
+    let ea = (core.read_imm_u32())?;
+    Ok(computed(core, 7, 1, ea))
 }
 pub fn predecrement_ay_8<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
-    Ok(predecrement_8(core, reg_ndx))
+    let ea = predecrement_8(core, reg_ndx);
+    Ok(computed(core, 4, reg_ndx as u8 - 8, ea))
 }
 pub fn postincrement_ay_8<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
-    Ok(postincrement_8(core, reg_ndx))
+    let ea = postincrement_8(core, reg_ndx);
+    Ok(computed(core, 3, reg_ndx as u8 - 8, ea))
 }
 pub fn predecrement_ay_16<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
-    Ok(predecrement_16(core, reg_ndx))
+    let ea = predecrement_16(core, reg_ndx);
+    Ok(computed(core, 4, reg_ndx as u8 - 8, ea))
 }
 pub fn postincrement_ay_16<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
-    Ok(postincrement_16(core, reg_ndx))
+    let ea = postincrement_16(core, reg_ndx);
+    Ok(computed(core, 3, reg_ndx as u8 - 8, ea))
 }
 pub fn predecrement_ay_32<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
-    Ok(predecrement_32(core, reg_ndx))
+    let ea = predecrement_32(core, reg_ndx);
+    Ok(computed(core, 4, reg_ndx as u8 - 8, ea))
 }
 pub fn postincrement_ay_32<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
-    Ok(postincrement_32(core, reg_ndx))
+    let ea = postincrement_32(core, reg_ndx);
+    Ok(computed(core, 3, reg_ndx as u8 - 8, ea))
 }
 pub fn address_indirect_ay<T: Core>(core: &mut T) -> Result<u32> {
-    Ok(ay!(core))
+    let ea = ay!(core);
+    let reg = ir_ay!(core) as u8 - 8;
+    Ok(computed(core, 2, reg, ea))
 }
 pub fn address_indirect_ax<T: Core>(core: &mut T) -> Result<u32> {
-    Ok(ax!(core))
+    let ea = ax!(core);
+    let reg = ir_ax!(core) as u8 - 8;
+    Ok(computed(core, 2, reg, ea))
 }
 pub fn displacement_ay<T: Core>(core: &mut T) -> Result<u32> {
     let reg_val = ay!(core);
-    displacement(core, reg_val)
+    let ea = (displacement(core, reg_val))?;
+    let reg = ir_ay!(core) as u8 - 8;
+    Ok(computed(core, 5, reg, ea))
 }
 pub fn displacement_ax<T: Core>(core: &mut T) -> Result<u32> {
     let reg_val = ax!(core);
-    displacement(core, reg_val)
+    let ea = (displacement(core, reg_val))?;
+    let reg = ir_ax!(core) as u8 - 8;
+    Ok(computed(core, 5, reg, ea))
 }
 pub fn displacement_pc<T: Core>(core: &mut T) -> Result<u32> {
     let old_pc = pc!(core);
-    displacement(core, old_pc)
+    let ea = (displacement(core, old_pc))?;
+    Ok(computed(core, 7, 2, ea))
 }
 pub fn index_ay<T: Core>(core: &mut T) -> Result<u32> {
     let reg_val = ay!(core);
-    index(core, reg_val)
+    let ea = (index(core, reg_val))?;
+    let reg = ir_ay!(core) as u8 - 8;
+    Ok(computed(core, 6, reg, ea))
 }
 pub fn index_ax<T: Core>(core: &mut T) -> Result<u32> {
     let reg_val = ax!(core);
-    index(core, reg_val)
+    let ea = (index(core, reg_val))?;
+    let reg = ir_ax!(core) as u8 - 8;
+    Ok(computed(core, 6, reg, ea))
 }
 pub fn index_pc<T: Core>(core: &mut T) -> Result<u32> {
     let pc = pc!(core);
-    index(core, pc)
+    let ea = (index(core, pc))?;
+    Ok(computed(core, 7, 3, ea))
+}
+// Reports a computed effective address, given as the 68000 mode and register fields
+fn computed<T: Core>(core: &mut T, mode: u8, reg: u8, ea: u32) -> u32 {
+    core.effective_address_computed(mode, reg, ea);
+    ea
 }
 pub fn predecrement_ax_8<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
-    Ok(predecrement_8(core, reg_ndx))
+    let ea = predecrement_8(core, reg_ndx);
+    Ok(computed(core, 4, reg_ndx as u8 - 8, ea))
 }
 pub fn predecrement_ax_16<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
-    Ok(predecrement_16(core, reg_ndx))
+    let ea = predecrement_16(core, reg_ndx);
+    Ok(computed(core, 4, reg_ndx as u8 - 8, ea))
 }
 pub fn predecrement_ax_32<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
-    Ok(predecrement_32(core, reg_ndx))
+    let ea = predecrement_32(core, reg_ndx);
+    Ok(computed(core, 4, reg_ndx as u8 - 8, ea))
 }
 pub fn postincrement_ax_8<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
-    Ok(postincrement_8(core, reg_ndx))
+    let ea = postincrement_8(core, reg_ndx);
+    Ok(computed(core, 3, reg_ndx as u8 - 8, ea))
 }
 pub fn postincrement_ax_16<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
-    Ok(postincrement_16(core, reg_ndx))
+    let ea = postincrement_16(core, reg_ndx);
+    Ok(computed(core, 3, reg_ndx as u8 - 8, ea))
 }
 pub fn postincrement_ax_32<T: Core>(core: &mut T) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
-    Ok(postincrement_32(core, reg_ndx))
+    let ea = postincrement_32(core, reg_ndx);
+    Ok(computed(core, 3, reg_ndx as u8 - 8, ea))
 }
 
 fn predecrement_8<T: Core>(core: &mut T, reg_ndx: usize) -> u32 {
@@ -0,0 +1,518 @@
+//! Static register usage decoding.
+//!
+//! [`register_effects`] decodes the instruction at a given address and
+//! reports which registers and condition code flags it reads and writes,
+//! without executing it. This is enough for liveness and dependency
+//! analysis over 68000 code, e.g. in an optimizer or a recompiler, without
+//! a full semantic model of every instruction.
+
+use crate::ram::{AddressBus, AddressSpace};
+use super::ops;
+
+/// Extend flag, in CCR bit layout
+pub const CCR_X: u8 = 0x10;
+/// Negative flag, in CCR bit layout
+pub const CCR_N: u8 = 0x08;
+/// Zero flag, in CCR bit layout
+pub const CCR_Z: u8 = 0x04;
+/// Overflow flag, in CCR bit layout
+pub const CCR_V: u8 = 0x02;
+/// Carry flag, in CCR bit layout
+pub const CCR_C: u8 = 0x01;
+
+const NZVC: u8 = CCR_N | CCR_Z | CCR_V | CCR_C;
+const XNZVC: u8 = CCR_X | NZVC;
+
+/// The registers and flags an instruction reads and writes.
+///
+/// Registers are bitmasks with bit `n` standing for D`n` or A`n`. Flags
+/// are bitmasks in CCR bit layout, see [`CCR_X`] and friends.
+///
+/// Byte and word writes to a data register leave the upper bits intact,
+/// so such registers are reported as both read and written. Writes to
+/// address registers always replace the whole register. A7 is reported
+/// for instructions that use the stack implicitly, and as written for
+/// instructions that load SR, as those may switch stack pointers.
+///
+/// Effects of exceptions that an instruction only takes conditionally,
+/// like `CHK`, `TRAPV` or a division by zero, are not included.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RegisterEffects {
+    /// Data registers read
+    pub data_read: u8,
+    /// Data registers written
+    pub data_written: u8,
+    /// Address registers read
+    pub address_read: u8,
+    /// Address registers written
+    pub address_written: u8,
+    /// Condition code flags read
+    pub ccr_read: u8,
+    /// Condition code flags written
+    pub ccr_written: u8,
+}
+
+impl RegisterEffects {
+    /// Returns true if any condition code flag is read.
+    pub fn reads_ccr(&self) -> bool {
+        self.ccr_read != 0
+    }
+
+    /// Returns true if any condition code flag is written.
+    pub fn writes_ccr(&self) -> bool {
+        self.ccr_written != 0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+    Modify,
+    /// Only the address is computed, as for `LEA` or `JMP`
+    Control,
+}
+
+impl Access {
+    fn reads(self) -> bool {
+        self == Access::Read || self == Access::Modify
+    }
+    fn writes(self) -> bool {
+        self == Access::Write || self == Access::Modify
+    }
+}
+
+struct Decoder<'a, A: AddressBus> {
+    mem: &'a A,
+    address_space: AddressSpace,
+    pc: u32,
+    /// offset of the next extension word, relative to `pc`
+    offset: u32,
+    effects: RegisterEffects,
+}
+
+impl<A: AddressBus> Decoder<'_, A> {
+    fn extension_word(&self) -> u16 {
+        self.mem.read_word(self.address_space, self.pc.wrapping_add(self.offset)) as u16
+    }
+
+    fn data(&mut self, reg: u16, access: Access, size: u32) {
+        if access.reads() || (access.writes() && size < 32) {
+            self.effects.data_read |= 1 << reg;
+        }
+        if access.writes() {
+            self.effects.data_written |= 1 << reg;
+        }
+    }
+
+    fn address(&mut self, reg: u16, access: Access) {
+        if access.reads() {
+            self.effects.address_read |= 1 << reg;
+        }
+        if access.writes() {
+            self.effects.address_written |= 1 << reg;
+        }
+    }
+
+    fn index(&mut self) {
+        let extension = self.extension_word();
+        let reg = (extension >> 12) & 7;
+        if extension & 0x8000 == 0 {
+            self.data(reg, Access::Read, 32);
+        } else {
+            self.address(reg, Access::Read);
+        }
+        self.offset += 2;
+    }
+
+    fn stack(&mut self) {
+        self.address(7, Access::Modify);
+    }
+
+    fn immediate(&mut self, size: u32) {
+        self.offset += if size == 32 { 4 } else { 2 };
+    }
+
+    /// Accounts for an effective address operand, named as in the handler names.
+    fn operand(&mut self, mode: &str, reg: u16, size: u32, access: Access) {
+        match mode {
+            "dn" => self.data(reg, access, size),
+            "an" => self.address(reg, access),
+            "ai" => self.address(reg, Access::Read),
+            "pi" | "pd" => self.address(reg, Access::Modify),
+            "di" => {
+                self.address(reg, Access::Read);
+                self.offset += 2;
+            }
+            "ix" => {
+                self.address(reg, Access::Read);
+                self.index();
+            }
+            "pcix" => self.index(),
+            "aw" | "pcdi" => self.offset += 2,
+            "al" => self.offset += 4,
+            "imm" => self.immediate(size),
+            _ => (),
+        }
+    }
+
+    fn registers(&mut self, mask: u16, access: Access) {
+        for reg in 0..8 {
+            if mask & (1 << reg) != 0 {
+                self.data(reg, access, 32);
+            }
+            if mask & (0x100 << reg) != 0 {
+                self.address(reg, access);
+            }
+        }
+    }
+}
+
+/// Returns the flags tested by the condition `cc` (as in `Bcc`), if it is one.
+fn condition_flags(cc: &str) -> Option<u8> {
+    Some(match cc {
+        "t" | "f" => 0,
+        "hi" | "ls" => CCR_C | CCR_Z,
+        "cc" | "cs" => CCR_C,
+        "ne" | "eq" => CCR_Z,
+        "vc" | "vs" => CCR_V,
+        "pl" | "mi" => CCR_N,
+        "ge" | "lt" => CCR_N | CCR_V,
+        "gt" | "le" => CCR_N | CCR_V | CCR_Z,
+        _ => return None,
+    })
+}
+
+/// Decodes the instruction at `pc` and returns the registers and flags it uses.
+///
+/// The instruction and any extension words are read from `mem` in the given
+/// address space. Illegal opcodes are reported as using the stack, as they
+/// always take an exception.
+pub fn register_effects<A: AddressBus>(mem: &A, address_space: AddressSpace, pc: u32) -> RegisterEffects {
+    let opcode = mem.read_word(address_space, pc) as u16;
+    let mut d = Decoder { mem, address_space, pc, offset: 2, effects: RegisterEffects::default() };
+    if !ops::is_legal_opcode(opcode) {
+        d.stack();
+        return d.effects;
+    }
+    let mut parts = ops::handler_name(opcode).split('_');
+    let mnemonic = parts.next().unwrap_or("");
+    let operands: Vec<&str> = parts.collect();
+    let size = operands.first().and_then(|size| size.parse::<u32>().ok()).unwrap_or(16);
+    let form = operands.get(1).copied().unwrap_or("");
+    let mode = operands.last().copied().unwrap_or("");
+    let (rx, ry) = ((opcode >> 9) & 7, opcode & 7);
+
+    let mut ccr_read = 0;
+    let mut ccr_written = NZVC;
+    match mnemonic {
+        "add" | "sub" | "and" | "or" => {
+            if form == "er" {
+                d.operand(mode, ry, size, Access::Read);
+                d.data(rx, Access::Modify, size);
+            } else {
+                d.data(rx, Access::Read, size);
+                d.operand(mode, ry, size, Access::Modify);
+            }
+            if mnemonic == "add" || mnemonic == "sub" {
+                ccr_written = XNZVC;
+            }
+        }
+        "cmp" | "chk" => {
+            d.operand(mode, ry, size, Access::Read);
+            d.data(rx, Access::Read, size);
+            if mnemonic == "chk" {
+                // N is only changed when the CHK exception is taken
+                ccr_written = CCR_Z | CCR_V | CCR_C;
+            }
+        }
+        "adda" | "suba" | "cmpa" => {
+            d.operand(mode, ry, size, Access::Read);
+            if mnemonic == "cmpa" {
+                d.address(rx, Access::Read);
+            } else {
+                d.address(rx, Access::Modify);
+                ccr_written = 0;
+            }
+        }
+        "eor" => {
+            d.data(rx, Access::Read, size);
+            d.operand(mode, ry, size, Access::Modify);
+        }
+        "addi" | "subi" | "andi" | "ori" | "eori" | "cmpi" => {
+            d.immediate(size);
+            match mode {
+                "toc" | "tos" => {
+                    ccr_read = XNZVC;
+                    ccr_written = XNZVC;
+                    if mode == "tos" {
+                        d.address(7, Access::Write);
+                    }
+                }
+                _ => {
+                    let access = if mnemonic == "cmpi" { Access::Read } else { Access::Modify };
+                    d.operand(mode, ry, size, access);
+                    if mnemonic == "addi" || mnemonic == "subi" {
+                        ccr_written = XNZVC;
+                    }
+                }
+            }
+        }
+        "addq" | "subq" => {
+            d.operand(mode, ry, size, Access::Modify);
+            ccr_written = if mode == "an" { 0 } else { XNZVC };
+        }
+        "addx" | "subx" | "abcd" | "sbcd" => {
+            if mode == "rr" {
+                d.data(ry, Access::Read, size);
+                d.data(rx, Access::Modify, size);
+            } else {
+                d.address(ry, Access::Modify);
+                d.address(rx, Access::Modify);
+            }
+            ccr_read = CCR_X | CCR_Z;
+            ccr_written = XNZVC;
+        }
+        "negx" | "nbcd" => {
+            d.operand(mode, ry, size, Access::Modify);
+            ccr_read = CCR_X | CCR_Z;
+            ccr_written = XNZVC;
+        }
+        "neg" => {
+            d.operand(mode, ry, size, Access::Modify);
+            ccr_written = XNZVC;
+        }
+        "not" | "tas" => d.operand(mode, ry, size, Access::Modify),
+        "clr" => d.operand(mode, ry, size, Access::Write),
+        "tst" => d.operand(mode, ry, size, Access::Read),
+        "ext" => d.data(ry, Access::Modify, if mode == "bw" { 16 } else { 32 }),
+        "swap" => d.data(ry, Access::Modify, 32),
+        "divs" | "divu" | "muls" | "mulu" => {
+            d.operand(mode, ry, size, Access::Read);
+            d.data(rx, Access::Modify, 32);
+        }
+        "lea" => {
+            d.operand(mode, ry, size, Access::Control);
+            d.address(rx, Access::Write);
+            ccr_written = 0;
+        }
+        "pea" | "jmp" | "jsr" => {
+            d.operand(mode, ry, size, Access::Control);
+            if mnemonic != "jmp" {
+                d.stack();
+            }
+            ccr_written = 0;
+        }
+        "asl" | "asr" | "lsl" | "lsr" | "rol" | "ror" | "roxl" | "roxr" => {
+            match form {
+                "r" => {
+                    d.data(rx, Access::Read, 32);
+                    d.data(ry, Access::Modify, size);
+                }
+                "s" => d.data(ry, Access::Modify, size),
+                _ => d.operand(mode, ry, size, Access::Modify),
+            }
+            let rotate = mnemonic.starts_with("ro") && !mnemonic.starts_with("rox");
+            ccr_written = if rotate { NZVC } else { XNZVC };
+            if mnemonic.starts_with("rox") || (form == "r" && !rotate) {
+                // ROXd shifts X in; a register count of zero leaves X alone
+                ccr_read = CCR_X;
+            }
+        }
+        "btst" | "bchg" | "bclr" | "bset" => {
+            if form == "r" {
+                d.data(rx, Access::Read, 32);
+            } else {
+                d.offset += 2;
+            }
+            let access = if mnemonic == "btst" { Access::Read } else { Access::Modify };
+            d.operand(mode, ry, size, access);
+            ccr_written = CCR_Z;
+        }
+        "bra" => ccr_written = 0,
+        "bsr" => {
+            d.stack();
+            ccr_written = 0;
+        }
+        "move" => {
+            match form {
+                "frs" => {
+                    d.operand(mode, ry, size, Access::Write);
+                    ccr_read = XNZVC;
+                    ccr_written = 0;
+                }
+                "toc" | "tos" => {
+                    d.operand(mode, ry, size, Access::Read);
+                    ccr_written = XNZVC;
+                    if form == "tos" {
+                        d.address(7, Access::Write);
+                    }
+                }
+                "fru" | "tou" => {
+                    d.address(ry, if form == "fru" { Access::Write } else { Access::Read });
+                    ccr_written = 0;
+                }
+                _ => {
+                    // the source extension words come first
+                    d.operand(mode, ry, size, Access::Read);
+                    d.operand(form, rx, size, Access::Write);
+                }
+            }
+        }
+        "movea" => {
+            d.operand(mode, ry, size, Access::Read);
+            d.address(rx, Access::Write);
+            ccr_written = 0;
+        }
+        "moveq" => d.data(rx, Access::Write, 32),
+        "movem" => {
+            let mut mask = d.extension_word();
+            d.offset += 2;
+            if form == "re" {
+                if mode == "pd" {
+                    // the mask is reversed for predecrement, bit 0 is A7
+                    mask = mask.reverse_bits();
+                }
+                d.registers(mask, Access::Read);
+            } else {
+                // words are sign extended to the whole register
+                d.registers(mask, Access::Write);
+            }
+            d.operand(mode, ry, size, Access::Control);
+            ccr_written = 0;
+        }
+        "movep" => {
+            d.address(ry, Access::Read);
+            d.data(rx, if form == "er" { Access::Write } else { Access::Read }, size);
+            ccr_written = 0;
+        }
+        "exg" => {
+            match mode {
+                "dd" => {
+                    d.data(rx, Access::Modify, 32);
+                    d.data(ry, Access::Modify, 32);
+                }
+                "aa" => {
+                    d.address(rx, Access::Modify);
+                    d.address(ry, Access::Modify);
+                }
+                _ => {
+                    d.data(rx, Access::Modify, 32);
+                    d.address(ry, Access::Modify);
+                }
+            }
+            ccr_written = 0;
+        }
+        "cmpm" => {
+            d.address(ry, Access::Modify);
+            d.address(rx, Access::Modify);
+        }
+        "link" => {
+            d.address(ry, Access::Modify);
+            d.stack();
+            ccr_written = 0;
+        }
+        "unlk" => {
+            d.address(ry, Access::Modify);
+            d.address(7, Access::Write);
+            ccr_written = 0;
+        }
+        "rtr" | "rte" => {
+            d.stack();
+            ccr_written = XNZVC;
+        }
+        "rts" | "trap" => {
+            d.stack();
+            ccr_written = 0;
+        }
+        "trapv" => {
+            ccr_read = CCR_V;
+            ccr_written = 0;
+        }
+        "stop" => {
+            d.address(7, Access::Write);
+            ccr_written = XNZVC;
+        }
+        _ if mnemonic.starts_with("db") && condition_flags(&mnemonic[2..]).is_some() => {
+            // DBT never decrements nor branches
+            if mnemonic != "dbt" {
+                d.data(ry, Access::Modify, 16);
+            }
+            ccr_read = condition_flags(&mnemonic[2..]).unwrap_or(0);
+            ccr_written = 0;
+        }
+        _ if mnemonic.starts_with('b') && condition_flags(&mnemonic[1..]).is_some() => {
+            ccr_read = condition_flags(&mnemonic[1..]).unwrap_or(0);
+            ccr_written = 0;
+        }
+        _ if mnemonic.starts_with('s') && condition_flags(&mnemonic[1..]).is_some() => {
+            d.operand(mode, ry, size, Access::Write);
+            ccr_read = condition_flags(&mnemonic[1..]).unwrap_or(0);
+            ccr_written = 0;
+        }
+        // NOP and RESET
+        _ => ccr_written = 0,
+    }
+    d.effects.ccr_read = ccr_read;
+    d.effects.ccr_written = ccr_written;
+    d.effects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{register_effects, RegisterEffects, CCR_C, CCR_X, CCR_Z, NZVC, XNZVC};
+    use crate::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM};
+
+    fn effects_of(code: &[u16]) -> RegisterEffects {
+        let mut mem = PagedMem::new(0);
+        for (i, &word) in code.iter().enumerate() {
+            mem.write_word(SUPERVISOR_PROGRAM, 0x1000 + 2 * i as u32, u32::from(word));
+        }
+        register_effects(&mem, SUPERVISOR_PROGRAM, 0x1000)
+    }
+
+    fn effects(data_read: u8, data_written: u8, address_read: u8, address_written: u8, ccr_read: u8, ccr_written: u8) -> RegisterEffects {
+        RegisterEffects { data_read, data_written, address_read, address_written, ccr_read, ccr_written }
+    }
+
+    #[test]
+    fn arithmetic_and_moves() {
+        // ADD.L D1, D2
+        assert_eq!(effects(0b110, 0b100, 0, 0, 0, XNZVC), effects_of(&[0xd481]));
+        // MOVE.B (A0)+, D3 only replaces the low byte of D3
+        assert_eq!(effects(0b1000, 0b1000, 0b1, 0b1, 0, NZVC), effects_of(&[0x1618]));
+        // MOVE.L D0, (d8, A1, A2.L)
+        assert_eq!(effects(0b1, 0, 0b110, 0, 0, NZVC), effects_of(&[0x2380, 0xa800]));
+        // ADDQ.W #1, A3 leaves the flags alone
+        assert_eq!(effects(0, 0, 0b1000, 0b1000, 0, 0), effects_of(&[0x524b]));
+        // ADDX.L D0, D1
+        assert_eq!(effects(0b11, 0b10, 0, 0, CCR_X | CCR_Z, XNZVC), effects_of(&[0xd380]));
+    }
+
+    #[test]
+    fn index_register_follows_immediate_data() {
+        // ADDI.L #$12345678, (d8, A0, D5.W)
+        assert_eq!(effects(0b10_0000, 0, 0b1, 0, 0, XNZVC), effects_of(&[0x06b0, 0x1234, 0x5678, 0x5000]));
+    }
+
+    #[test]
+    fn implicit_effects() {
+        // BEQ.B tests Z only
+        assert_eq!(effects(0, 0, 0, 0, CCR_Z, 0), effects_of(&[0x6702]));
+        // DBHI D4 decrements the low word of D4
+        assert_eq!(effects(0b1_0000, 0b1_0000, 0, 0, CCR_C | CCR_Z, 0), effects_of(&[0x52cc, 0xfffe]));
+        // JSR (A0) and RTS use the stack pointer
+        assert_eq!(effects(0, 0, 0x81, 0x80, 0, 0), effects_of(&[0x4e90]));
+        assert_eq!(effects(0, 0, 0x80, 0x80, 0, 0), effects_of(&[0x4e75]));
+        // MOVE.W D0, SR may switch stacks
+        assert_eq!(effects(0b1, 0, 0, 0x80, 0, XNZVC), effects_of(&[0x46c0]));
+    }
+
+    #[test]
+    fn movem_touches_registers_in_mask() {
+        // MOVEM.L D0-D1/A4, -(A7): mask is reversed for predecrement
+        assert_eq!(effects(0b11, 0, 0x90, 0x80, 0, 0), effects_of(&[0x48e7, 0xc008]));
+        // MOVEM.W (A0)+, D2/A1
+        assert_eq!(effects(0, 0b100, 0b1, 0b11, 0, 0), effects_of(&[0x4c98, 0x0204]));
+    }
+}
@@ -0,0 +1,213 @@
+//! Per-instruction execution logs for differential testing.
+//!
+//! An [`ExecutionLog`] records the register state execution started from,
+//! and the program counter, opcode, resulting register state and cycle
+//! count of every instruction executed by
+//! [`ConfiguredCore::record_execution`](super::ConfiguredCore::record_execution).
+//! Two logs, e.g. from r68k and another emulator fed the same program, can
+//! be compared with [`ExecutionLog::compare`] to find where they first
+//! diverge. The [`Display`](fmt::Display) format has one line per
+//! instruction, so logs can also be written to files and diffed as text.
+
+use std::fmt;
+
+/// The state recorded for one executed instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Address of the instruction
+    pub pc: u32,
+    /// The opcode word at `pc`
+    pub opcode: u16,
+    /// D0-D7 and A0-A7 after the instruction
+    pub dar: [u32; 16],
+    /// The status register after the instruction
+    pub sr: u16,
+    /// Cycles taken, including any exception processing
+    pub cycles: i32,
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08x} {:04x}", self.pc, self.opcode)?;
+        for reg in &self.dar {
+            write!(f, " {:08x}", reg)?;
+        }
+        write!(f, " {:04x} {}", self.sr, self.cycles)
+    }
+}
+
+/// The register state before the first recorded instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InitialState {
+    /// The program counter
+    pub pc: u32,
+    /// The status register
+    pub sr: u16,
+    /// D0-D7 and A0-A7, A7 being the stack pointer of the mode in `sr`
+    pub dar: [u32; 16],
+    /// The user stack pointer
+    pub usp: u32,
+    /// The supervisor stack pointer
+    pub ssp: u32,
+}
+
+/// A recorded sequence of executed instructions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExecutionLog {
+    /// The state execution started from, if known. Logs recorded by
+    /// [`record_execution`](super::ConfiguredCore::record_execution) always
+    /// have it; logs converted from other emulators may not.
+    pub initial: Option<InitialState>,
+    /// The recorded instructions, in execution order
+    pub entries: Vec<LogEntry>,
+}
+
+/// The part of the state that differs between two logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// The instruction address
+    Pc,
+    /// The opcode word
+    Opcode,
+    /// A data register (0-7)
+    D(u8),
+    /// An address register (0-7)
+    A(u8),
+    /// The status register
+    Sr,
+    /// The cycle count
+    Cycles,
+    /// One log ends before the other; the values are the log lengths
+    Length,
+}
+
+/// A difference between two logs at the same instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the instruction in the logs
+    pub index: usize,
+    /// What differs
+    pub field: Field,
+    /// The value in the log `compare` was called on
+    pub expected: u32,
+    /// The value in the other log
+    pub actual: u32,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "instruction {}: {:?} expected {:08x}, was {:08x}", self.index, self.field, self.expected, self.actual)
+    }
+}
+
+impl ExecutionLog {
+    /// Creates an empty log.
+    pub fn new() -> ExecutionLog {
+        ExecutionLog::default()
+    }
+
+    /// Appends an entry to the log.
+    pub fn push(&mut self, entry: LogEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Returns the number of recorded instructions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no instructions have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Compares this log against `other` and returns the first divergence.
+    ///
+    /// All fields that differ at the first differing instruction are
+    /// returned, so that e.g. a wrong flag and a wrong register caused by
+    /// the same instruction are reported together. If one log is a prefix
+    /// of the other, a single [`Field::Length`] divergence is returned.
+    /// The result is empty if the logs are identical.
+    pub fn compare(&self, other: &ExecutionLog) -> Vec<Divergence> {
+        for (index, (a, b)) in self.entries.iter().zip(&other.entries).enumerate() {
+            let divergences = divergences(index, a, b);
+            if !divergences.is_empty() {
+                return divergences;
+            }
+        }
+        if self.len() != other.len() {
+            let index = self.len().min(other.len());
+            return vec![Divergence { index, field: Field::Length, expected: self.len() as u32, actual: other.len() as u32 }];
+        }
+        Vec::new()
+    }
+}
+
+/// Returns the fields that differ between two entries for the same instruction.
+pub(crate) fn divergences(index: usize, a: &LogEntry, b: &LogEntry) -> Vec<Divergence> {
+    let mut divergences = Vec::new();
+    let mut check = |field, expected: u32, actual: u32| {
+        if expected != actual {
+            divergences.push(Divergence { index, field, expected, actual });
+        }
+    };
+    check(Field::Pc, a.pc, b.pc);
+    check(Field::Opcode, u32::from(a.opcode), u32::from(b.opcode));
+    for reg in 0..8 {
+        check(Field::D(reg as u8), a.dar[reg], b.dar[reg]);
+    }
+    for reg in 0..8 {
+        check(Field::A(reg as u8), a.dar[8 + reg], b.dar[8 + reg]);
+    }
+    check(Field::Sr, u32::from(a.sr), u32::from(b.sr));
+    check(Field::Cycles, a.cycles as u32, b.cycles as u32);
+    divergences
+}
+
+impl fmt::Display for ExecutionLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Divergence, ExecutionLog, Field, LogEntry};
+
+    fn entry(pc: u32, d0: u32, sr: u16) -> LogEntry {
+        let mut dar = [0; 16];
+        dar[0] = d0;
+        LogEntry { pc, opcode: 0x4e71, dar, sr, cycles: 4 }
+    }
+
+    #[test]
+    fn compare_reports_all_fields_of_first_divergence() {
+        let a = ExecutionLog { initial: None, entries: vec![entry(0x100, 1, 0x2700), entry(0x102, 2, 0x2700), entry(0x104, 3, 0x2700)] };
+        let b = ExecutionLog { initial: None, entries: vec![entry(0x100, 1, 0x2700), entry(0x102, 5, 0x2704), entry(0x104, 7, 0x2700)] };
+        assert_eq!(vec![
+            Divergence { index: 1, field: Field::D(0), expected: 2, actual: 5 },
+            Divergence { index: 1, field: Field::Sr, expected: 0x2700, actual: 0x2704 },
+        ], a.compare(&b));
+        assert!(a.compare(&a.clone()).is_empty());
+    }
+
+    #[test]
+    fn compare_reports_shorter_log() {
+        let a = ExecutionLog { initial: None, entries: vec![entry(0x100, 1, 0x2700), entry(0x102, 2, 0x2700)] };
+        let mut b = a.clone();
+        b.entries.pop();
+        assert_eq!(vec![Divergence { index: 1, field: Field::Length, expected: 2, actual: 1 }], a.compare(&b));
+    }
+
+    #[test]
+    fn display_has_one_line_per_instruction() {
+        let log = ExecutionLog { initial: None, entries: vec![entry(0x100, 1, 0x2700), entry(0x102, 2, 0x2700)] };
+        let text = log.to_string();
+        assert_eq!(2, text.lines().count());
+        assert!(text.starts_with("00000100 4e71 00000001 00000000 "));
+        assert!(text.lines().next().unwrap().ends_with(" 2700 4"));
+    }
+}
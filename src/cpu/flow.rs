@@ -0,0 +1,144 @@
+//! Static control-flow decoding.
+//!
+//! [`successors`] decodes the instruction at a given address and reports
+//! where execution can continue afterwards, without executing it. This is
+//! the building block for reconstructing basic blocks and control-flow
+//! graphs, e.g. in a recompiler or an interactive disassembler.
+
+use crate::ram::{AddressBus, AddressSpace};
+use super::ops;
+
+/// The possible next program counters of an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Successors {
+    /// Not a control-flow instruction; execution continues at the given address.
+    FallThrough(u32),
+    /// A conditional branch (`Bcc`, `DBcc`).
+    Branch {
+        /// Target when the branch is taken.
+        taken: u32,
+        /// The following instruction.
+        not_taken: u32,
+    },
+    /// An unconditional jump to a statically known target (`BRA`, `JMP`).
+    Jump(u32),
+    /// A subroutine call (`BSR`, `JSR`).
+    Call {
+        /// The subroutine address, or `None` if it is computed at run time.
+        target: Option<u32>,
+        /// The address pushed as return address.
+        return_address: u32,
+    },
+    /// A jump whose target is computed at run time, e.g. `JMP (A0)`.
+    Indirect,
+    /// A return from subroutine or exception (`RTS`, `RTR`, `RTE`).
+    Return,
+    /// The opcode is not a legal instruction and takes an exception.
+    Illegal,
+}
+
+/// Decodes the instruction at `pc` and returns its possible successors.
+///
+/// The instruction and any extension words are read from `mem` in the given
+/// address space. Instructions that raise exceptions as a side effect of
+/// normal operation (`TRAP`, `CHK`, `TRAPV`, `STOP`) are treated as falling
+/// through, as that is where execution normally resumes.
+pub fn successors<A: AddressBus>(mem: &A, address_space: AddressSpace, pc: u32) -> Successors {
+    let opcode = mem.read_word(address_space, pc) as u16;
+    let length = match ops::instruction_length(opcode) {
+        Some(length) => length,
+        None => return Successors::Illegal,
+    };
+    let next = pc.wrapping_add(length);
+    let ext = pc.wrapping_add(2);
+    let word_at = |address: u32| mem.read_word(address_space, address) as i16 as u32;
+    let long_at = |address: u32| mem.read_long(address_space, address);
+
+    let name = ops::handler_name(opcode);
+    let mnemonic = name.split('_').next().unwrap_or("");
+    let mode = name.rsplit('_').next().unwrap_or("");
+    match mnemonic {
+        "rts" | "rtr" | "rte" => Successors::Return,
+        "jmp" | "jsr" => {
+            let target = match mode {
+                "aw" => Some(word_at(ext)),
+                "al" => Some(long_at(ext)),
+                "pcdi" => Some(ext.wrapping_add(word_at(ext))),
+                _ => None,
+            };
+            match (mnemonic, target) {
+                ("jsr", target) => Successors::Call { target, return_address: next },
+                (_, Some(target)) => Successors::Jump(target),
+                (_, None) => Successors::Indirect,
+            }
+        }
+        "dbt" => Successors::FallThrough(next),
+        _ if mnemonic.starts_with("db") => Successors::Branch { taken: ext.wrapping_add(word_at(ext)), not_taken: next },
+        // Bcc, BRA and BSR; the bit operations have longer mnemonics
+        _ if mnemonic.starts_with('b') && mnemonic.len() == 3 => {
+            let displacement = if mode == "8" { opcode as i8 as u32 } else { word_at(ext) };
+            let target = ext.wrapping_add(displacement);
+            match mnemonic {
+                "bra" => Successors::Jump(target),
+                "bsr" => Successors::Call { target: Some(target), return_address: next },
+                _ => Successors::Branch { taken: target, not_taken: next },
+            }
+        }
+        _ => Successors::FallThrough(next),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{successors, Successors};
+    use crate::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM};
+
+    fn successors_of(code: &[u16]) -> Successors {
+        let mut mem = PagedMem::new(0);
+        for (i, &word) in code.iter().enumerate() {
+            mem.write_word(SUPERVISOR_PROGRAM, 0x1000 + 2 * i as u32, u32::from(word));
+        }
+        successors(&mem, SUPERVISOR_PROGRAM, 0x1000)
+    }
+
+    #[test]
+    fn plain_instructions_fall_through() {
+        assert_eq!(Successors::FallThrough(0x1002), successors_of(&[0x4e71])); // NOP
+        assert_eq!(Successors::FallThrough(0x1006), successors_of(&[0x203c, 0, 0])); // MOVE.L #imm, D0
+        assert_eq!(Successors::FallThrough(0x1002), successors_of(&[0x4e41])); // TRAP #1
+    }
+
+    #[test]
+    fn conditional_branches_have_two_successors() {
+        // BEQ.B *-2
+        assert_eq!(Successors::Branch { taken: 0x1000, not_taken: 0x1002 }, successors_of(&[0x67fe]));
+        // BNE.W *+0x100
+        assert_eq!(Successors::Branch { taken: 0x1102, not_taken: 0x1004 }, successors_of(&[0x6600, 0x0100]));
+        // DBF D0, *-4
+        assert_eq!(Successors::Branch { taken: 0x0ffe, not_taken: 0x1004 }, successors_of(&[0x51c8, 0xfffc]));
+        // DBT D0 never branches
+        assert_eq!(Successors::FallThrough(0x1004), successors_of(&[0x50c8, 0xfffc]));
+    }
+
+    #[test]
+    fn unconditional_transfers() {
+        assert_eq!(Successors::Jump(0x1012), successors_of(&[0x6010])); // BRA.B
+        assert_eq!(Successors::Call { target: Some(0x1022), return_address: 0x1004 },
+                   successors_of(&[0x6100, 0x0020])); // BSR.W
+        assert_eq!(Successors::Jump(0xffff_8000), successors_of(&[0x4ef8, 0x8000])); // JMP (abs).W
+        assert_eq!(Successors::Jump(0x0002_0000), successors_of(&[0x4ef9, 0x0002, 0x0000])); // JMP (abs).L
+        assert_eq!(Successors::Jump(0x1012), successors_of(&[0x4efa, 0x0010])); // JMP (d16, PC)
+        assert_eq!(Successors::Indirect, successors_of(&[0x4ed0])); // JMP (A0)
+        assert_eq!(Successors::Call { target: None, return_address: 0x1004 },
+                   successors_of(&[0x4ea8, 0x0010])); // JSR (d16, A0)
+    }
+
+    #[test]
+    fn returns_and_illegal_opcodes() {
+        assert_eq!(Successors::Return, successors_of(&[0x4e75])); // RTS
+        assert_eq!(Successors::Return, successors_of(&[0x4e73])); // RTE
+        assert_eq!(Successors::Return, successors_of(&[0x4e77])); // RTR
+        assert_eq!(Successors::Illegal, successors_of(&[0x4afc])); // ILLEGAL
+        assert_eq!(Successors::Illegal, successors_of(&[0xa000])); // Line 1010
+    }
+}
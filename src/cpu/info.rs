@@ -0,0 +1,186 @@
+//! Instruction metadata.
+//!
+//! [`instruction_info`] classifies the instruction at a given address by
+//! operation size, operand count, control flow and privilege, from the
+//! opcode word alone. It is a cheaper alternative to the
+//! [`disassembler`](super::disassembler) for analysis passes, such as
+//! linters and static analyzers, that do not need operand text.
+
+use crate::ram::{AddressBus, AddressSpace};
+use super::ops;
+
+/// The size of the data an instruction operates on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Size {
+    /// 8 bits, `.B`
+    Byte,
+    /// 16 bits, `.W`
+    Word,
+    /// 32 bits, `.L`
+    Long,
+}
+
+/// How an instruction affects the flow of control.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlFlow {
+    /// Execution continues with the following instruction, or takes an
+    /// exception (`TRAP`, `CHK`)
+    Sequential,
+    /// A conditional branch (`Bcc`, `DBcc`)
+    Branch,
+    /// An unconditional jump (`BRA`, `JMP`)
+    Jump,
+    /// A subroutine call (`BSR`, `JSR`)
+    Call,
+    /// A return from subroutine or exception (`RTS`, `RTR`, `RTE`)
+    Return,
+}
+
+/// Metadata about a decoded instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstructionInfo {
+    /// The operation size, `None` for instructions without one, such as
+    /// `NOP`, `RTS` and branches
+    pub size: Option<Size>,
+    /// The number of operands in assembler syntax, e.g. 2 for
+    /// `ADD.W D0, D1` and 1 for a branch target
+    pub operands: u8,
+    /// The effect on control flow
+    pub flow: ControlFlow,
+    /// True if executing the instruction in user mode raises a privilege
+    /// violation
+    pub privileged: bool,
+    /// Length in bytes, including extension words
+    pub length: u32,
+}
+
+/// Decodes the opcode at `pc` and returns its metadata, or `None` if it is
+/// not a legal instruction.
+///
+/// Only the opcode word is read from `mem`, in the given address space.
+///
+/// # Example
+///
+/// ```rust
+/// use r68k::cpu::info::{instruction_info, ControlFlow, Size};
+/// use r68k::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM};
+///
+/// let mut mem = PagedMem::new(0);
+/// // ADDI.L #1, D0
+/// mem.write_word(SUPERVISOR_PROGRAM, 0x1000, 0x0680);
+/// let info = instruction_info(&mem, SUPERVISOR_PROGRAM, 0x1000).unwrap();
+/// assert_eq!((Some(Size::Long), 2, ControlFlow::Sequential, 6), (info.size, info.operands, info.flow, info.length));
+/// ```
+pub fn instruction_info<A: AddressBus>(mem: &A, address_space: AddressSpace, pc: u32) -> Option<InstructionInfo> {
+    let opcode = mem.read_word(address_space, pc) as u16;
+    let length = ops::instruction_length(opcode)?;
+    let mut parts = ops::handler_name(opcode).split('_');
+    let mnemonic = parts.next().unwrap_or("");
+    let size_part = parts.next().unwrap_or("");
+    let form = parts.next().unwrap_or("");
+
+    let is_branch = mnemonic.starts_with("db") || (mnemonic.starts_with('b') && mnemonic.len() == 3);
+    let flow = match mnemonic {
+        "bra" | "jmp" => ControlFlow::Jump,
+        "bsr" | "jsr" => ControlFlow::Call,
+        "rts" | "rtr" | "rte" => ControlFlow::Return,
+        _ if is_branch => ControlFlow::Branch,
+        _ => ControlFlow::Sequential,
+    };
+    let size = match (mnemonic, size_part) {
+        ("nop" | "reset" | "rte" | "rtr" | "rts" | "trap" | "trapv" | "stop" |
+         "jmp" | "jsr" | "link" | "unlk", _) => None,
+        _ if is_branch => None,
+        (_, "8") => Some(Size::Byte),
+        (_, "16" | "bw") => Some(Size::Word),
+        _ => Some(Size::Long),
+    };
+    let operands = match mnemonic {
+        "nop" | "reset" | "rte" | "rtr" | "rts" | "trapv" => 0,
+        "trap" | "stop" | "jmp" | "jsr" | "pea" | "clr" | "neg" | "negx" | "not" | "tst" |
+        "nbcd" | "tas" | "swap" | "ext" | "unlk" => 1,
+        "bra" | "bsr" => 1,
+        _ if mnemonic.starts_with("db") => 2,
+        _ if is_branch => 1,
+        // Scc; SUB is the only other short mnemonic starting with s
+        _ if mnemonic.starts_with('s') && mnemonic.len() <= 3 && mnemonic != "sub" => 1,
+        // shifts and rotates of memory
+        "asl" | "asr" | "lsl" | "lsr" | "rol" | "ror" | "roxl" | "roxr" if form != "r" && form != "s" => 1,
+        _ => 2,
+    };
+    let privileged = match mnemonic {
+        "rte" | "reset" | "stop" => true,
+        "andi" | "ori" | "eori" => form == "tos",
+        "move" => matches!(form, "tos" | "tou" | "fru"),
+        _ => false,
+    };
+    Some(InstructionInfo { size, operands, flow, privileged, length })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{instruction_info, ControlFlow, InstructionInfo, Size};
+    use crate::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM};
+
+    fn info_of(opcode: u16) -> Option<InstructionInfo> {
+        let mut mem = PagedMem::new(0);
+        mem.write_word(SUPERVISOR_PROGRAM, 0x1000, u32::from(opcode));
+        instruction_info(&mem, SUPERVISOR_PROGRAM, 0x1000)
+    }
+
+    fn summary(opcode: u16) -> (Option<Size>, u8, ControlFlow, bool) {
+        let info = info_of(opcode).unwrap();
+        (info.size, info.operands, info.flow, info.privileged)
+    }
+
+    #[test]
+    fn sizes_and_operand_counts() {
+        use super::ControlFlow::Sequential;
+        assert_eq!((None, 0, Sequential, false), summary(0x4e71)); // NOP
+        assert_eq!((Some(Size::Byte), 2, Sequential, false), summary(0x1001)); // MOVE.B D1, D0
+        assert_eq!((Some(Size::Word), 2, Sequential, false), summary(0xd041)); // ADD.W D1, D0
+        assert_eq!((Some(Size::Long), 2, Sequential, false), summary(0x7001)); // MOVEQ #1, D0
+        assert_eq!((Some(Size::Long), 1, Sequential, false), summary(0x4480)); // NEG.L D0
+        assert_eq!((Some(Size::Byte), 1, Sequential, false), summary(0x57c0)); // SEQ D0
+        assert_eq!((Some(Size::Byte), 2, Sequential, false), summary(0x9001)); // SUB.B D1, D0
+        assert_eq!((Some(Size::Word), 1, Sequential, false), summary(0x4880)); // EXT.W D0
+        assert_eq!((Some(Size::Word), 1, Sequential, false), summary(0xe0d0)); // ASR (A0)
+        assert_eq!((Some(Size::Word), 2, Sequential, false), summary(0xe240)); // ASR.W #1, D0
+        assert_eq!((None, 1, Sequential, false), summary(0x4e41)); // TRAP #1
+        // bit operations are long on data registers, byte in memory
+        assert_eq!((Some(Size::Long), 2, Sequential, false), summary(0x0800)); // BTST #n, D0
+        assert_eq!((Some(Size::Byte), 2, Sequential, false), summary(0x0810)); // BTST #n, (A0)
+        assert_eq!(6, info_of(0x0680).unwrap().length); // ADDI.L #imm, D0
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_eq!((None, 1, ControlFlow::Branch, false), summary(0x67fe)); // BEQ.S
+        assert_eq!((None, 2, ControlFlow::Branch, false), summary(0x51c8)); // DBF D0
+        assert_eq!((None, 1, ControlFlow::Jump, false), summary(0x6000)); // BRA.W
+        assert_eq!((None, 1, ControlFlow::Jump, false), summary(0x4ed0)); // JMP (A0)
+        assert_eq!((None, 1, ControlFlow::Call, false), summary(0x6100)); // BSR.W
+        assert_eq!((None, 1, ControlFlow::Call, false), summary(0x4e90)); // JSR (A0)
+        assert_eq!((None, 0, ControlFlow::Return, false), summary(0x4e75)); // RTS
+    }
+
+    #[test]
+    fn privileged_instructions() {
+        assert!(summary(0x4e73).3); // RTE
+        assert!(summary(0x4e70).3); // RESET
+        assert!(summary(0x4e72).3); // STOP
+        assert!(summary(0x46c0).3); // MOVE D0, SR
+        assert!(summary(0x4e60).3); // MOVE A0, USP
+        assert!(summary(0x027c).3); // ANDI #imm, SR
+        assert!(!summary(0x023c).3); // ANDI #imm, CCR
+        assert!(!summary(0x40c0).3); // MOVE SR, D0 is not privileged on the 68000
+        assert!(!summary(0x44c0).3); // MOVE D0, CCR
+    }
+
+    #[test]
+    fn illegal_opcodes_have_no_info() {
+        assert_eq!(None, info_of(0x4afc)); // ILLEGAL
+        assert_eq!(None, info_of(0xa000));
+        assert_eq!(None, info_of(0xd008)); // ADD.B A0, D0
+    }
+}
@@ -20,12 +20,14 @@
 //! let cycles = cpu.execute(100);
 //! ```
 
+use std::collections::VecDeque;
 use std::result;
+use std::ops::Range;
 
 /// Result type for CPU operations that may raise exceptions.
 pub type Result<T> = result::Result<T, Exception>;
 
-use crate::interrupts::{InterruptController, AutoInterruptController, SPURIOUS_INTERRUPT};
+use crate::interrupts::{InterruptController, AutoInterruptController, PeriodicInterrupt, SPURIOUS_INTERRUPT};
 use crate::ram::loggingmem::{LoggingMem, OpsLogger};
 
 /// Test-only CPU configuration with logging memory. Not part of the public API.
@@ -44,7 +46,15 @@ pub type Handler<T> = fn(&mut T) -> Result<Cycles>;
 /// The complete instruction set as a vector of 65536 handlers (one per opcode).
 pub type InstructionSet<T> = Vec<Handler<T>>;
 
-use crate::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM, SUPERVISOR_DATA, USER_PROGRAM, USER_DATA};
+/// A native routine run in place of emulated code, see
+/// [`ConfiguredCore::set_trampoline`].
+pub type Trampoline<T, A> = Box<dyn FnMut(&mut ConfiguredCore<T, A>)>;
+
+/// A native handler run in place of the built-in one for an opcode, see
+/// [`ConfiguredCore::override_opcode`].
+pub type OpcodeOverride<T, A> = Box<dyn FnMut(&mut ConfiguredCore<T, A>) -> Cycles>;
+
+use crate::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM, SUPERVISOR_DATA, USER_PROGRAM, USER_DATA, CPU_SPACE, ADDRBUS_MASK};
 
 /// A standard CPU configuration with paged memory and autovectored interrupts.
 ///
@@ -91,9 +101,24 @@ impl Cpu {
     }
 }
 
+pub mod builder;
+pub mod coverage;
+pub mod disassembler;
+pub mod effects;
+pub mod execlog;
+pub mod flow;
+pub mod info;
+pub mod operand;
 pub mod ops;
+pub mod patch;
+pub mod reverse;
+pub mod stackframe;
+pub mod timing;
 mod effective_address;
 mod operator;
+mod status;
+pub use self::status::StatusRegister;
+use self::disassembler::DisasmStyle;
 
 /// Core CPU interface used by instruction handlers and exception callbacks.
 ///
@@ -149,6 +174,40 @@ pub trait Core {
     /// Sets flags from a condition code register value.
     fn ccr_to_flags(&mut self, ccr: u16);
 
+    /// Returns whether `opcode` is a legal instruction on this CPU.
+    ///
+    /// Opcodes that would raise an Illegal Instruction or a Line 1010/1111
+    /// exception are not legal. Only the operation word is considered, so
+    /// any extension words are not decoded. Instructions added in family
+    /// members later than the [model](ConfiguredCore::set_model) emulated
+    /// are not legal.
+    fn is_legal_opcode(&self, opcode: u16) -> bool;
+
+    /// Returns the number of cycles left in the current `execute` call.
+    ///
+    /// The budget is counted down after every instruction, so while an
+    /// instruction or its exception is being processed, this is what was
+    /// left when the instruction started. Outside of `execute` it is 0.
+    /// May be negative when the last instruction overran the budget.
+    fn remaining_budget(&self) -> i32;
+
+    /// Returns the disassembly of the instruction being executed, e.g.
+    /// `MOVE.W (A0)+, D1`.
+    ///
+    /// The text is decoded from the opcode the interpreter dispatched on
+    /// and the extension words it fetched, recorded as they were fetched,
+    /// so it shows exactly the instruction executed even if the instruction
+    /// overwrote itself. It stays valid until the next instruction starts,
+    /// so it can be read in [`Callbacks::exception_callback`] and in the
+    /// closure passed to [`execute_with`](ConfiguredCore::execute_with).
+    /// Empty before the first instruction and while an interrupt or
+    /// trampoline is processed.
+    fn current_disassembly(&self) -> String;
+
+    /// Returns the disassembly of the instruction being executed in
+    /// `style`, see [`current_disassembly`](Self::current_disassembly).
+    fn current_disassembly_with(&self, style: DisasmStyle) -> String;
+
     // === Memory access methods ===
 
     /// Reads a byte from data space.
@@ -178,6 +237,9 @@ pub trait Core {
 
     // === Internal methods (used by instruction handlers) ===
 
+    #[doc(hidden)]
+    fn effective_address_computed(&mut self, mode: u8, reg: u8, address: u32);
+
     #[doc(hidden)]
     fn ax(&mut self) -> &mut u32;
     #[doc(hidden)]
@@ -266,6 +328,18 @@ pub trait Core {
     fn stop_instruction_processing(&mut self);
     #[doc(hidden)]
     fn allow_tas_writeback(&mut self) -> bool;
+    #[doc(hidden)]
+    fn read_before_write(&self) -> bool;
+    #[doc(hidden)]
+    fn model(&self) -> CpuModel;
+    #[doc(hidden)]
+    fn control_register(&self, register: u16) -> Option<u32>;
+    #[doc(hidden)]
+    fn set_control_register(&mut self, register: u16, value: u32) -> bool;
+    #[doc(hidden)]
+    fn read_moves(&mut self, address: u32, size: u8) -> Result<u32>;
+    #[doc(hidden)]
+    fn write_moves(&mut self, address: u32, size: u8, value: u32) -> Result<()>;
 }
 
 /// The main 68000 CPU emulator, parameterized over memory and interrupt controller.
@@ -275,8 +349,8 @@ pub trait Core {
 ///
 /// # Type Parameters
 ///
-/// - `T`: The interrupt controller type, implementing [`InterruptController`](crate::interrupts::InterruptController)
-/// - `A`: The memory/address bus type, implementing [`AddressBus`](crate::ram::AddressBus)
+/// - `T`: The interrupt controller type, implementing [`InterruptController`]
+/// - `A`: The memory/address bus type, implementing [`AddressBus`]
 ///
 /// # Example
 ///
@@ -334,6 +408,10 @@ pub struct ConfiguredCore<T: InterruptController, A: AddressBus> {
     pub v_flag: u32,
     /// Negative flag.
     pub n_flag: u32,
+    /// Trace flag T1, stored as in SR (0x8000 = trace every instruction).
+    t1_flag: u32,
+    /// Set when T1 was set at the start of the current instruction.
+    tracing: bool,
     /// Prefetch buffer address.
     pub prefetch_addr: u32,
     /// Prefetch buffer data.
@@ -355,6 +433,73 @@ pub struct ConfiguredCore<T: InterruptController, A: AddressBus> {
     /// This is reset at the start of each instruction and added to the
     /// instruction's cycle count at the end.
     pending_wait_cycles: i32,
+    /// Stop `execute` after exactly the cycle budget, see [`set_cycle_exact`](Self::set_cycle_exact).
+    cycle_exact: bool,
+    /// Cycles of the last instruction that did not fit into the previous budget.
+    owed_cycles: i32,
+    /// State of the external HALT input.
+    ///
+    /// While asserted, the CPU is frozen between instructions and neither
+    /// consumes cycles nor retires instructions.
+    halt_line: bool,
+    /// Data watchpoints that stop execution when accessed.
+    watchpoints: Vec<Watchpoint>,
+    /// The most recent watchpoint hit, if any.
+    last_watchpoint_hit: Option<WatchpointHit>,
+    /// Set when a watchpoint is hit during the current instruction.
+    watchpoint_triggered: bool,
+    /// Native routines run in place of the code at their address, `None`
+    /// while running.
+    trampolines: Vec<(u32, Option<Trampoline<T, A>>)>,
+    /// Native handlers run in place of the built-in ones for their opcode,
+    /// `None` while running.
+    opcode_overrides: Vec<(u16, Option<OpcodeOverride<T, A>>)>,
+    /// Bus cycles (address space, address, is_write) performed during the current instruction.
+    bus_cycles: Vec<(AddressSpace, u32, bool)>,
+    /// How [`step()`](Self::step) treats exceptions.
+    step_mode: StepMode,
+    /// Number of exceptions (including interrupts) processed so far.
+    exceptions_taken: u64,
+    /// The exception most recently processed by the CPU, if any.
+    last_exception: Option<Exception>,
+    /// Skip illegal instructions instead of taking the exception (analysis aid).
+    illegal_as_nop: bool,
+    /// Read CLR and Scc memory destinations before writing them, as the 68000 does.
+    read_before_write: bool,
+    /// Fetch instruction words through the two word prefetch queue, rather than one at a time.
+    prefetch_emulation: bool,
+    /// The CPU model emulated, which the instruction set was built for.
+    model: CpuModel,
+    /// Source function code register, the address space MOVES reads (68010).
+    sfc: u8,
+    /// Destination function code register, the address space MOVES writes (68010).
+    dfc: u8,
+    /// Vector base register, the address of the exception vector table (68010).
+    vbr: u32,
+    /// Exceptions processed since the last instruction that completed normally.
+    exception_nesting: u32,
+    /// Halt when `exception_nesting` exceeds this (sandboxing aid).
+    max_exception_nesting: Option<u32>,
+    /// Cycles left in the current `execute` call, 0 outside of it.
+    remaining_budget: i32,
+    /// Effective addresses (mode, register, address) computed during the current instruction.
+    effective_addresses: Vec<(u8, u8, u32)>,
+    /// Snapshots for stepping backward, when reverse debugging is enabled.
+    history: Option<Box<reverse::History<T, A>>>,
+    /// Long word aligned addresses of recent prefetches, when self-modifying code detection is enabled.
+    fetched_code: Option<VecDeque<u32>>,
+    /// Addresses of writes to recently fetched code during the current instruction.
+    self_modifying_writes: Vec<u32>,
+    /// Address space, address and opcode of the instruction being executed.
+    current_instruction: Option<(AddressSpace, u32, u16)>,
+    /// Address and value of the extension words fetched by the current instruction.
+    fetched_words: Vec<(u32, u16)>,
+    /// Accesses made by the current instruction, when capturing them is enabled.
+    instruction_accesses: Option<Vec<BusAccess>>,
+    /// Regions never to execute from, when the uninitialized execution check is enabled.
+    invalid_code_regions: Option<Vec<Range<u32>>>,
+    /// Executed instruction addresses, when coverage is enabled.
+    coverage: Option<coverage::Coverage>,
 }
 impl<T: InterruptController, A: AddressBus> Core for ConfiguredCore<T, A> {
     fn dar(&mut self) -> &mut [u32; 16] {
@@ -404,39 +549,51 @@ impl<T: InterruptController, A: AddressBus> Core for ConfiguredCore<T, A> {
         self.x_flag_as_1()
     }
     fn read_data_byte(&mut self, address: u32) -> Result<u32> {
+        self.check_bus_error(address, 1, AccessType::Read, false)?;
         self.read_data_byte(address)
     }
     fn read_data_word(&mut self, address: u32) -> Result<u32> {
+        self.check_bus_error(address, 2, AccessType::Read, false)?;
         self.read_data_word(address)
     }
     fn read_data_long(&mut self, address: u32) -> Result<u32> {
+        self.check_bus_error(address, 4, AccessType::Read, false)?;
         self.read_data_long(address)
     }
     fn read_program_byte(&mut self, address: u32) -> Result<u32> {
+        self.check_bus_error(address, 1, AccessType::Read, true)?;
         self.read_program_byte(address)
     }
     fn read_program_word(&mut self, address: u32) -> Result<u32> {
+        self.check_bus_error(address, 2, AccessType::Read, true)?;
         self.read_program_word(address)
     }
     fn read_program_long(&mut self, address: u32) -> Result<u32> {
+        self.check_bus_error(address, 4, AccessType::Read, true)?;
         self.read_program_long(address)
     }
     fn write_data_byte(&mut self, address: u32, value: u32) -> Result<()> {
+        self.check_bus_error(address, 1, AccessType::Write, false)?;
         self.write_data_byte(address, value)
     }
     fn write_data_word(&mut self, address: u32, value: u32) -> Result<()> {
+        self.check_bus_error(address, 2, AccessType::Write, false)?;
         self.write_data_word(address, value)
     }
     fn write_data_long(&mut self, address: u32, value: u32) -> Result<()> {
+        self.check_bus_error(address, 4, AccessType::Write, false)?;
         self.write_data_long(address, value)
     }
     fn write_program_byte(&mut self, address: u32, value: u32) -> Result<()> {
+        self.check_bus_error(address, 1, AccessType::Write, true)?;
         self.write_program_byte(address, value)
     }
     fn write_program_word(&mut self, address: u32, value: u32) -> Result<()> {
+        self.check_bus_error(address, 2, AccessType::Write, true)?;
         self.write_program_word(address, value)
     }
     fn write_program_long(&mut self, address: u32, value: u32) -> Result<()> {
+        self.check_bus_error(address, 4, AccessType::Write, true)?;
         self.write_program_long(address, value)
     }
     fn status_register(&self) -> u16 {
@@ -451,6 +608,24 @@ impl<T: InterruptController, A: AddressBus> Core for ConfiguredCore<T, A> {
     fn ccr_to_flags(&mut self, ccr: u16) {
         self.ccr_to_flags(ccr)
     }
+    fn is_legal_opcode(&self, opcode: u16) -> bool {
+        ops::is_legal_opcode_for(self.model, opcode)
+    }
+    fn remaining_budget(&self) -> i32 {
+        self.remaining_budget
+    }
+    fn current_disassembly(&self) -> String {
+        self.current_disassembly_with(DisasmStyle::default())
+    }
+    fn current_disassembly_with(&self, style: DisasmStyle) -> String {
+        match self.current_instruction {
+            Some((address_space, pc, opcode)) => disassembler::disassemble_fetched(&self.mem, address_space, pc, opcode, &self.fetched_words, style),
+            None => String::new(),
+        }
+    }
+    fn effective_address_computed(&mut self, mode: u8, reg: u8, address: u32) {
+        self.effective_addresses.push((mode, reg, address));
+    }
     fn cond_t(&self) -> bool {
         true
     }
@@ -565,10 +740,61 @@ impl<T: InterruptController, A: AddressBus> Core for ConfiguredCore<T, A> {
     fn allow_tas_writeback(&mut self) -> bool {
         true
     }
+    fn read_before_write(&self) -> bool {
+        self.read_before_write
+    }
+    fn model(&self) -> CpuModel {
+        self.model
+    }
+    fn control_register(&self, register: u16) -> Option<u32> {
+        match register {
+            CR_SFC => Some(u32::from(self.sfc)),
+            CR_DFC => Some(u32::from(self.dfc)),
+            CR_USP => Some(self.usp()),
+            CR_VBR => Some(self.vbr),
+            _ => None,
+        }
+    }
+    fn set_control_register(&mut self, register: u16, value: u32) -> bool {
+        match register {
+            CR_SFC => self.set_sfc(value as u8),
+            CR_DFC => self.set_dfc(value as u8),
+            CR_USP => self.inactive_usp = value, // MOVEC is privileged
+            CR_VBR => self.vbr = value,
+            _ => return false,
+        }
+        true
+    }
+    fn read_moves(&mut self, address: u32, size: u8) -> Result<u32> {
+        let fc = self.sfc;
+        self.alternate_space_access(fc, address, size, AccessType::Read, 0)
+    }
+    fn write_moves(&mut self, address: u32, size: u8, value: u32) -> Result<()> {
+        let fc = self.dfc;
+        self.alternate_space_access(fc, address, size, AccessType::Write, value).map(|_| ())
+    }
 }
 /// Index of the stack pointer register (A7) in the `dar` array.
 pub const STACK_POINTER_REG: usize = 15;
 
+/// Number of instructions disassembled by [`ConfiguredCore::debug_report`].
+pub const DEBUG_REPORT_INSTRUCTIONS: usize = 5;
+
+/// Number of stack long words listed by [`ConfiguredCore::debug_report`].
+pub const DEBUG_REPORT_STACK_DEPTH: usize = 8;
+
+/// Bytes of stack [`ConfiguredCore::load_and_run`] leaves above a program
+/// loaded at address 0.
+pub const LOAD_AND_RUN_STACK_SIZE: u32 = 0x400;
+
+/// Cycles [`ConfiguredCore::step`] spends at most on a step over an
+/// exception handler, a little over a second at 8 MHz.
+pub const STEP_OVER_MAX_CYCLES: i32 = 10_000_000;
+
+/// Number of recent prefetches tracked for self-modifying code detection,
+/// see [`ConfiguredCore::enable_smc_detection`].
+pub const SMC_FETCHES_TRACKED: usize = 256;
+
 /// Represents CPU cycle counts for timing-accurate emulation.
 ///
 /// The 68000 executes instructions in a specific number of clock cycles.
@@ -642,6 +868,75 @@ pub trait Callbacks {
     /// Return `Ok(Cycles)` to handle the exception yourself (consuming the specified cycles),
     /// or `Err(ex)` to let the CPU handle it via its normal exception processing.
     fn exception_callback(&mut self, core: &mut impl Core, ex: Exception) -> Result<Cycles>;
+
+    /// Called once for each bus cycle performed by the CPU.
+    ///
+    /// The 68000 has a 16-bit data bus, so a long word access results in two
+    /// bus cycles, and instruction prefetch reads two words at a time. Every
+    /// bus cycle takes four clock periods; any remaining cycles of an
+    /// instruction are internal and do not need the bus. Hosts modelling
+    /// bus contention (e.g. DMA on Amiga chip RAM) can use this to stall only
+    /// the bus cycles.
+    ///
+    /// The calls for an instruction are made, in order, once the instruction
+    /// (or exception processing) has completed. The interrupt acknowledge
+    /// cycle is not reported. The default implementation does nothing.
+    fn bus_cycle(&mut self, _address: u32, _is_write: bool) {}
+
+    /// Called for each bus cycle with the address space it was made in.
+    ///
+    /// This reports the same cycles as [`bus_cycle`](Self::bus_cycle) and in
+    /// the same order, plus the interrupt acknowledge cycle, which reads the
+    /// vector number in [`CPU_SPACE`] at an address
+    /// with all bits set except A1-A3, which hold the interrupt level. The
+    /// function code driven on FC0-FC2 is `address_space.fc()`. The default
+    /// implementation does nothing.
+    fn bus_cycle_fc(&mut self, _address_space: AddressSpace, _address: u32, _is_write: bool) {}
+
+    /// Called when an illegal instruction is skipped because
+    /// [`ConfiguredCore::set_illegal_as_nop`] is enabled.
+    ///
+    /// Receives the opcode and its address. The default implementation does nothing.
+    fn illegal_instruction_skipped(&mut self, _opcode: u16, _pc: u32) {}
+
+    /// Called when a STOP instruction has stopped the CPU.
+    ///
+    /// Receives the status register loaded by STOP. Return `Some(cycles)`
+    /// to have the CPU idle for that many cycles and return from execution,
+    /// e.g. up to when the host knows the next device interrupt is due.
+    /// Return `None` to idle for the rest of the cycle budget, which is
+    /// also what the default implementation does.
+    fn on_stop(&mut self, _sr: u16) -> Option<u64> {
+        None
+    }
+
+    /// Called for each effective address computed by an instruction.
+    ///
+    /// `mode` and `reg` are the 6-bit EA field of the operand as encoded in
+    /// the instruction (mode 7 selects absolute and PC relative modes by
+    /// `reg`), and `resolved` is the computed address. Only memory operands
+    /// compute an address, so register direct and immediate operands are
+    /// not reported. Like [`bus_cycle`](Self::bus_cycle), the calls for an
+    /// instruction are made in order once it has completed. The default
+    /// implementation does nothing.
+    fn effective_address_computed(&mut self, _mode: u8, _reg: u8, _resolved: u32) {}
+
+    /// Called for each write to recently fetched code, when enabled with
+    /// [`ConfiguredCore::enable_smc_detection`].
+    ///
+    /// Receives the address written to. Like [`bus_cycle`](Self::bus_cycle),
+    /// the calls for an instruction are made once it has completed. The
+    /// default implementation does nothing.
+    fn self_modifying_write(&mut self, _address: u32) {}
+
+    /// Called when an instruction is fetched from uninitialized memory or
+    /// an invalid code region, when enabled with
+    /// [`ConfiguredCore::enable_uninitialized_execution_check`].
+    ///
+    /// Receives the address of the instruction. Unlike the other
+    /// notifications, this is called before the instruction executes. The
+    /// default implementation does nothing.
+    fn execute_uninitialized(&mut self, _pc: u32) {}
 }
 
 struct EmulateAllExceptions;
@@ -688,8 +983,30 @@ impl ProcessingState {
     }
 }
 
+/// How [`ConfiguredCore::step`] treats exceptions raised while stepping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepMode {
+    /// Stop at the first instruction of the exception handler.
+    Into,
+    /// Run the exception handler until it returns, and stop there.
+    OverExceptions,
+    /// Run interrupt handlers until they return, but stop at the first
+    /// instruction of the handlers of other exceptions, as with `Into`.
+    OverInterrupts,
+}
+
+/// The member of the 68000 family a core emulates, see
+/// [`ConfiguredCore::set_model`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CpuModel {
+    /// The MC68000, the default.
+    M68000,
+    /// The MC68010.
+    M68010,
+}
+
 /// Type of memory access that caused an exception.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AccessType {
     /// Memory read operation.
     Read,
@@ -698,6 +1015,48 @@ pub enum AccessType {
 }
 use crate::ram::AddressSpace;
 
+/// A data watchpoint, see [`ConfiguredCore::add_watchpoint`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// First address covered by the watchpoint.
+    pub address: u32,
+    /// Number of bytes covered by the watchpoint.
+    pub size: u8,
+    /// Whether writes to the range trigger the watchpoint.
+    pub on_write: bool,
+    /// Whether reads from the range trigger the watchpoint.
+    pub on_read: bool,
+}
+
+/// Details of the memory access that triggered a [`Watchpoint`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchpointHit {
+    /// The watchpoint that was hit.
+    pub watchpoint: Watchpoint,
+    /// Address of the access (may start outside the watched range).
+    pub address: u32,
+    /// Size of the access in bytes (1, 2 or 4).
+    pub size: u8,
+    /// Whether the access was a read or a write.
+    pub access_type: AccessType,
+    /// The value read or written.
+    pub value: u32,
+}
+
+/// A memory access made by the CPU, see
+/// [`ConfiguredCore::last_instruction_accesses`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BusAccess {
+    /// Address space of the access, giving its function code.
+    pub address_space: AddressSpace,
+    /// Address of the access.
+    pub address: u32,
+    /// Size of the access in bytes (1, 2 or 4).
+    pub size: u8,
+    /// Whether the access was a read or a write.
+    pub access_type: AccessType,
+}
+
 /// CPU exceptions that can occur during instruction execution.
 ///
 /// Exceptions are the 68000's mechanism for handling errors, traps, and interrupts.
@@ -708,8 +1067,47 @@ use crate::ram::AddressSpace;
 /// Each exception type has an associated vector number. The vector address is
 /// calculated as `vector_number * 4`, pointing to the exception handler address
 /// in the vector table at the start of memory.
+///
+/// # Stacked Program Counter
+///
+/// The PC pushed onto the supervisor stack decides whether a handler's `RTE`
+/// retries the faulting instruction or resumes after it:
+///
+/// | Exception | Stacked PC |
+/// |-----------|------------|
+/// | Illegal Instruction | Address of the illegal instruction |
+/// | Line 1010/1111 | Address of the unimplemented instruction |
+/// | Privilege Violation | Address of the privileged instruction |
+/// | Format Error (68010) | Address of the `RTE` |
+/// | TRAP, TRAPV, CHK, Divide by Zero | Address of the next instruction |
+/// | Trace | Address of the next instruction |
+/// | Interrupt | Address of the next instruction to be executed |
+/// | Address Error, Bus Error | PC at the time of the fault (see below) |
+///
+/// Handlers for the first group that emulate the instruction must advance the
+/// stacked PC past it (including any extension words) before returning with `RTE`.
+///
+/// The 68000 cannot restart instructions after an address or bus error. The stacked
+/// PC is the program counter as advanced by the faulting instruction at the
+/// time of the access; for a misaligned instruction fetch it is the odd
+/// address itself, while for an operand access it usually points somewhere
+/// past the opcode word.
 #[derive(Clone, Copy, Debug)]
 pub enum Exception {
+    /// Bus error: an access was terminated by the external bus.
+    ///
+    /// Raised when [`AddressBus::bus_error`]
+    /// rejects an operand access. Uses the same stack frame as an address error.
+    BusError {
+        /// The address of the rejected access.
+        address: u32,
+        /// Whether the access was a read or write.
+        access_type: AccessType,
+        /// CPU state when the error occurred.
+        processing_state: ProcessingState,
+        /// The address space (user/supervisor, program/data).
+        address_space: AddressSpace,
+    },
     /// Address error: word/long access at odd address.
     ///
     /// Contains the faulting address, access type, CPU state, and address space.
@@ -735,6 +1133,11 @@ pub enum Exception {
     ///
     /// Fields: (instruction_register, program_counter)
     PrivilegeViolation(u16, u32),
+    /// RTE found a stack frame with a format the CPU does not know
+    /// (68010 only).
+    ///
+    /// Fields: (instruction_register, program_counter)
+    FormatError(u16, u32),
     /// Unimplemented instruction (A-line or F-line).
     ///
     /// Fields: (instruction_register, program_counter, vector_number)
@@ -743,19 +1146,29 @@ pub enum Exception {
     ///
     /// Fields: (interrupt_level, vector_number)
     Interrupt(u8, u8),
+    /// Trace exception after an instruction that started with T set.
+    ///
+    /// Taken by the CPU itself after the instruction has completed, so it
+    /// is never passed to [`Callbacks::exception_callback`].
+    Trace,
 }
 use std::fmt;
 impl fmt::Display for Exception {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            Exception::BusError {
+                address, access_type, processing_state, address_space
+                } => write!(f, "Bus Error: {access_type:?} {address_space:?} at {address:08x} during {processing_state:?} processing"),
             Exception::AddressError {
                 address, access_type, processing_state, address_space
                 } => write!(f, "Address Error: {access_type:?} {address_space:?} at {address:08x} during {processing_state:?} processing"),
             Exception::IllegalInstruction(ir, pc) => write!(f, "Illegal Instruction {ir:04x} at {pc:08x}"),
             Exception::Trap(num, ea_cyc) => write!(f, "Trap: {num:02x} (ea cyc {ea_cyc})"),
             Exception::PrivilegeViolation(ir, pc) => write!(f, "Privilege Violation {ir:04x} at {pc:08x}"),
+            Exception::FormatError(ir, pc) => write!(f, "Format Error {ir:04x} at {pc:08x}"),
             Exception::UnimplementedInstruction(ir, pc, _) => write!(f, "Unimplemented Instruction {ir:04x} at {pc:08x}"),
             Exception::Interrupt(irq, vec) => write!(f, "Interrupt {irq:1x} (vector {vec:02x})"),
+            Exception::Trace => write!(f, "Trace"),
         }
     }
 }
@@ -763,12 +1176,15 @@ use std::error;
 impl error::Error for Exception {
     fn description(&self) -> &str {
          match *self {
+            Exception::BusError{..} => "Bus Error",
             Exception::AddressError{..} => "Address Error",
             Exception::IllegalInstruction(_, _) => "Illegal Instruction",
             Exception::Trap(_, _) => "Trap",
             Exception::PrivilegeViolation(_, _) => "PrivilegeViolation",
+            Exception::FormatError(_, _) => "FormatError",
             Exception::UnimplementedInstruction(_, _, _) => "UnimplementedInstruction",
             Exception::Interrupt(_, _) => "Interrupt",
+            Exception::Trace => "Trace",
          }
     }
     fn cause(&self) -> Option<&dyn error::Error> {
@@ -786,6 +1202,7 @@ const NFLAG_SET: u32 =  0x80;
 const VFLAG_SET: u32 =  0x80;
 const CFLAG_SET: u32 = 0x100;
 const CPU_SR_MASK: u16 = 0xa71f; /* T1 -- S  -- -- I2 I1 I0 -- -- -- X  N  Z  V  C  */
+const TFLAG_SET: u32 = 0x8000;
 const CPU_SR_INT_MASK: u32 = 0x0700;
 
 const VFLAG_CLEAR: u32 =  0x00;
@@ -795,31 +1212,80 @@ const CFLAG_CLEAR: u32 =  0x00;
 const SFLAG_CLEAR: u32 =  0x00;
 const ZFLAG_CLEAR: u32 =  0xffff_ffff; // used as "non-z-flag"
 
+// 68010 control registers, as numbered by MOVEC
+const CR_SFC: u16 = 0x000;
+const CR_DFC: u16 = 0x001;
+const CR_USP: u16 = 0x800;
+const CR_VBR: u16 = 0x801;
+
 // Exception Vectors
-//pub const EXCEPTION_BUS_ERROR: u8               =  2;
+pub const EXCEPTION_BUS_ERROR: u8               =  2;
 pub const EXCEPTION_ADDRESS_ERROR: u8           =  3;
 pub const EXCEPTION_ILLEGAL_INSTRUCTION: u8     =  4;
 pub const EXCEPTION_ZERO_DIVIDE: u8             =  5;
 pub const EXCEPTION_CHK: u8                     =  6;
 pub const EXCEPTION_TRAPV: u8                   =  7;
 pub const EXCEPTION_PRIVILEGE_VIOLATION: u8     =  8;
-// pub const EXCEPTION_TRACE: u8                   =  9;
+pub const EXCEPTION_TRACE: u8                   =  9;
 pub const EXCEPTION_UNIMPLEMENTED_1010: u8      = 10;
 pub const EXCEPTION_UNIMPLEMENTED_1111: u8      = 11;
-// pub const EXCEPTION_FORMAT_ERROR: u8            = 14;
+pub const EXCEPTION_FORMAT_ERROR: u8            = 14;
 // pub const EXCEPTION_UNINITIALIZED_INTERRUPT: u8 = 15;
 // pub const EXCEPTION_SPURIOUS_INTERRUPT: u8      = 24;
 // pub const EXCEPTION_INTERRUPT_AUTOVECTOR: u8    = 24;
 pub const EXCEPTION_TRAP_BASE: u8               = 32;
 
+/// Standard names of exception vectors 0-63, indexed by vector number.
+///
+/// Vectors 64-255 are user interrupt vectors; see [`vector_name`].
+pub const EXCEPTION_VECTOR_NAMES: [&str; 64] = [
+    "Reset SSP", "Reset PC", "Bus Error", "Address Error",
+    "Illegal Instruction", "Zero Divide", "CHK", "TRAPV",
+    "Privilege Violation", "Trace", "Line 1010 Emulator", "Line 1111 Emulator",
+    "Reserved", "Reserved", "Format Error", "Uninitialized Interrupt",
+    "Reserved", "Reserved", "Reserved", "Reserved",
+    "Reserved", "Reserved", "Reserved", "Reserved",
+    "Spurious Interrupt", "Level 1 Autovector", "Level 2 Autovector", "Level 3 Autovector",
+    "Level 4 Autovector", "Level 5 Autovector", "Level 6 Autovector", "Level 7 Autovector",
+    "TRAP #0", "TRAP #1", "TRAP #2", "TRAP #3",
+    "TRAP #4", "TRAP #5", "TRAP #6", "TRAP #7",
+    "TRAP #8", "TRAP #9", "TRAP #10", "TRAP #11",
+    "TRAP #12", "TRAP #13", "TRAP #14", "TRAP #15",
+    "Reserved", "Reserved", "Reserved", "Reserved",
+    "Reserved", "Reserved", "Reserved", "Reserved",
+    "Reserved", "Reserved", "Reserved", "Reserved",
+    "Reserved", "Reserved", "Reserved", "Reserved",
+];
+
+/// Returns the standard name of an exception vector.
+///
+/// # Example
+///
+/// ```rust
+/// use r68k::cpu::vector_name;
+///
+/// assert_eq!("Address Error", vector_name(3));
+/// assert_eq!("Level 4 Autovector", vector_name(28));
+/// assert_eq!("TRAP #15", vector_name(47));
+/// assert_eq!("User Interrupt", vector_name(64));
+/// ```
+pub fn vector_name(vector: u8) -> &'static str {
+    EXCEPTION_VECTOR_NAMES.get(vector as usize).copied().unwrap_or("User Interrupt")
+}
+
 impl TestCore {
     pub fn new(base: u32) -> TestCore {
         TestCore {
             pc: base, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Group0Exception,
             dar: [0u32; 16], mem: LoggingMem::new(0xaaaa_aaaa, OpsLogger::new()), instruction_set: ops::instruction_set(),
             irq_level: 0, int_ctrl: AutoInterruptController::new(),
-            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
-            cycle_granularity: 1, pending_wait_cycles: 0,
+            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff, t1_flag: 0, tracing: false,
+            cycle_granularity: 1, pending_wait_cycles: 0, cycle_exact: false, owed_cycles: 0, halt_line: false,
+            watchpoints: Vec::new(), last_watchpoint_hit: None, watchpoint_triggered: false,
+            bus_cycles: Vec::new(), step_mode: StepMode::Into, exceptions_taken: 0, last_exception: None,
+            illegal_as_nop: false, read_before_write: false, prefetch_emulation: true, model: CpuModel::M68000, sfc: 0, dfc: 0, vbr: 0, exception_nesting: 0, max_exception_nesting: None, remaining_budget: 0, effective_addresses: Vec::new(), trampolines: Vec::new(), opcode_overrides: Vec::new(), history: None,
+            fetched_code: None, self_modifying_writes: Vec::new(), current_instruction: None, fetched_words: Vec::new(), instruction_accesses: None,
+            invalid_code_regions: None, coverage: None,
         }
     }
     pub fn new_auto() -> TestCore {
@@ -837,8 +1303,13 @@ impl TestCore {
             pc: base, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Normal,
             dar: [0u32; 16], mem: lm, instruction_set: ops::instruction_set(),
             irq_level: 0, int_ctrl: AutoInterruptController::new(),
-            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
-            cycle_granularity: 1, pending_wait_cycles: 0,
+            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff, t1_flag: 0, tracing: false,
+            cycle_granularity: 1, pending_wait_cycles: 0, cycle_exact: false, owed_cycles: 0, halt_line: false,
+            watchpoints: Vec::new(), last_watchpoint_hit: None, watchpoint_triggered: false,
+            bus_cycles: Vec::new(), step_mode: StepMode::Into, exceptions_taken: 0, last_exception: None,
+            illegal_as_nop: false, read_before_write: false, prefetch_emulation: true, model: CpuModel::M68000, sfc: 0, dfc: 0, vbr: 0, exception_nesting: 0, max_exception_nesting: None, remaining_budget: 0, effective_addresses: Vec::new(), trampolines: Vec::new(), opcode_overrides: Vec::new(), history: None,
+            fetched_code: None, self_modifying_writes: Vec::new(), current_instruction: None, fetched_words: Vec::new(), instruction_accesses: None,
+            invalid_code_regions: None, coverage: None,
         }
     }
 }
@@ -872,8 +1343,13 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
             pc: base, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Group0Exception,
             dar: [0u32; 16], mem: memory, instruction_set: ops::instruction_set(),
             irq_level: 0, int_ctrl,
-            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff,
-            cycle_granularity: 1, pending_wait_cycles: 0,
+            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffff_ffff, t1_flag: 0, tracing: false,
+            cycle_granularity: 1, pending_wait_cycles: 0, cycle_exact: false, owed_cycles: 0, halt_line: false,
+            watchpoints: Vec::new(), last_watchpoint_hit: None, watchpoint_triggered: false,
+            bus_cycles: Vec::new(), step_mode: StepMode::Into, exceptions_taken: 0, last_exception: None,
+            illegal_as_nop: false, read_before_write: false, prefetch_emulation: true, model: CpuModel::M68000, sfc: 0, dfc: 0, vbr: 0, exception_nesting: 0, max_exception_nesting: None, remaining_budget: 0, effective_addresses: Vec::new(), trampolines: Vec::new(), opcode_overrides: Vec::new(), history: None,
+            fetched_code: None, self_modifying_writes: Vec::new(), current_instruction: None, fetched_words: Vec::new(), instruction_accesses: None,
+            invalid_code_regions: None, coverage: None,
         }
     }
 
@@ -918,1352 +1394,6136 @@ impl<T: InterruptController, A: AddressBus> ConfiguredCore<T, A> {
         self.cycle_granularity
     }
 
-    /// Rounds a cycle count up to the configured granularity boundary.
-    #[inline]
-    fn align_cycles(&self, cycles: Cycles) -> Cycles {
-        if self.cycle_granularity == 1 {
-            cycles
-        } else {
-            // Round up: (n + g - 1) / g * g
-            let n = cycles.0;
-            let g = self.cycle_granularity;
-            Cycles(((n + g - 1) / g) * g)
+    /// Makes [`execute`](Self::execute) consume exactly the cycles it is given.
+    ///
+    /// An instruction is always executed as a whole; the budget is only
+    /// checked between instructions, so normally the last instruction of an
+    /// `execute` call can overrun it. In cycle exact mode, the cycles of
+    /// that instruction which exceed the budget are carried over instead:
+    /// the call returns exactly the budget, and the next calls spend their
+    /// budget on the carried over cycles before starting another
+    /// instruction. The instruction's effects on registers and memory are
+    /// all visible when it starts, so this keeps the timing of a host that
+    /// runs the CPU in fixed slices (e.g. interleaved with bus contention or
+    /// video) exact, but it does not model the CPU stopping inside an
+    /// instruction.
+    ///
+    /// [`execute1`](Self::execute1) always executes a whole instruction and
+    /// includes any carried over cycles in its result. Disabling cycle exact
+    /// mode drops the carried over cycles. Disabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::{Cpu, Cycles};
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.set_cycle_exact(true);
+    /// // MOVE.L #$12345678, D0 takes 12 cycles
+    /// cpu.load_and_run(&[0x20, 0x3c, 0x12, 0x34, 0x56, 0x78], 0x1000, 0x1000, 0);
+    /// assert_eq!(Cycles(5), cpu.execute(5));
+    /// assert_eq!(0x1234_5678, cpu.dar[0]);
+    /// assert_eq!(7, cpu.owed_cycles());
+    /// assert_eq!(Cycles(5), cpu.execute(5));
+    /// assert_eq!(2, cpu.owed_cycles());
+    /// ```
+    pub fn set_cycle_exact(&mut self, enabled: bool) {
+        self.cycle_exact = enabled;
+        if !enabled {
+            self.owed_cycles = 0;
         }
     }
 
-    /// Performs a CPU reset, loading initial SSP and PC from the vector table.
-    ///
-    /// This simulates a hardware reset:
-    /// 1. Enters supervisor mode
-    /// 2. Sets interrupt mask to highest priority
-    /// 3. Reads initial SSP from address 0x000000
-    /// 4. Reads initial PC from address 0x000004
-    /// 5. Enters normal processing state
-    ///
-    /// The memory must contain valid reset vectors at addresses 0-7.
-    pub fn reset(&mut self) {
-        self.processing_state = ProcessingState::Group0Exception;
-        self.s_flag = SFLAG_SET;
-        self.int_mask = CPU_SR_INT_MASK;
-        self.prefetch_addr = 1; // non-zero, or the prefetch won't kick in
-        self.jump(0);
-        // these reads cannot possibly cause AddressError, as we forced PC to 0
-        self.dar[15] = self.read_imm_u32().unwrap();
-        let new_pc = self.read_imm_u32().unwrap();
-        self.jump(new_pc);
-        self.processing_state = ProcessingState::Normal;
+    /// Returns whether cycle exact mode is enabled, see [`set_cycle_exact`](Self::set_cycle_exact).
+    pub fn cycle_exact(&self) -> bool {
+        self.cycle_exact
     }
-    /// Returns the extend flag as 0 or 1.
-    pub fn x_flag_as_1(&self) -> u32 {
-        (self.x_flag>>8)&1
+
+    /// Returns the cycles carried over to the next `execute` call in cycle exact mode.
+    pub fn owed_cycles(&self) -> i32 {
+        self.owed_cycles
     }
 
-    /// Returns the full 16-bit status register value.
+    /// Drives the external HALT input.
     ///
-    /// The status register contains:
-    /// - Bits 15-13: Trace mode (T1, T0) and reserved
-    /// - Bit 13: Supervisor mode (S)
-    /// - Bits 10-8: Interrupt priority mask (I2, I1, I0)
-    /// - Bit 4: Extend flag (X)
-    /// - Bit 3: Negative flag (N)
-    /// - Bit 2: Zero flag (Z)
-    /// - Bit 1: Overflow flag (V)
-    /// - Bit 0: Carry flag (C)
-    pub fn status_register(&self) -> u16 {
-        ((self.s_flag << 11)                |
-        self.int_mask                        |
-        ((self.x_flag & XFLAG_SET) >> 4)    |
-        ((self.n_flag & NFLAG_SET) >> 4)    |
-        ((not1!(self.not_z_flag))  << 2)    |
-        ((self.v_flag & VFLAG_SET) >> 6)    |
-        ((self.c_flag & CFLAG_SET) >> 8)) as u16
-    }
-    /// Returns the condition code register (lower 8 bits of status register).
+    /// External devices such as DMA controllers or hardware debuggers assert
+    /// this line to freeze the CPU at the next instruction boundary. While
+    /// the line is asserted, [`execute()`](Self::execute) returns immediately
+    /// without consuming any cycles or retiring any instructions. Releasing
+    /// the line resumes execution where it left off.
     ///
-    /// Contains: X, N, Z, V, C flags.
-    pub fn condition_code_register(&self) -> u16 {
-        self.status_register() & 0xff
+    /// This is unrelated to the STOP instruction (which waits for an
+    /// interrupt) and to the [`ProcessingState::Halted`] state entered on a
+    /// double bus fault (which requires a reset).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::{Cpu, Cycles};
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.reset();
+    ///
+    /// cpu.set_halt_line(true);
+    /// assert_eq!(Cycles(0), cpu.execute(1000));
+    /// cpu.set_halt_line(false);
+    /// ```
+    pub fn set_halt_line(&mut self, halted: bool) {
+        self.halt_line = halted;
     }
 
-    /// Returns the current user stack pointer value.
-    ///
-    /// In supervisor mode, returns the saved USP. In user mode, returns A7.
-    pub fn usp(&self) -> u32 {
-        if self.s_flag > 0 {
-            self.inactive_usp
-        } else {
-            self.dar[15]
-        }
+    /// Returns whether the external HALT input is currently asserted.
+    pub fn halt_line(&self) -> bool {
+        self.halt_line
     }
 
-    /// Returns the current supervisor stack pointer value.
+    /// Makes illegal instructions behave like a two-byte NOP.
     ///
-    /// In supervisor mode, returns A7. In user mode, returns the saved SSP.
-    pub fn ssp(&self) -> u32 {
-        if self.s_flag > 0 {
-            self.dar[15]
-        } else {
-            self.inactive_ssp
-        }
+    /// This is not hardware behavior; it is meant for analysis tooling that
+    /// wants to run through a whole ROM without stopping at undecoded opcodes.
+    /// When enabled, an illegal instruction that is not handled by
+    /// [`Callbacks::exception_callback`] is reported to
+    /// [`Callbacks::illegal_instruction_skipped`], and execution continues
+    /// at the following word after 4 cycles, instead of taking the Illegal
+    /// Instruction exception (vector 4). Line 1010 and 1111 opcodes still
+    /// take their exceptions. Disabled by default.
+    pub fn set_illegal_as_nop(&mut self, enabled: bool) {
+        self.illegal_as_nop = enabled;
     }
 
-    /// Sets CPU flags from a status register value.
-    ///
-    /// This updates all flags and the interrupt mask from the given SR value.
-    /// If the supervisor bit changes, the stack pointers are swapped.
-    pub fn sr_to_flags(&mut self, sr: u16) {
-        let sr = u32::from(sr & CPU_SR_MASK);
-        let old_sflag = self.s_flag;
-        self.int_mask = sr & CPU_SR_INT_MASK;
-        self.s_flag =           (sr >> 11) & SFLAG_SET;
-        self.x_flag =            (sr <<  4) & XFLAG_SET;
-        self.n_flag =            (sr <<  4) & NFLAG_SET;
-        self.not_z_flag = not1!(sr & 0b00100);
-        self.v_flag =            (sr <<  6) & VFLAG_SET;
-        self.c_flag =            (sr <<  8) & CFLAG_SET;
-        if old_sflag != self.s_flag {
-            if self.s_flag == SFLAG_SET {
-                self.inactive_usp = self.dar[15];
-                self.dar[15] = self.inactive_ssp;
-            } else {
-                self.inactive_ssp = self.dar[15];
-                self.dar[15] = self.inactive_usp;
-            }
-        }
-        // println!("{} {:016b} {} {}", self.flags(), sr, self.not_z_flag, sr & 0b00100);
+    /// Returns whether illegal instructions are skipped, see [`set_illegal_as_nop`](Self::set_illegal_as_nop).
+    pub fn illegal_as_nop(&self) -> bool {
+        self.illegal_as_nop
     }
-    /// Sets CPU flags from a condition code register value.
+
+    /// Makes `CLR` and `Scc` read their memory destination before writing it.
     ///
-    /// Only updates the lower 8 bits (X, N, Z, V, C), preserving the upper byte.
-    pub fn ccr_to_flags(&mut self, ccr: u16) {
-        let sr = self.status_register();
-        self.sr_to_flags((sr & 0xff00) | (ccr & 0xff));
+    /// The 68000 reads the destination of these instructions, and discards
+    /// the value, before writing it. Musashi skips the read, and so does
+    /// r68k by default. Enable this where the read matters, e.g. for memory
+    /// mapped registers that change when read. The read takes no extra
+    /// cycles, as it is part of the published instruction timing, but goes
+    /// through the bus like any other, with its wait states and bus errors.
+    pub fn set_read_before_write(&mut self, enabled: bool) {
+        self.read_before_write = enabled;
     }
 
-    /// Returns a human-readable string representation of the current flags.
+    /// Returns whether `CLR` and `Scc` read their destination first, see
+    /// [`set_read_before_write`](Self::set_read_before_write).
+    pub fn read_before_write(&self) -> bool {
+        self.read_before_write
+    }
+
+    /// Chooses whether instruction words are fetched through the prefetch
+    /// queue (the default) or read one word at a time.
     ///
-    /// Format: `-S7XNZVC` where:
-    /// - S/U: Supervisor/User mode
-    /// - 0-7: Interrupt priority mask
-    /// - X, N, Z, V, C: Flag letters if set, `-` if clear
+    /// With prefetch emulation, program memory is read a long word at a
+    /// time, and words already in the queue are not read again, as with
+    /// Musashi. Without it, each opcode and extension word is read on its
+    /// own when the instruction needs it, which is simpler to follow in a
+    /// bus log. Instruction cycles are the same either way: the published
+    /// timings already include the prefetch, so it adds no cycles of its
+    /// own. Only the bus accesses, and the wait states reported for them,
+    /// differ.
+    pub fn set_prefetch_emulation(&mut self, enabled: bool) {
+        self.prefetch_emulation = enabled;
+        self.prefetch_addr = 1; // invalidate, words may have been read past it
+    }
+
+    /// Returns whether instruction words are fetched through the prefetch
+    /// queue, see [`set_prefetch_emulation`](Self::set_prefetch_emulation).
+    pub fn prefetch_emulation(&self) -> bool {
+        self.prefetch_emulation
+    }
+
+    /// Chooses the CPU model to emulate, the 68000 by default.
     ///
-    /// Example: `-S7X-Z--` means supervisor mode, mask 7, X and Z flags set.
-    pub fn flags(&self) -> String {
-        let sr = self.status_register();
-        let supervisor = (sr >> 13) & 1;
-        let irq_mask = (0x700 & sr) >> 8;
+    /// The 68010 differs from the 68000 in that:
+    /// - `MOVE from CCR` is added, so that user code can read the flags,
+    /// - `MOVE from SR` is privileged,
+    /// - `MOVEC` moves the SFC, DFC, USP and VBR control registers, and an
+    ///   unknown control register is an illegal instruction,
+    /// - `MOVES` reads from the address space of [`sfc`](Self::sfc) and
+    ///   writes to that of [`dfc`](Self::dfc),
+    /// - exception vectors are fetched relative to [`vbr`](Self::vbr),
+    /// - exception stack frames end in a format word, see
+    ///   [`stackframe`]: bus and address errors stack a format 8 frame,
+    ///   other exceptions a format 0 frame. RTE unstacks either, and takes
+    ///   a Format Error exception (vector 14) on any other format, with
+    ///   the frame left in place. The faulted instruction of a format 8
+    ///   frame is not continued; execution resumes at the stacked PC.
+    ///
+    /// Other 68010 additions are not emulated yet, and instruction and
+    /// exception timing is that of the 68000. Opcode overrides and
+    /// trampolines are kept. The disassembler and the other decoding tools
+    /// cover the 68000 instruction set only.
+    pub fn set_model(&mut self, model: CpuModel) {
+        self.model = model;
+        self.instruction_set = ops::instruction_set_for(model);
+    }
 
-        format!("-{}{}{}{}{}{}{}",
-        if supervisor > 0 {'S'} else {'U'},
-        irq_mask,
-        if 0 < (sr >> 4) & 1 {'X'} else {'-'},
-        if 0 < (sr >> 3) & 1 {'N'} else {'-'},
-        if 0 < (sr >> 2) & 1 {'Z'} else {'-'},
-        if 0 < (sr >> 1) & 1 {'V'} else {'-'},
-        if 0 < (sr     ) & 1 {'C'} else {'-'})
+    /// Returns the CPU model emulated, see [`set_model`](Self::set_model).
+    pub fn model(&self) -> CpuModel {
+        self.model
     }
-    fn prefetch_if_needed(&mut self) -> bool {
-        // does current PC overlap with fetched data
-        let fetched = if self.pc & !3 != self.prefetch_addr {
-            self.prefetch_addr = self.pc & !3;
-            let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-            self.pending_wait_cycles += self.mem.wait_cycles(self.prefetch_addr, 4, false);
-            self.prefetch_data = self.mem.read_long(address_space, self.prefetch_addr);
-            true
-        } else {
-            false
-        };
-        self.pc = self.pc.wrapping_add(2);
-        fetched
+
+    /// Returns the source function code register, the 3-bit function code
+    /// of the address space `MOVES` reads from (68010).
+    pub fn sfc(&self) -> u8 {
+        self.sfc
     }
-    pub fn read_imm_u32(&mut self) -> Result<u32> {
-        if self.pc & 1 > 0 {
-            let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-            return Err(Exception::AddressError{address: self.pc, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
-        }
-        self.prefetch_if_needed();
-        let prev_prefetch_data = self.prefetch_data;
-        Ok(if self.prefetch_if_needed() {
-            (prev_prefetch_data << 16) | (self.prefetch_data >> 16)
-        } else {
-            prev_prefetch_data
-        })
+
+    /// Sets the source function code register; only the low three bits are kept.
+    pub fn set_sfc(&mut self, fc: u8) {
+        self.sfc = fc & 7;
     }
-    pub fn read_imm_i16(&mut self) -> Result<i16> {
-        self.read_imm_u16().map(|val| val as i16)
+
+    /// Returns the destination function code register, the 3-bit function
+    /// code of the address space `MOVES` writes to (68010).
+    pub fn dfc(&self) -> u8 {
+        self.dfc
     }
-    pub fn read_imm_u16(&mut self) -> Result<u16> {
-        // the Musashi read_imm_16 calls cpu_read_long as part of prefetch
-        if self.pc & 1 > 0 {
-            let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-            return Err(Exception::AddressError{address: self.pc, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
-        }
-        self.prefetch_if_needed();
-        Ok(((self.prefetch_data >> ((2 - ((self.pc.wrapping_sub(2)) & 2))<<3)) & 0xffff) as u16)
-    }
-    pub fn push_sp(&mut self) -> u32 {
-         let new_sp = (Wrapping(self.dar[15]) - Wrapping(4)).0;
-         self.dar[15] = new_sp;
-         self.write_data_long(new_sp, new_sp).unwrap();
-         new_sp
+
+    /// Sets the destination function code register; only the low three bits are kept.
+    pub fn set_dfc(&mut self, fc: u8) {
+        self.dfc = fc & 7;
     }
-    pub fn push_32(&mut self, value: u32) -> u32 {
-         let new_sp = (Wrapping(self.dar[15]) - Wrapping(4)).0;
-         self.dar[15] = new_sp;
-         self.write_data_long(new_sp, value).unwrap();
-         new_sp
+
+    /// Returns the vector base register, the address of the exception
+    /// vector table (68010). It is always 0 on the 68000.
+    pub fn vbr(&self) -> u32 {
+        self.vbr
     }
-    pub fn pop_32(&mut self) -> u32 {
-        let sp = self.dar[15];
-        let data = self.read_data_long(sp).unwrap();
-        self.dar[15] = sp.wrapping_add(4);
-        data
+
+    /// Sets the vector base register. It is ignored by the 68000 model.
+    pub fn set_vbr(&mut self, vbr: u32) {
+        self.vbr = vbr;
     }
-    pub fn push_16(&mut self, value: u16) -> u32 {
-         let new_sp = (Wrapping(self.dar[15]) - Wrapping(2)).0;
-         self.dar[15] = new_sp;
-         self.write_data_word(new_sp, u32::from(value)).unwrap();
-         new_sp
+
+    /// Returns the exception the CPU most recently processed, including
+    /// interrupts and trace exceptions, or `None` if it has not processed
+    /// any yet.
+    ///
+    /// The value stays until the next exception replaces it. Exceptions
+    /// handled by [`Callbacks::exception_callback`] and illegal instructions
+    /// skipped by [`set_illegal_as_nop`](Self::set_illegal_as_nop) are not
+    /// processed by the CPU and don't replace it.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::{Cpu, Exception, EXCEPTION_ZERO_DIVIDE};
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// // DIVU D1, D0 with D1 = 0
+    /// cpu.load_and_run(&[0x80, 0xc1], 0x1000, 0x1000, 0);
+    /// cpu.execute1();
+    /// assert!(matches!(cpu.last_exception(), Some(Exception::Trap(EXCEPTION_ZERO_DIVIDE, _))));
+    /// ```
+    pub fn last_exception(&self) -> Option<Exception> {
+        self.last_exception
     }
-    pub fn pop_16(&mut self) -> u16 {
-        let sp = self.dar[15];
-        let data = self.read_data_word(sp).unwrap() as u16;
-        self.dar[15] = sp.wrapping_add(2);
-        data
+
+    /// Halts the CPU when more than `depth` exceptions are processed in a
+    /// row, without an instruction completing normally in between.
+    ///
+    /// This is not hardware behavior; the 68000 only halts on a double bus
+    /// or address error. It is meant for fuzzing and sandboxing, where an
+    /// exception handler that faults again, e.g. a trap vector pointing at
+    /// a TRAP instruction, would otherwise spin in exception processing
+    /// for the rest of every `execute` call. Interrupts and trace exceptions
+    /// count as exceptions; an exception handled by [`Callbacks::exception_callback`]
+    /// or an illegal instruction skipped by
+    /// [`set_illegal_as_nop`](Self::set_illegal_as_nop) counts as a
+    /// completed instruction. `None`, the default, disables the guard.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::{Cpu, ProcessingState};
+    /// use r68k::ram::{AddressBus, SUPERVISOR_DATA};
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.set_max_exception_nesting(Some(10));
+    /// // TRAP #0 with the TRAP #0 vector pointing back at it
+    /// cpu.load_and_run(&[0x4e, 0x40], 0x1000, 0x1000, 0);
+    /// cpu.mem.write_long(SUPERVISOR_DATA, 32 * 4, 0x1000);
+    /// cpu.execute(100_000);
+    /// assert_eq!(ProcessingState::Halted, cpu.processing_state);
+    /// ```
+    pub fn set_max_exception_nesting(&mut self, depth: Option<u32>) {
+        self.max_exception_nesting = depth;
     }
-    pub fn read_data_byte(&mut self, address: u32) -> Result<u32> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        self.pending_wait_cycles += self.mem.wait_cycles(address, 1, false);
-        Ok(self.mem.read_byte(address_space, address))
+
+    /// Returns the exception nesting limit, see [`set_max_exception_nesting`](Self::set_max_exception_nesting).
+    pub fn max_exception_nesting(&self) -> Option<u32> {
+        self.max_exception_nesting
     }
-    pub fn read_program_byte(&mut self, address: u32) -> Result<u32> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        self.pending_wait_cycles += self.mem.wait_cycles(address, 1, false);
-        Ok(self.mem.read_byte(address_space, address))
+
+    /// Reports writes to recently fetched code.
+    ///
+    /// From now on the core remembers the addresses of the last
+    /// [`SMC_FETCHES_TRACKED`] instruction prefetches, each covering a long
+    /// word, and reports every write that overlaps one of them to
+    /// [`Callbacks::self_modifying_write`]. This helps finding programs that
+    /// overwrite their own code, intentionally or not. Only code fetched
+    /// after enabling is tracked; enabling again forgets it.
+    pub fn enable_smc_detection(&mut self) {
+        self.fetched_code = Some(VecDeque::with_capacity(SMC_FETCHES_TRACKED));
     }
-    pub fn write_data_byte(&mut self, address: u32, value: u32) -> Result<()> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        self.pending_wait_cycles += self.mem.wait_cycles(address, 1, true);
-        self.mem.write_byte(address_space, address, value);
-        Ok(())
+
+    /// Stops reporting writes to fetched code, see [`enable_smc_detection`](Self::enable_smc_detection).
+    pub fn disable_smc_detection(&mut self) {
+        self.fetched_code = None;
     }
-    pub fn write_program_byte(&mut self, address: u32, value: u32) -> Result<()> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        self.pending_wait_cycles += self.mem.wait_cycles(address, 1, true);
-        self.mem.write_byte(address_space, address, value);
-        Ok(())
+
+    /// Reports execution of uninitialized memory.
+    ///
+    /// From now on every instruction fetched from an address the bus
+    /// reports as never written (see [`AddressBus::is_initialized`]), or
+    /// from one of `invalid_regions`, is reported to
+    /// [`Callbacks::execute_uninitialized`] before it executes. This catches
+    /// runaway jumps early, instead of after executing garbage. Regions are
+    /// on the 24-bit address bus. [Trampolines](Self::set_trampoline) are
+    /// not checked.
+    pub fn enable_uninitialized_execution_check(&mut self, invalid_regions: Vec<Range<u32>>) {
+        self.invalid_code_regions = Some(invalid_regions);
     }
-    pub fn read_data_word(&mut self, address: u32) -> Result<u32> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        if address & 1 > 0 {
-            Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
-        } else {
-            self.pending_wait_cycles += self.mem.wait_cycles(address, 2, false);
-            Ok(self.mem.read_word(address_space, address))
-        }
+
+    /// Stops reporting execution of uninitialized memory, see
+    /// [`enable_uninitialized_execution_check`](Self::enable_uninitialized_execution_check).
+    pub fn disable_uninitialized_execution_check(&mut self) {
+        self.invalid_code_regions = None;
     }
-    pub fn read_program_word(&mut self, address: u32) -> Result<u32> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        if address & 1 > 0 {
-            Err(Exception::AddressError {address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
-        } else {
-            self.pending_wait_cycles += self.mem.wait_cycles(address, 2, false);
-            Ok(self.mem.read_word(address_space, address))
-        }
+
+    /// Starts recording which addresses in `[base, base + size)` instructions
+    /// are executed from.
+    ///
+    /// Allocates a [`Coverage`](coverage::Coverage) bitmap of one bit per
+    /// byte, `size / 8` bytes in all, and marks the address of every
+    /// instruction executed in the range; addresses outside it are not
+    /// tracked. Enabling again starts over with an empty bitmap.
+    pub fn enable_coverage(&mut self, base: u32, size: u32) {
+        self.coverage = Some(coverage::Coverage::new(base, size));
     }
-    pub fn write_data_word(&mut self, address: u32, value: u32) -> Result<()> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        if address & 1 > 0 {
-            Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
-        } else {
-            self.pending_wait_cycles += self.mem.wait_cycles(address, 2, true);
-            self.mem.write_word(address_space, address, value);
-            Ok(())
-        }
+
+    /// Stops recording coverage and frees the bitmap.
+    pub fn disable_coverage(&mut self) {
+        self.coverage = None;
     }
-    pub fn write_program_word(&mut self, address: u32, value: u32) -> Result<()> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        if address & 1 > 0 {
-            Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
-        } else {
-            self.pending_wait_cycles += self.mem.wait_cycles(address, 2, true);
-            self.mem.write_word(address_space, address, value);
-            Ok(())
-        }
+
+    /// Returns the coverage recorded since [`enable_coverage`](Self::enable_coverage),
+    /// or `None` if it is not enabled.
+    pub fn coverage(&self) -> Option<&coverage::Coverage> {
+        self.coverage.as_ref()
     }
-    pub fn read_data_long(&mut self, address: u32) -> Result<u32> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        if address & 1 > 0 {
-            Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
-        } else {
-            self.pending_wait_cycles += self.mem.wait_cycles(address, 4, false);
-            Ok(self.mem.read_long(address_space, address))
+
+    fn executes_uninitialized(&self, pc: u32) -> bool {
+        match &self.invalid_code_regions {
+            Some(regions) => !self.mem.is_initialized(pc) ||
+                regions.iter().any(|range| range.contains(&(pc & ADDRBUS_MASK))),
+            None => false,
         }
     }
-    pub fn read_program_long(&mut self, address: u32) -> Result<u32> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        if address & 1 > 0 {
-            Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
-        } else {
-            self.pending_wait_cycles += self.mem.wait_cycles(address, 4, false);
-            Ok(self.mem.read_long(address_space, address))
-        }
+
+    /// Enables or disables capturing the memory accesses of each instruction,
+    /// see [`last_instruction_accesses`](Self::last_instruction_accesses).
+    /// Disabled by default.
+    pub fn set_access_capture(&mut self, enabled: bool) {
+        self.instruction_accesses = if enabled { Some(Vec::new()) } else { None };
     }
-    pub fn write_data_long(&mut self, address: u32, value: u32) -> Result<()> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        if address & 1 > 0 {
-            Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
-        } else {
-            self.pending_wait_cycles += self.mem.wait_cycles(address, 4, true);
-            self.mem.write_long(address_space, address, value);
-            Ok(())
-        }
+
+    /// Returns the memory accesses of the last instruction, in the order
+    /// they were made, while access capture is enabled.
+    ///
+    /// The list includes instruction prefetches, operand reads and writes,
+    /// and, when the instruction caused an exception or was an interrupt,
+    /// the stacking, vector fetch and interrupt acknowledge accesses. It is
+    /// replaced when the next instruction starts, and empty when capture is
+    /// disabled. Prefetches are long word reads of the aligned long word
+    /// holding PC, as r68k models the prefetch queue, or word reads without
+    /// [prefetch emulation](Self::set_prefetch_emulation); a long word
+    /// access is listed once even though the 68000 performs it in two bus
+    /// cycles.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::{AccessType, BusAccess, Cpu};
+    /// use r68k::ram::SUPERVISOR_DATA;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.set_access_capture(true);
+    /// // MOVE.W (A0), D0
+    /// cpu.load_and_run(&[0x30, 0x10], 0x1000, 0x1000, 0);
+    /// cpu.dar[8] = 0x2000;
+    /// cpu.execute1();
+    /// let last = *cpu.last_instruction_accesses().last().unwrap();
+    /// assert_eq!(BusAccess { address_space: SUPERVISOR_DATA, address: 0x2000, size: 2, access_type: AccessType::Read }, last);
+    /// ```
+    pub fn last_instruction_accesses(&self) -> &[BusAccess] {
+        self.instruction_accesses.as_deref().unwrap_or(&[])
     }
-    pub fn write_program_long(&mut self, address: u32, value: u32) -> Result<()> {
-        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        if address & 1 > 0 {
-            Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
-        } else {
-            self.pending_wait_cycles += self.mem.wait_cycles(address, 4, true);
-            self.mem.write_long(address_space, address, value);
-            Ok(())
+
+    fn track_fetch(&mut self, address: u32) {
+        if let Some(fetched) = &mut self.fetched_code {
+            if let Some(index) = fetched.iter().position(|&a| a == address) {
+                fetched.remove(index);
+            } else if fetched.len() == SMC_FETCHES_TRACKED {
+                fetched.pop_front();
+            }
+            fetched.push_back(address);
         }
     }
-    pub fn jump(&mut self, pc: u32) {
-        self.pc = pc;
-    }
-    pub fn jump_vector(&mut self, vector: u8) {
-        let vector_address = u32::from(vector) << 2;
-        self.pc = self.read_data_long(vector_address).unwrap();
+
+    /// Adds a data watchpoint covering `size` bytes starting at `address`.
+    ///
+    /// When an instruction (or exception processing) reads or writes any
+    /// byte in the range, as selected by `on_read` and `on_write`, execution
+    /// stops after that instruction completes and [`execute()`](Self::execute)
+    /// returns the cycles consumed so far. The access is reported by
+    /// [`last_watchpoint_hit()`](Self::last_watchpoint_hit).
+    ///
+    /// Instruction fetches do not trigger watchpoints.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::{Cpu, AccessType};
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.add_watchpoint(0x2000, 4, true, false);
+    /// // MOVE.L D0,$2000 ; NOP
+    /// cpu.load_and_run(&[0x23, 0xc0, 0x00, 0x00, 0x20, 0x00, 0x4e, 0x71], 0x1000, 0x1000, 1000);
+    ///
+    /// let hit = cpu.last_watchpoint_hit().unwrap();
+    /// assert_eq!(AccessType::Write, hit.access_type);
+    /// assert_eq!(0x1006, cpu.pc);
+    /// ```
+    pub fn add_watchpoint(&mut self, address: u32, size: u8, on_write: bool, on_read: bool) {
+        self.watchpoints.push(Watchpoint { address, size, on_write, on_read });
     }
-    pub fn ensure_supervisor_mode(&mut self) -> u16 {
-        let backup_sr = self.status_register();
-        // if in user mode, swap stack pointers!
-        if self.s_flag == SFLAG_CLEAR {
-            self.inactive_usp = self.dar[15];
-            self.dar[15] = self.inactive_ssp;
-        }
-        // enter supervisor mode
-        self.s_flag = SFLAG_SET;
-        backup_sr
+
+    /// Removes all watchpoints.
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
     }
-    pub fn handle_address_error(&mut self, bad_address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace) -> Cycles
-    {
-        if processing_state == ProcessingState::Group0Exception {
-            self.processing_state = ProcessingState::Halted;
-            return Cycles(0);
-        }
-        self.processing_state = ProcessingState::Group0Exception;
-        let backup_sr = self.ensure_supervisor_mode();
 
-        // Bus error stack frame (68000 only).
-        let (pc, ir) = (self.pc, self.ir);
-        self.push_32(pc);
-        self.push_16(backup_sr);
-        self.push_16(ir);
-        self.push_32(bad_address);    /* access address */
-        /* 0 0 0 0 0 0 0 0 0 0 0 R/W I/N FC
-         * R/W  0 = write, 1 = read
-         * I/N  0 = instruction, 1 = not
-         * FC   3-bit function code
-         */
-        let access_info = match access_type {AccessType::Read => 0b10000, _ => 0 } |
-            if processing_state.instruction_processing() { 0 } else { 0b01000 } |
-            (address_space.fc() as u16);
-        self.push_16(access_info);
-        self.jump_vector(EXCEPTION_ADDRESS_ERROR);
-        Cycles(50)
+    /// Returns the most recent watchpoint hit, if any.
+    pub fn last_watchpoint_hit(&self) -> Option<WatchpointHit> {
+        self.last_watchpoint_hit
     }
-    pub fn handle_unimplemented_instruction(&mut self, pc: u32, vector: u8) -> Cycles {
-        // somewhat unclear if the unimplemented instruction exceptions
-        // are Group 1 or 2 exceptions. They are mentioned together with
-        // "illegal instruction" which is clearly defined as a group 1
-        // exception,  but the text ("6.3.6 Illegal and Unimplemented
-        // Instructions" in the M68000UM) mentions that illegal
-        // instructions push a group 2 stack frame. On the 68000 G1 and
-        // G2 exception stack frames are identical, so maybe it doesn't
-        // really matter. EASy68k considers them group 2 exceptions. For
-        // the time being, we do too.
-        self.handle_exception(ProcessingState::Group2Exception, pc, vector, 34)
+
+    /// Runs `handler` instead of the code at `address`.
+    ///
+    /// When execution reaches `address`, the handler is called in place of
+    /// the instruction there, and can read and write registers and memory
+    /// through the core it is passed. The core then returns as if an `RTS`
+    /// had been executed, popping the return address from the stack, and
+    /// charges the 16 cycles of the `RTS`. This allows replacing a
+    /// subroutine, e.g. a slow ROM routine, with a native implementation.
+    ///
+    /// A pending interrupt is taken before the handler runs. Setting a
+    /// trampoline at an address that already has one replaces it, also
+    /// from within the handler, which can remove itself as well, e.g. to
+    /// run only once. Trampolines are not carried over to clones of the
+    /// core.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// // multiplies D0 by D1, returning the result in D0
+    /// cpu.set_trampoline(0x2000, Box::new(|core: &mut Cpu| core.dar[0] *= core.dar[1]));
+    /// cpu.dar[0] = 6;
+    /// cpu.dar[1] = 7;
+    /// // JSR $2000 ; STOP #$2700
+    /// cpu.load_and_run(&[0x4e, 0xb8, 0x20, 0x00, 0x4e, 0x72, 0x27, 0x00], 0x1000, 0x1000, 1000);
+    /// assert_eq!(42, cpu.dar[0]);
+    /// assert_eq!(0x1008, cpu.pc);
+    /// ```
+    pub fn set_trampoline(&mut self, address: u32, handler: Trampoline<T, A>) {
+        self.remove_trampoline(address);
+        self.trampolines.push((address, Some(handler)));
     }
-    pub fn handle_illegal_instruction(&mut self, pc: u32) -> Cycles {
-        self.handle_exception(ProcessingState::Group1Exception, pc, EXCEPTION_ILLEGAL_INSTRUCTION, 34)
+
+    /// Removes the trampoline at `address`, if any.
+    pub fn remove_trampoline(&mut self, address: u32) {
+        self.trampolines.retain(|(at, _)| *at != address);
     }
-    pub fn handle_privilege_violation(&mut self, pc: u32) -> Cycles {
-        self.handle_exception(ProcessingState::Group1Exception, pc, EXCEPTION_PRIVILEGE_VIOLATION, 34)
+
+    /// Removes all trampolines.
+    pub fn clear_trampolines(&mut self) {
+        self.trampolines.clear();
     }
-    pub fn handle_trap(&mut self, trap: u8, cycles: i32) -> Cycles {
-        let pc = self.pc;
-        self.handle_exception(ProcessingState::Group2Exception, pc, trap, cycles)
+
+    /// Runs `handler` instead of the built-in handler whenever `opcode` is
+    /// executed.
+    ///
+    /// The handler is called once the operation word has been fetched, with
+    /// PC pointing just past it and `ir` holding `opcode`. It
+    /// must read any extension words itself and leave PC at the next
+    /// instruction. The cycles it returns are charged for the instruction
+    /// as they are, so keeping cycle accuracy is up to the handler, and it
+    /// cannot raise exceptions. Interrupts and tracing happen around it as
+    /// for any instruction. This allows prototyping instruction set
+    /// extensions, or high level emulation of line A/F traps.
+    ///
+    /// Overriding an opcode that already has an override replaces it, also
+    /// from within the handler, which can remove itself as well. Overrides
+    /// are not carried over to clones of the core.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::{Cpu, Cycles};
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// // make line A opcode $a000 swap D0 and D1
+    /// cpu.override_opcode(0xa000, Box::new(|core: &mut Cpu| {
+    ///     core.dar.swap(0, 1);
+    ///     Cycles(8)
+    /// }));
+    /// cpu.dar[0] = 1;
+    /// // DC.W $a000 ; STOP #$2700
+    /// cpu.load_and_run(&[0xa0, 0x00, 0x4e, 0x72, 0x27, 0x00], 0x1000, 0x1000, 1000);
+    /// assert_eq!((0, 1), (cpu.dar[0], cpu.dar[1]));
+    /// ```
+    pub fn override_opcode(&mut self, opcode: u16, handler: OpcodeOverride<T, A>) {
+        self.remove_opcode_override(opcode);
+        self.opcode_overrides.push((opcode, Some(handler)));
     }
 
-    pub fn handle_exception(&mut self, new_state: ProcessingState, pc: u32, vector: u8, cycles: i32) -> Cycles {
-        self.processing_state = new_state;
-        let backup_sr = self.ensure_supervisor_mode();
+    /// Restores the built-in handler for `opcode`.
+    pub fn remove_opcode_override(&mut self, opcode: u16) {
+        self.opcode_overrides.retain(|(overridden, _)| *overridden != opcode);
+    }
 
-        // Group 1 and 2 stack frame (68000 only).
-        self.push_32(pc);
-        self.push_16(backup_sr);
+    /// Restores the built-in handlers for all opcodes.
+    pub fn clear_opcode_overrides(&mut self) {
+        self.opcode_overrides.clear();
+    }
 
-        self.jump_vector(vector);
-        Cycles(cycles)
+    /// Stops taking snapshots for [`step_back`](Self::step_back) and drops
+    /// the ones taken.
+    pub fn disable_reverse_debugging(&mut self) {
+        self.history = None;
     }
 
-    pub fn handle_interrupt(&mut self, irq_level: u8, vector: u8) -> Cycles {
-        let pc = self.pc;
-        self.processing_state = ProcessingState::Group1Exception;
-        let backup_sr = self.ensure_supervisor_mode();
-        // new mask set here, in order to exclude from backup_sr
-        self.int_mask = u32::from(irq_level) << 8;
-        self.irq_level = irq_level;
+    fn take_snapshot_if_due(&mut self) {
+        let capture = match &self.history {
+            Some(history) if history.snapshot_due() => history.capture,
+            _ => return,
+        };
+        let position = self.history.as_ref().map_or(0, |history| history.position);
+        let snapshot = capture(self, position);
+        if let Some(history) = &mut self.history {
+            history.push(snapshot);
+        }
+    }
 
-        // Musashi jumps first, and stacks later for interrupts,
-        // but the other way around for exceptions
-        self.jump_vector(vector);
+    // Runs the trampoline at PC, if there is one and no interrupt is due,
+    // and returns from it.
+    fn run_trampoline(&mut self) -> Option<Cycles> {
+        if self.trampolines.is_empty() || self.pending_interrupt().is_some() {
+            return None;
+        }
+        let address = self.pc;
+        // a trampoline that is already running is not entered again
+        let mut handler = self.trampolines.iter_mut().find(|(at, _)| *at == address)?.1.take()?;
+        handler(self);
+        // put the handler back, unless it has removed or replaced itself
+        if let Some((_, slot @ None)) = self.trampolines.iter_mut().find(|(at, _)| *at == address) {
+            *slot = Some(handler);
+        }
+        let pc = self.pop_32();
+        self.jump(pc);
+        Some(Cycles(16))
+    }
 
-        // Group 1 and 2 stack frame (68000 only).
-        self.push_32(pc);
-        self.push_16(backup_sr);
+    // Runs the override for `opcode`, if there is one.
+    fn run_opcode_override(&mut self, opcode: u16) -> Option<Cycles> {
+        if self.opcode_overrides.is_empty() {
+            return None;
+        }
+        // an override that is already running is not entered again
+        let mut handler = self.opcode_overrides.iter_mut().find(|(overridden, _)| *overridden == opcode)?.1.take()?;
+        let cycles = handler(self);
+        // put the handler back, unless it has removed or replaced itself
+        if let Some((_, slot @ None)) = self.opcode_overrides.iter_mut().find(|(overridden, _)| *overridden == opcode) {
+            *slot = Some(handler);
+        }
+        Some(cycles)
+    }
 
-        // 44 cycles for an interrupt according to MC68000UM, Table 8-14
-        // The interrupt acknowledge cycle is assumed to take four clock periods
-        Cycles(44)
+    /// Rounds a cycle count up to the configured granularity boundary.
+    #[inline]
+    fn align_cycles(&self, cycles: Cycles) -> Cycles {
+        if self.cycle_granularity == 1 {
+            cycles
+        } else {
+            // Round up: (n + g - 1) / g * g
+            let n = cycles.0;
+            let g = self.cycle_granularity;
+            Cycles(((n + g - 1) / g) * g)
+        }
     }
-    fn stopped_with_pending_interrups(&mut self) -> bool {
-        self.processing_state == ProcessingState::Stopped && self.pending_interrupt().is_some()
+
+    /// Performs a CPU reset, loading initial SSP and PC from the vector table.
+    ///
+    /// This simulates a hardware reset:
+    /// 1. Enters supervisor mode
+    /// 2. Sets interrupt mask to highest priority
+    /// 3. Reads initial SSP from address 0x000000
+    /// 4. Reads initial PC from address 0x000004
+    /// 5. Enters normal processing state
+    ///
+    /// The memory must contain valid reset vectors at addresses 0-7.
+    pub fn reset(&mut self) {
+        self.processing_state = ProcessingState::Group0Exception;
+        self.s_flag = SFLAG_SET;
+        self.t1_flag = 0;
+        self.int_mask = CPU_SR_INT_MASK;
+        self.exception_nesting = 0;
+        self.vbr = 0;
+        self.prefetch_addr = 1; // non-zero, or the prefetch won't kick in
+        self.jump(0);
+        // these reads cannot possibly cause AddressError, as we forced PC to 0
+        self.dar[15] = self.read_imm_u32().unwrap();
+        let new_pc = self.read_imm_u32().unwrap();
+        self.jump(new_pc);
+        self.processing_state = ProcessingState::Normal;
     }
-    fn can_execute(&mut self) -> bool {
-        self.processing_state.running() || self.stopped_with_pending_interrups()
+    /// Performs a CPU reset to an explicit SSP and PC, without reading the vector table.
+    ///
+    /// The CPU ends up in the same state as after [`reset()`](Self::reset):
+    /// supervisor mode with the interrupt mask at 7 and tracing off (the
+    /// upper byte of SR is `0x2700`) and normal processing, but A7 and the program
+    /// counter are set to `ssp` and `pc` instead of being fetched from
+    /// addresses 0 and 4. This is convenient for tests and for hosts that
+    /// don't map the vector table at reset.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.reset_to(0x8000, 0x1000);
+    /// assert_eq!(0x1000, cpu.pc);
+    /// assert_eq!(0x8000, cpu.dar[15]);
+    /// assert_eq!(0x2700, cpu.status_register() & 0xff00);
+    /// ```
+    pub fn reset_to(&mut self, ssp: u32, pc: u32) {
+        self.s_flag = SFLAG_SET;
+        self.t1_flag = 0;
+        self.int_mask = CPU_SR_INT_MASK;
+        self.exception_nesting = 0;
+        self.vbr = 0;
+        self.prefetch_addr = 1; // invalidate, memory may have changed
+        self.dar[15] = ssp;
+        self.jump(pc);
+        self.processing_state = ProcessingState::Normal;
     }
-    fn pending_interrupt(&self) -> Option<u8> {
-        let old_level = self.irq_level;
-        let new_level = self.int_ctrl.highest_priority();
-        let edge_triggered_nmi = old_level != 7 && new_level == 7;
-        if u32::from(new_level) << 8 > self.int_mask || edge_triggered_nmi {
-            Some(new_level)
+    /// Returns the extend flag as 0 or 1.
+    pub fn x_flag_as_1(&self) -> u32 {
+        (self.x_flag>>8)&1
+    }
+
+    /// Returns the full 16-bit status register value.
+    ///
+    /// The status register contains:
+    /// - Bits 15-13: Trace mode (T1, T0) and reserved
+    /// - Bit 13: Supervisor mode (S)
+    /// - Bits 10-8: Interrupt priority mask (I2, I1, I0)
+    /// - Bit 4: Extend flag (X)
+    /// - Bit 3: Negative flag (N)
+    /// - Bit 2: Zero flag (Z)
+    /// - Bit 1: Overflow flag (V)
+    /// - Bit 0: Carry flag (C)
+    pub fn status_register(&self) -> u16 {
+        (self.t1_flag                       |
+        (self.s_flag << 11)                 |
+        self.int_mask                        |
+        ((self.x_flag & XFLAG_SET) >> 4)    |
+        ((self.n_flag & NFLAG_SET) >> 4)    |
+        ((not1!(self.not_z_flag))  << 2)    |
+        ((self.v_flag & VFLAG_SET) >> 6)    |
+        ((self.c_flag & CFLAG_SET) >> 8)) as u16
+    }
+    /// Returns the condition code register (lower 8 bits of status register).
+    ///
+    /// Contains: X, N, Z, V, C flags.
+    pub fn condition_code_register(&self) -> u16 {
+        self.status_register() & 0xff
+    }
+
+    /// Returns the current user stack pointer value.
+    ///
+    /// In supervisor mode, returns the saved USP. In user mode, returns A7.
+    pub fn usp(&self) -> u32 {
+        if self.s_flag > 0 {
+            self.inactive_usp
         } else {
-            None
+            self.dar[15]
         }
     }
-    pub fn read_instruction(&mut self) -> Result<u16> {
-        // first check for interrupts
-        if let Some(irq) = self.pending_interrupt() {
-            let vector = self.int_ctrl.acknowledge_interrupt(irq).unwrap_or(SPURIOUS_INTERRUPT);
-            Err(Exception::Interrupt(irq, vector))
+
+    /// Returns the current supervisor stack pointer value.
+    ///
+    /// In supervisor mode, returns A7. In user mode, returns the saved SSP.
+    pub fn ssp(&self) -> u32 {
+        if self.s_flag > 0 {
+            self.dar[15]
         } else {
-            // not interrupted, read instruction from PC
-            self.read_imm_u16()
+            self.inactive_ssp
         }
     }
-    /// Executes a single instruction.
+
+    /// Decodes the instruction at `pc` and returns where execution can continue.
     ///
-    /// This is a convenience method equivalent to `execute(1)`.
-    /// Returns the number of cycles consumed by the instruction.
-    pub fn execute1(&mut self) -> Cycles {
-        self.execute(1)
+    /// See [`flow::successors`] for details. Memory is read directly, without
+    /// wait states, watchpoints or bus cycle reporting.
+    pub fn successors(&self, address_space: AddressSpace, pc: u32) -> flow::Successors {
+        flow::successors(&self.mem, address_space, pc)
     }
 
-    /// Executes instructions for up to the specified number of cycles.
+    /// Decodes the instruction at `pc` and returns its size, operand count,
+    /// control flow and privilege.
     ///
-    /// The CPU will execute instructions until the cycle budget is exhausted
-    /// or the CPU enters a non-running state (Stopped or Halted).
+    /// See [`info::instruction_info`] for details. Memory is read directly,
+    /// without wait states, watchpoints or bus cycle reporting.
+    pub fn instruction_info(&self, address_space: AddressSpace, pc: u32) -> Option<info::InstructionInfo> {
+        info::instruction_info(&self.mem, address_space, pc)
+    }
+
+    /// Decodes an effective address field with extension words at `pc`.
     ///
-    /// # Arguments
+    /// See [`operand::decode_ea`] for details. Memory is read directly,
+    /// without wait states, watchpoints or bus cycle reporting.
+    pub fn decode_ea(&self, address_space: AddressSpace, pc: u32, mode: u8, reg: u8, size: u8) -> (operand::Operand, u32) {
+        operand::decode_ea(&self.mem, address_space, pc, mode, reg, size)
+    }
+
+    /// Disassembles the instruction at `pc` in `style` and returns the text
+    /// and the instruction length in bytes.
     ///
-    /// * `cycles` - Maximum number of cycles to execute
+    /// See [`disassembler::disassemble_with`] for details. Memory is read
+    /// directly, without wait states, watchpoints or bus cycle reporting.
+    pub fn disassemble_with(&self, address_space: AddressSpace, pc: u32, style: DisasmStyle) -> (String, u32) {
+        disassembler::disassemble_with(&self.mem, address_space, pc, style)
+    }
+
+    /// Decodes the instruction at `pc` and returns the registers and flags it uses.
     ///
-    /// # Returns
+    /// See [`effects::register_effects`] for details. Memory is read directly,
+    /// without wait states, watchpoints or bus cycle reporting.
+    pub fn register_effects(&self, address_space: AddressSpace, pc: u32) -> effects::RegisterEffects {
+        effects::register_effects(&self.mem, address_space, pc)
+    }
+
+    /// Returns the top `depth` long words of the supervisor stack.
+    ///
+    /// Reads upwards from the SSP regardless of the current mode, using the
+    /// supervisor data address space. The first element is the long word at
+    /// the SSP. Memory is read directly, without wait states, watchpoints or
+    /// bus cycle reporting.
+    pub fn dump_supervisor_stack(&self, depth: usize) -> Vec<u32> {
+        self.dump_stack(self.ssp(), SUPERVISOR_DATA, depth)
+    }
+
+    /// Returns the top `depth` long words of the user stack.
+    ///
+    /// Like [`dump_supervisor_stack`](Self::dump_supervisor_stack), but reads
+    /// from the USP using the user data address space.
+    pub fn dump_user_stack(&self, depth: usize) -> Vec<u32> {
+        self.dump_stack(self.usp(), USER_DATA, depth)
+    }
+
+    /// Returns all 256 exception vectors.
     ///
-    /// The actual number of cycles consumed. This may exceed the requested
-    /// amount if the last instruction takes more cycles than remaining.
+    /// The 68000 has no vector base register, so the table is always the
+    /// 1024 bytes at address 0, read as long words from the supervisor data
+    /// address space. Memory is read directly, without wait states,
+    /// watchpoints or bus cycle reporting. Use [`vector_name()`] to label
+    /// the entries.
     ///
     /// # Example
     ///
     /// ```rust
-    /// use r68k::cpu::ConfiguredCore;
-    /// use r68k::ram::PagedMem;
-    /// use r68k::interrupts::AutoInterruptController;
+    /// use r68k::cpu::{Cpu, vector_name};
     ///
-    /// let memory = PagedMem::new(0);
-    /// let mut cpu = ConfiguredCore::new_with(0, AutoInterruptController::new(), memory);
-    /// cpu.reset();
-    ///
-    /// // Execute for approximately 1000 cycles
-    /// let actual_cycles = cpu.execute(1000);
-    /// println!("Consumed {} cycles", actual_cycles.0);
+    /// let cpu = Cpu::new(0);
+    /// for (vector, handler) in cpu.dump_vector_table().iter().enumerate().take(12) {
+    ///     println!("{:3} {:08x} {}", vector, handler, vector_name(vector as u8));
+    /// }
     /// ```
-    pub fn execute(&mut self, cycles: i32) -> Cycles {
-        self.execute_with_state(cycles, &mut EmulateAllExceptions)
+    pub fn dump_vector_table(&self) -> [u32; 256] {
+        let mut table = [0; 256];
+        for (vector, entry) in table.iter_mut().enumerate() {
+            *entry = self.mem.read_long(SUPERVISOR_DATA, vector as u32 * 4);
+        }
+        table
     }
 
-    /// Executes instructions with custom exception handling.
-    ///
-    /// Like [`execute()`](Self::execute), but allows intercepting exceptions
-    /// via a [`Callbacks`] implementation. This is useful for implementing
-    /// OS traps, debuggers, or custom I/O.
-    ///
-    /// # Arguments
+    fn dump_stack(&self, sp: u32, address_space: AddressSpace, depth: usize) -> Vec<u32> {
+        (0..depth as u32)
+            .map(|i| self.mem.read_long(address_space, sp.wrapping_add(i * 4)))
+            .collect()
+    }
+
+    /// Returns a multi-line report of the execution state, for bug reports
+    /// and for inspecting a hang.
     ///
-    /// * `cycles` - Maximum number of cycles to execute
-    /// * `state` - A [`Callbacks`] implementation for exception handling
+    /// Lists the registers, the status register with decoded
+    /// [`flags`](Self::flags), the processing state, requested interrupts
+    /// and whether the mask blocks them, the disassembly of the next
+    /// [`DEBUG_REPORT_INSTRUCTIONS`] instructions at PC and the top
+    /// [`DEBUG_REPORT_STACK_DEPTH`] long words of the active stack. Memory
+    /// is read directly, without wait states, watchpoints or bus cycle
+    /// reporting.
     ///
     /// # Example
     ///
     /// ```rust
-    /// use r68k::cpu::{ConfiguredCore, Callbacks, Core, Cycles, Exception, Result};
-    /// use r68k::ram::PagedMem;
-    /// use r68k::interrupts::AutoInterruptController;
-    ///
-    /// struct MyHandler;
-    /// impl Callbacks for MyHandler {
-    ///     fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
-    ///         Err(ex) // Let CPU handle all exceptions
-    ///     }
-    /// }
+    /// use r68k::demo::minimal_system;
     ///
-    /// let memory = PagedMem::new(0);
-    /// let mut cpu = ConfiguredCore::new_with(0, AutoInterruptController::new(), memory);
-    /// cpu.reset();
-    ///
-    /// let mut handler = MyHandler;
-    /// cpu.execute_with_state(1000, &mut handler);
+    /// // MOVEQ #42, D0
+    /// let mut cpu = minimal_system(&[0x70, 0x2a]);
+    /// cpu.execute1();
+    /// println!("{}", cpu.debug_report());
+    /// assert!(cpu.debug_report().contains("D0 0000002a"));
     /// ```
-    pub fn execute_with_state<S: Callbacks>(&mut self, cycles: i32, state: &mut S) -> Cycles {
-        let cycles = Cycles(cycles);
-        let mut remaining_cycles = cycles;
-        while remaining_cycles.any() && self.can_execute() {
-            // Reset wait state accumulator for this instruction
-            self.pending_wait_cycles = 0;
-            // Read an instruction from PC (increments PC by 2)
-            let result = self.read_instruction().and_then(|opcode| {
-                    self.ir = opcode;
-                    // Call instruction handler to mutate Core accordingly
-                    self.instruction_set[opcode as usize](self)
-                });
-            let cycles_used = match result {
-                Ok(cycles_used) => cycles_used,
-                Err(ex) => {
-                    match state.exception_callback(self, ex) {
-                        Ok(cycles_used) => cycles_used,
-                        Err(Exception::AddressError { address, access_type, processing_state, address_space }) =>
-                            self.handle_address_error(address, access_type, processing_state, address_space),
-                        Err(Exception::IllegalInstruction(_, pc)) =>
-                            self.handle_illegal_instruction(pc),
-                        Err(Exception::UnimplementedInstruction(_, pc, vector)) =>
-                            self.handle_unimplemented_instruction(pc, vector),
-                        Err(Exception::Trap(num, ea_calculation_cycles)) =>
-                            self.handle_trap(num, ea_calculation_cycles),
-                        Err(Exception::PrivilegeViolation(_, pc)) =>
-                            self.handle_privilege_violation(pc),
-                        Err(Exception::Interrupt(irq, vec)) =>
-                            self.handle_interrupt(irq, vec),
-                    }
-                }
-            };
-            // Add accumulated wait state cycles and apply granularity alignment
-            let total_cycles = Cycles(cycles_used.0 + self.pending_wait_cycles);
-            remaining_cycles = remaining_cycles - self.align_cycles(total_cycles);
+    pub fn debug_report(&self) -> String {
+        use std::fmt::Write;
+        let mut report = String::new();
+        for (bank, first) in [('D', 0), ('A', 8)].iter() {
+            for row in [0, 4].iter() {
+                let registers: Vec<String> = (0..4)
+                    .map(|i| format!("{}{} {:08x}", bank, row + i, self.dar[first + row + i]))
+                    .collect();
+                writeln!(report, "{}", registers.join("  ")).unwrap();
+            }
         }
-        if self.processing_state.running() {
-            cycles - remaining_cycles
+        writeln!(report, "PC {:08x}  SR {:04x} ({})  USP {:08x}  SSP {:08x}",
+            self.pc, self.status_register(), self.flags(), self.usp(), self.ssp()).unwrap();
+        writeln!(report, "State: {:?}", self.processing_state).unwrap();
+        let level = self.int_ctrl.highest_priority();
+        match (level, self.pending_interrupt()) {
+            (0, _) => writeln!(report, "Interrupts: none requested"),
+            (_, Some(level)) => writeln!(report, "Interrupts: level {} pending", level),
+            (level, None) => writeln!(report, "Interrupts: level {} requested, masked", level),
+        }.unwrap();
+        let (code_space, stack_space) = if self.s_flag != 0 {
+            (SUPERVISOR_PROGRAM, SUPERVISOR_DATA)
         } else {
-            // if not running, consume all available cycles
-            // including overconsumed cycles
-            let adjust = if remaining_cycles.0 < 0 { remaining_cycles } else { Cycles(0) };
-            cycles - adjust
+            (USER_PROGRAM, USER_DATA)
+        };
+        writeln!(report, "Code:").unwrap();
+        let mut pc = self.pc;
+        for i in 0..DEBUG_REPORT_INSTRUCTIONS {
+            let (text, length) = disassembler::disassemble(&self.mem, code_space, pc);
+            writeln!(report, "{} {:08x}  {}", if i == 0 { '>' } else { ' ' }, pc, text).unwrap();
+            pc = pc.wrapping_add(length);
         }
-    }
-}
-
-impl Clone for TestCore {
-    fn clone(&self) -> Self {
-        let mut lm = LoggingMem::new(self.mem.initializer, OpsLogger::new());
-        lm.copy_from(&self.mem);
-        assert_eq!(0, lm.logger.len());
-        TestCore {
-            pc: self.pc, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: self.inactive_ssp, inactive_usp: self.inactive_usp, ir: self.ir, processing_state: self.processing_state,
-            dar: self.dar, mem: lm, instruction_set: ops::instruction_set(),
-            irq_level: 0, int_ctrl: AutoInterruptController::new(),
-            s_flag: self.s_flag, int_mask: self.int_mask, x_flag: self.x_flag, v_flag: self.v_flag, c_flag: self.c_flag, n_flag: self.n_flag, not_z_flag: self.not_z_flag,
-            cycle_granularity: self.cycle_granularity, pending_wait_cycles: 0,
+        writeln!(report, "Stack:").unwrap();
+        let sp = self.dar[STACK_POINTER_REG];
+        for (i, value) in self.dump_stack(sp, stack_space, DEBUG_REPORT_STACK_DEPTH).iter().enumerate() {
+            writeln!(report, "  {:08x}  {:08x}", sp.wrapping_add(4 * i as u32), value).unwrap();
         }
+        report
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::{TestCore, Cycles};
-    use super::ops; //::instruction_set;
-    use crate::ram::{AddressBus, SUPERVISOR_PROGRAM, USER_PROGRAM, USER_DATA};
-    use crate::ram::loggingmem::Operation;
-    use crate::cpu::ops::opcodes;
 
-    #[test]
-    fn new_sets_pc() {
-        let cpu = TestCore::new(256);
-        assert_eq!(256, cpu.pc);
+    /// Sets CPU flags from a status register value.
+    ///
+    /// This updates all flags and the interrupt mask from the given SR value.
+    /// If the supervisor bit changes, the stack pointers are swapped.
+    ///
+    /// Together with [`reset_to()`](Self::reset_to), this puts a core in a
+    /// given mode without executing any code, e.g. for tests;
+    /// [`CoreBuilder`](builder::CoreBuilder) does both when building a core.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.reset_to(0x8000, 0x1000);
+    /// cpu.inactive_usp = 0x4000;
+    /// // user mode, all interrupts unmasked
+    /// cpu.sr_to_flags(0x0000);
+    /// assert_eq!(0x4000, cpu.dar[15]);
+    /// assert_eq!(0x8000, cpu.ssp());
+    /// ```
+    pub fn sr_to_flags(&mut self, sr: u16) {
+        let sr = u32::from(sr & CPU_SR_MASK);
+        let old_sflag = self.s_flag;
+        self.int_mask = sr & CPU_SR_INT_MASK;
+        self.t1_flag =                  sr & TFLAG_SET;
+        self.s_flag =           (sr >> 11) & SFLAG_SET;
+        self.x_flag =            (sr <<  4) & XFLAG_SET;
+        self.n_flag =            (sr <<  4) & NFLAG_SET;
+        self.not_z_flag = not1!(sr & 0b00100);
+        self.v_flag =            (sr <<  6) & VFLAG_SET;
+        self.c_flag =            (sr <<  8) & CFLAG_SET;
+        if old_sflag != self.s_flag {
+            if self.s_flag == SFLAG_SET {
+                self.inactive_usp = self.dar[15];
+                self.dar[15] = self.inactive_ssp;
+            } else {
+                self.inactive_ssp = self.dar[15];
+                self.dar[15] = self.inactive_usp;
+            }
+        }
+        // println!("{} {:016b} {} {}", self.flags(), sr, self.not_z_flag, sr & 0b00100);
+    }
+    /// Sets CPU flags from a condition code register value.
+    ///
+    /// Only updates the lower 8 bits (X, N, Z, V, C), preserving the upper byte.
+    pub fn ccr_to_flags(&mut self, ccr: u16) {
+        let sr = self.status_register();
+        self.sr_to_flags((sr & 0xff00) | (ccr & 0xff));
+    }
+
+    /// Returns a human-readable string representation of the current flags.
+    ///
+    /// Format: `-S7XNZVC` where:
+    /// - S/U: Supervisor/User mode
+    /// - 0-7: Interrupt priority mask
+    /// - X, N, Z, V, C: Flag letters if set, `-` if clear
+    ///
+    /// Example: `-S7X-Z--` means supervisor mode, mask 7, X and Z flags set.
+    pub fn flags(&self) -> String {
+        let sr = self.status_register();
+        let supervisor = (sr >> 13) & 1;
+        let irq_mask = (0x700 & sr) >> 8;
+
+        format!("-{}{}{}{}{}{}{}",
+        if supervisor > 0 {'S'} else {'U'},
+        irq_mask,
+        if 0 < (sr >> 4) & 1 {'X'} else {'-'},
+        if 0 < (sr >> 3) & 1 {'N'} else {'-'},
+        if 0 < (sr >> 2) & 1 {'Z'} else {'-'},
+        if 0 < (sr >> 1) & 1 {'V'} else {'-'},
+        if 0 < (sr     ) & 1 {'C'} else {'-'})
+    }
+    fn prefetch_if_needed(&mut self) -> bool {
+        // does current PC overlap with fetched data
+        let fetched = if self.pc & !3 != self.prefetch_addr {
+            self.prefetch_addr = self.pc & !3;
+            let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+            self.pending_wait_cycles += self.mem.wait_cycles(self.prefetch_addr, 4, false);
+            self.prefetch_data = self.mem.read_long(address_space, self.prefetch_addr);
+            self.record_bus_cycles(address_space, self.prefetch_addr, 4, false);
+            self.track_fetch(self.prefetch_addr);
+            true
+        } else {
+            false
+        };
+        self.pc = self.pc.wrapping_add(2);
+        fetched
+    }
+    /// Reads the word at PC, bypassing the prefetch queue.
+    fn fetch_word(&mut self) -> u16 {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+        self.pending_wait_cycles += self.mem.wait_cycles(self.pc, 2, false);
+        let word = self.mem.read_word(address_space, self.pc) as u16;
+        self.record_bus_cycles(address_space, self.pc, 2, false);
+        self.track_fetch(self.pc & !3);
+        self.pc = self.pc.wrapping_add(2);
+        word
+    }
+    pub fn read_imm_u32(&mut self) -> Result<u32> {
+        if self.pc & 1 > 0 {
+            let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+            return Err(Exception::AddressError{address: self.pc, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
+        }
+        let value = if !self.prefetch_emulation {
+            (u32::from(self.fetch_word()) << 16) | u32::from(self.fetch_word())
+        } else {
+            self.prefetch_if_needed();
+            let prev_prefetch_data = self.prefetch_data;
+            if self.prefetch_if_needed() {
+                (prev_prefetch_data << 16) | (self.prefetch_data >> 16)
+            } else {
+                prev_prefetch_data
+            }
+        };
+        self.fetched_words.push((self.pc.wrapping_sub(4), (value >> 16) as u16));
+        self.fetched_words.push((self.pc.wrapping_sub(2), value as u16));
+        Ok(value)
+    }
+    pub fn read_imm_i16(&mut self) -> Result<i16> {
+        self.read_imm_u16().map(|val| val as i16)
+    }
+    pub fn read_imm_u16(&mut self) -> Result<u16> {
+        // the Musashi read_imm_16 calls cpu_read_long as part of prefetch
+        if self.pc & 1 > 0 {
+            let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+            return Err(Exception::AddressError{address: self.pc, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
+        }
+        let value = if !self.prefetch_emulation {
+            self.fetch_word()
+        } else {
+            self.prefetch_if_needed();
+            ((self.prefetch_data >> ((2 - ((self.pc.wrapping_sub(2)) & 2))<<3)) & 0xffff) as u16
+        };
+        self.fetched_words.push((self.pc.wrapping_sub(2), value));
+        Ok(value)
+    }
+    pub fn push_sp(&mut self) -> u32 {
+         let new_sp = (Wrapping(self.dar[15]) - Wrapping(4)).0;
+         self.dar[15] = new_sp;
+         self.write_data_long(new_sp, new_sp).unwrap();
+         new_sp
+    }
+    pub fn push_32(&mut self, value: u32) -> u32 {
+         let new_sp = (Wrapping(self.dar[15]) - Wrapping(4)).0;
+         self.dar[15] = new_sp;
+         self.write_data_long(new_sp, value).unwrap();
+         new_sp
+    }
+    pub fn pop_32(&mut self) -> u32 {
+        let sp = self.dar[15];
+        let data = self.read_data_long(sp).unwrap();
+        self.dar[15] = sp.wrapping_add(4);
+        data
+    }
+    pub fn push_16(&mut self, value: u16) -> u32 {
+         let new_sp = (Wrapping(self.dar[15]) - Wrapping(2)).0;
+         self.dar[15] = new_sp;
+         self.write_data_word(new_sp, u32::from(value)).unwrap();
+         new_sp
+    }
+    pub fn pop_16(&mut self) -> u16 {
+        let sp = self.dar[15];
+        let data = self.read_data_word(sp).unwrap() as u16;
+        self.dar[15] = sp.wrapping_add(2);
+        data
+    }
+    // Asks the bus whether an instruction's operand access should be
+    // terminated with a bus error. Misaligned accesses are left for the
+    // accessor to report as an address error, which takes priority.
+    fn check_bus_error(&self, address: u32, size: u8, access_type: AccessType, program: bool) -> Result<()> {
+        if size > 1 && address & 1 > 0 {
+            return Ok(());
+        }
+        let address_space = match (self.s_flag != 0, program) {
+            (true, true) => SUPERVISOR_PROGRAM,
+            (true, false) => SUPERVISOR_DATA,
+            (false, true) => USER_PROGRAM,
+            (false, false) => USER_DATA,
+        };
+        if self.mem.bus_error(address_space, address, size, access_type == AccessType::Write) {
+            Err(Exception::BusError{address, access_type, address_space, processing_state: self.processing_state})
+        } else {
+            Ok(())
+        }
+    }
+    fn record_bus_cycles(&mut self, address_space: AddressSpace, address: u32, size: u8, is_write: bool) {
+        if let Some(accesses) = &mut self.instruction_accesses {
+            let access_type = if is_write { AccessType::Write } else { AccessType::Read };
+            accesses.push(BusAccess { address_space, address, size, access_type });
+        }
+        self.bus_cycles.push((address_space, address, is_write));
+        // a long word takes two bus cycles on the 16-bit data bus
+        if size == 4 {
+            self.bus_cycles.push((address_space, address.wrapping_add(2), is_write));
+        }
+    }
+    fn completed_access(&mut self, address_space: AddressSpace, address: u32, size: u8, access_type: AccessType, value: u32) {
+        self.record_bus_cycles(address_space, address, size, access_type == AccessType::Write);
+        if access_type == AccessType::Write {
+            if let Some(fetched) = &self.fetched_code {
+                let end = address.wrapping_add(u32::from(size) - 1);
+                if fetched.iter().any(|&code| code == address & !3 || code == end & !3) {
+                    self.self_modifying_writes.push(address);
+                }
+            }
+        }
+        if self.watchpoints.is_empty() {
+            return;
+        }
+        let start = u64::from(address);
+        let end = start + u64::from(size);
+        let hit = self.watchpoints.iter().find(|wp| {
+            let triggers = match access_type { AccessType::Read => wp.on_read, AccessType::Write => wp.on_write };
+            let wp_start = u64::from(wp.address);
+            triggers && start < wp_start + u64::from(wp.size) && wp_start < end
+        });
+        if let Some(&watchpoint) = hit {
+            self.last_watchpoint_hit = Some(WatchpointHit { watchpoint, address, size, access_type, value });
+            self.watchpoint_triggered = true;
+        }
+    }
+    // MOVES accesses the address space its function code selects, rather
+    // than the current data space. Reserved function codes and CPU space
+    // have no memory behind them, so accesses there end in a bus error.
+    fn alternate_space_access(&mut self, fc: u8, address: u32, size: u8, access_type: AccessType, value: u32) -> Result<u32> {
+        let is_write = access_type == AccessType::Write;
+        let address_space = match AddressSpace::from_fc(u32::from(fc)) {
+            Some(space) if space != CPU_SPACE => space,
+            _ => return Err(Exception::BusError{address, access_type, address_space: CPU_SPACE, processing_state: self.processing_state}),
+        };
+        if size > 1 && address & 1 > 0 {
+            return Err(Exception::AddressError{address, access_type, address_space, processing_state: self.processing_state});
+        }
+        if self.mem.bus_error(address_space, address, size, is_write) {
+            return Err(Exception::BusError{address, access_type, address_space, processing_state: self.processing_state});
+        }
+        self.pending_wait_cycles += self.mem.wait_cycles(address, size, is_write);
+        let value = match (size, is_write) {
+            (1, false) => self.mem.read_byte(address_space, address),
+            (2, false) => self.mem.read_word(address_space, address),
+            (_, false) => self.mem.read_long(address_space, address),
+            (1, true) => { self.mem.write_byte(address_space, address, value); value },
+            (2, true) => { self.mem.write_word(address_space, address, value); value },
+            (_, true) => { self.mem.write_long(address_space, address, value); value },
+        };
+        self.completed_access(address_space, address, size, access_type, value);
+        Ok(value)
+    }
+    pub fn read_data_byte(&mut self, address: u32) -> Result<u32> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
+        self.pending_wait_cycles += self.mem.wait_cycles(address, 1, false);
+        let value = self.mem.read_byte(address_space, address);
+        self.completed_access(address_space, address, 1, AccessType::Read, value);
+        Ok(value)
+    }
+    pub fn read_program_byte(&mut self, address: u32) -> Result<u32> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+        self.pending_wait_cycles += self.mem.wait_cycles(address, 1, false);
+        let value = self.mem.read_byte(address_space, address);
+        self.completed_access(address_space, address, 1, AccessType::Read, value);
+        Ok(value)
+    }
+    pub fn write_data_byte(&mut self, address: u32, value: u32) -> Result<()> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
+        self.pending_wait_cycles += self.mem.wait_cycles(address, 1, true);
+        self.mem.write_byte(address_space, address, value);
+        self.completed_access(address_space, address, 1, AccessType::Write, value);
+        Ok(())
+    }
+    pub fn write_program_byte(&mut self, address: u32, value: u32) -> Result<()> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+        self.pending_wait_cycles += self.mem.wait_cycles(address, 1, true);
+        self.mem.write_byte(address_space, address, value);
+        self.completed_access(address_space, address, 1, AccessType::Write, value);
+        Ok(())
+    }
+    pub fn read_data_word(&mut self, address: u32) -> Result<u32> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
+        if address & 1 > 0 {
+            Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
+        } else {
+            self.pending_wait_cycles += self.mem.wait_cycles(address, 2, false);
+            let value = self.mem.read_word(address_space, address);
+            self.completed_access(address_space, address, 2, AccessType::Read, value);
+            Ok(value)
+        }
+    }
+    pub fn read_program_word(&mut self, address: u32) -> Result<u32> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+        if address & 1 > 0 {
+            Err(Exception::AddressError {address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
+        } else {
+            self.pending_wait_cycles += self.mem.wait_cycles(address, 2, false);
+            let value = self.mem.read_word(address_space, address);
+            self.completed_access(address_space, address, 2, AccessType::Read, value);
+            Ok(value)
+        }
+    }
+    pub fn write_data_word(&mut self, address: u32, value: u32) -> Result<()> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
+        if address & 1 > 0 {
+            Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
+        } else {
+            self.pending_wait_cycles += self.mem.wait_cycles(address, 2, true);
+            self.mem.write_word(address_space, address, value);
+            self.completed_access(address_space, address, 2, AccessType::Write, value);
+            Ok(())
+        }
+    }
+    pub fn write_program_word(&mut self, address: u32, value: u32) -> Result<()> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+        if address & 1 > 0 {
+            Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
+        } else {
+            self.pending_wait_cycles += self.mem.wait_cycles(address, 2, true);
+            self.mem.write_word(address_space, address, value);
+            self.completed_access(address_space, address, 2, AccessType::Write, value);
+            Ok(())
+        }
+    }
+    pub fn read_data_long(&mut self, address: u32) -> Result<u32> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
+        if address & 1 > 0 {
+            Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
+        } else {
+            self.pending_wait_cycles += self.mem.wait_cycles(address, 4, false);
+            let value = self.mem.read_long(address_space, address);
+            self.completed_access(address_space, address, 4, AccessType::Read, value);
+            Ok(value)
+        }
+    }
+    pub fn read_program_long(&mut self, address: u32) -> Result<u32> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+        if address & 1 > 0 {
+            Err(Exception::AddressError{address, access_type: AccessType::Read, address_space, processing_state: self.processing_state})
+        } else {
+            self.pending_wait_cycles += self.mem.wait_cycles(address, 4, false);
+            let value = self.mem.read_long(address_space, address);
+            self.completed_access(address_space, address, 4, AccessType::Read, value);
+            Ok(value)
+        }
+    }
+    pub fn write_data_long(&mut self, address: u32, value: u32) -> Result<()> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
+        if address & 1 > 0 {
+            Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
+        } else {
+            self.pending_wait_cycles += self.mem.wait_cycles(address, 4, true);
+            self.mem.write_long(address_space, address, value);
+            self.completed_access(address_space, address, 4, AccessType::Write, value);
+            Ok(())
+        }
+    }
+    pub fn write_program_long(&mut self, address: u32, value: u32) -> Result<()> {
+        let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+        if address & 1 > 0 {
+            Err(Exception::AddressError{address, access_type: AccessType::Write, address_space, processing_state: self.processing_state})
+        } else {
+            self.pending_wait_cycles += self.mem.wait_cycles(address, 4, true);
+            self.mem.write_long(address_space, address, value);
+            self.completed_access(address_space, address, 4, AccessType::Write, value);
+            Ok(())
+        }
+    }
+    pub fn jump(&mut self, pc: u32) {
+        self.pc = pc;
+    }
+    pub fn jump_vector(&mut self, vector: u8) {
+        let vbr = if self.model == CpuModel::M68000 { 0 } else { self.vbr };
+        let vector_address = vbr.wrapping_add(u32::from(vector) << 2);
+        self.pc = self.read_data_long(vector_address).unwrap();
+    }
+    pub fn ensure_supervisor_mode(&mut self) -> u16 {
+        let backup_sr = self.status_register();
+        // if in user mode, swap stack pointers!
+        if self.s_flag == SFLAG_CLEAR {
+            self.inactive_usp = self.dar[15];
+            self.dar[15] = self.inactive_ssp;
+        }
+        // enter supervisor mode
+        self.s_flag = SFLAG_SET;
+        backup_sr
+    }
+    // exception processing turns off tracing, and a pending trace of the
+    // instruction that caused the exception is not taken
+    fn clear_trace(&mut self) {
+        self.t1_flag = 0;
+        self.tracing = false;
+    }
+    pub fn handle_trace(&mut self) -> Cycles {
+        let pc = self.pc;
+        self.handle_exception(ProcessingState::Group1Exception, pc, EXCEPTION_TRACE, 34)
+    }
+    pub fn handle_address_error(&mut self, bad_address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace) -> Cycles
+    {
+        self.handle_group0_exception(EXCEPTION_ADDRESS_ERROR, bad_address, access_type, processing_state, address_space)
+    }
+    pub fn handle_bus_error(&mut self, bad_address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace) -> Cycles
+    {
+        self.handle_group0_exception(EXCEPTION_BUS_ERROR, bad_address, access_type, processing_state, address_space)
+    }
+    fn handle_group0_exception(&mut self, vector: u8, bad_address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace) -> Cycles
+    {
+        if processing_state == ProcessingState::Group0Exception {
+            self.processing_state = ProcessingState::Halted;
+            return Cycles(0);
+        }
+        self.processing_state = ProcessingState::Group0Exception;
+        self.exceptions_taken += 1;
+        let backup_sr = self.ensure_supervisor_mode();
+        self.clear_trace();
+
+        let (pc, ir) = (self.pc, self.ir);
+        if self.model != CpuModel::M68000 {
+            self.push_bus_fault_frame(vector, pc, backup_sr, bad_address, access_type, address_space);
+            self.jump_vector(vector);
+            return Cycles(50);
+        }
+        // Bus error stack frame (68000 only).
+        self.push_32(pc);
+        self.push_16(backup_sr);
+        self.push_16(ir);
+        self.push_32(bad_address);    /* access address */
+        /* 0 0 0 0 0 0 0 0 0 0 0 R/W I/N FC
+         * R/W  0 = write, 1 = read
+         * I/N  0 = instruction, 1 = not
+         * FC   3-bit function code
+         */
+        let access_info = match access_type {AccessType::Read => 0b10000, _ => 0 } |
+            if processing_state.instruction_processing() { 0 } else { 0b01000 } |
+            (address_space.fc() as u16);
+        self.push_16(access_info);
+        self.jump_vector(vector);
+        Cycles(50)
+    }
+    // The 68010 format 8 frame. Only the fields a handler can interpret
+    // are filled in; the internal state, which the 68010 would use to
+    // continue the faulted instruction, is left zero.
+    fn push_bus_fault_frame(&mut self, vector: u8, pc: u32, sr: u16, bad_address: u32, access_type: AccessType, address_space: AddressSpace) {
+        for _ in 0..16 {
+            self.push_16(0); // internal information
+        }
+        self.push_16(self.ir); // instruction input buffer
+        self.push_16(0);
+        self.push_16(0); // data input buffer
+        self.push_16(0);
+        self.push_16(0); // data output buffer
+        self.push_16(0);
+        self.push_32(bad_address);
+        /* RR -- IF DF RM HB BY RW -- -- -- -- -- FC
+         * IF/DF instruction/data fetch, RW 1 = read
+         */
+        let fetch = if address_space.fc() & 3 == 2 { 0x2000 } else { 0x1000 };
+        let read = match access_type { AccessType::Read => 0x100, _ => 0 };
+        self.push_16(fetch | read | address_space.fc() as u16);
+        self.push_format_word(8, vector);
+        self.push_32(pc);
+        self.push_16(sr);
+    }
+    pub fn handle_unimplemented_instruction(&mut self, pc: u32, vector: u8) -> Cycles {
+        // somewhat unclear if the unimplemented instruction exceptions
+        // are Group 1 or 2 exceptions. They are mentioned together with
+        // "illegal instruction" which is clearly defined as a group 1
+        // exception,  but the text ("6.3.6 Illegal and Unimplemented
+        // Instructions" in the M68000UM) mentions that illegal
+        // instructions push a group 2 stack frame. On the 68000 G1 and
+        // G2 exception stack frames are identical, so maybe it doesn't
+        // really matter. EASy68k considers them group 2 exceptions. For
+        // the time being, we do too.
+        self.handle_exception(ProcessingState::Group2Exception, pc, vector, 34)
+    }
+    pub fn handle_illegal_instruction(&mut self, pc: u32) -> Cycles {
+        self.handle_exception(ProcessingState::Group1Exception, pc, EXCEPTION_ILLEGAL_INSTRUCTION, 34)
+    }
+    pub fn handle_privilege_violation(&mut self, pc: u32) -> Cycles {
+        self.handle_exception(ProcessingState::Group1Exception, pc, EXCEPTION_PRIVILEGE_VIOLATION, 34)
+    }
+    pub fn handle_format_error(&mut self, pc: u32) -> Cycles {
+        self.handle_exception(ProcessingState::Group2Exception, pc, EXCEPTION_FORMAT_ERROR, 34)
+    }
+    pub fn handle_trap(&mut self, trap: u8, cycles: i32) -> Cycles {
+        let pc = self.pc;
+        self.handle_exception(ProcessingState::Group2Exception, pc, trap, cycles)
+    }
+
+    pub fn handle_exception(&mut self, new_state: ProcessingState, pc: u32, vector: u8, cycles: i32) -> Cycles {
+        self.processing_state = new_state;
+        self.exceptions_taken += 1;
+        let backup_sr = self.ensure_supervisor_mode();
+        self.clear_trace();
+
+        // Group 1 and 2 stack frame, format 0 on the 68010
+        self.push_format_word(0, vector);
+        self.push_32(pc);
+        self.push_16(backup_sr);
+
+        self.jump_vector(vector);
+        Cycles(cycles)
+    }
+    // the 68010 stacks the frame format and the vector offset below PC
+    fn push_format_word(&mut self, format: u16, vector: u8) {
+        if self.model != CpuModel::M68000 {
+            self.push_16((format << 12) | (u16::from(vector) << 2));
+        }
+    }
+
+    pub fn handle_interrupt(&mut self, irq_level: u8, vector: u8) -> Cycles {
+        let pc = self.pc;
+        self.processing_state = ProcessingState::Group1Exception;
+        self.exceptions_taken += 1;
+        let backup_sr = self.ensure_supervisor_mode();
+        self.clear_trace();
+        // new mask set here, in order to exclude from backup_sr
+        self.int_mask = u32::from(irq_level) << 8;
+        self.irq_level = irq_level;
+
+        // Musashi jumps first, and stacks later for interrupts,
+        // but the other way around for exceptions
+        self.jump_vector(vector);
+
+        // Group 1 and 2 stack frame, format 0 on the 68010
+        self.push_format_word(0, vector);
+        self.push_32(pc);
+        self.push_16(backup_sr);
+
+        // 44 cycles for an interrupt according to MC68000UM, Table 8-14
+        // The interrupt acknowledge cycle is assumed to take four clock periods
+        Cycles(44)
+    }
+    fn stopped_with_pending_interrups(&mut self) -> bool {
+        self.processing_state == ProcessingState::Stopped && self.pending_interrupt().is_some()
+    }
+    fn can_execute(&mut self) -> bool {
+        self.processing_state.running() || self.stopped_with_pending_interrups()
+    }
+    fn pending_interrupt(&self) -> Option<u8> {
+        let old_level = self.irq_level;
+        let new_level = self.int_ctrl.highest_priority();
+        let edge_triggered_nmi = old_level != 7 && new_level == 7;
+        if u32::from(new_level) << 8 > self.int_mask || edge_triggered_nmi {
+            Some(new_level)
+        } else {
+            None
+        }
+    }
+    pub fn read_instruction(&mut self) -> Result<u16> {
+        // first check for interrupts
+        if let Some(irq) = self.pending_interrupt() {
+            // the vector number is read in CPU space, with the level on A1-A3
+            self.record_bus_cycles(CPU_SPACE, ADDRBUS_MASK & (0xffff_fff1 | (u32::from(irq) << 1)), 1, false);
+            let vector = self.int_ctrl.acknowledge_interrupt(irq).unwrap_or(SPURIOUS_INTERRUPT);
+            let vector = self.mem.interrupt_acknowledge(irq).unwrap_or(vector);
+            Err(Exception::Interrupt(irq, vector))
+        } else {
+            // not interrupted, read instruction from PC
+            self.read_imm_u16()
+        }
+    }
+    /// Executes a single instruction.
+    ///
+    /// This is a convenience method equivalent to `execute(1)`.
+    /// Returns the number of cycles consumed by the instruction, plus those
+    /// carried over from a previous instruction in [cycle exact
+    /// mode](Self::set_cycle_exact).
+    pub fn execute1(&mut self) -> Cycles {
+        let owed = std::mem::take(&mut self.owed_cycles);
+        let cycle_exact = std::mem::replace(&mut self.cycle_exact, false);
+        let cycles = self.execute(1);
+        self.cycle_exact = cycle_exact;
+        Cycles(owed) + cycles
+    }
+
+    /// Executes up to `max_instructions` instructions, recording each one.
+    ///
+    /// The log starts with the registers before the first instruction. For
+    /// every instruction it holds its address and opcode, and the
+    /// registers, status register and cycles after it has executed (or
+    /// after the exception it raised has been initiated). Recording ends
+    /// early if the CPU stops or halts.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// // MOVEQ #1, D0 ; ADDQ.L #1, D0 ; STOP #$2700
+    /// let program = [0x70, 0x01, 0x52, 0x80, 0x4e, 0x72, 0x27, 0x00];
+    /// cpu.load_and_run(&program, 0x1000, 0x1000, 0);
+    /// let log = cpu.record_execution(10);
+    /// assert_eq!(3, log.len());
+    /// assert_eq!(2, log.entries[1].dar[0]);
+    /// ```
+    pub fn record_execution(&mut self, max_instructions: u32) -> execlog::ExecutionLog {
+        let mut log = execlog::ExecutionLog::new();
+        log.initial = Some(execlog::InitialState {
+            pc: self.pc, sr: self.status_register(), dar: self.dar, usp: self.usp(), ssp: self.ssp(),
+        });
+        for _ in 0..max_instructions {
+            if !self.processing_state.running() {
+                break;
+            }
+            let entry = self.execute_logged();
+            log.push(entry);
+        }
+        log
+    }
+
+    /// Re-executes a recorded log and checks that every instruction matches.
+    ///
+    /// If `golden` has an [`initial`](execlog::ExecutionLog::initial)
+    /// state, as logs recorded with
+    /// [`record_execution`](Self::record_execution) do, the registers are
+    /// first restored from it, with the prefetch invalidated. Memory is not
+    /// part of the log and must have the contents it had when `golden` was
+    /// recorded, as must the registers of a log without an initial
+    /// state. Execution stops at the first
+    /// instruction that differs, leaving the CPU right after it for
+    /// inspection, and the first differing field is returned. If the CPU
+    /// stops or halts before the end of the log, a
+    /// [`Field::Length`](execlog::Field::Length) divergence with the
+    /// number of instructions executed is returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    /// use r68k::ram::AddressBus;
+    ///
+    /// // MOVEQ #1, D0 ; ADDQ.L #1, D0 ; STOP #$2700
+    /// let program = [0x70, 0x01, 0x52, 0x80, 0x4e, 0x72, 0x27, 0x00];
+    /// let mut original = Cpu::new(0);
+    /// original.load_and_run(&program, 0x1000, 0x1000, 0);
+    /// let golden = original.record_execution(10);
+    ///
+    /// // the registers are restored from the log, only memory is needed
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.mem.copy_from(&original.mem);
+    /// assert_eq!(Ok(()), cpu.verify_against(&golden));
+    /// ```
+    pub fn verify_against(&mut self, golden: &execlog::ExecutionLog) -> result::Result<(), execlog::Divergence> {
+        if let Some(initial) = golden.initial {
+            let mut start = patch::Patch::new();
+            start.sr = Some(initial.sr);
+            for (register, value) in start.registers.iter_mut().zip(initial.dar.iter()) {
+                *register = Some(*value);
+            }
+            start.usp = Some(initial.usp);
+            start.ssp = Some(initial.ssp);
+            start.pc = Some(initial.pc);
+            self.apply_patch(&start);
+        }
+        for (index, expected) in golden.entries.iter().enumerate() {
+            if !self.processing_state.running() {
+                return Err(execlog::Divergence { index, field: execlog::Field::Length, expected: golden.len() as u32, actual: index as u32 });
+            }
+            let actual = self.execute_logged();
+            if let Some(&divergence) = execlog::divergences(index, expected, &actual).first() {
+                return Err(divergence);
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_logged(&mut self) -> execlog::LogEntry {
+        let pc = self.pc;
+        let address_space = if self.s_flag > 0 { SUPERVISOR_PROGRAM } else { USER_PROGRAM };
+        let opcode = self.mem.read_word(address_space, pc) as u16;
+        let cycles = self.execute1();
+        execlog::LogEntry { pc, opcode, dar: self.dar, sr: self.status_register(), cycles: cycles.0 }
+    }
+
+    /// Sets how [`step()`](Self::step) treats exceptions. The default is [`StepMode::Into`].
+    pub fn set_step_mode(&mut self, mode: StepMode) {
+        self.step_mode = mode;
+    }
+
+    /// Returns the current step mode.
+    pub fn step_mode(&self) -> StepMode {
+        self.step_mode
+    }
+
+    /// Single-steps one instruction, for use by debuggers.
+    ///
+    /// If the instruction raises an exception, or an interrupt is taken,
+    /// the behavior depends on the [`StepMode`]:
+    ///
+    /// - [`StepMode::Into`] stops at the first instruction of the handler,
+    ///   just like [`execute1()`](Self::execute1).
+    /// - [`StepMode::OverExceptions`] keeps executing until the handler has
+    ///   returned (its stack frame has been popped by `RTE`), or the CPU stops
+    ///   or halts. Nested exceptions inside the handler are run as well. A
+    ///   handler that does not return within [`STEP_OVER_MAX_CYCLES`] is
+    ///   left running, see [`step_within()`](Self::step_within).
+    /// - [`StepMode::OverInterrupts`] does the same for interrupts only, so
+    ///   that stepping through main-line code is not derailed into interrupt
+    ///   handlers. A step that services an interrupt ends once the handler
+    ///   has returned, before the interrupted instruction; the next step
+    ///   executes it.
+    ///
+    /// Returns the total number of cycles consumed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::{Cpu, StepMode};
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.reset();
+    /// cpu.set_step_mode(StepMode::OverExceptions);
+    /// let cycles = cpu.step();
+    /// ```
+    pub fn step(&mut self) -> Cycles {
+        self.step_within(STEP_OVER_MAX_CYCLES).0
+    }
+
+    /// Single-steps one instruction like [`step()`](Self::step), stepping
+    /// over exception handlers for at most `max_cycles` cycles.
+    ///
+    /// Returns the cycles consumed, and false if a handler being stepped
+    /// over has not returned, because the budget ran out or the CPU stopped
+    /// or halted in it. The CPU is then left inside the handler. The
+    /// instruction itself and the exception processing are always
+    /// executed, even if they exceed the budget.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::{Cpu, StepMode};
+    /// use r68k::ram::{AddressBus, SUPERVISOR_DATA};
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.set_step_mode(StepMode::OverExceptions);
+    /// // TRAP #0 ; $1002: BRA.S $1002, the TRAP #0 handler never returning
+    /// cpu.load_and_run(&[0x4e, 0x40, 0x60, 0xfe], 0x1000, 0x1000, 0);
+    /// cpu.mem.write_long(SUPERVISOR_DATA, 32 * 4, 0x1002);
+    /// let (_, returned) = cpu.step_within(1000);
+    /// assert!(!returned);
+    /// assert_eq!(0x1002, cpu.pc);
+    /// ```
+    pub fn step_within(&mut self, max_cycles: i32) -> (Cycles, bool) {
+        let exceptions_taken = self.exceptions_taken;
+        let mut cycles = self.execute1();
+        let step_over = match self.step_mode {
+            StepMode::Into => false,
+            StepMode::OverExceptions => true,
+            // an interrupt is taken instead of executing an instruction
+            StepMode::OverInterrupts =>
+                self.current_instruction.is_none() && matches!(self.last_exception, Some(Exception::Interrupt(..))),
+        };
+        if step_over && self.exceptions_taken != exceptions_taken {
+            // the handler has returned once its frame is popped off the supervisor stack
+            let frame = self.ssp();
+            while self.ssp() <= frame {
+                if !self.processing_state.running() || self.halt_line || cycles.0 >= max_cycles {
+                    return (cycles, false);
+                }
+                cycles = cycles + self.execute1();
+            }
+        }
+        (cycles, true)
+    }
+
+    /// Executes instructions for up to the specified number of cycles.
+    ///
+    /// The CPU will execute instructions until the cycle budget is exhausted
+    /// or the CPU enters a non-running state (Stopped or Halted).
+    ///
+    /// # Arguments
+    ///
+    /// * `cycles` - Maximum number of cycles to execute
+    ///
+    /// # Returns
+    ///
+    /// The actual number of cycles consumed. The budget is only checked
+    /// between instructions, so this may exceed the requested amount if
+    /// the last instruction takes more cycles than remaining, unless
+    /// [cycle exact mode](Self::set_cycle_exact) is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::ConfiguredCore;
+    /// use r68k::ram::PagedMem;
+    /// use r68k::interrupts::AutoInterruptController;
+    ///
+    /// let memory = PagedMem::new(0);
+    /// let mut cpu = ConfiguredCore::new_with(0, AutoInterruptController::new(), memory);
+    /// cpu.reset();
+    ///
+    /// // Execute for approximately 1000 cycles
+    /// let actual_cycles = cpu.execute(1000);
+    /// println!("Consumed {} cycles", actual_cycles.0);
+    /// ```
+    pub fn execute(&mut self, cycles: i32) -> Cycles {
+        self.execute_with_state(cycles, &mut EmulateAllExceptions)
+    }
+
+    /// Executes instructions with custom exception handling.
+    ///
+    /// Like [`execute()`](Self::execute), but allows intercepting exceptions
+    /// via a [`Callbacks`] implementation. This is useful for implementing
+    /// OS traps, debuggers, or custom I/O.
+    ///
+    /// # Arguments
+    ///
+    /// * `cycles` - Maximum number of cycles to execute
+    /// * `state` - A [`Callbacks`] implementation for exception handling
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::{ConfiguredCore, Callbacks, Core, Cycles, Exception, Result};
+    /// use r68k::ram::PagedMem;
+    /// use r68k::interrupts::AutoInterruptController;
+    ///
+    /// struct MyHandler;
+    /// impl Callbacks for MyHandler {
+    ///     fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
+    ///         Err(ex) // Let CPU handle all exceptions
+    ///     }
+    /// }
+    ///
+    /// let memory = PagedMem::new(0);
+    /// let mut cpu = ConfiguredCore::new_with(0, AutoInterruptController::new(), memory);
+    /// cpu.reset();
+    ///
+    /// let mut handler = MyHandler;
+    /// cpu.execute_with_state(1000, &mut handler);
+    /// ```
+    pub fn execute_with_state<S: Callbacks>(&mut self, cycles: i32, state: &mut S) -> Cycles {
+        self.execute_instructions(cycles, state, |_| true)
+    }
+
+    /// Executes instructions, calling back into the host at every instruction boundary.
+    ///
+    /// Like [`execute()`](Self::execute), but `f` is called after each
+    /// instruction has been retired (or an exception has been initiated).
+    /// Returning `false` from `f` stops execution early, even if there are
+    /// cycles left in the budget. This lets a cooperative host poll I/O,
+    /// check for UI events or implement custom stop conditions without
+    /// the overhead of repeated [`execute1()`](Self::execute1) calls.
+    ///
+    /// # Arguments
+    ///
+    /// * `cycles` - Maximum number of cycles to execute
+    /// * `f` - Called with the CPU after each instruction; return `false` to stop
+    ///
+    /// # Returns
+    ///
+    /// The number of cycles actually consumed.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// cpu.reset();
+    ///
+    /// // Run until PC reaches 0x1000, or 10000 cycles have passed
+    /// let cycles = cpu.execute_with(10000, |core| core.pc != 0x1000);
+    /// ```
+    pub fn execute_with<F: FnMut(&mut Self) -> bool>(&mut self, cycles: i32, f: F) -> Cycles {
+        self.execute_instructions(cycles, &mut EmulateAllExceptions, f)
+    }
+
+    /// Executes for up to `max_cycles` cycles and returns the longest time
+    /// an interrupt could have had to wait to be recognized.
+    ///
+    /// Interrupts are only recognized at instruction boundaries, and only
+    /// if their level is above the interrupt mask. The measurement is for
+    /// an interrupt just above the mask in effect when it starts: a
+    /// boundary counts as a recognition point unless the program has
+    /// raised the mask above that level, so the result is the longest
+    /// stretch of cycles between two such points. This is the longest
+    /// instruction, or the longest section run with interrupts masked,
+    /// whichever is longer. A section still masked at the end of the
+    /// window counts up to the end. Cycles spent stopped with the
+    /// interrupt unmasked don't delay it. Exceptions and interrupts taken
+    /// meanwhile are processed as usual, and their processing counts like
+    /// an instruction.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// // MOVE #$2700, SR ; NOP ; NOP ; MOVE #$2000, SR ; NOP
+    /// let program = [0x46, 0xfc, 0x27, 0x00, 0x4e, 0x71, 0x4e, 0x71, 0x46, 0xfc, 0x20, 0x00, 0x4e, 0x71];
+    /// cpu.load_and_run(&program, 0x1000, 0x1000, 0);
+    /// cpu.sr_to_flags(0x2000);
+    /// // an interrupt arriving during the first MOVE waits until the
+    /// // second one has unmasked it, 16 + 4 + 4 + 16 cycles later
+    /// assert_eq!(40, cpu.measure_max_interrupt_latency(44));
+    /// ```
+    pub fn measure_max_interrupt_latency(&mut self, max_cycles: i32) -> u64 {
+        let base_mask = self.int_mask;
+        let mut last_check = 0i64;
+        let mut longest = 0i64;
+        let used = self.execute_with(max_cycles, |core| {
+            let elapsed = i64::from(max_cycles) - i64::from(core.remaining_budget);
+            if core.int_mask <= base_mask {
+                longest = longest.max(elapsed - last_check);
+                last_check = elapsed;
+            }
+            true
+        });
+        if self.int_mask > base_mask {
+            longest = longest.max(i64::from(used.0) - last_check);
+        }
+        longest.max(0) as u64
+    }
+
+    /// Executes instructions until a memory location holds a given value.
+    ///
+    /// The byte, word or long word (`size` 1, 2 or 4) at `address` in
+    /// `address_space` is compared against `value` before starting and at
+    /// every instruction boundary, e.g. to wait for a boot sequence to set
+    /// a ready flag. Memory is read directly, without wait states,
+    /// watchpoints or bus cycle reporting.
+    ///
+    /// # Returns
+    ///
+    /// The number of cycles consumed, and whether the condition was met
+    /// within `max_cycles`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is not 1, 2 or 4.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    /// use r68k::ram::SUPERVISOR_DATA;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// // MOVEQ #-1, D0 ; MOVE.B D0, $2000.W ; STOP #$2700
+    /// let program = [0x70, 0xff, 0x11, 0xc0, 0x20, 0x00, 0x4e, 0x72, 0x27, 0x00];
+    /// cpu.load_and_run(&program, 0x1000, 0x1000, 0);
+    /// let (_, ready) = cpu.execute_until_memory(SUPERVISOR_DATA, 0x2000, 1, 0xff, 1000);
+    /// assert!(ready);
+    /// assert_eq!(0x1006, cpu.pc);
+    /// ```
+    pub fn execute_until_memory(&mut self, address_space: AddressSpace, address: u32, size: u8, value: u32, max_cycles: i32) -> (Cycles, bool) {
+        assert!(size == 1 || size == 2 || size == 4);
+        let condition = move |core: &Self| {
+            let current = match size {
+                1 => core.mem.read_byte(address_space, address),
+                2 => core.mem.read_word(address_space, address),
+                _ => core.mem.read_long(address_space, address),
+            };
+            current == value
+        };
+        if condition(self) {
+            return (Cycles(0), true);
+        }
+        let mut met = false;
+        let cycles = self.execute_with(max_cycles, |core| {
+            met = condition(core);
+            !met
+        });
+        (cycles, met)
+    }
+
+    /// Executes until the current subroutine returns, for a debugger's
+    /// "finish" command.
+    ///
+    /// Counts the call depth from here on: `JSR`, `BSR` and the entry into
+    /// an exception handler go one level deeper, and `RTS`, `RTR` and `RTE`
+    /// one level back. Execution stops after the return that leaves the
+    /// level it started at, so nested calls, and interrupts taken on the
+    /// way, run to completion. An instruction that takes an exception
+    /// instead of completing, e.g. a `JSR` to an odd address, only counts
+    /// as the exception.
+    ///
+    /// # Returns
+    ///
+    /// The number of cycles consumed, and whether the subroutine returned
+    /// within `max_cycles`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// // BSR.S $1006 ; STOP #$2700 ; $1006: BSR.S $100a ; RTS ; $100a: RTS
+    /// let program = [0x61, 0x04, 0x4e, 0x72, 0x27, 0x00, 0x61, 0x02, 0x4e, 0x75, 0x4e, 0x75];
+    /// cpu.load_and_run(&program, 0x1000, 0x1000, 18);
+    /// assert_eq!(0x1006, cpu.pc);
+    /// let (_, returned) = cpu.run_until_return(1000);
+    /// assert!(returned);
+    /// assert_eq!(0x1002, cpu.pc);
+    /// ```
+    pub fn run_until_return(&mut self, max_cycles: i32) -> (Cycles, bool) {
+        let mut depth = 0i64;
+        let mut exceptions_taken = self.exceptions_taken;
+        let cycles = self.execute_with(max_cycles, |core| {
+            if core.instruction_completed() {
+                if let Some((_, _, opcode)) = core.current_instruction {
+                    match ops::handler_name(opcode).split('_').next() {
+                        Some("jsr") | Some("bsr") => depth += 1,
+                        Some("rts") | Some("rtr") | Some("rte") => depth -= 1,
+                        _ => (),
+                    }
+                }
+            }
+            depth += (core.exceptions_taken - exceptions_taken) as i64;
+            exceptions_taken = core.exceptions_taken;
+            depth >= 0
+        });
+        (cycles, depth < 0)
+    }
+
+    // True if the last instruction completed instead of raising an
+    // exception. A nonzero nesting count means it raised one, except for the
+    // trace exception that follows a completed instruction.
+    fn instruction_completed(&self) -> bool {
+        match self.exception_nesting {
+            0 => true,
+            1 => matches!(self.last_exception, Some(Exception::Trace)),
+            _ => false,
+        }
+    }
+
+    /// Loads a program into memory and runs it, all in one call.
+    ///
+    /// Writes `program` to memory starting at `load_addr`, enters supervisor
+    /// mode, jumps to `entry` and executes for up to `max_cycles` cycles.
+    /// The interrupt mask and condition codes are left as they are. If the
+    /// supervisor stack pointer is zero, it is first pointed just below the
+    /// loaded program, so that subroutine calls and exceptions have
+    /// somewhere to go. A program loaded at address 0 has nothing below it,
+    /// so its stack is [`LOAD_AND_RUN_STACK_SIZE`] bytes above its end
+    /// instead.
+    ///
+    /// This is a convenience wrapper for examples, experiments and tests.
+    /// Full-system emulations should set up memory and reset vectors and use
+    /// [`reset()`](Self::reset) and [`execute()`](Self::execute) instead.
+    ///
+    /// # Returns
+    ///
+    /// The number of cycles consumed, as for [`execute()`](Self::execute).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// // MOVEQ #42, D0 ; STOP #$2700
+    /// let program = [0x70, 0x2a, 0x4e, 0x72, 0x27, 0x00];
+    /// cpu.load_and_run(&program, 0x1000, 0x1000, 100);
+    /// assert_eq!(42, cpu.dar[0]);
+    /// ```
+    pub fn load_and_run(&mut self, program: &[u8], load_addr: u32, entry: u32, max_cycles: i32) -> Cycles {
+        for (offset, byte) in program.iter().enumerate() {
+            self.mem.write_byte(SUPERVISOR_PROGRAM, load_addr.wrapping_add(offset as u32), u32::from(*byte));
+        }
+        if self.s_flag == SFLAG_CLEAR {
+            self.sr_to_flags(self.status_register() | 0x2000);
+        }
+        if self.dar[STACK_POINTER_REG] == 0 {
+            self.dar[STACK_POINTER_REG] = if load_addr == 0 {
+                (program.len() as u32 + LOAD_AND_RUN_STACK_SIZE + 1) & !1
+            } else {
+                load_addr & !1
+            };
+        }
+        self.processing_state = ProcessingState::Normal;
+        self.prefetch_addr = 1; // invalidate, the program may have overwritten prefetched data
+        self.jump(entry);
+        self.execute(max_cycles)
+    }
+
+    /// Applies register overrides and memory writes in one step.
+    ///
+    /// Call it between instructions, outside of [`execute()`](Self::execute)
+    /// or from an [`execute_with`](Self::execute_with) closure. The status
+    /// register is set first, so that A7 in the patch is the stack pointer
+    /// of the new mode, then the data and address registers, the stack
+    /// pointers, PC, and finally memory, in the order of the writes. Memory
+    /// is written directly in the supervisor data address space, without
+    /// wait states, watchpoints or bus cycle reporting, and the prefetch is
+    /// invalidated in case the patch changed code.
+    pub fn apply_patch(&mut self, patch: &patch::Patch) {
+        if let Some(sr) = patch.sr {
+            self.sr_to_flags(sr);
+        }
+        for (register, value) in self.dar.iter_mut().zip(patch.registers.iter()) {
+            if let Some(value) = *value {
+                *register = value;
+            }
+        }
+        let (usp, ssp) = if self.s_flag != 0 {
+            (&mut self.inactive_usp, &mut self.dar[STACK_POINTER_REG])
+        } else {
+            (&mut self.dar[STACK_POINTER_REG], &mut self.inactive_ssp)
+        };
+        if let Some(value) = patch.usp {
+            *usp = value;
+        }
+        if let Some(value) = patch.ssp {
+            *ssp = value;
+        }
+        if let Some(pc) = patch.pc {
+            self.jump(pc);
+        }
+        for &(address, value) in &patch.memory {
+            self.mem.write_byte(SUPERVISOR_DATA, address, u32::from(value));
+        }
+        self.prefetch_addr = 1; // invalidate, the patch may have changed code
+    }
+
+    fn execute_instructions<S: Callbacks, F: FnMut(&mut Self) -> bool>(&mut self, cycles: i32, state: &mut S, at_boundary: F) -> Cycles {
+        if !self.cycle_exact || self.halt_line {
+            return self.run_instructions(cycles, state, at_boundary);
+        }
+        // finish paying for the instruction that overran the previous budget
+        let paid = self.owed_cycles.clamp(0, cycles.max(0));
+        self.owed_cycles -= paid;
+        if paid == cycles {
+            return Cycles(paid);
+        }
+        let budget = cycles - paid;
+        let Cycles(used) = self.run_instructions(budget, state, at_boundary);
+        if used > budget {
+            self.owed_cycles = used - budget;
+            Cycles(cycles)
+        } else {
+            Cycles(paid + used)
+        }
+    }
+    fn run_instructions<S: Callbacks, F: FnMut(&mut Self) -> bool>(&mut self, cycles: i32, state: &mut S, mut at_boundary: F) -> Cycles {
+        if self.halt_line {
+            // the bus is held by an external device, nothing happens
+            return Cycles(0);
+        }
+        let cycles = Cycles(cycles);
+        let mut remaining_cycles = cycles;
+        let mut idle_cycles = None;
+        while remaining_cycles.any() && self.can_execute() {
+            self.remaining_budget = remaining_cycles.0;
+            // Reset wait state accumulator for this instruction
+            self.pending_wait_cycles = 0;
+            self.watchpoint_triggered = false;
+            self.bus_cycles.clear();
+            self.effective_addresses.clear();
+            self.self_modifying_writes.clear();
+            self.current_instruction = None;
+            self.fetched_words.clear();
+            if let Some(accesses) = &mut self.instruction_accesses {
+                accesses.clear();
+            }
+            self.tracing = self.t1_flag != 0;
+            self.take_snapshot_if_due();
+            // Read an instruction from PC (increments PC by 2)
+            let result = if let Some(cycles) = self.run_trampoline() {
+                Ok(cycles)
+            } else {
+                let pc = self.pc;
+                self.read_instruction().and_then(|opcode| {
+                    self.ir = opcode;
+                    let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+                    self.current_instruction = Some((address_space, pc, opcode));
+                    if self.executes_uninitialized(pc) {
+                        state.execute_uninitialized(pc);
+                    }
+                    if let Some(coverage) = &mut self.coverage {
+                        coverage.mark(pc & ADDRBUS_MASK);
+                    }
+                    if let Some(cycles) = self.run_opcode_override(opcode) {
+                        return Ok(cycles);
+                    }
+                    // Call instruction handler to mutate Core accordingly
+                    self.instruction_set[opcode as usize](self)
+                })
+            };
+            let cycles_used = match result {
+                Ok(cycles_used) => {
+                    self.exception_nesting = 0;
+                    cycles_used
+                }
+                Err(ex) => {
+                    match state.exception_callback(self, ex) {
+                        Ok(cycles_used) => {
+                            self.exception_nesting = 0;
+                            cycles_used
+                        }
+                        Err(Exception::IllegalInstruction(opcode, pc)) if self.illegal_as_nop => {
+                            self.exception_nesting = 0;
+                            state.illegal_instruction_skipped(opcode, pc);
+                            self.jump(pc.wrapping_add(2));
+                            Cycles(4)
+                        }
+                        Err(ex) if self.max_exception_nesting.is_some_and(|max| self.exception_nesting >= max) => {
+                            // runaway exceptions, give up like on a double fault
+                            self.last_exception = Some(ex);
+                            self.processing_state = ProcessingState::Halted;
+                            self.tracing = false;
+                            Cycles(0)
+                        }
+                        Err(ex) => {
+                            self.last_exception = Some(ex);
+                            self.exception_nesting += 1;
+                            match ex {
+                                Exception::BusError { address, access_type, processing_state, address_space } =>
+                                    self.handle_bus_error(address, access_type, processing_state, address_space),
+                                Exception::AddressError { address, access_type, processing_state, address_space } =>
+                                    self.handle_address_error(address, access_type, processing_state, address_space),
+                                Exception::IllegalInstruction(_, pc) =>
+                                    self.handle_illegal_instruction(pc),
+                                Exception::UnimplementedInstruction(_, pc, vector) =>
+                                    self.handle_unimplemented_instruction(pc, vector),
+                                Exception::Trap(num, ea_calculation_cycles) =>
+                                    self.handle_trap(num, ea_calculation_cycles),
+                                Exception::PrivilegeViolation(_, pc) =>
+                                    self.handle_privilege_violation(pc),
+                                Exception::FormatError(_, pc) =>
+                                    self.handle_format_error(pc),
+                                Exception::Interrupt(irq, vec) =>
+                                    self.handle_interrupt(irq, vec),
+                                Exception::Trace =>
+                                    self.handle_trace(),
+                            }
+                        }
+                    }
+                }
+            };
+            // with T1 set at its start, the instruction is followed by a trace
+            // exception, unless it raised an exception itself
+            let cycles_used = if self.tracing {
+                self.last_exception = Some(Exception::Trace);
+                if self.max_exception_nesting.is_some_and(|max| self.exception_nesting >= max) {
+                    self.processing_state = ProcessingState::Halted;
+                    self.tracing = false;
+                    cycles_used
+                } else {
+                    self.exception_nesting += 1;
+                    cycles_used + self.handle_trace()
+                }
+            } else {
+                cycles_used
+            };
+            if let Some(history) = &mut self.history {
+                history.position += 1;
+            }
+            let bus_cycles = std::mem::take(&mut self.bus_cycles);
+            for &(address_space, address, is_write) in &bus_cycles {
+                if address_space != CPU_SPACE {
+                    state.bus_cycle(address, is_write);
+                }
+                state.bus_cycle_fc(address_space, address, is_write);
+            }
+            self.bus_cycles = bus_cycles;
+            for &(mode, reg, address) in &self.effective_addresses {
+                state.effective_address_computed(mode, reg, address);
+            }
+            for &address in &self.self_modifying_writes {
+                state.self_modifying_write(address);
+            }
+            // Add accumulated wait state cycles and apply granularity alignment
+            let total_cycles = Cycles(cycles_used.0 + self.pending_wait_cycles);
+            remaining_cycles = remaining_cycles - self.align_cycles(total_cycles);
+            self.remaining_budget = remaining_cycles.0;
+            idle_cycles = if self.processing_state == ProcessingState::Stopped {
+                state.on_stop(self.status_register())
+            } else {
+                None
+            };
+            if self.watchpoint_triggered || !at_boundary(self) {
+                break;
+            }
+        }
+        self.remaining_budget = 0;
+        if self.processing_state.running() {
+            cycles - remaining_cycles
+        } else if let Some(idle) = idle_cycles {
+            let idle = i32::try_from(idle).unwrap_or(i32::MAX);
+            Cycles((cycles - remaining_cycles).0.saturating_add(idle))
+        } else {
+            // if not running, consume all available cycles
+            // including overconsumed cycles
+            let adjust = if remaining_cycles.0 < 0 { remaining_cycles } else { Cycles(0) };
+            cycles - adjust
+        }
+    }
+}
+
+impl<T: InterruptController + Clone, A: AddressBus + Clone> ConfiguredCore<T, A> {
+    /// Starts taking snapshots for [`step_back`](Self::step_back).
+    ///
+    /// A snapshot of the registers, memory and interrupt controller is
+    /// taken before every `snapshot_interval`th instruction from now on, and
+    /// the most recent [`reverse::SNAPSHOTS_KEPT`] are kept. Shorter
+    /// intervals make stepping back faster, at the cost of copying memory
+    /// more often. Enabling again starts over with no snapshots.
+    pub fn enable_reverse_debugging(&mut self, snapshot_interval: u32) {
+        self.history = Some(Box::new(reverse::History::new(snapshot_interval, reverse::Snapshot::capture)));
+    }
+
+    /// Returns to the state before the most recently executed instruction.
+    ///
+    /// The nearest snapshot before that instruction is restored, and the
+    /// instructions from there up to the previous one are executed again.
+    /// An instruction here is one step of [`execute()`](Self::execute), so
+    /// taking an interrupt or exception counts as one. Re-execution starts
+    /// from the interrupt controller state in the snapshot, and does not
+    /// replay interrupts or bus changes the host made since. Snapshots
+    /// after the new position are dropped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::Cpu;
+    ///
+    /// let mut cpu = Cpu::new(0);
+    /// // MOVEQ #1, D0 ; ADDQ.L #1, D0 ; ADDQ.L #1, D0
+    /// cpu.load_and_run(&[0x70, 0x01, 0x52, 0x80, 0x52, 0x80], 0x1000, 0x1000, 0);
+    /// cpu.enable_reverse_debugging(16);
+    /// cpu.execute1();
+    /// cpu.execute1();
+    /// cpu.execute1();
+    /// assert_eq!(3, cpu.dar[0]);
+    ///
+    /// cpu.step_back().unwrap();
+    /// assert_eq!(2, cpu.dar[0]);
+    /// assert_eq!(0x1004, cpu.pc);
+    /// ```
+    pub fn step_back(&mut self) -> result::Result<(), reverse::ReverseError> {
+        use self::reverse::ReverseError;
+        let mut history = self.history.take().ok_or(ReverseError::NotEnabled)?;
+        let restored = history.position.checked_sub(1).and_then(|target| {
+            history.rewind_to(target).map(|snapshot| snapshot.restore(self)).map(|_| target)
+        });
+        self.history = Some(history);
+        let target = restored.ok_or(ReverseError::NoHistory)?;
+        while let Some(position) = self.history.as_ref().map(|history| history.position) {
+            if position == target {
+                return Ok(());
+            }
+            self.execute1();
+            if self.history.as_ref().map(|history| history.position) == Some(position) {
+                return Err(ReverseError::Diverged);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<C: InterruptController, A: AddressBus> ConfiguredCore<PeriodicInterrupt<C>, A> {
+    /// Executes instructions while advancing the periodic interrupt.
+    ///
+    /// Like [`execute()`](Self::execute), but execution is split at each
+    /// timer period so that the [`PeriodicInterrupt`] is requested as soon
+    /// as its period elapses, and is taken at the next instruction boundary
+    /// (subject to the interrupt mask). Cycles spent in the STOP state
+    /// count towards the period, so a timer interrupt wakes a stopped CPU.
+    /// So do wait states added by [`AddressBus::wait_cycles`], so slow
+    /// memory accesses let the period elapse after fewer instructions.
+    ///
+    /// # Returns
+    ///
+    /// The number of cycles consumed, as for [`execute()`](Self::execute).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::cpu::ConfiguredCore;
+    /// use r68k::interrupts::{AutoInterruptController, PeriodicInterrupt};
+    /// use r68k::ram::PagedMem;
+    ///
+    /// let timer = PeriodicInterrupt::new(AutoInterruptController::new(), 1000, 6, 30);
+    /// let mut cpu = ConfiguredCore::new_with(0x1000, timer, PagedMem::new(0));
+    /// cpu.execute_timed(5000);
+    /// assert_eq!(1000, cpu.int_ctrl.cycles_until_next());
+    /// ```
+    pub fn execute_timed(&mut self, cycles: i32) -> Cycles {
+        let mut remaining = cycles;
+        while remaining > 0 {
+            let slice = remaining.min(self.int_ctrl.cycles_until_next());
+            let used = self.execute(slice).0;
+            if used <= 0 {
+                break;
+            }
+            self.int_ctrl.advance(used);
+            remaining -= used;
+        }
+        Cycles(cycles - remaining)
+    }
+}
+
+impl Clone for TestCore {
+    fn clone(&self) -> Self {
+        let mut lm = LoggingMem::new(self.mem.initializer, OpsLogger::new());
+        lm.copy_from(&self.mem);
+        assert_eq!(0, lm.logger.len());
+        TestCore {
+            pc: self.pc, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: self.inactive_ssp, inactive_usp: self.inactive_usp, ir: self.ir, processing_state: self.processing_state,
+            dar: self.dar, mem: lm, instruction_set: ops::instruction_set_for(self.model),
+            irq_level: 0, int_ctrl: AutoInterruptController::new(),
+            s_flag: self.s_flag, int_mask: self.int_mask, x_flag: self.x_flag, v_flag: self.v_flag, c_flag: self.c_flag, n_flag: self.n_flag, not_z_flag: self.not_z_flag, t1_flag: self.t1_flag, tracing: false,
+            cycle_granularity: self.cycle_granularity, pending_wait_cycles: 0, cycle_exact: self.cycle_exact, owed_cycles: self.owed_cycles, halt_line: self.halt_line,
+            watchpoints: self.watchpoints.clone(), last_watchpoint_hit: self.last_watchpoint_hit, watchpoint_triggered: false,
+            bus_cycles: Vec::new(), step_mode: self.step_mode, exceptions_taken: self.exceptions_taken, last_exception: self.last_exception,
+            illegal_as_nop: self.illegal_as_nop, read_before_write: self.read_before_write, prefetch_emulation: self.prefetch_emulation, model: self.model, sfc: self.sfc, dfc: self.dfc, vbr: self.vbr, exception_nesting: self.exception_nesting, max_exception_nesting: self.max_exception_nesting, remaining_budget: 0, effective_addresses: Vec::new(), trampolines: Vec::new(), opcode_overrides: Vec::new(), history: None,
+            fetched_code: None, self_modifying_writes: Vec::new(), current_instruction: None, fetched_words: Vec::new(), instruction_accesses: self.instruction_accesses.as_ref().map(|_| Vec::new()),
+            invalid_code_regions: self.invalid_code_regions.clone(), coverage: self.coverage.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TestCore, Cycles, DEBUG_REPORT_STACK_DEPTH};
+    use super::ops; //::instruction_set;
+    use crate::ram::{AddressBus, SUPERVISOR_PROGRAM, SUPERVISOR_DATA, USER_PROGRAM, USER_DATA, AddressSpace};
+    use crate::ram::loggingmem::Operation;
+    use crate::cpu::ops::opcodes;
+
+    #[test]
+    fn new_sets_pc() {
+        let cpu = TestCore::new(256);
+        assert_eq!(256, cpu.pc);
+    }
+
+    #[test]
+    fn new_mem_sets_pc_and_mem() {
+        let base = 128;
+        let cpu = TestCore::new_mem(base, &[1u8, 2u8, 3u8, 4u8, 5u8, 6u8]);
+        assert_eq!(128, cpu.pc);
+        assert_eq!(1, cpu.mem.read_byte(SUPERVISOR_PROGRAM, 128));
+        assert_eq!(2, cpu.mem.read_byte(SUPERVISOR_PROGRAM, 129));
+    }
+
+    #[test]
+    fn a_jump_changes_pc() {
+        let mut cpu = TestCore::new(0);
+        cpu.jump(128);
+        assert_eq!(128, cpu.pc);
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn a_read_imm_u32_changes_pc() {
+        let base = 128;
+        let mut cpu = TestCore::new(base);
+        cpu.read_imm_u32();
+        assert_eq!(base+4, cpu.pc);
+    }
+
+    #[test]
+    fn a_read_imm_u32_reads_from_pc() {
+        let base = 128;
+        let mut cpu = TestCore::new_mem(base, &[2u8, 1u8, 3u8, 4u8]);
+        let val = cpu.read_imm_u32().unwrap();
+        assert_eq!((2<<24)+(1<<16)+(3<<8)+4, val);
+    }
+
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn a_read_imm_u16_changes_pc() {
+        let base = 128;
+        let mut cpu = TestCore::new(base);
+        cpu.read_imm_u16();
+        assert_eq!(base+2, cpu.pc);
+    }
+
+    #[test]
+    fn a_read_imm_u16_reads_from_pc() {
+        let base = 128;
+        let mut cpu = TestCore::new_mem(base, &[2u8, 1u8, 3u8, 4u8]);
+        assert_eq!("-S7-----", cpu.flags());
+
+        let val = cpu.read_imm_u16().unwrap();
+        assert_eq!((2<<8)+(1<<0), val);
+        assert_eq!(Operation::ReadLong(SUPERVISOR_PROGRAM, base, 0x02010304), cpu.mem.logger.ops()[0]);
+    }
+
+    #[test]
+    fn an_user_mode_read_imm_u16_is_reflected_in_mem_ops() {
+        let base = 128;
+        let mut cpu = TestCore::new_mem(base, &[2u8, 1u8, 3u8, 4u8]);
+        cpu.s_flag = 0;
+        assert_eq!("-U7-----", cpu.flags());
+
+        let val = cpu.read_imm_u16().unwrap();
+        assert_eq!((2<<8)+(1<<0), val);
+        assert_eq!(Operation::ReadLong(USER_PROGRAM, base, 0x02010304), cpu.mem.logger.ops()[0]);
+    }
+
+    #[test]
+    fn a_reset_reads_sp_and_pc_from_0() {
+        let mut cpu = TestCore::new_mem(0, &[0u8,0u8,1u8,0u8, 0u8,0u8,0u8,128u8]);
+        cpu.reset();
+        assert_eq!(256, cpu.dar[15]);
+        assert_eq!(128, cpu.pc);
+        assert_eq!("-S7-----", cpu.flags());
+        assert_eq!(Operation::ReadLong(SUPERVISOR_PROGRAM, 0, 0x100), cpu.mem.logger.ops()[0]);
+    }
+
+    #[test]
+    fn a_reset_to_sets_sp_and_pc_without_reading_vectors() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71]); // 0x4e71 NOP
+        cpu.s_flag = 0;
+        cpu.int_mask = 0;
+        cpu.processing_state = super::ProcessingState::Halted;
+        cpu.reset_to(0x400, 0x40);
+        assert_eq!(0x400, cpu.dar[15]);
+        assert_eq!(0x40, cpu.pc);
+        assert_eq!("-S7-----", cpu.flags());
+        assert_eq!(super::ProcessingState::Normal, cpu.processing_state);
+        assert!(cpu.mem.logger.ops().is_empty());
+
+        assert_eq!(Cycles(4), cpu.execute1());
+        assert_eq!(0x42, cpu.pc);
+    }
+
+    #[test]
+    fn vector_table_dump_reads_all_256_vectors_from_address_0() {
+        let mut cpu = TestCore::new(0x1000);
+        cpu.mem.write_long(SUPERVISOR_DATA, 2 * 4, 0x0000_2000);
+        cpu.mem.write_long(SUPERVISOR_DATA, 3 * 4, 0x0000_3000);
+        cpu.mem.write_long(SUPERVISOR_DATA, 255 * 4, 0x00ff_0000);
+        let table = cpu.dump_vector_table();
+        assert_eq!(256, table.len());
+        assert_eq!(0x2000, table[super::EXCEPTION_BUS_ERROR as usize]);
+        assert_eq!(0x3000, table[super::EXCEPTION_ADDRESS_ERROR as usize]);
+        assert_eq!(0x00ff_0000, table[255]);
+    }
+
+    #[test]
+    fn stack_dumps_follow_ssp_and_usp_in_either_mode() {
+        let mut cpu = TestCore::new(0x40);
+        cpu.dar[15] = 0x400;
+        cpu.inactive_usp = 0x800;
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x400, 0x1111_1111);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x404, 0x2222_2222);
+        cpu.mem.write_long(USER_DATA, 0x800, 0x3333_3333);
+        cpu.mem.write_long(USER_DATA, 0x804, 0x4444_4444);
+
+        assert_eq!(vec![0x1111_1111, 0x2222_2222], cpu.dump_supervisor_stack(2));
+        assert_eq!(vec![0x3333_3333, 0x4444_4444], cpu.dump_user_stack(2));
+
+        // in user mode A7 is the USP, but the dumps still find the right stack
+        cpu.sr_to_flags(0x0000);
+        assert_eq!(0x800, cpu.dar[15]);
+        assert_eq!(vec![0x1111_1111], cpu.dump_supervisor_stack(1));
+        assert_eq!(vec![0x3333_3333, 0x4444_4444], cpu.dump_user_stack(2));
+        assert!(cpu.dump_user_stack(0).is_empty());
+    }
+
+    #[test]
+    fn debug_report_shows_registers_code_stack_and_interrupts() {
+        // MOVEQ #42, D0 ; NOP
+        let mut cpu = TestCore::new_mem_init(0x40, &[0x70, 0x2a, 0x4e, 0x71], 0);
+        cpu.dar[9] = 0x1234_5678;
+        cpu.dar[15] = 0x400;
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x404, 0xcafe_f00d);
+        cpu.sr_to_flags(0x2314);
+        cpu.int_ctrl.request_interrupt(2);
+        let report = cpu.debug_report();
+        assert!(report.contains("D0 00000000  D1 00000000  D2 00000000  D3 00000000\n"), "{}", report);
+        assert!(report.contains("A0 00000000  A1 12345678"), "{}", report);
+        assert!(report.contains("PC 00000040  SR 2314 (-S3X-Z--)"), "{}", report);
+        assert!(report.contains("State: Normal\nInterrupts: level 2 requested, masked\n"), "{}", report);
+        assert!(report.contains("> 00000040  MOVEQ #42, D0\n  00000042  NOP\n"), "{}", report);
+        assert!(report.contains("Stack:\n  00000400  00000000\n  00000404  cafef00d\n"), "{}", report);
+        assert_eq!(DEBUG_REPORT_STACK_DEPTH, report.split("Stack:").nth(1).unwrap().lines().skip(1).count());
+
+        cpu.int_ctrl.request_interrupt(5);
+        assert!(cpu.debug_report().contains("Interrupts: level 5 pending"));
+    }
+
+    #[test]
+    fn prefetch_state_does_not_change_instruction_cycles() {
+        // (program, cycles) per the published tables
+        let cases: [(&[u8], i32); 8] = [
+            (&[0x4e, 0x71], 4),                                     // NOP
+            (&[0x70, 0x2a], 4),                                     // MOVEQ #42, D0
+            (&[0xd2, 0x40], 4),                                     // ADD.W D0, D1
+            (&[0x32, 0x18], 8),                                     // MOVE.W (A0)+, D1
+            (&[0x20, 0x3c, 0x12, 0x34, 0x56, 0x78], 12),            // MOVE.L #$12345678, D0
+            (&[0x06, 0x90, 0x00, 0x01, 0x00, 0x00], 28),            // ADDI.L #$10000, (A0)
+            (&[0xc2, 0xc0], 38),                                    // MULU.W D0, D1
+            (&[0x4e, 0xd0], 8),                                     // JMP (A0)
+        ];
+        // at both alignments, so that extension words may cross a long word
+        // and need a second prefetch
+        for &base in [0x40, 0x42].iter() {
+            for &(program, expected) in cases.iter() {
+                let mut cycles = Vec::new();
+                let mut results = Vec::new();
+                // (queue filled, prefetch emulation)
+                for &(warm, prefetch) in [(false, true), (true, true), (false, false)].iter() {
+                    let mut cpu = TestCore::new_mem_init(base, program, 0x4e71_4e71);
+                    cpu.set_prefetch_emulation(prefetch);
+                    cpu.dar[8] = 0x100;
+                    if warm {
+                        cpu.prefetch_if_needed();
+                        cpu.pc = base;
+                    }
+                    cycles.push(cpu.execute1().0);
+                    results.push((cpu.pc, cpu.dar, cpu.mem.read_long(SUPERVISOR_DATA, 0x100)));
+                }
+                assert_eq!(vec![expected; 3], cycles, "{:02x?} at {:x}", program, base);
+                assert!(results.iter().all(|result| *result == results[0]), "{:02x?} at {:x}", program, base);
+            }
+        }
+    }
+
+    #[test]
+    fn without_prefetch_emulation_instruction_words_are_read_one_at_a_time() {
+        // MOVE.L #$12345678, D0 at a long word boundary, then NOPs
+        let program = [0x20, 0x3c, 0x12, 0x34, 0x56, 0x78];
+        let mut reads = Vec::new();
+        for &prefetch in [true, false].iter() {
+            let mut cpu = TestCore::new_mem_init(0x40, &program, 0x4e71_4e71);
+            cpu.set_prefetch_emulation(prefetch);
+            assert_eq!(prefetch, cpu.prefetch_emulation());
+            cpu.set_access_capture(true);
+            assert_eq!(Cycles(12), cpu.execute1());
+            assert_eq!(0x1234_5678, cpu.dar[0]);
+            reads.push(cpu.last_instruction_accesses().iter().map(|access| (access.address, access.size)).collect::<Vec<_>>());
+        }
+        assert_eq!(vec![(0x40, 4), (0x44, 4)], reads[0]);
+        assert_eq!(vec![(0x40, 2), (0x42, 2), (0x44, 2)], reads[1]);
+    }
+
+    #[test]
+    fn apply_patch_sets_registers_stacks_and_memory() {
+        use super::patch::Patch;
+        // MOVEQ #1, D0 ; MOVEQ #2, D0
+        let mut cpu = TestCore::new_mem_init(0x40, &[0x70, 0x01, 0x70, 0x02], 0);
+        cpu.dar[15] = 0x400;
+        cpu.inactive_usp = 0x800;
+        cpu.dar[1] = 0x1111;
+        cpu.execute1();
+        assert!(Patch::new().is_empty());
+
+        let mut patch = Patch::new();
+        patch.sr = Some(0x0004);
+        patch.registers[2] = Some(0x2222);
+        patch.registers[15] = Some(0x900);
+        patch.ssp = Some(0x500);
+        patch.pc = Some(0x40);
+        // MOVEQ #1, D0 becomes MOVEQ #3, D0
+        patch.write_bytes(0x41, &[0x03]);
+        patch.write_long(0x100, 0xcafe_f00d);
+        cpu.apply_patch(&patch);
+        assert_eq!("-U0--Z--", cpu.flags());
+        assert_eq!((0x1111, 0x2222), (cpu.dar[1], cpu.dar[2]));
+        assert_eq!((0x900, 0x500), (cpu.usp(), cpu.ssp()));
+        assert_eq!(0xcafe_f00d, cpu.mem.read_long(SUPERVISOR_DATA, 0x100));
+        // the patched code runs, not the stale prefetch
+        cpu.execute1();
+        assert_eq!(3, cpu.dar[0]);
+    }
+
+    #[test]
+    fn run_until_return_runs_nested_calls_and_interrupts() {
+        // BSR.S $50 ; NOP ; RTS ; ... $50: NOP ; RTS
+        let mut cpu = TestCore::new_mem_init(0x40, &[0x61, 0x0e, 0x4e, 0x71, 0x4e, 0x75], 0x4e71_4e71);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x52, 0x4e75);
+        // the level 4 autovector handler is a lone RTE
+        cpu.mem.write_long(SUPERVISOR_DATA, 28 * 4, 0x200);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x200, 0x4e73);
+        // return address of the subroutine we are in
+        cpu.dar[15] = 0x3fc;
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x3fc, 0x100);
+        cpu.sr_to_flags(0x2000);
+        cpu.int_ctrl.request_interrupt(4);
+        let (cycles, returned) = cpu.run_until_return(1000);
+        assert!(returned);
+        assert_eq!((0x100, 0x400), (cpu.pc, cpu.dar[15]));
+        // BSR, NOP, RTS, NOP, RTS, and the interrupt with its RTE
+        assert_eq!(Cycles(18 + 4 + 16 + 4 + 16 + 44 + 20), cycles);
+
+        cpu.jump(0x40);
+        cpu.dar[15] = 0x3fc;
+        assert_eq!((Cycles(22), false), cpu.run_until_return(20));
+    }
+
+    #[test]
+    fn coverage_marks_executed_instructions() {
+        // MOVEQ #1, D0 ; BRA.S $48 ; MOVEQ #2, D0 ; $48: NOP
+        let mut cpu = TestCore::new_mem_init(0x40, &[0x70, 0x01, 0x60, 0x04, 0x70, 0x02], 0x4e71_4e71);
+        assert!(cpu.coverage().is_none());
+        cpu.enable_coverage(0x40, 0x0a);
+        for _ in 0..4 {
+            cpu.execute1();
+        }
+        assert_eq!(0x4c, cpu.pc);
+        let coverage = cpu.coverage().unwrap();
+        // the NOP at $4a is beyond the range
+        assert_eq!(vec![0x40, 0x42, 0x48], coverage.executed().collect::<Vec<_>>());
+
+        cpu.disable_coverage();
+        assert!(cpu.coverage().is_none());
+    }
+
+    #[test]
+    fn indexed_operands_cost_more_than_address_register_indirect() {
+        // (opcode with (A0), opcode with (4,A0,D1.W), cycles for each)
+        let cases = [
+            (0x3010, 0x3030, 8, 14),    // MOVE.W <ea>, D0
+            (0x2010, 0x2030, 12, 18),   // MOVE.L <ea>, D0
+            (0xd050, 0xd070, 8, 14),    // ADD.W <ea>, D0
+            (0x3080, 0x3180, 8, 14),    // MOVE.W D0, <ea>
+            (0x43d0, 0x43f0, 4, 12),    // LEA <ea>, A1
+        ];
+        for &(plain, indexed, plain_cycles, indexed_cycles) in cases.iter() {
+            let mut cycles = Vec::new();
+            for &opcode in [plain, indexed].iter() {
+                // the brief extension word of the indexed form, D1.W with displacement 4
+                let mut cpu = TestCore::new_mem_init(0x40, &[(opcode >> 8) as u8, opcode as u8, 0x10, 0x04], 0x4e71_4e71);
+                cpu.dar[1] = 0x10;
+                cpu.dar[8] = 0x100;
+                cycles.push(cpu.execute1().0);
+            }
+            assert_eq!(vec![plain_cycles, indexed_cycles], cycles, "{:04x}", plain);
+        }
+    }
+
+    #[test]
+    fn execute_reads_from_pc_and_does_not_panic_on_illegal_instruction() {
+        let mut cpu = TestCore::new_mem(0xba, &[0xba,0xd1,1u8,0u8, 0u8,0u8,0u8,128u8]);
+        cpu.execute1();
+    }
+    #[test]
+    fn execute_does_not_panic_on_odd_pc() {
+        let mut cpu = TestCore::new_mem(0xbd, &[0x00, 0x0a, 0x00, 0x00]);
+        cpu.execute1();
+    }
+
+    #[test]
+    fn execute_can_execute_instruction_handler_0a() {
+        let mut cpu = TestCore::new_mem(0xba, &[0x00, 0x0A, 1u8,0u8, 0u8,0u8,0u8,128u8]);
+        cpu.instruction_set = ops::fake::instruction_set();
+        cpu.execute1();
+        assert_eq!(0xabcd, cpu.dar[0]);
+        assert_eq!(0x0000, cpu.dar[1]);
+    }
+
+    #[test]
+    fn execute_can_execute_instruction_handler_0b() {
+        let mut cpu = TestCore::new_mem(0xba, &[0x00, 0x0B, 1u8,0u8, 0u8,0u8,0u8,128u8]);
+        cpu.instruction_set = ops::fake::instruction_set();
+        cpu.execute1();
+        assert_eq!(0x0000, cpu.dar[0]);
+        assert_eq!(0xbcde, cpu.dar[1]);
+    }
+
+
+    #[test]
+    fn execute_can_execute_set_dx() {
+        // first byte 40 is register D0
+        // 42 == D1
+        // 44 == D2
+        // 46 == D3
+        // 48 == D4
+        // 4a == D5
+        // 4c == D6
+        // 4e == D7
+        let mut cpu = TestCore::new_mem(0x40, &[0x4c, 0x00, 1u8, 0u8]);
+        cpu.instruction_set = ops::fake::instruction_set();
+        cpu.execute1();
+        assert_eq!(0xcdef, cpu.dar[6]);
+    }
+
+    #[test]
+    fn array_elems() {
+        let mut arr = [1, 2, 3, 4];
+        let marr = &mut arr;
+        let elem: &mut i32 = &mut (marr[1]);
+        // let mut elem2: &mut i32 = &mut (arr[2]);
+        assert_eq!(2, *elem);
+        *elem = 200;
+        assert_eq!(200, *elem);
+        // assert_eq!(200, &mut marr[1]);
+    }
+    #[test]
+    fn cycle_counting() {
+        // 0xc308 = abcd_8_mm taking 18 cycles
+        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x08]);
+        let Cycles(count) = cpu.execute1();
+        assert_eq!(18, count);
+    }
+    #[test]
+    fn cycle_counting_exec2() {
+        // 0xc308 = abcd_8_mm taking 18 cycles
+        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x08, 0xc3, 0x08]);
+        let Cycles(count) = cpu.execute(20);
+        assert_eq!(18*2, count);
+    }
+
+    #[test]
+    fn cycle_granularity_rounds_up_to_boundary() {
+        // 0xc308 = abcd_8_mm taking 18 cycles
+        // With granularity 4, should round up to 20
+        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x08]);
+        cpu.set_cycle_granularity(4);
+        let Cycles(count) = cpu.execute1();
+        assert_eq!(20, count); // 18 rounded up to next multiple of 4
+    }
+
+    #[test]
+    fn cycle_granularity_no_change_when_already_aligned() {
+        // 0xc300 = abcd_8_rr taking 6 cycles (already aligned to 4)
+        // NOP = 0x4e71 taking 4 cycles (already aligned)
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71]);
+        cpu.set_cycle_granularity(4);
+        let Cycles(count) = cpu.execute1();
+        assert_eq!(4, count); // Already a multiple of 4
+    }
+
+    #[test]
+    fn cycle_granularity_atari_st_mode() {
+        // Simulate Atari ST timing with two instructions
+        // 0xc308 = abcd_8_mm taking 18 cycles -> rounds to 20
+        // 0xc300 = abcd_8_rr taking 6 cycles -> rounds to 8
+        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x08, 0xc3, 0x00]);
+        cpu.set_cycle_granularity(4);
+
+        // Execute first instruction - takes 18 cycles, aligned to 20
+        let Cycles(count) = cpu.execute1();
+        assert_eq!(20, count);
+
+        // Execute second instruction - takes 6 cycles, aligned to 8
+        let Cycles(count2) = cpu.execute1();
+        assert_eq!(8, count2);
+
+        // Total: 28 cycles aligned (vs 24 raw) - matching Atari ST bus behavior
+        assert_eq!(28, count + count2);
+    }
+
+    #[test]
+    fn cycle_exact_execute_carries_overrun_into_next_call() {
+        // NOPs, 4 cycles each
+        let mut cpu = TestCore::new_mem_init(0x40, &[], 0x4e71_4e71);
+        cpu.set_cycle_exact(true);
+
+        assert_eq!(Cycles(6), cpu.execute(6));
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!(2, cpu.owed_cycles());
+
+        // the second NOP is paid for, but not started
+        assert_eq!(Cycles(1), cpu.execute(1));
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!(1, cpu.owed_cycles());
+
+        // 1 cycle to finish, then three NOPs overrunning the budget by 3
+        assert_eq!(Cycles(10), cpu.execute(10));
+        assert_eq!(0x4a, cpu.pc);
+        assert_eq!(3, cpu.owed_cycles());
+
+        // execute1 settles the overrun and runs one whole NOP
+        assert_eq!(Cycles(3 + 4), cpu.execute1());
+        assert_eq!(0x4c, cpu.pc);
+        assert_eq!(0, cpu.owed_cycles());
+
+        assert_eq!(Cycles(5), cpu.execute(5));
+        cpu.set_cycle_exact(false);
+        assert_eq!(0, cpu.owed_cycles());
+        assert_eq!(Cycles(8), cpu.execute(5));
+    }
+
+    #[test]
+    fn abcd_8_rr() {
+        // opcodes c100 - c107, c300 - c307, etc.
+        // or more generally c[13579bdf]0[0-7]
+        // where [13579bdf] is DX (dest regno) and [0-7] is DY (src regno)
+        // so c300 means D1 = D0 + D1 in BCD
+        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x00]);
+
+        cpu.dar[0] = 0x16;
+        cpu.dar[1] = 0x26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(0x42, cpu.dar[1]);
+    }
+    #[test]
+    fn abcd_8_mm() {
+        // opcodes c108 - c10f, c308 - c30f, etc.
+        // or more generally c[13579bdf]0[8-f]
+        // where [13579bdf] is AX (dest regno) and [8-f] is AY (src regno)
+        // so c308 means A1 = A0 + A1 in BCD
+        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x08]);
+
+        cpu.dar[8+0] = 0x160+1;
+        cpu.dar[8+1] = 0x260+1;
+        cpu.mem.write_byte(USER_DATA, 0x160, 0x16);
+        cpu.mem.write_byte(USER_DATA, 0x260, 0x26);
+        cpu.execute1();
+        let res = cpu.mem.read_byte(USER_DATA, 0x260);
+
+        // 16 + 26 is 42
+        assert_eq!(0x42, res);
+    }
+
+    fn negate_multibyte(opcode: [u8; 2], value: &[u8], x_flag: u32) -> (TestCore, Vec<u8>) {
+        // execute the same -(A0) instruction once per byte, least significant byte first
+        let program: Vec<u8> = value.iter().flat_map(|_| opcode).collect();
+        let mut cpu = TestCore::new_mem(0x40, &program);
+        let addr = 0x100;
+        for (offset, byte) in value.iter().enumerate() {
+            cpu.mem.write_byte(USER_DATA, addr + offset as u32, u32::from(*byte));
+        }
+        cpu.dar[8] = addr + value.len() as u32;
+        cpu.x_flag = x_flag;
+        cpu.not_z_flag = super::ZFLAG_SET; // multi-precision code sets Z before the first step
+        for _ in value {
+            cpu.execute1();
+        }
+        let result = (0..value.len() as u32).map(|offset| cpu.mem.read_byte(USER_DATA, addr + offset) as u8).collect();
+        (cpu, result)
+    }
+    const X: u16 = 0b10000;
+    const Z: u16 = 0b00100;
+    const C: u16 = 0b00001;
+    fn bcd_to_decimal(bcd: &[u8]) -> u32 {
+        bcd.iter().fold(0, |acc, b| acc * 100 + u32::from(b >> 4) * 10 + u32::from(b & 0xf))
+    }
+
+    #[test]
+    fn nbcd_8_pd_chain_negates_multibyte_bcd_value() {
+        // opcodes 4820 is NBCD     -(A0)
+        let (cpu, result) = negate_multibyte([0x48, 0x20], &[0x00, 0x12, 0x34], super::XFLAG_CLEAR);
+
+        // ten's complement of 001234 is 998766
+        assert_eq!(1_000_000 - 1234, bcd_to_decimal(&result));
+        assert_eq!(vec![0x99, 0x87, 0x66], result);
+        assert_eq!(0x100, cpu.dar[8]);
+        // borrow propagates out of the top byte, and the result is non-zero
+        // (N and V are undefined for NBCD, so only X, Z and C are checked)
+        assert_eq!(X | C, cpu.condition_code_register() & (X | Z | C));
+    }
+    #[test]
+    fn nbcd_8_pd_chain_with_x_set_subtracts_extra_one() {
+        // opcodes 4820 is NBCD     -(A0)
+        let (cpu, result) = negate_multibyte([0x48, 0x20], &[0x00, 0x12, 0x34], super::XFLAG_SET);
+
+        assert_eq!(1_000_000 - 1234 - 1, bcd_to_decimal(&result));
+        assert_eq!(X | C, cpu.condition_code_register() & (X | Z | C));
+    }
+    #[test]
+    fn nbcd_8_pd_chain_of_zero_keeps_z_and_clears_x() {
+        // opcodes 4820 is NBCD     -(A0)
+        let (cpu, result) = negate_multibyte([0x48, 0x20], &[0x00, 0x00, 0x00], super::XFLAG_CLEAR);
+
+        assert_eq!(vec![0x00, 0x00, 0x00], result);
+        assert_eq!(Z, cpu.condition_code_register() & (X | Z | C));
+    }
+    #[test]
+    fn nbcd_8_pd_chain_z_is_sticky_once_cleared() {
+        // opcodes 4820 is NBCD     -(A0)
+        // low byte is non-zero, later bytes produce 0x99 but must never set Z again
+        let (cpu, result) = negate_multibyte([0x48, 0x20], &[0x00, 0x00, 0x01], super::XFLAG_CLEAR);
+
+        assert_eq!(vec![0x99, 0x99, 0x99], result);
+        assert_eq!(X | C, cpu.condition_code_register() & (X | Z | C));
+    }
+    #[test]
+    fn negx_8_pd_chain_negates_multibyte_binary_value() {
+        // opcodes 4020 is NEGX.B   -(A0)
+        let (cpu, result) = negate_multibyte([0x40, 0x20], &[0x00, 0x12, 0x34], super::XFLAG_CLEAR);
+
+        // two's complement of 0x001234 is 0xffedcc
+        assert_eq!(vec![0xff, 0xed, 0xcc], result);
+        assert_eq!("-S7XN--C", cpu.flags());
+
+        let (cpu, result) = negate_multibyte([0x40, 0x20], &[0x00, 0x00, 0x00], super::XFLAG_CLEAR);
+        assert_eq!(vec![0x00, 0x00, 0x00], result);
+        assert_eq!("-S7--Z--", cpu.flags());
+    }
+
+    #[test]
+    fn add_8_er_d() {
+        // opcodes d000 - d007, d200 - d207, etc.
+        // or more generally d[02468ace]0[0-7]
+        // where [02468ace] is DX (dest regno) and [0-7] is DY (src regno)
+
+        // opcodes d200 is ADD.B    D0, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x00]);
+
+        cpu.dar[0] = 16;
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+    }
+
+    #[test]
+    fn add_8_er_pi() {
+        // opcodes d018 - d01f, d218 - d21f, etc.
+        // or more generally d[02468ace]1[8-f]
+        // where [02468ace] is DX (dest regno) and [8-f] is AY (src regno)
+
+        // opcodes d218 is ADD.B    (A0)+, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x18]);
+        let addr = 0x100;
+        cpu.dar[8+0] = addr;
+        cpu.mem.write_byte(USER_DATA, addr, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+        assert_eq!(addr+1, cpu.dar[8+0]);
+    }
+
+    #[test]
+    fn add_8_er_pd() {
+        // opcodes d020 - d027, d220 - d227, etc.
+        // or more generally d[02468ace]2[0-7]
+        // where [02468ace] is DX (dest regno) and [0-7] is AY (src regno)
+
+        // opcodes d220 is ADD.B    -(A0), D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x20]);
+        let addr = 0x100;
+        cpu.dar[8+0] = addr;
+        cpu.mem.write_byte(USER_DATA, addr-1, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+        assert_eq!(addr-1, cpu.dar[8+0]);
+    }
+
+    #[test]
+    fn add_8_er_ai() {
+        // opcodes d010 - d017, d210 - d217, etc.
+        // or more generally d[02468ace]1[0-7]
+        // where [02468ace] is DX (dest regno) and [0-7] is AY (src regno)
+
+        // opcodes d210 is ADD.B    (A0), D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x10]);
+
+        let addr = 0x100;
+        cpu.dar[8+0] = addr;
+        cpu.mem.write_byte(USER_DATA, addr, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+        assert_eq!(addr, cpu.dar[8+0]);
+    }
+    #[test]
+    fn add_8_er_di_with_positive_displacement() {
+        // opcodes d028 - d02f, d228 - d22f, etc.
+        // or more generally d[02468ace]2[8-f]
+        // where [02468ace] is DX (dest regno) and [8-f] is AY (src regno)
+
+        // opcodes d228,0108 is ADD.B    (0x108, A0), D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x28, 0x01, 0x08]);
+
+        let addr = 0x100;
+        cpu.dar[8+0] = addr;
+        let displaced_addr = addr + 0x108;
+        cpu.mem.write_byte(USER_DATA, displaced_addr, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+        assert_eq!(addr, cpu.dar[8+0]);
+    }
+    #[test]
+    fn add_8_er_di_with_negative_displacement() {
+        // opcodes d028 - d02f, d228 - d22f, etc. followed by an extension word
+        // or more generally d[02468ace]2[8-f]
+        // where [02468ace] is DX (dest regno) and [8-f] is AY (src regno)
+
+        // opcodes d228,FFFE is ADD.B    (-2, A0), D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x28, 0xFF, 0xFE]);
+
+        let addr = 0x100;
+        cpu.dar[8+0] = addr;
+        let displaced_addr = addr - 2;
+        cpu.mem.write_byte(USER_DATA, displaced_addr, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+        assert_eq!(addr, cpu.dar[8+0]);
+    }
+    #[test]
+    fn add_8_er_ix_with_positive_displacement() {
+        // opcodes d030 - d037, d230 - d237, etc. followed by an extension word
+        // or more generally d[02468ace]3[0-7]
+        // where [02468ace] is DX (dest regno) and [0-7] is AY (src regno)
+
+        // opcodes d230,9002 is ADD.B    (2, A0, A1), D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x30, 0x90, 0x02]);
+
+        let addr = 0x100;
+        let index = 0x10;
+        let displacement = 2;
+        cpu.dar[8+0] = addr;
+        cpu.dar[8+1] = index;
+        let effective_addr = addr + index + displacement;
+        cpu.mem.write_byte(USER_DATA, effective_addr, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+        assert_eq!(addr, cpu.dar[8+0]);
+    }
+    #[test]
+    fn add_8_er_ix_with_negative_displacement() {
+        // opcodes d030 - d037, d230 - d237, etc. followed by an extension word
+        // or more generally d[02468ace]3[0-7]
+        // where [02468ace] is DX (dest regno) and [0-7] is AY (src regno)
+
+        // opcodes d230,90FE is ADD.B    (-2, A0, A1), D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x30, 0x90, 0xFE]);
+
+        let addr = 0x100;
+        let index = 0x10;
+        let displacement = 2;
+        cpu.dar[8+0] = addr;
+        cpu.dar[8+1] = index;
+        let effective_addr = addr + index - displacement;
+        cpu.mem.write_byte(USER_DATA, effective_addr, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+        assert_eq!(addr, cpu.dar[8+0]);
+    }
+    #[test]
+    fn ix_with_same_base_and_index_register_uses_its_value_twice() {
+        // 2030,8804 is MOVE.L (4, A0, A0.L), D0
+        let mut cpu = TestCore::new_mem(0x40, &[0x20, 0x30, 0x88, 0x04]);
+        cpu.dar[8] = 0x1000;
+        cpu.mem.write_long(USER_DATA, 0x1000 + 0x1000 + 4, 0xcafe_babe);
+        cpu.execute1();
+        assert_eq!(0xcafe_babe, cpu.dar[0]);
+        assert_eq!(0x1000, cpu.dar[8]);
+
+        // 41f0,8804 is LEA (4, A0, A0.L), A0, which also writes the register
+        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0xf0, 0x88, 0x04]);
+        cpu.dar[8] = 0x1000;
+        cpu.execute1();
+        assert_eq!(0x2004, cpu.dar[8]);
+
+        // 41f0,80fc is LEA (-4, A0, A0.W), A0; the index is the sign-extended low word
+        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0xf0, 0x80, 0xfc]);
+        cpu.dar[8] = 0x0001_8000;
+        cpu.execute1();
+        assert_eq!(0x0001_8000u32.wrapping_sub(0x8000).wrapping_sub(4), cpu.dar[8]);
+    }
+    #[test]
+    fn add_8_er_aw() {
+        // opcodes d038, d238, d438, etc. followed by an extension word
+        // or more generally d[02468ace]38
+        // where [02468ace] is DX (dest regno) and the extension word is
+        // the 16-bit absolute address
+
+        // opcodes d238,0108 is ADD.B    $0108, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x38, 0x01, 0x08]);
+        cpu.mem.write_byte(USER_DATA, 0x108, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+    }
+    #[test]
+    fn add_8_er_al() {
+        // opcodes d039, d239, d439, etc. followed by two extension words
+        // or more generally d[02468ace]39
+
+        // where [02468ace] is DX (dest regno) and the first extension
+        // word is the high order word of the 32-bit absolute address,
+        // and the second extension word is the low order word.
+
+        // opcodes d239,0009,0000 is ADD.B    $90000, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x39, 0x00, 0x09, 0x00, 0x00]);
+        cpu.mem.write_byte(USER_DATA, 0x90000, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+    }
+    #[test]
+    fn add_8_er_pcdi() {
+        // opcodes d03a, d23a, d43a, etc. followed by an extension word
+        // or more generally d[02468ace]3a
+
+        // where [02468ace] is DX (dest regno)
+        // opcodes d23a,0108 is ADD.B    ($0108, PC), D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x3a, 0x01, 0x08]);
+        let addr = 0x40+2+0x0108;
+        cpu.mem.write_byte(USER_DATA, addr, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+    }
+    #[test]
+    fn add_8_er_pcix() {
+        // opcodes d03b, d23b, d43b, etc. followed by an extension word
+        // or more generally d[02468ace]3b
+
+        // where [02468ace] is DX (dest regno)
+        // opcodes d23b,9002 is ADD.B    (2, PC, A1), D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x3b, 0x90, 0x02]);
+        let addr = cpu.pc + 2; // will be +2 after reading instruction word
+        let index = 0x10;
+        let displacement = 2;
+        cpu.dar[8+1] = index;
+        let effective_addr = addr + index + displacement;
+        cpu.mem.write_byte(USER_DATA, effective_addr, 16);
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+    }
+    #[test]
+    fn add_8_er_imm() {
+        // opcodes d03c, d23c, d43c, etc. followed by an extension word
+        // or more generally d[02468ace]3c
+        // where [02468ace] is DX (dest regno)
+
+        // opcodes d23c,0010 is ADD.B    #16, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x3c, 0x00, 0x10]);
+
+        cpu.dar[1] = 26;
+        cpu.execute1();
+
+        // 16 + 26 is 42
+        assert_eq!(42, cpu.dar[1]);
+    }
+    #[test]
+    fn add_16_re_pi() { //0xD400, 0xD700
+        let mut cpu = TestCore::new_mem(0x40, &[0xd3, 0x58]);
+        cpu.dar[8+0] = 0x40;
+        cpu.dar[1] = 0xa8;
+
+        cpu.execute1();
+        for op in cpu.mem.logger.ops() {
+            println!("{:?}", op);
+        }
+        let word = cpu.read_data_word(64).unwrap();
+        assert_eq!(0xd358 + 0xa8, word);
+    }
+    #[test]
+    fn cmpa_16_imm_sign_extends_source_to_32_bits() {
+        // opcodes b0fc,8000 is CMPA.W   #$8000, A0
+        let mut cpu = TestCore::new_mem(0x40, &[0xb0, 0xfc, 0x80, 0x00]);
+        cpu.dar[8] = 0xffff_8000;
+        cpu.execute1();
+
+        // #$8000 is compared as $ffff8000, so the operands are equal
+        assert_eq!("-S7--Z--", cpu.flags());
+        assert_eq!(0xffff_8000, cpu.dar[8]);
+        assert_eq!(0x44, cpu.pc);
+    }
+    #[test]
+    fn cmpa_16_imm_compares_all_32_bits_of_address_register() {
+        // opcodes b0fc,8000 is CMPA.W   #$8000, A0
+        let mut cpu = TestCore::new_mem(0x40, &[0xb0, 0xfc, 0x80, 0x00]);
+        cpu.dar[8] = 0x0000_8000;
+        cpu.execute1();
+
+        // $00008000 - $ffff8000 borrows, and is not equal
+        assert_eq!("-S7----C", cpu.flags());
+        assert_eq!(0x0000_8000, cpu.dar[8]);
+    }
+    // Flags of ADD or SUB per the MC68000 Programmer's Reference Manual,
+    // computed on wide integers: returns the result and the CCR.
+    fn reference_add_sub(bits: u32, subtract: bool, dst: u32, src: u32) -> (u32, u16) {
+        let mask = (1u64 << bits) - 1;
+        let (dst, src) = (u64::from(dst) & mask, u64::from(src) & mask);
+        let signed = |value: u64| ((value << (64 - bits)) as i64) >> (64 - bits);
+        let (wide, signed_wide) = if subtract {
+            (dst.wrapping_sub(src), signed(dst) - signed(src))
+        } else {
+            (dst + src, signed(dst) + signed(src))
+        };
+        let res = wide & mask;
+        let carry = if subtract { src > dst } else { wide > mask };
+        let overflow = signed_wide != signed(res);
+        let ccr = (if carry { 0x11 } else { 0 }) | (if res >> (bits - 1) != 0 { 0x08 } else { 0 }) |
+            (if res == 0 { 0x04 } else { 0 }) | (if overflow { 0x02 } else { 0 });
+        (res as u32, ccr)
+    }
+
+    // Runs ADD or SUB of the given size between D0 and (A0), in the given
+    // direction, and returns the destination, the CCR and the cycles taken.
+    fn run_add_sub(cpu: &mut TestCore, bits: u32, subtract: bool, to_memory: bool, dst: u32, src: u32) -> (u32, u16, i32) {
+        let size = match bits { 8 => 0, 16 => 1, _ => 2 };
+        // ADD/SUB.size (A0), D0 or D0, (A0)
+        let opcode = if subtract { 0x9010 } else { 0xd010 } | (u32::from(to_memory) << 8) | (size << 6);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x40, opcode);
+        cpu.pc = 0x40;
+        cpu.prefetch_addr = 1;
+        cpu.dar[8] = 0x1000;
+        // start from the inverse of every flag the instruction should set
+        let (_, expected_ccr) = reference_add_sub(bits, subtract, dst, src);
+        cpu.sr_to_flags(0x2700 | (!expected_ccr & 0x1f));
+        let (register, memory) = if to_memory { (src, dst) } else { (dst, src) };
+        cpu.dar[0] = register;
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x1000, memory << (32 - bits));
+        let cycles = cpu.execute1().0;
+        let result = if to_memory {
+            cpu.mem.read_long(SUPERVISOR_DATA, 0x1000) >> (32 - bits)
+        } else {
+            // the upper bits of D0 are left alone
+            assert_eq!(dst & !(u32::MAX >> (32 - bits)), cpu.dar[0] & !(u32::MAX >> (32 - bits)));
+            cpu.dar[0] & (u32::MAX >> (32 - bits))
+        };
+        (result, cpu.status_register() & 0x1f, cycles)
+    }
+
+    fn check_add_sub(cpu: &mut TestCore, dst: u32, src: u32) -> bool {
+        // (An) source or destination: (er byte/word, er long, re byte/word, re long)
+        let cycles = |bits, to_memory| match (to_memory, bits) { (false, 32) => 14, (false, _) => 8, (true, 32) => 20, (true, _) => 12 };
+        for &bits in &[8, 16, 32] {
+            for &subtract in &[false, true] {
+                for &to_memory in &[false, true] {
+                    let (res, ccr) = reference_add_sub(bits, subtract, dst, src);
+                    let dst = if to_memory { dst & (u32::MAX >> (32 - bits)) } else { dst };
+                    let src = src & (u32::MAX >> (32 - bits));
+                    if run_add_sub(cpu, bits, subtract, to_memory, dst, src) != (res, ccr, cycles(bits, to_memory)) {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn add_sub_flags_at_boundaries_match_reference() {
+        const BOUNDARIES: [u32; 14] = [0, 1, 2, 0x7e, 0x7f, 0x80, 0xff, 0x7fff, 0x8000, 0xffff,
+            0x7fff_ffff, 0x8000_0000, 0xffff_fffe, 0xffff_ffff];
+        let mut cpu = TestCore::new(0x40);
+        for &dst in &BOUNDARIES {
+            for &src in &BOUNDARIES {
+                assert!(check_add_sub(&mut cpu, dst, src), "dst {:08x} src {:08x}", dst, src);
+            }
+        }
+    }
+
+    #[test]
+    fn add_sub_flags_match_reference() {
+        use quickcheck::{QuickCheck, TestResult};
+        fn prop(dst: u32, src: u32) -> TestResult {
+            let mut cpu = TestCore::new(0x40);
+            TestResult::from_bool(check_add_sub(&mut cpu, dst, src))
+        }
+        QuickCheck::new().tests(100).quickcheck(prop as fn(u32, u32) -> TestResult);
+    }
+
+    #[test]
+    fn cmp_8_signed_overflow_boundary() {
+        // opcodes b001 is CMP.B    D1, D0
+        let mut cpu = TestCore::new_mem(0x40, &[0xb0, 0x01]);
+        cpu.dar[0] = 0x80;
+        cpu.dar[1] = 0x7f;
+        cpu.execute1();
+
+        // -128 - 127 overflows, result 0x01 is positive, no borrow
+        assert_eq!("-S7---V-", cpu.flags());
+        assert_eq!(0x80, cpu.dar[0]);
+        assert_eq!(0x7f, cpu.dar[1]);
+    }
+    #[test]
+    fn cmp_16_signed_overflow_boundary() {
+        // opcodes b041 is CMP.W    D1, D0
+        let mut cpu = TestCore::new_mem(0x40, &[0xb0, 0x41]);
+        cpu.dar[0] = 0x8000;
+        cpu.dar[1] = 0x7fff;
+        cpu.execute1();
+
+        // -32768 - 32767 overflows, result 0x0001 is positive, no borrow
+        assert_eq!("-S7---V-", cpu.flags());
+        assert_eq!(0x8000, cpu.dar[0]);
+        assert_eq!(0x7fff, cpu.dar[1]);
+    }
+    #[test]
+    fn cmp_16_reversed_signed_overflow_boundary() {
+        // opcodes b041 is CMP.W    D1, D0
+        let mut cpu = TestCore::new_mem(0x40, &[0xb0, 0x41]);
+        cpu.dar[0] = 0x7fff;
+        cpu.dar[1] = 0x8000;
+        cpu.execute1();
+
+        // 32767 - -32768 overflows, result 0xffff is negative, and borrows
+        assert_eq!("-S7-N-VC", cpu.flags());
+        assert_eq!(0x7fff, cpu.dar[0]);
+        assert_eq!(0x8000, cpu.dar[1]);
+    }
+    #[test]
+    fn cmp_32_signed_overflow_boundary() {
+        // opcodes b081 is CMP.L    D1, D0
+        let mut cpu = TestCore::new_mem(0x40, &[0xb0, 0x81]);
+        cpu.dar[0] = 0x8000_0000;
+        cpu.dar[1] = 0x7fff_ffff;
+        cpu.execute1();
+
+        assert_eq!("-S7---V-", cpu.flags());
+        assert_eq!(0x8000_0000, cpu.dar[0]);
+        assert_eq!(0x7fff_ffff, cpu.dar[1]);
+    }
+    #[test]
+    fn cmp_preserves_x_flag() {
+        // opcodes b041 is CMP.W    D1, D0
+        let mut cpu = TestCore::new_mem(0x40, &[0xb0, 0x41]);
+        cpu.x_flag = super::XFLAG_SET;
+        cpu.dar[0] = 0x0001;
+        cpu.dar[1] = 0x0002;
+        cpu.execute1();
+
+        // borrow sets C, but X is left alone
+        assert_eq!("-S7XN--C", cpu.flags());
+
+        // and a compare without borrow does not clear it
+        let mut cpu = TestCore::new_mem(0x40, &[0xb0, 0x41]);
+        cpu.x_flag = super::XFLAG_SET;
+        cpu.dar[0] = 0x0002;
+        cpu.dar[1] = 0x0002;
+        cpu.execute1();
+        assert_eq!("-S7X-Z--", cpu.flags());
+    }
+    #[test]
+    fn cmpi_16_signed_overflow_boundary_leaves_memory_operand_unchanged() {
+        // opcodes 0c50,8000 is CMPI.W   #$8000, (A0)
+        let mut cpu = TestCore::new_mem(0x40, &[0x0c, 0x50, 0x80, 0x00]);
+        let addr = 0x100;
+        cpu.dar[8] = addr;
+        cpu.mem.write_word(USER_DATA, addr, 0x7fff);
+        cpu.x_flag = super::XFLAG_SET;
+        cpu.execute1();
+
+        assert_eq!("-S7XN-VC", cpu.flags());
+        assert_eq!(0x7fff, cpu.mem.read_word(USER_DATA, addr));
+        assert_eq!(addr, cpu.dar[8]);
+        // the only write is the one setting up the operand
+        let writes = cpu.mem.logger.ops().into_iter().filter(|op| matches!(op,
+            Operation::WriteByte(..) | Operation::WriteWord(..) | Operation::WriteLong(..))).count();
+        assert_eq!(1, writes);
+    }
+    #[test]
+    fn cmpi_32_dn_leaves_register_unchanged() {
+        // opcodes 0c80,8000,0000 is CMPI.L   #$80000000, D0
+        let mut cpu = TestCore::new_mem(0x40, &[0x0c, 0x80, 0x80, 0x00, 0x00, 0x00]);
+        cpu.dar[0] = 0x7fff_ffff;
+        cpu.execute1();
+
+        assert_eq!("-S7-N-VC", cpu.flags());
+        assert_eq!(0x7fff_ffff, cpu.dar[0]);
+        assert_eq!(0x46, cpu.pc);
+    }
+
+    #[test]
+    fn adda_16_imm_sign_extends_and_preserves_flags() {
+        // opcodes d0fc,ffff is ADDA.W   #$FFFF, A0
+        let mut cpu = TestCore::new_mem(0x40, &[0xd0, 0xfc, 0xff, 0xff]);
+        cpu.dar[8] = 0x0001_0000;
+        cpu.sr_to_flags(0x2700);
+        cpu.execute1();
+
+        // $FFFF is -1, so A0 is decremented across the word boundary
+        assert_eq!(0x0000_ffff, cpu.dar[8]);
+        assert_eq!("-S7-----", cpu.flags());
+    }
+    #[test]
+    fn adda_16_dn_uses_only_low_word_of_source() {
+        // opcodes d0c1 is ADDA.W   D1, A0
+        let mut cpu = TestCore::new_mem(0x40, &[0xd0, 0xc1]);
+        cpu.dar[1] = 0x1234_7fff;
+        cpu.dar[8] = 0xffff_ffff;
+        cpu.sr_to_flags(0x271f);
+        cpu.execute1();
+
+        // $7FFF is the largest positive word, no sign extension
+        assert_eq!(0x0000_7ffe, cpu.dar[8]);
+        assert_eq!("-S7XNZVC", cpu.flags());
+    }
+    #[test]
+    fn adda_32_imm_adds_full_long() {
+        // opcodes d1fc,0000,ffff is ADDA.L   #$0000FFFF, A0
+        let mut cpu = TestCore::new_mem(0x40, &[0xd1, 0xfc, 0x00, 0x00, 0xff, 0xff]);
+        cpu.dar[8] = 0x0001_0000;
+        cpu.sr_to_flags(0x2715);
+        cpu.execute1();
+
+        assert_eq!(0x0001_ffff, cpu.dar[8]);
+        assert_eq!("-S7X-Z-C", cpu.flags());
+    }
+    #[test]
+    fn suba_16_imm_sign_extends_and_preserves_flags() {
+        // opcodes 90fc,8000 is SUBA.W   #$8000, A0
+        let mut cpu = TestCore::new_mem(0x40, &[0x90, 0xfc, 0x80, 0x00]);
+        cpu.dar[8] = 0x0000_1000;
+        cpu.sr_to_flags(0x270a);
+        cpu.execute1();
+
+        // $8000 is -32768, so subtracting it adds $8000
+        assert_eq!(0x0000_9000, cpu.dar[8]);
+        assert_eq!("-S7-N-V-", cpu.flags());
+    }
+    #[test]
+    fn suba_32_imm_subtracts_full_long_and_wraps() {
+        // opcodes 91fc,0000,8000 is SUBA.L   #$00008000, A0
+        let mut cpu = TestCore::new_mem(0x40, &[0x91, 0xfc, 0x00, 0x00, 0x80, 0x00]);
+        cpu.dar[8] = 0x0000_1000;
+        cpu.sr_to_flags(0x2700);
+        cpu.execute1();
+
+        assert_eq!(0xffff_9000, cpu.dar[8]);
+        assert_eq!("-S7-----", cpu.flags());
+    }
+
+    fn cycles_and_length(code: &[u8]) -> (Cycles, u32) {
+        let mut cpu = TestCore::new_mem(0x40, code);
+        cpu.dar[8] = 0x100;
+        let cycles = cpu.execute1();
+        (cycles, cpu.pc - 0x40)
+    }
+    #[test]
+    fn immediate_instructions_charge_for_long_immediate_fetch() {
+        // opcodes 0640,1234 is ADDI.W   #$1234, D0
+        assert_eq!((Cycles(8), 4), cycles_and_length(&[0x06, 0x40, 0x12, 0x34]));
+        // opcodes 0680,1234,5678 is ADDI.L   #$12345678, D0
+        assert_eq!((Cycles(16), 6), cycles_and_length(&[0x06, 0x80, 0x12, 0x34, 0x56, 0x78]));
+        // opcodes 0450,1234 is SUBI.W   #$1234, (A0)
+        assert_eq!((Cycles(16), 4), cycles_and_length(&[0x04, 0x50, 0x12, 0x34]));
+        // opcodes 0490,1234,5678 is SUBI.L   #$12345678, (A0)
+        assert_eq!((Cycles(28), 6), cycles_and_length(&[0x04, 0x90, 0x12, 0x34, 0x56, 0x78]));
+        // opcodes 0280,1234,5678 is ANDI.L   #$12345678, D0 (as in Musashi)
+        assert_eq!((Cycles(14), 6), cycles_and_length(&[0x02, 0x80, 0x12, 0x34, 0x56, 0x78]));
+        // opcodes 0080,1234,5678 is ORI.L    #$12345678, D0
+        assert_eq!((Cycles(16), 6), cycles_and_length(&[0x00, 0x80, 0x12, 0x34, 0x56, 0x78]));
+        // opcodes 0a80,1234,5678 is EORI.L   #$12345678, D0
+        assert_eq!((Cycles(16), 6), cycles_and_length(&[0x0a, 0x80, 0x12, 0x34, 0x56, 0x78]));
+        // opcodes 0c40,1234 is CMPI.W   #$1234, D0
+        assert_eq!((Cycles(8), 4), cycles_and_length(&[0x0c, 0x40, 0x12, 0x34]));
+        // opcodes 0c80,1234,5678 is CMPI.L   #$12345678, D0
+        assert_eq!((Cycles(14), 6), cycles_and_length(&[0x0c, 0x80, 0x12, 0x34, 0x56, 0x78]));
+        // opcodes 303c,1234 is MOVE.W   #$1234, D0
+        assert_eq!((Cycles(8), 4), cycles_and_length(&[0x30, 0x3c, 0x12, 0x34]));
+        // opcodes 203c,1234,5678 is MOVE.L   #$12345678, D0
+        assert_eq!((Cycles(12), 6), cycles_and_length(&[0x20, 0x3c, 0x12, 0x34, 0x56, 0x78]));
+    }
+
+    #[test]
+    fn absolute_short_addresses_are_sign_extended() {
+        // MOVE.W $8000.W, D0 ; MOVE.W D1, $8002.W ; MOVE.W $7ffe.W, D2
+        let mut cpu = TestCore::new_mem(0x40, &[0x30, 0x38, 0x80, 0x00, 0x31, 0xc1, 0x80, 0x02, 0x34, 0x38, 0x7f, 0xfe]);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0xff_8000, 0x1234);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x00_8000, 0x5678);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x00_8002, 0x0000);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x00_7ffe, 0x9abc);
+        cpu.mem.write_word(SUPERVISOR_DATA, 0xff_7ffe, 0x0000);
+        cpu.dar[1] = 0xbeef;
+
+        cpu.execute1();
+        assert_eq!(0x1234, cpu.dar[0] & 0xffff);
+        assert!(cpu.mem.logger.ops().contains(&Operation::ReadWord(SUPERVISOR_DATA, 0xff_8000, 0x1234)));
+        cpu.execute1();
+        assert_eq!(0xbeef, cpu.mem.read_word(SUPERVISOR_DATA, 0xff_8002));
+        assert_eq!(0x0000, cpu.mem.read_word(SUPERVISOR_DATA, 0x00_8002));
+        // addresses below 0x8000 stay at the bottom of the address space
+        cpu.execute1();
+        assert_eq!(0x9abc, cpu.dar[2] & 0xffff);
+    }
+
+    // Runs a PC relative JMP or JSR at 0x40 and returns the cycles, the new
+    // PC and the return address pushed, if any.
+    fn pc_relative_jump(code: &[u8]) -> (Cycles, u32, Option<u32>) {
+        let mut cpu = TestCore::new_mem(0x40, code);
+        cpu.dar[0] = 4;
+        cpu.dar[9] = 0x0001_0000;
+        cpu.dar[15] = 0x400;
+        let cycles = cpu.execute1();
+        let pushed = if cpu.dar[15] == 0x400 - 4 { Some(cpu.mem.read_long(SUPERVISOR_DATA, 0x400 - 4)) } else { None };
+        (cycles, cpu.pc, pushed)
+    }
+    #[test]
+    fn pc_relative_jmp_and_jsr_use_extension_word_address() {
+        // opcodes 4eba,0100 is JSR (*+$102,PC): the base is the extension word at 0x42
+        assert_eq!((Cycles(18), 0x142, Some(0x44)), pc_relative_jump(&[0x4e, 0xba, 0x01, 0x00]));
+        // opcodes 4eba,fffe is JSR (-2,PC), a call to itself
+        assert_eq!((Cycles(18), 0x40, Some(0x44)), pc_relative_jump(&[0x4e, 0xba, 0xff, 0xfe]));
+        // opcodes 4ebb,98fe is JSR (-2,PC,A1.L)
+        assert_eq!((Cycles(22), 0x0001_0040, Some(0x44)), pc_relative_jump(&[0x4e, 0xbb, 0x98, 0xfe]));
+        // opcodes 4efa,0010 is JMP (*+$12,PC)
+        assert_eq!((Cycles(10), 0x52, None), pc_relative_jump(&[0x4e, 0xfa, 0x00, 0x10]));
+        // opcodes 4efb,0006 is JMP (6,PC,D0.W), as in a jump table
+        assert_eq!((Cycles(14), 0x42 + 6 + 4, None), pc_relative_jump(&[0x4e, 0xfb, 0x00, 0x06]));
+    }
+    fn branch_cycles(code: &[u8], ccr: u16) -> (Cycles, u32) {
+        let mut cpu = TestCore::new_mem(0x40, code);
+        cpu.dar[0] = 0x1234_0002;
+        cpu.ccr_to_flags(ccr);
+        let cycles = cpu.execute1();
+        (cycles, cpu.pc)
+    }
+    #[test]
+    fn bcc_8_taken_costs_two_cycles_more_than_not_taken() {
+        // opcodes 6710 is BEQ.B    *+$12
+        assert_eq!((Cycles(10), 0x52), branch_cycles(&[0x67, 0x10], Z));
+        assert_eq!((Cycles(8), 0x42), branch_cycles(&[0x67, 0x10], 0));
+    }
+    #[test]
+    fn bcc_16_taken_costs_two_cycles_less_than_not_taken() {
+        // opcodes 6700,0010 is BEQ.W    *+$12
+        assert_eq!((Cycles(10), 0x52), branch_cycles(&[0x67, 0x00, 0x00, 0x10], Z));
+        assert_eq!((Cycles(12), 0x44), branch_cycles(&[0x67, 0x00, 0x00, 0x10], 0));
+    }
+    #[test]
+    fn bra_costs_the_same_for_both_displacement_sizes() {
+        // opcodes 6010 is BRA.B    *+$12, 6000,0010 is BRA.W    *+$12
+        assert_eq!((Cycles(10), 0x52), branch_cycles(&[0x60, 0x10], 0));
+        assert_eq!((Cycles(10), 0x52), branch_cycles(&[0x60, 0x00, 0x00, 0x10], 0));
+    }
+    #[test]
+    fn dbcc_cycles_depend_on_condition_and_counter() {
+        // opcodes 57c8,fffe is DBEQ     D0, *
+        // condition true: no decrement, fall through
+        assert_eq!((Cycles(12), 0x44), branch_cycles(&[0x57, 0xc8, 0xff, 0xfe], Z));
+        // condition false, counter not expired: loop
+        assert_eq!((Cycles(10), 0x40), branch_cycles(&[0x57, 0xc8, 0xff, 0xfe], 0));
+
+        // counter expires: fall through
+        let mut cpu = TestCore::new_mem(0x40, &[0x57, 0xc8, 0xff, 0xfe]);
+        cpu.dar[0] = 0x1234_0000;
+        assert_eq!(Cycles(14), cpu.execute1());
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!(0x1234_ffff, cpu.dar[0]);
+    }
+
+    #[test]
+    fn dbt_falls_through_without_decrementing() {
+        // opcodes 50c8,fffe is DBT      D0, *
+        let mut cpu = TestCore::new_mem(0x40, &[0x50, 0xc8, 0xff, 0xfe]);
+        cpu.dar[0] = 0;
+        assert_eq!(Cycles(12), cpu.execute1());
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!(0, cpu.dar[0]);
+    }
+    #[test]
+    fn dbf_loops_until_counter_underflows() {
+        // 7002 is MOVEQ #2, D0; 5281 is ADDQ.L #1, D1; 51c8,fffc is DBF D0, *-2; then STOP
+        let mut cpu = TestCore::new_mem(0x40, &[0x70, 0x02, 0x52, 0x81, 0x51, 0xc8, 0xff, 0xfc, 0x4e, 0x72, 0x27, 0x00]);
+        cpu.dar[1] = 0;
+        cpu.execute(200);
+        // body runs counter + 1 times, then the low word wraps to -1
+        assert_eq!(3, cpu.dar[1]);
+        assert_eq!(0x0000_ffff, cpu.dar[0]);
+        assert_eq!(super::ProcessingState::Stopped, cpu.processing_state);
+    }
+    #[test]
+    fn dbne_stops_when_condition_becomes_true() {
+        // 7005 is MOVEQ #5, D0; 5381 is SUBQ.L #1, D1; 56c8,fffc is DBNE D0, *-2; then STOP
+        let mut cpu = TestCore::new_mem(0x40, &[0x70, 0x05, 0x53, 0x81, 0x56, 0xc8, 0xff, 0xfc, 0x4e, 0x72, 0x27, 0x00]);
+        cpu.dar[1] = 2;
+        cpu.execute(200);
+        // SUBQ leaves Z clear (NE) once D1 reaches 1, before the counter expires
+        assert_eq!(1, cpu.dar[1]);
+        assert_eq!(5, cpu.dar[0]);
+        assert_eq!(super::ProcessingState::Stopped, cpu.processing_state);
+    }
+
+    #[test]
+    fn moveq_ff_sign_extends_to_full_long() {
+        // opcodes 70ff is MOVEQ    #$FF, D0
+        let mut cpu = TestCore::new_mem(0x40, &[0x70, 0xff]);
+        cpu.dar[0] = 0x1234_5678;
+        cpu.sr_to_flags(0x2713);
+        cpu.execute1();
+
+        assert_eq!(0xffff_ffff, cpu.dar[0]);
+        assert_eq!("-S7XN---", cpu.flags());
+    }
+    #[test]
+    fn moveq_7f_is_largest_positive_immediate() {
+        // opcodes 747f is MOVEQ    #$7F, D2
+        let mut cpu = TestCore::new_mem(0x40, &[0x74, 0x7f]);
+        cpu.dar[2] = 0xffff_ffff;
+        cpu.sr_to_flags(0x270f);
+        cpu.execute1();
+
+        assert_eq!(0x0000_007f, cpu.dar[2]);
+        assert_eq!("-S7-----", cpu.flags());
+    }
+    #[test]
+    fn moveq_80_is_smallest_negative_immediate() {
+        // opcodes 7480 is MOVEQ    #$80, D2
+        let mut cpu = TestCore::new_mem(0x40, &[0x74, 0x80]);
+        cpu.dar[2] = 0x0000_0000;
+        cpu.sr_to_flags(0x2700);
+        cpu.execute1();
+
+        assert_eq!(0xffff_ff80, cpu.dar[2]);
+        assert_eq!("-S7-N---", cpu.flags());
+    }
+    #[test]
+    fn moveq_00_clears_full_register_and_sets_z() {
+        // opcodes 7e00 is MOVEQ    #$00, D7
+        let mut cpu = TestCore::new_mem(0x40, &[0x7e, 0x00]);
+        cpu.dar[7] = 0xdead_beef;
+        cpu.sr_to_flags(0x270b);
+        cpu.execute1();
+
+        assert_eq!(0, cpu.dar[7]);
+        assert_eq!("-S7--Z--", cpu.flags());
+        assert_eq!(0x42, cpu.pc);
+    }
+
+    #[test]
+    fn movem_32_re_pd_writes_registers_in_reverse_order() {
+        // opcodes 48e7,f000 is MOVEM.L  D0-D3, -(A7)
+        let mut cpu = TestCore::new_mem(0x40, &[0x48, 0xe7, 0xf0, 0x00]);
+        cpu.dar[15] = 0x400;
+        for i in 0..4 {
+            cpu.dar[i] = 0x1111_1111 * (i as u32 + 1);
+        }
+        let before = cpu.mem.logger.len();
+        let cycles = cpu.execute1();
+
+        // 8 cycles base, 8 per long transferred
+        assert_eq!(Cycles(8 + 4 * 8), cycles);
+        assert_eq!(0x3f0, cpu.dar[15]);
+        let writes: Vec<Operation> = cpu.mem.logger.ops()[before..].iter().cloned()
+            .filter(|op| matches!(op, Operation::WriteLong(..)))
+            .collect();
+        assert_eq!(vec![
+            Operation::WriteLong(SUPERVISOR_DATA, 0x3fc, 0x4444_4444),
+            Operation::WriteLong(SUPERVISOR_DATA, 0x3f8, 0x3333_3333),
+            Operation::WriteLong(SUPERVISOR_DATA, 0x3f4, 0x2222_2222),
+            Operation::WriteLong(SUPERVISOR_DATA, 0x3f0, 0x1111_1111),
+        ], writes);
+    }
+    #[test]
+    fn movem_16_er_pi_charges_four_cycles_per_word_in_user_space() {
+        // opcodes 4c98,0003 is MOVEM.W  (A0)+, D0-D1
+        let mut cpu = TestCore::new_mem(0x40, &[0x4c, 0x98, 0x00, 0x03]);
+        cpu.s_flag = 0;
+        cpu.dar[8] = 0x100;
+        cpu.mem.write_word(USER_DATA, 0x100, 0x8001);
+        cpu.mem.write_word(USER_DATA, 0x102, 0x0002);
+        let before = cpu.mem.logger.len();
+        let cycles = cpu.execute1();
+
+        // 12 cycles base, 4 per word transferred
+        assert_eq!(Cycles(12 + 2 * 4), cycles);
+        assert_eq!(0xffff_8001, cpu.dar[0]);
+        assert_eq!(0x0000_0002, cpu.dar[1]);
+        assert_eq!(0x104, cpu.dar[8]);
+        let reads: Vec<Operation> = cpu.mem.logger.ops()[before..].iter().cloned()
+            .filter(|op| matches!(op, Operation::ReadWord(..)))
+            .collect();
+        assert_eq!(vec![
+            Operation::ReadWord(USER_DATA, 0x100, 0x8001),
+            Operation::ReadWord(USER_DATA, 0x102, 0x0002),
+        ], reads);
+    }
+
+    fn data_accesses_since(cpu: &TestCore, before: usize) -> Vec<Operation> {
+        cpu.mem.logger.ops()[before..].iter().cloned()
+            .filter(|op| matches!(op, Operation::ReadByte(..) | Operation::WriteByte(..)))
+            .collect()
+    }
+    #[test]
+    fn tas_8_ai_reads_then_writes_the_same_byte() {
+        // opcodes 4ad0 is TAS      (A0)
+        let mut cpu = TestCore::new_mem(0x40, &[0x4a, 0xd0]);
+        cpu.dar[8] = 0x100;
+        cpu.mem.write_byte(SUPERVISOR_DATA, 0x100, 0x00);
+        let before = cpu.mem.logger.len();
+        let cycles = cpu.execute1();
+
+        assert_eq!(Cycles(18), cycles);
+        assert_eq!(vec![
+            Operation::ReadByte(SUPERVISOR_DATA, 0x100, 0x00),
+            Operation::WriteByte(SUPERVISOR_DATA, 0x100, 0x80),
+        ], data_accesses_since(&cpu, before));
+        assert_eq!("-S7--Z--", cpu.flags());
+    }
+    #[test]
+    fn st_8_ai_writes_without_reading_like_musashi() {
+        // opcodes 50d0 is ST       (A0)
+        let mut cpu = TestCore::new_mem(0x40, &[0x50, 0xd0]);
+        cpu.dar[8] = 0x100;
+        assert!(!cpu.read_before_write());
+        let before = cpu.mem.logger.len();
+        let cycles = cpu.execute1();
+
+        // a real 68000 reads (A0) first; Musashi, and by default we, do not
+        assert_eq!(Cycles(12), cycles);
+        assert_eq!(vec![
+            Operation::WriteByte(SUPERVISOR_DATA, 0x100, 0xff),
+        ], data_accesses_since(&cpu, before));
+    }
+    #[test]
+    fn scc_and_clr_read_before_writing_when_enabled() {
+        // opcodes 50d0 is ST       (A0)
+        //         51d0 is SF       (A0)
+        //         4210 is CLR.B    (A0)
+        let mut cpu = TestCore::new_mem(0x40, &[0x50, 0xd0, 0x51, 0xd0, 0x42, 0x10]);
+        cpu.set_read_before_write(true);
+        cpu.dar[8] = 0x100;
+        cpu.mem.write_byte(SUPERVISOR_DATA, 0x100, 0x55);
+        let before = cpu.mem.logger.len();
+        assert_eq!(Cycles(12), cpu.execute1());
+        assert_eq!(Cycles(12), cpu.execute1());
+        assert_eq!(Cycles(12), cpu.execute1());
+
+        assert_eq!(vec![
+            Operation::ReadByte(SUPERVISOR_DATA, 0x100, 0x55),
+            Operation::WriteByte(SUPERVISOR_DATA, 0x100, 0xff),
+            Operation::ReadByte(SUPERVISOR_DATA, 0x100, 0xff),
+            Operation::WriteByte(SUPERVISOR_DATA, 0x100, 0x00),
+            Operation::ReadByte(SUPERVISOR_DATA, 0x100, 0x00),
+            Operation::WriteByte(SUPERVISOR_DATA, 0x100, 0x00),
+        ], data_accesses_since(&cpu, before));
+    }
+
+    #[test]
+    fn op_with_extension_word_moves_pc_past_extension_word() {
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x30, 0x90, 0xFE]);
+        cpu.execute1();
+        assert_eq!(0x44, cpu.pc);
+    }
+
+    #[test]
+    fn status_register_roundtrip(){
+        let mut core = TestCore::new(0x40);
+        //Status register bits are:
+        //      TTSM_0iii_000X_NZVC;
+        let f=0b0000_1000_1110_0000; // these bits should always be zero
+        let s=0b0010_0000_0000_0000;
+        let i=0b0000_0111_0000_0000;
+        let x=0b0000_0000_0001_0000;
+        let n=0b0000_0000_0000_1000;
+        let z=0b0000_0000_0000_0100;
+        let v=0b0000_0000_0000_0010;
+        let c=0b0000_0000_0000_0001;
+        let flags = vec![x,n,z,v,c,f,s,i,0];
+        for sf in flags {
+            core.sr_to_flags(sf);
+            let sr = core.status_register();
+            let expected = if sf == f {0} else {sf};
+            assert_eq!(expected, sr);
+        }
+    }
+    #[test]
+    fn clones_have_independent_registers() {
+        let mut core = TestCore::new(0x40);
+        core.dar[1] = 0x16;
+        let mut clone = core.clone();
+        assert_eq!(0x16, core.dar[1]);
+        assert_eq!(0x16, clone.dar[1]);
+        clone.dar[1] = 0x32;
+        assert_eq!(0x16, core.dar[1]);
+        assert_eq!(0x32, clone.dar[1]);
+    }
+
+    #[test]
+    fn user_mode_chk_16_pd_with_trap_uses_sp_correctly() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0xa7]); // 0x41a7 CHK.W -(A7), D0
+        cpu.write_data_long(super::EXCEPTION_CHK as u32 * 4, 0x1010).unwrap(); // set up exception vector 6
+        cpu.s_flag = super::SFLAG_CLEAR; // user mode
+        cpu.inactive_ssp = 0x200; // Supervisor stack at 0x200
+        cpu.dar[15] = 0x100; // User stack at 0x100
+        cpu.dar[0] = 0xF123; // negative, will cause a trap (vector 6) and enter supervisor mode
+
+        cpu.execute1();
+        assert_eq!(0x1010, cpu.pc);
+        assert_eq!(super::SFLAG_SET, cpu.s_flag);
+        assert_eq!(0x100-2, cpu.inactive_usp); // check USP, decremented by A7 PD
+        assert_eq!(0x200-6, cpu.dar[15]); // check SSP
+    }
+
+    #[test]
+    fn sr_to_flags_can_enter_user_mode_and_swap_stackpointers() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0xa7]); // 0x41a7 CHK.W -(A7), D0
+        cpu.s_flag = super::SFLAG_SET;
+        cpu.inactive_usp = 0x1000;
+        cpu.dar[15] = 0x2000;
+        assert_eq!(super::SFLAG_SET, cpu.s_flag);
+        assert_eq!(0x2000, cpu.ssp());
+        cpu.sr_to_flags(0); // User mode
+        assert_eq!(0x1000, cpu.usp());
+        assert_eq!(0x1000, cpu.dar[15]);
+        assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
+    }
+
+    #[test]
+    fn sr_to_flags_can_enter_supervisor_mode_and_swap_stackpointers() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0xa7]); // 0x41a7 CHK.W -(A7), D0
+        cpu.s_flag = super::SFLAG_CLEAR;
+        cpu.inactive_ssp = 0x1000;
+        cpu.dar[15] = 0x2000;
+        assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
+        assert_eq!(0x2000, cpu.usp());
+        cpu.sr_to_flags(0xffff); // Supa mode
+        assert_eq!(0x1000, cpu.ssp());
+        assert_eq!(0x1000, cpu.dar[15]);
+        assert_eq!(super::SFLAG_SET, cpu.s_flag);
+    }
+
+    #[test]
+    fn core_can_stop() {
+        let initial_pc = 0x40;
+        let mut cpu = TestCore::new_mem_init(initial_pc, &[0x4e, 0x72, 0x00, 0x00], opcodes::OP_NOP);
+        cpu.sr_to_flags(0x7fff); // Supa mode, not tracing
+        cpu.execute1();
+        assert_eq!(0x0000, cpu.status_register());
+        let next_instruction = initial_pc + 2 + 2; // 40 + instruction word + immediate word
+        assert_eq!(next_instruction, cpu.pc);
+        // continue executing some cycles
+        let chunk_of_cycles = 400;
+        let Cycles(consumed) = cpu.execute(chunk_of_cycles);
+        assert_eq!(chunk_of_cycles, consumed);
+        // but as the cpu is stopped, pc should still point
+        // to the next instruction
+        assert_eq!(next_instruction, cpu.pc);
+    }
+
+    use crate::interrupts::InterruptController;
+    #[test]
+    fn reset_calls_interrupt_controller() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x70]); // 0x4e70 RESET
+        cpu.int_ctrl.request_interrupt(5);
+        cpu.execute1(); // will execute RESET, which will reset all IRQs
+        assert_eq!(0, cpu.int_ctrl.highest_priority());
+    }
+
+    #[test]
+    fn chk_taken_adds_exception_processing_to_ea_time() {
+        // 0x4181 is CHK.W D1, D0
+        let mut cpu = exception_test_core(&[0x41, 0x81]);
+        cpu.dar[0] = 5;
+        cpu.dar[1] = 10;
+        assert_eq!(Cycles(10), cpu.execute1());
+        assert_eq!(0x42, cpu.pc);
+
+        let mut cpu = exception_test_core(&[0x41, 0x81]);
+        cpu.dar[0] = 11;
+        cpu.dar[1] = 10;
+        assert_eq!(Cycles(44), cpu.execute1());
+        assert_eq!(0x42, stacked_pc(&cpu));
+
+        // 0x4190 is CHK.W (A0), D0, with 4 cycles of EA calculation
+        let mut cpu = exception_test_core(&[0x41, 0x90]);
+        cpu.dar[8] = 0x200;
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x200, 10);
+        cpu.dar[0] = 10;
+        assert_eq!(Cycles(14), cpu.execute1());
+
+        let mut cpu = exception_test_core(&[0x41, 0x90]);
+        cpu.dar[8] = 0x200;
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x200, 10);
+        cpu.dar[0] = 0xffff;
+        assert_eq!(Cycles(48), cpu.execute1());
+    }
+
+    #[test]
+    fn trapv_taken_costs_exception_processing() {
+        // 0x4e76 is TRAPV
+        let mut cpu = exception_test_core(&[0x4e, 0x76]);
+        assert_eq!(Cycles(4), cpu.execute1());
+        assert_eq!(0x42, cpu.pc);
+
+        let mut cpu = exception_test_core(&[0x4e, 0x76]);
+        cpu.sr_to_flags(0x2702);
+        assert_eq!(Cycles(34), cpu.execute1());
+        assert_eq!(0x42, stacked_pc(&cpu));
+        assert_eq!(0x400 - 6, cpu.dar[15]);
+    }
+
+    #[test]
+    fn processing_state_is_known_in_g2_exception_handler() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0x90]); // 0x4190 CHK.W (A0), D0
+        cpu.write_data_long(super::EXCEPTION_CHK as u32 * 4, 0x1010).unwrap(); // set up exception vector 6
+        cpu.write_data_word(0x1010, opcodes::OP_RTE_32).unwrap(); // handler is just RTE
+        cpu.s_flag = super::SFLAG_CLEAR; // user mode
+        cpu.inactive_ssp = 0x200; // Supervisor stack at 0x200
+        cpu.dar[15] = 0x100; // User stack at 0x100
+        cpu.dar[0] = 0xF123; // negative, will cause a trap (vector 6) and enter the handler in supervisor mode
+
+        cpu.execute1();
+        assert_eq!(0x1010, cpu.pc);
+        assert_eq!(super::SFLAG_SET, cpu.s_flag);
+        assert_eq!(super::ProcessingState::Group2Exception, cpu.processing_state);
+        assert_eq!(0x200-6, cpu.dar[15]); // check SSP
+
+        cpu.execute1(); // will execute RTE
+        // which should return to user mode, instruction after CHK, normal state
+        assert_eq!(0x42, cpu.pc);
+        assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
+        assert_eq!(super::ProcessingState::Normal, cpu.processing_state);
+    }
+
+    #[test]
+    fn processing_state_is_known_in_g1_exception_handler() {
+        // real illegal instruction = 0x4afc, but any illegal instruction should work
+        let mut cpu = TestCore::new_mem(0x40, &[0x4a, 0xfc]);
+        cpu.write_data_long(super::EXCEPTION_ILLEGAL_INSTRUCTION as u32 * 4, 0x1010).unwrap(); // set up exception vector
+        cpu.write_data_word(0x1010, opcodes::OP_RTE_32).unwrap(); // handler is just RTE
+        cpu.s_flag = super::SFLAG_CLEAR; // user mode
+        cpu.inactive_ssp = 0x200; // Supervisor stack at 0x200
+        cpu.dar[15] = 0x100; // User stack at 0x100
+
+        cpu.execute1(); // will execute the illegal instruction and enter the handler in supervisor mode
+        assert_eq!(0x1010, cpu.pc);
+        assert_eq!(super::SFLAG_SET, cpu.s_flag);
+        assert_eq!(super::ProcessingState::Group1Exception, cpu.processing_state);
+
+        cpu.execute1(); // will execute RTE
+        // which should return to user mode, faulting instruction, normal state
+        assert_eq!(0x40, cpu.pc);
+        assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
+        assert_eq!(super::ProcessingState::Normal, cpu.processing_state);
+    }
+
+    #[test]
+    fn processing_state_is_known_in_g0_exception_handler() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0xa0]); // 0x41a0 CHK.W -(A0), D0
+        cpu.write_data_long(super::EXCEPTION_ADDRESS_ERROR as u32 * 4, 0x1010).unwrap(); // set up exception vector
+        cpu.write_data_word(0x1010, 0x504F).unwrap(); // handler is ADD #8, A7
+        cpu.write_data_word(0x1012, opcodes::OP_RTE_32).unwrap(); // followed by RTE
+
+        cpu.s_flag = super::SFLAG_CLEAR; // user mode
+        cpu.inactive_ssp = 0x200; // Supervisor stack at 0x200
+        cpu.dar[15] = 0x100; // User stack at 0x100
+        cpu.dar[8] = 0x0023; // odd, will cause an address error exception and enter the handler in supervisor mode
+
+        cpu.execute1();
+        assert_eq!(0x1010, cpu.pc);
+        assert_eq!(0x200-14, cpu.dar[15]);
+        assert_eq!(super::SFLAG_SET, cpu.s_flag);
+        assert_eq!(super::ProcessingState::Group0Exception, cpu.processing_state);
+
+        cpu.execute1(); // will execute ADD #8, A7 (to skip g0 stuff in stack frame)
+        assert_eq!(0x1012, cpu.pc);
+        assert_eq!(0x200-6, cpu.dar[15]);
+
+        cpu.execute1(); // will execute RTE
+        // which should return to user mode, after faulted instruction, normal state
+        assert_eq!(0x42, cpu.pc); // this is what address errors stack in Musashi
+        assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
+        assert_eq!(super::ProcessingState::Normal, cpu.processing_state);
+    }
+
+    #[test]
+    fn stop_changes_processing_state() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x72]); // 0x4e72 STOP
+        cpu.execute1(); // will execute STOP
+        assert_eq!(super::ProcessingState::Stopped, cpu.processing_state);
+    }
+
+    #[test]
+    fn interrupt_can_trigger_from_stopped_state() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x72]); // 0x4e72 STOP
+        let supervisor_bit = 1 << 13;
+        let irq_mask = 0;
+        cpu.sr_to_flags(supervisor_bit | irq_mask);
+        let vec4handler = 0x2F0000;
+        let autovector_base = 24;
+        let irq = 5;
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, (autovector_base + irq) * 4 , vec4handler);
+        // opcodes d278,0108 is ADD.W    $0108, D1
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, vec4handler, 0xd2780108);
+        cpu.execute1(); // will execute STOP
+        assert_eq!(super::ProcessingState::Stopped, cpu.processing_state);
+        cpu.int_ctrl.request_interrupt(irq as u8);
+        cpu.execute1(); // will trigger interrupt handler
+        assert_eq!(super::ProcessingState::Group1Exception, cpu.processing_state);
+        assert_eq!(vec4handler, cpu.pc);
+        cpu.execute1(); // should execute first instruction of handler, which is 4 bytes
+        assert_eq!(vec4handler + 4, cpu.pc);
+    }
+
+    #[test]
+    fn pending_interrupt_check_does_not_change_state() {
+        // opcodes d200 is ADD.B    D0, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x00]);
+        let supervisor_bit = 1 << 13;
+        let irq_mask = 0;
+        cpu.sr_to_flags(supervisor_bit | irq_mask);
+        cpu.int_ctrl.request_interrupt(4);
+        assert_eq!(Some(4), cpu.pending_interrupt());
+        assert_eq!(Some(4), cpu.pending_interrupt());
+        // but is cleared after initiating interrupt handling
+        assert_eq!(Cycles(44), cpu.execute1());
+        assert_eq!(None, cpu.pending_interrupt());
+    }
+
+    #[test]
+    fn can_enter_halted_state() {
+        // halted state is entered when a second group 0 exception
+        // (except external reset) occurs in a group 0 exception handler
+        let mut cpu = TestCore::new_mem(0x41, &[0xd2, 0x00]); // d200 is ADD.B D0, D1
+        let address_error_handler = 0x2F0001;
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, super::EXCEPTION_ADDRESS_ERROR as u32 * 4, address_error_handler);
+        // opcodes d278,0108 is ADD.W    $0108, D1
+        cpu.execute1(); // will execute at an odd address, invoking the address error exception handler
+        assert_eq!(super::ProcessingState::Group0Exception, cpu.processing_state);
+        cpu.execute1(); // will execute the handler at an odd address,
+        // this should trigger a second address error, which should halt the processor
+        assert_eq!(super::ProcessingState::Halted, cpu.processing_state);
+        // in the halted state, the only way to win, is not to play.
+        // An external reset is needed.
+    }
+
+    #[test]
+    fn execute_with_calls_back_after_each_instruction() {
+        // 0x4e71 is NOP taking 4 cycles
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71, 0x4e, 0x71, 0x4e, 0x71]);
+        let mut pcs = Vec::new();
+        let cycles = cpu.execute_with(12, |core| { pcs.push(core.pc); true });
+        assert_eq!(Cycles(12), cycles);
+        assert_eq!(vec![0x42, 0x44, 0x46], pcs);
+    }
+
+    #[test]
+    fn execute_with_stops_early_when_callback_returns_false() {
+        // 0x4e71 is NOP taking 4 cycles
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71, 0x4e, 0x71, 0x4e, 0x71]);
+        let cycles = cpu.execute_with(1000, |core| core.pc != 0x44);
+        assert_eq!(Cycles(8), cycles);
+        assert_eq!(0x44, cpu.pc);
+    }
+
+    #[test]
+    fn max_interrupt_latency_is_longest_instruction_or_masked_section() {
+        // NOP ; MULU D1, D0 taking 38 cycles with D1 = 0 ; NOP ; MOVE #$2700, SR ; NOP
+        let program = [0x4e, 0x71, 0xc0, 0xc1, 0x4e, 0x71, 0x46, 0xfc, 0x27, 0x00, 0x4e, 0x71];
+        let mut cpu = TestCore::new_mem_init(0x40, &program, 0x4e71_4e71);
+        cpu.dar[1] = 0;
+        cpu.sr_to_flags(0x2000);
+        assert_eq!(38, cpu.measure_max_interrupt_latency(46));
+        assert_eq!(0x46, cpu.pc);
+
+        // the section masked from the MOVE to SR lasts until the end of the window
+        assert_eq!(16 + 4 + 40, cpu.measure_max_interrupt_latency(60));
+        assert_eq!(7, cpu.status_register() >> 8 & 7);
+
+        // with the mask already at 7, only instruction lengths count
+        cpu.jump(0x40);
+        assert_eq!(38, cpu.measure_max_interrupt_latency(100));
+    }
+
+    #[test]
+    fn current_disassembly_names_the_instruction_just_executed() {
+        // MOVE.W (A0)+, D1 ; ADDQ.L #2, D1 ; BRA.S *
+        let mut cpu = TestCore::new_mem(0x40, &[0x32, 0x18, 0x54, 0x81, 0x60, 0xfe]);
+        cpu.dar[8] = 0x40;
+        assert_eq!("", cpu.current_disassembly());
+        let mut lines = Vec::new();
+        cpu.execute_with(1000, |core| { lines.push(core.current_disassembly()); lines.len() < 4 });
+        assert_eq!(vec!["MOVE.W (A0)+, D1", "ADDQ.L #2, D1", "BRA.S $44", "BRA.S $44"], lines);
+    }
+
+    #[test]
+    fn current_disassembly_shows_extension_words_as_fetched() {
+        use super::disassembler::{DisasmStyle, NumberStyle, SizeSuffix};
+        // MOVE.W D0, ($42).W overwrites its own extension word
+        let mut cpu = TestCore::new_mem(0x40, &[0x31, 0xc0, 0x00, 0x42]);
+        cpu.dar[0] = 0x1234;
+        cpu.execute1();
+        assert_eq!(0x1234, cpu.mem.read_word(SUPERVISOR_PROGRAM, 0x42));
+        assert_eq!("MOVE.W D0, $0042.W", cpu.current_disassembly());
+        let style = DisasmStyle { numbers: NumberStyle::C, size_suffix: SizeSuffix::Attached, lowercase: true };
+        assert_eq!("movew d0, 0x0042.w", cpu.current_disassembly_with(style));
+        assert_eq!(("movew d0, 0x1234.w".to_string(), 4), cpu.disassemble_with(SUPERVISOR_PROGRAM, 0x40, style));
+    }
+
+    #[test]
+    fn current_disassembly_reads_words_skipped_by_the_instruction() {
+        // BNE.W $1000 with Z set is not taken, and skips its displacement
+        let mut cpu = TestCore::new_mem(0x40, &[0x66, 0x00, 0x0f, 0xbe]);
+        cpu.not_z_flag = 0;
+        cpu.execute1();
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!("BNE.W $1000", cpu.current_disassembly());
+    }
+
+    #[test]
+    fn load_and_run_loads_program_and_sets_up_stack() {
+        let mut cpu = TestCore::new(0);
+        // 0x6104 is BSR.B +4, 0x4e72,0x2700 is STOP #$2700
+        // 0x702a is MOVEQ #42, D0, 0x4e75 is RTS
+        let program = [0x61, 0x04, 0x4e, 0x72, 0x27, 0x00, 0x70, 0x2a, 0x4e, 0x75];
+        cpu.load_and_run(&program, 0x1000, 0x1000, 1000);
+
+        assert_eq!(42, cpu.dar[0]);
+        assert_eq!(0x1006, cpu.pc);
+        assert_eq!(0x1000, cpu.dar[15]);
+        assert_eq!(super::ProcessingState::Stopped, cpu.processing_state);
+    }
+
+    #[test]
+    fn load_and_run_keeps_existing_stack() {
+        let mut cpu = TestCore::new(0);
+        cpu.dar[15] = 0x8000;
+        // 0x4e71 is NOP
+        let cycles = cpu.load_and_run(&[0x4e, 0x71, 0x4e, 0x71], 0x2000, 0x2002, 4);
+
+        assert_eq!(Cycles(4), cycles);
+        assert_eq!(0x2004, cpu.pc);
+        assert_eq!(0x8000, cpu.dar[15]);
+    }
+
+    #[test]
+    fn load_and_run_enters_supervisor_mode_with_a_stack_above_address_0() {
+        let mut cpu = TestCore::new(0);
+        cpu.inactive_usp = 0x8000;
+        cpu.sr_to_flags(0x0300);
+        cpu.inactive_ssp = 0;
+        // 0x6102 is BSR.B +2, 0x4e71 is NOP, 0x4e75 is RTS
+        cpu.load_and_run(&[0x61, 0x02, 0x4e, 0x71, 0x4e, 0x75], 0, 0, 18);
+
+        assert_eq!(0x2300, cpu.status_register());
+        assert_eq!(0x8000, cpu.usp());
+        assert_eq!(0x4, cpu.pc);
+        assert_eq!(6 + super::LOAD_AND_RUN_STACK_SIZE - 4, cpu.dar[15]);
+        assert_eq!(0x2, cpu.mem.read_long(SUPERVISOR_DATA, cpu.dar[15]));
+    }
+
+    #[test]
+    fn write_watchpoint_stops_execution_after_instruction() {
+        // 0x4e71 is NOP, 0x31c0,0x0100 is MOVE.W D0, ($0100).W
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71, 0x31, 0xc0, 0x01, 0x00, 0x4e, 0x71]);
+        cpu.dar[0] = 0x1234;
+        cpu.add_watchpoint(0x101, 1, true, false);
+        assert_eq!(None, cpu.last_watchpoint_hit());
+
+        let cycles = cpu.execute(100);
+        assert_eq!(Cycles(4 + 12), cycles);
+        assert_eq!(0x46, cpu.pc);
+        let hit = cpu.last_watchpoint_hit().unwrap();
+        assert_eq!(0x100, hit.address);
+        assert_eq!(2, hit.size);
+        assert_eq!(super::AccessType::Write, hit.access_type);
+        assert_eq!(0x1234, hit.value);
+
+        // continuing runs to the end of the budget
+        assert_eq!(Cycles(4), cpu.execute(4));
+        assert_eq!(0x48, cpu.pc);
+    }
+
+    #[test]
+    fn read_watchpoint_ignores_writes_and_instruction_fetches() {
+        // 0x31c0,0x0100 is MOVE.W D0, ($0100).W
+        // 0x3238,0x0100 is MOVE.W ($0100).W, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0x31, 0xc0, 0x01, 0x00, 0x32, 0x38, 0x01, 0x00, 0x4e, 0x71]);
+        cpu.dar[0] = 0x5678;
+        cpu.add_watchpoint(0x100, 2, false, true);
+        cpu.add_watchpoint(0x40, 16, false, true);
+
+        let cycles = cpu.execute(100);
+        assert_eq!(Cycles(12 + 12), cycles);
+        assert_eq!(0x48, cpu.pc);
+        let hit = cpu.last_watchpoint_hit().unwrap();
+        assert_eq!(0x100, hit.watchpoint.address);
+        assert_eq!(super::AccessType::Read, hit.access_type);
+        assert_eq!(0x5678, hit.value);
+
+        cpu.clear_watchpoints();
+        cpu.pc = 0x44;
+        assert_eq!(Cycles(12 + 4), cpu.execute(16));
+    }
+
+    fn trap_test_core() -> TestCore {
+        // 0x4e41 is TRAP #1, 0x4e71 is NOP
+        let mut cpu = exception_test_core(&[0x4e, 0x41, 0x4e, 0x71]);
+        // handler: 0x7001 is MOVEQ #1, D0, 0x4e73 is RTE
+        cpu.mem.write_long(SUPERVISOR_DATA, 33 * 4, 0x100);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x100, 0x7001);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x102, 0x4e73);
+        cpu
+    }
+
+    #[test]
+    fn interrupt_after_trap_is_taken_at_boundary_with_trap_frame_intact() {
+        let mut cpu = trap_test_core();
+        cpu.sr_to_flags(0x2000);
+        // level 5 autovector handler: 0x7202 is MOVEQ #2, D1, 0x4e73 is RTE
+        cpu.mem.write_long(SUPERVISOR_DATA, 29 * 4, 0x180);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x180, 0x7202);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x182, 0x4e73);
+
+        assert_eq!(Cycles(38), cpu.execute1());
+        assert_eq!(0x100, cpu.pc);
+        cpu.int_ctrl.request_interrupt(5);
+
+        // the interrupt is recognized at the next instruction boundary, before
+        // the first instruction of the TRAP handler, and stacked on top of the
+        // complete TRAP frame
+        assert_eq!(Cycles(44), cpu.execute1());
+        assert_eq!(0x180, cpu.pc);
+        assert_eq!(0x100, stacked_pc(&cpu));
+        assert_eq!(0x400 - 12, cpu.dar[15]);
+        assert_eq!(0x2000, cpu.mem.read_word(SUPERVISOR_DATA, 0x400 - 6));
+        assert_eq!(0x42, cpu.mem.read_long(SUPERVISOR_DATA, 0x400 - 4));
+        assert_eq!(0, cpu.dar[0]);
+
+        // MOVEQ and RTE of the interrupt handler, then the TRAP handler runs
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!(0x100, cpu.pc);
+        cpu.execute1();
+        assert_eq!(1, cpu.dar[0]);
+        assert_eq!(2, cpu.dar[1]);
+        assert_eq!(0x400 - 6, cpu.dar[15]);
+    }
+
+    #[test]
+    fn interrupt_is_delivered_to_core_configured_for_user_mode() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71, 0x4e, 0x71]);
+        cpu.reset_to(0x400, 0x40);
+        cpu.inactive_usp = 0x300;
+        cpu.sr_to_flags(0x0000);
+        cpu.mem.write_long(SUPERVISOR_DATA, 26 * 4, 0x180);
+        cpu.int_ctrl.request_interrupt(2);
+
+        assert_eq!(Cycles(44), cpu.execute1());
+        assert_eq!(0x180, cpu.pc);
+        assert_eq!(0x2200, cpu.status_register());
+        // frame is on the supervisor stack, with the user mode SR
+        assert_eq!(0x400 - 6, cpu.dar[15]);
+        assert_eq!(0x0000, cpu.mem.read_word(SUPERVISOR_DATA, 0x400 - 6));
+        assert_eq!(0x300, cpu.usp());
+    }
+
+    #[test]
+    fn interrupts_must_be_above_the_mask_except_level_7() {
+        for level in 1..=7u8 {
+            for mask in [level - 1, level].iter() {
+                let mut cpu = TestCore::new_mem_init(0x40, &[], 0x4e71_4e71);
+                cpu.reset_to(0x400, 0x40);
+                cpu.mem.write_long(SUPERVISOR_DATA, (24 + u32::from(level)) * 4, 0x180);
+                cpu.sr_to_flags(0x2000 | (u16::from(*mask) << 8));
+                cpu.int_ctrl.request_interrupt(level);
+                cpu.execute1();
+                let serviced = *mask < level || level == 7;
+                assert_eq!(if serviced { 0x180 } else { 0x42 }, cpu.pc, "level {} mask {}", level, mask);
+            }
+        }
+    }
+
+    #[test]
+    fn move_to_sr_changes_interrupt_recognition_at_the_next_boundary() {
+        // MOVE #$2000, SR ; MOVEQ #1, D0 ; MOVE #$2700, SR ; MOVEQ #2, D1
+        let mut cpu = TestCore::new_mem(0x40, &[0x46, 0xfc, 0x20, 0x00, 0x70, 0x01, 0x46, 0xfc, 0x27, 0x00, 0x72, 0x02]);
+        cpu.reset_to(0x400, 0x40);
+        // level 4 handler: MOVEQ #4, D2 ; RTE
+        cpu.mem.write_long(SUPERVISOR_DATA, 28 * 4, 0x180);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x180, 0x7404);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x182, 0x4e73);
+        cpu.int_ctrl.request_interrupt(4);
+
+        // unmasking doesn't interrupt the MOVE itself, but the interrupt
+        // is taken before the following instruction
+        assert_eq!(Cycles(16), cpu.execute1());
+        assert_eq!((0x44, 0x2000), (cpu.pc, cpu.status_register()));
+        cpu.execute1();
+        assert_eq!(0x180, cpu.pc);
+        assert_eq!(0x44, stacked_pc(&cpu));
+        assert_eq!(0, cpu.dar[0]);
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!((0x44, 4), (cpu.pc, cpu.dar[2]));
+        cpu.execute1();
+        assert_eq!(1, cpu.dar[0]);
+
+        // after masking, a new request has to wait
+        cpu.execute1();
+        cpu.int_ctrl.request_interrupt(4);
+        cpu.execute1();
+        assert_eq!((0x4c, 2), (cpu.pc, cpu.dar[1]));
+        assert_eq!(4, cpu.int_ctrl.highest_priority());
+    }
+
+    #[test]
+    fn nested_interrupts_stack_and_restore_each_mask() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71, 0x4e, 0x71]);
+        cpu.reset_to(0x400, 0x40);
+        cpu.sr_to_flags(0x2000);
+        // level 3 handler: NOP ; MOVEQ #3, D0 ; RTE
+        cpu.mem.write_long(SUPERVISOR_DATA, 27 * 4, 0x180);
+        for (offset, word) in [0x4e71, 0x7003, 0x4e73].iter().enumerate() {
+            cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x180 + 2 * offset as u32, *word);
+        }
+        // level 5 handler: MOVEQ #5, D1 ; RTE
+        cpu.mem.write_long(SUPERVISOR_DATA, 29 * 4, 0x1c0);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x1c0, 0x7205);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x1c2, 0x4e73);
+        cpu.mem.write_long(SUPERVISOR_DATA, 26 * 4, 0x1e0);
+
+        cpu.int_ctrl.request_interrupt(3);
+        cpu.execute1();
+        assert_eq!(0x180, cpu.pc);
+        assert_eq!(0x2300, cpu.status_register());
+        assert_eq!(0x400 - 6, cpu.dar[15]);
+        assert_eq!(0x2000, cpu.mem.read_word(SUPERVISOR_DATA, 0x400 - 6));
+        assert_eq!(0x40, stacked_pc(&cpu));
+
+        // level 5 preempts the level 3 handler, level 2 has to wait
+        cpu.int_ctrl.request_interrupt(5);
+        cpu.int_ctrl.request_interrupt(2);
+        cpu.execute1();
+        assert_eq!(0x1c0, cpu.pc);
+        assert_eq!(0x2500, cpu.status_register());
+        assert_eq!(0x400 - 12, cpu.dar[15]);
+        assert_eq!(0x2300, cpu.mem.read_word(SUPERVISOR_DATA, 0x400 - 12));
+        assert_eq!(0x180, stacked_pc(&cpu));
+
+        // MOVEQ and RTE return to the level 3 handler, at its mask
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!(0x180, cpu.pc);
+        assert_eq!(0x2300, cpu.status_register());
+        assert_eq!(0x400 - 6, cpu.dar[15]);
+        assert_eq!(5, cpu.dar[1]);
+
+        // level 2 stays masked until the level 3 handler returns
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!(3, cpu.dar[0]);
+        assert_eq!(0x184, cpu.pc);
+        cpu.execute1();
+        assert_eq!(0x40, cpu.pc);
+        assert_eq!(0x2000, cpu.status_register());
+        assert_eq!(0x400, cpu.dar[15]);
+
+        cpu.execute1();
+        assert_eq!(0x1e0, cpu.pc);
+        assert_eq!(0x2200, cpu.status_register());
+        assert_eq!(0x2000, cpu.mem.read_word(SUPERVISOR_DATA, 0x400 - 6));
+        assert_eq!(0x40, stacked_pc(&cpu));
+    }
+
+    #[test]
+    fn last_exception_is_kept_until_the_next_one() {
+        // 0x4e71 is NOP, 0x4e41 is TRAP #1, 0x4afc is ILLEGAL
+        let mut cpu = exception_test_core(&[0x4e, 0x71, 0x4e, 0x41, 0x4a, 0xfc, 0x4a, 0xfc]);
+        cpu.mem.write_long(SUPERVISOR_DATA, 33 * 4, 0x44);
+        cpu.mem.write_long(SUPERVISOR_DATA, 26 * 4, 0x46);
+        cpu.execute1();
+        assert!(cpu.last_exception().is_none());
+        cpu.execute1();
+        assert!(matches!(cpu.last_exception(), Some(super::Exception::Trap(33, _))));
+        cpu.set_illegal_as_nop(true);
+        cpu.execute1();
+        assert!(matches!(cpu.last_exception(), Some(super::Exception::Trap(33, _))));
+        cpu.sr_to_flags(0x2000);
+        cpu.int_ctrl.request_interrupt(2);
+        cpu.execute1();
+        assert!(matches!(cpu.last_exception(), Some(super::Exception::Interrupt(2, 26))));
+        assert_eq!(0x46, cpu.pc);
+    }
+
+    #[test]
+    fn trace_exceptions_are_recorded_and_nest() {
+        use super::ProcessingState;
+        // NOP, traced into a handler at 0x100 whose ILLEGAL (0x4afc) vectors back to itself
+        let mut cpu = exception_test_core(&[0x4e, 0x71]);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_TRACE as u32 * 4, 0x100);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_ILLEGAL_INSTRUCTION as u32 * 4, 0x100);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x100, 0x4afc);
+        cpu.sr_to_flags(0xa700);
+        cpu.set_max_exception_nesting(Some(2));
+        cpu.execute1();
+        assert_eq!(0x100, cpu.pc);
+        assert!(matches!(cpu.last_exception(), Some(super::Exception::Trace)));
+        cpu.execute1();
+        assert!(matches!(cpu.last_exception(), Some(super::Exception::IllegalInstruction(0x4afc, 0x100))));
+        // the trace counts as the first of the two exceptions in a row
+        cpu.execute1();
+        assert_eq!(ProcessingState::Halted, cpu.processing_state);
+    }
+
+    #[test]
+    fn exception_nesting_guard_halts_runaway_exceptions_only() {
+        use super::ProcessingState;
+        // 0x4e40 is TRAP #0, its vector pointing back at it
+        let mut cpu = exception_test_core(&[0x4e, 0x40]);
+        cpu.mem.write_long(SUPERVISOR_DATA, 32 * 4, 0x40);
+        assert_eq!(None, cpu.max_exception_nesting());
+        cpu.execute(1000);
+        assert_ne!(ProcessingState::Halted, cpu.processing_state);
+
+        let mut cpu = exception_test_core(&[0x4e, 0x40]);
+        cpu.mem.write_long(SUPERVISOR_DATA, 32 * 4, 0x40);
+        cpu.set_max_exception_nesting(Some(2));
+        cpu.execute1();
+        cpu.execute1();
+        assert_ne!(ProcessingState::Halted, cpu.processing_state);
+        cpu.execute1();
+        assert_eq!(ProcessingState::Halted, cpu.processing_state);
+        assert_eq!(0x400 - 2 * 6, cpu.dar[15]);
+
+        // a handler completing an instruction ends the nesting
+        // TRAP #0 ; NOP, with the handler at 0x100 being RTE
+        let mut cpu = exception_test_core(&[0x4e, 0x40, 0x4e, 0x71]);
+        cpu.mem.write_long(SUPERVISOR_DATA, 32 * 4, 0x100);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x100, 0x4e73);
+        cpu.set_max_exception_nesting(Some(1));
+        for _ in 0..3 {
+            cpu.execute1();
+            cpu.execute1();
+            cpu.jump(0x40);
+        }
+        assert_ne!(ProcessingState::Halted, cpu.processing_state);
+    }
+
+    #[test]
+    fn rte_setting_t_traces_the_instruction_it_returns_to() {
+        // 0x4e73 is RTE, returning to MOVEQ #1, D0 ; MOVEQ #2, D1 in user mode
+        let mut cpu = exception_test_core(&[0x4e, 0x73]);
+        cpu.inactive_usp = 0x300;
+        cpu.mem.write_word(SUPERVISOR_DATA, 0x400, 0x8000);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x402, 0x100);
+        cpu.mem.write_word(USER_PROGRAM, 0x100, 0x7001);
+        cpu.mem.write_word(USER_PROGRAM, 0x102, 0x7202);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_TRACE as u32 * 4, 0x180);
+
+        cpu.execute1();
+        assert_eq!(0x100, cpu.pc);
+        assert_eq!(0x8000, cpu.status_register());
+
+        // the trace exception follows the first instruction of the resumed code
+        assert_eq!(Cycles(4 + 34), cpu.execute1());
+        assert_eq!(0x180, cpu.pc);
+        assert_eq!(1, cpu.dar[0]);
+        assert_eq!(0, cpu.dar[1]);
+        assert_eq!(0x2000, cpu.status_register());
+        assert_eq!(0x400, cpu.dar[15]);
+        assert_eq!(0x8000, cpu.mem.read_word(SUPERVISOR_DATA, 0x400));
+        assert_eq!(0x102, stacked_pc(&cpu));
+    }
+
+    #[test]
+    fn move_to_sr_setting_t_traces_the_following_instruction() {
+        // 0x46fc is MOVE #$a700, SR, then MOVEQ #1, D0 ; MOVEQ #2, D1
+        let mut cpu = exception_test_core(&[0x46, 0xfc, 0xa7, 0x00, 0x70, 0x01, 0x72, 0x02]);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_TRACE as u32 * 4, 0x180);
+
+        assert_eq!(Cycles(16), cpu.execute1());
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!(0xa700, cpu.status_register());
+
+        assert_eq!(Cycles(4 + 34), cpu.execute1());
+        assert_eq!(0x180, cpu.pc);
+        assert_eq!(1, cpu.dar[0]);
+        assert_eq!(0x2700, cpu.status_register());
+        assert_eq!(0xa700, cpu.mem.read_word(SUPERVISOR_DATA, 0x400 - 6));
+        assert_eq!(0x46, stacked_pc(&cpu));
+    }
+
+    #[test]
+    fn step_into_stops_at_first_instruction_of_handler() {
+        let mut cpu = trap_test_core();
+        assert_eq!(super::StepMode::Into, cpu.step_mode());
+        assert_eq!(Cycles(38), cpu.step());
+        assert_eq!(0x100, cpu.pc);
+        assert_eq!(0, cpu.dar[0]);
+    }
+
+    #[test]
+    fn step_over_exceptions_runs_handler_until_it_returns() {
+        let mut cpu = trap_test_core();
+        cpu.set_step_mode(super::StepMode::OverExceptions);
+        // TRAP, MOVEQ and RTE
+        assert_eq!(Cycles(38 + 4 + 20), cpu.step());
+        assert_eq!(0x42, cpu.pc);
+        assert_eq!(1, cpu.dar[0]);
+        assert_eq!(0x400, cpu.dar[15]);
+        // without exceptions, a step is a single instruction
+        assert_eq!(Cycles(4), cpu.step());
+        assert_eq!(0x44, cpu.pc);
+    }
+
+    #[test]
+    fn step_over_gives_up_when_the_budget_runs_out() {
+        let mut cpu = trap_test_core();
+        cpu.set_step_mode(super::StepMode::OverExceptions);
+        // the handler loops with 0x60fe, BRA.S to itself, taking 10 cycles
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x102, 0x60fe);
+        assert_eq!((Cycles(38 + 4 + 6 * 10), false), cpu.step_within(100));
+        assert_eq!(0x102, cpu.pc);
+
+        // the budget is checked before each instruction, so RTE may exceed it
+        let mut cpu = trap_test_core();
+        cpu.set_step_mode(super::StepMode::OverExceptions);
+        assert_eq!((Cycles(38 + 4 + 20), true), cpu.step_within(50));
+        assert_eq!(0x42, cpu.pc);
+    }
+
+    #[test]
+    fn step_over_interrupts_runs_interrupt_handlers_only() {
+        let mut cpu = trap_test_core();
+        cpu.set_step_mode(super::StepMode::OverInterrupts);
+        cpu.sr_to_flags(0x2000);
+        // level 5 autovector handler: 0x7202 is MOVEQ #2, D1, 0x4e73 is RTE
+        cpu.mem.write_long(SUPERVISOR_DATA, 29 * 4, 0x200);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x200, 0x7202);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x202, 0x4e73);
+        cpu.int_ctrl.request_interrupt(5);
+        // interrupt, MOVEQ and RTE, back before the TRAP
+        assert_eq!(Cycles(44 + 4 + 20), cpu.step());
+        assert_eq!((0x40, 2, 0x400), (cpu.pc, cpu.dar[1], cpu.dar[15]));
+        // but the TRAP handler is stepped into
+        assert_eq!(Cycles(38), cpu.step());
+        assert_eq!(0x100, cpu.pc);
+    }
+
+    #[test]
+    fn halt_line_freezes_execution_until_released() {
+        // 0x4e71 is NOP taking 4 cycles
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71, 0x4e, 0x71, 0x4e, 0x71]);
+        assert_eq!(Cycles(4), cpu.execute1());
+        assert_eq!(0x42, cpu.pc);
+
+        cpu.set_halt_line(true);
+        assert!(cpu.halt_line());
+        assert_eq!(Cycles(0), cpu.execute(100));
+        assert_eq!(0x42, cpu.pc);
+        // not even an interrupt gets through while halted
+        cpu.int_ctrl.request_interrupt(7);
+        assert_eq!(Cycles(0), cpu.execute(100));
+        assert_eq!(0x42, cpu.pc);
+        assert_eq!(super::ProcessingState::Normal, cpu.processing_state);
+
+        cpu.int_ctrl.acknowledge_interrupt(7);
+        cpu.set_halt_line(false);
+        assert_eq!(Cycles(8), cpu.execute(8));
+        assert_eq!(0x46, cpu.pc);
+    }
+
+    #[test]
+    fn nmi_has_no_effect_in_halted_state() {
+        let mut cpu = TestCore::new_mem(0x41, &[0x4e, 0x72]); // 0x4e72 STOP
+        cpu.processing_state = super::ProcessingState::Halted;
+
+        // Normally this would trigger a NMI, but should not in halted state
+        cpu.int_ctrl.request_interrupt(7);
+        cpu.execute1(); // will do nothing
+        assert_eq!(super::ProcessingState::Halted, cpu.processing_state);
+    }
+
+    // Group 1 and 2 exception frames have SR at SP, and PC at SP+2
+    fn stacked_pc(cpu: &TestCore) -> u32 {
+        cpu.mem.read_long(SUPERVISOR_DATA, cpu.dar[15] + 2)
+    }
+
+    #[test]
+    fn periodic_interrupt_wakes_stopped_cpu_once_per_period() {
+        use super::{ConfiguredCore, ProcessingState};
+        use crate::interrupts::{AutoInterruptController, PeriodicInterrupt};
+        use crate::ram::PagedMem;
+        let timer = PeriodicInterrupt::new(AutoInterruptController::new(), 1000, 5, 0x40);
+        let mut cpu = ConfiguredCore::new_with(0x40, timer, PagedMem::new(0));
+        // 0x4e72,0x2000 is STOP #$2000, 0x60fa is BRA.B back to the STOP
+        for (i, word) in [0x4e72u32, 0x2000, 0x60fa].iter().enumerate() {
+            cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x40 + 2 * i as u32, *word);
+        }
+        // handler: 0x5280 is ADDQ.L #1, D0, 0x4e73 is RTE
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x40 * 4, 0x200);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x200, 0x5280);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x202, 0x4e73);
+        cpu.dar[15] = 0x400;
+        cpu.processing_state = ProcessingState::Normal;
+
+        assert_eq!(Cycles(4500), cpu.execute_timed(4500));
+        assert_eq!(4, cpu.dar[0]);
+        assert_eq!(ProcessingState::Stopped, cpu.processing_state);
+        assert_eq!(500, cpu.int_ctrl.cycles_until_next());
+    }
+
+    // memory with wait states on accesses at or above `slow_from`
+    struct SlowMem {
+        mem: crate::ram::PagedMem,
+        slow_from: u32,
+        wait: i32,
+    }
+
+    impl crate::ram::BusWrapper for SlowMem {
+        type Inner = crate::ram::PagedMem;
+        fn inner(&self) -> &crate::ram::PagedMem {
+            &self.mem
+        }
+        fn inner_mut(&mut self) -> &mut crate::ram::PagedMem {
+            &mut self.mem
+        }
+        fn wait_cycles(&self, address: u32, _access_size: u8, _is_write: bool) -> i32 {
+            if address >= self.slow_from { self.wait } else { 0 }
+        }
+    }
+
+    #[test]
+    fn wait_states_delay_periodic_interrupt_recognition() {
+        use super::{ConfiguredCore, ProcessingState};
+        use crate::interrupts::{AutoInterruptController, PeriodicInterrupt};
+        use crate::ram::PagedMem;
+        // (wait states per read, MOVE.W (A0)+, D0 instructions executed before the interrupt)
+        for (wait, executed) in [(0, 13), (4, 9)].iter() {
+            let timer = PeriodicInterrupt::new(AutoInterruptController::new(), 100, 5, 0x40);
+            let mem = SlowMem { mem: PagedMem::new(0), slow_from: 0x3000, wait: *wait };
+            let mut cpu = ConfiguredCore::new_with(0x1000, timer, mem);
+            for i in 0..20 {
+                cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x1000 + 2 * i, 0x3018);
+            }
+            // handler: 0x4e72,0x2700 is STOP #$2700
+            cpu.mem.write_long(SUPERVISOR_DATA, 0x40 * 4, 0x200);
+            cpu.mem.write_long(SUPERVISOR_PROGRAM, 0x200, 0x4e72_2700);
+            cpu.sr_to_flags(0x2000);
+            cpu.dar[8] = 0x3000;
+            cpu.dar[15] = 0x400;
+            cpu.processing_state = ProcessingState::Normal;
+
+            cpu.execute_timed(200);
+            assert_eq!(ProcessingState::Stopped, cpu.processing_state, "wait {}", wait);
+            assert_eq!(0x3000 + 2 * executed, cpu.dar[8], "wait {}", wait);
+        }
+    }
+
+    fn exception_test_core(contents: &[u8]) -> TestCore {
+        let mut cpu = TestCore::new_mem(0x40, contents);
+        cpu.dar[15] = 0x400;
+        cpu
+    }
+
+    #[test]
+    fn move_from_ccr_is_illegal_and_move_from_sr_unprivileged_on_68000() {
+        // 0x42c0 is MOVE CCR, D0 (68010+), 0x40c1 is MOVE SR, D1
+        let mut cpu = exception_test_core(&[0x40, 0xc1, 0x42, 0xc0]);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_ILLEGAL_INSTRUCTION as u32 * 4, 0x200);
+        cpu.inactive_ssp = 0x400;
+        cpu.dar[15] = 0x300;
+        cpu.sr_to_flags(0x0004);
+        cpu.dar[1] = 0xffff_ffff;
+        cpu.execute1();
+        assert_eq!(0xffff_0004, cpu.dar[1]);
+        assert_eq!(0x42, cpu.pc);
+        cpu.execute1();
+        assert_eq!(0x200, cpu.pc);
+        assert_eq!(0x42, stacked_pc(&cpu));
+    }
+
+    #[test]
+    fn move_from_ccr_works_and_move_from_sr_is_privileged_in_user_mode_on_68010() {
+        use super::CpuModel;
+        // 0x42c0 is MOVE CCR, D0, 0x42d0 is MOVE CCR, (A0), 0x40c1 is MOVE SR, D1
+        let mut cpu = exception_test_core(&[0x42, 0xc0, 0x42, 0xd0, 0x40, 0xc1]);
+        cpu.set_model(CpuModel::M68010);
+        assert_eq!(CpuModel::M68010, cpu.model());
+        assert!(cpu.is_legal_opcode(0x42c0));
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_PRIVILEGE_VIOLATION as u32 * 4, 0x200);
+        cpu.inactive_ssp = 0x400;
+        cpu.dar[15] = 0x300;
+        cpu.sr_to_flags(0x0715);
+        cpu.dar[0] = 0xffff_ffff;
+        cpu.dar[1] = 0xffff_ffff;
+        cpu.dar[8] = 0x100;
+        assert_eq!(Cycles(4), cpu.execute1());
+        assert_eq!(0xffff_0015, cpu.dar[0]);
+        assert_eq!(Cycles(12), cpu.execute1());
+        assert_eq!(0x15, cpu.mem.read_word(USER_DATA, 0x100));
+        cpu.execute1();
+        assert_eq!(0xffff_ffff, cpu.dar[1]);
+        assert_eq!(0x200, cpu.pc);
+        assert_eq!(0x44, stacked_pc(&cpu));
+    }
+
+    #[test]
+    fn exceptions_stack_a_format_word_that_rte_unstacks_on_68010() {
+        use super::CpuModel;
+        // 0x4e40 is TRAP #0, 0x4e71 is NOP; the handler is RTE, 0x4e73
+        let mut cpu = exception_test_core(&[0x4e, 0x40, 0x4e, 0x71]);
+        cpu.set_model(CpuModel::M68010);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_TRAP_BASE as u32 * 4, 0x200);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x200, 0x4e73);
+        cpu.execute1();
+        assert_eq!(0x200, cpu.pc);
+        assert_eq!(0x400 - 8, cpu.dar[15]);
+        assert_eq!(u32::from(super::EXCEPTION_TRAP_BASE) << 2, cpu.mem.read_word(SUPERVISOR_DATA, cpu.dar[15] + 6));
+        assert_eq!(Cycles(20), cpu.execute1());
+        assert_eq!(0x42, cpu.pc);
+        assert_eq!(0x400, cpu.dar[15]);
+    }
+
+    #[test]
+    fn address_error_stacks_a_bus_fault_frame_that_rte_unstacks_on_68010() {
+        use super::CpuModel;
+        use super::stackframe::{read_frame, StackFrame};
+        // 0x3010 is MOVE.W (A0), D0; the handler is RTE, 0x4e73
+        let mut cpu = exception_test_core(&[0x30, 0x10, 0x4e, 0x71]);
+        cpu.set_model(CpuModel::M68010);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_ADDRESS_ERROR as u32 * 4, 0x200);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x200, 0x4e73);
+        cpu.dar[8] = 0x101;
+        cpu.execute1();
+        assert_eq!(0x200, cpu.pc);
+        assert_eq!(0x400 - 58, cpu.dar[15]);
+        match read_frame(&cpu.mem, SUPERVISOR_DATA, cpu.dar[15]) {
+            Some(StackFrame::BusFault { vector, special_status, fault_address, instruction_input, .. }) => {
+                assert_eq!(super::EXCEPTION_ADDRESS_ERROR, vector);
+                // data fetch, read, supervisor data space
+                assert_eq!(0x1105, special_status);
+                assert_eq!(0x101, fault_address);
+                assert_eq!(0x3010, instruction_input);
+            }
+            frame => panic!("expected a bus fault frame, got {:?}", frame),
+        }
+        let stacked = stacked_pc(&cpu);
+        cpu.execute1();
+        assert_eq!(stacked, cpu.pc);
+        assert_eq!(0x400, cpu.dar[15]);
     }
 
     #[test]
-    fn new_mem_sets_pc_and_mem() {
-        let base = 128;
-        let cpu = TestCore::new_mem(base, &[1u8, 2u8, 3u8, 4u8, 5u8, 6u8]);
-        assert_eq!(128, cpu.pc);
-        assert_eq!(1, cpu.mem.read_byte(SUPERVISOR_PROGRAM, 128));
-        assert_eq!(2, cpu.mem.read_byte(SUPERVISOR_PROGRAM, 129));
+    fn rte_of_an_unknown_frame_format_is_a_format_error_on_68010() {
+        use super::CpuModel;
+        // formats 2 (68020 six word) and 0xf are not 68010 frames
+        for &format_word in [0x2000u32, 0xf000].iter() {
+            // 0x4e73 is RTE
+            let mut cpu = exception_test_core(&[0x4e, 0x73]);
+            cpu.set_model(CpuModel::M68010);
+            cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_FORMAT_ERROR as u32 * 4, 0x200);
+            cpu.dar[15] = 0x3f0;
+            cpu.mem.write_word(SUPERVISOR_DATA, 0x3f0, 0x2700);
+            cpu.mem.write_long(SUPERVISOR_DATA, 0x3f2, 0x300);
+            cpu.mem.write_word(SUPERVISOR_DATA, 0x3f6, format_word);
+            assert_eq!(Cycles(34), cpu.execute1());
+            assert_eq!(0x200, cpu.pc, "format word {:04x}", format_word);
+            assert_eq!(0x40, stacked_pc(&cpu));
+            // the bad frame is left in place below the format error frame
+            assert_eq!(0x3f0 - 8, cpu.dar[15]);
+            assert_eq!(u32::from(super::EXCEPTION_FORMAT_ERROR) << 2, cpu.mem.read_word(SUPERVISOR_DATA, 0x3f0 - 2));
+            assert_eq!(format_word, cpu.mem.read_word(SUPERVISOR_DATA, 0x3f6));
+        }
     }
 
     #[test]
-    fn a_jump_changes_pc() {
-        let mut cpu = TestCore::new(0);
-        cpu.jump(128);
-        assert_eq!(128, cpu.pc);
+    fn moves_uses_the_address_spaces_of_sfc_and_dfc_on_68010() {
+        use super::{AccessType, CpuModel};
+        // 0x0e50,0x0800 is MOVES.W D0, (A0), 0x0e91,0x2000 is MOVES.L (A1), D2
+        let mut cpu = exception_test_core(&[0x0e, 0x50, 0x08, 0x00, 0x0e, 0x91, 0x20, 0x00]);
+        cpu.set_model(CpuModel::M68010);
+        cpu.set_dfc(1);
+        cpu.set_sfc(0xfe); // only the low three bits are kept
+        assert_eq!((6, 1), (cpu.sfc(), cpu.dfc()));
+        cpu.set_access_capture(true);
+        cpu.dar[0] = 0x1234;
+        cpu.dar[8] = 0x100;
+        cpu.dar[9] = 0x200;
+        cpu.mem.write_long(SUPERVISOR_PROGRAM, 0x200, 0xdead_beef);
+
+        assert_eq!(Cycles(18), cpu.execute1());
+        let writes = cpu.last_instruction_accesses().iter()
+            .filter(|access| access.access_type == AccessType::Write)
+            .map(|access| (access.address_space, access.address)).collect::<Vec<_>>();
+        assert_eq!(vec![(USER_DATA, 0x100)], writes);
+        assert_eq!(0x1234, cpu.mem.read_word(USER_DATA, 0x100));
+
+        assert_eq!(Cycles(22), cpu.execute1());
+        assert!(cpu.last_instruction_accesses().iter().any(|access| access.address_space == SUPERVISOR_PROGRAM && access.address == 0x200));
+        assert_eq!(0xdead_beef, cpu.dar[2]);
     }
 
     #[test]
-    #[allow(unused_must_use)]
-    fn a_read_imm_u32_changes_pc() {
-        let base = 128;
-        let mut cpu = TestCore::new(base);
-        cpu.read_imm_u32();
-        assert_eq!(base+4, cpu.pc);
+    fn moves_and_movec_are_privileged_on_68010() {
+        use super::CpuModel;
+        // 0x0e50,0x0800 is MOVES.W D0, (A0), 0x4e7a,0x1000 is MOVEC SFC, D1
+        for code in [[0x0e, 0x50, 0x08, 0x00], [0x4e, 0x7a, 0x10, 0x00]].iter() {
+            let mut cpu = exception_test_core(code);
+            cpu.set_model(CpuModel::M68010);
+            cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_PRIVILEGE_VIOLATION as u32 * 4, 0x200);
+            cpu.inactive_ssp = 0x400;
+            cpu.dar[15] = 0x300;
+            cpu.sr_to_flags(0x0000);
+            cpu.execute1();
+            assert_eq!(0x200, cpu.pc);
+            assert_eq!(0x40, stacked_pc(&cpu));
+        }
     }
 
     #[test]
-    fn a_read_imm_u32_reads_from_pc() {
-        let base = 128;
-        let mut cpu = TestCore::new_mem(base, &[2u8, 1u8, 3u8, 4u8]);
-        let val = cpu.read_imm_u32().unwrap();
-        assert_eq!((2<<24)+(1<<16)+(3<<8)+4, val);
-    }
+    fn movec_moves_control_registers_and_vbr_relocates_vectors_on_68010() {
+        use super::CpuModel;
+        // 0x4e7b,0x0801 is MOVEC D0, VBR, 0x4e7b,0x1000 is MOVEC D1, SFC,
+        // 0x4e7a,0xa800 is MOVEC USP, A2, 0x4e7a,0x3001 is MOVEC DFC, D3,
+        // 0x4e40 is TRAP #0
+        let mut cpu = exception_test_core(&[0x4e, 0x7b, 0x08, 0x01, 0x4e, 0x7b, 0x10, 0x00,
+                                            0x4e, 0x7a, 0xa8, 0x00, 0x4e, 0x7a, 0x30, 0x01, 0x4e, 0x40]);
+        cpu.set_model(CpuModel::M68010);
+        cpu.set_dfc(5);
+        cpu.inactive_usp = 0x800;
+        cpu.dar[0] = 0x1000;
+        cpu.dar[1] = 0xff;
+        cpu.dar[3] = 0xffff_ffff;
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_TRAP_BASE as u32 * 4, 0x200);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x1000 + super::EXCEPTION_TRAP_BASE as u32 * 4, 0x300);
+
+        assert_eq!(Cycles(10), cpu.execute1());
+        assert_eq!(0x1000, cpu.vbr());
+        cpu.execute1();
+        assert_eq!(7, cpu.sfc());
+        assert_eq!(Cycles(12), cpu.execute1());
+        assert_eq!(0x800, cpu.dar[10]);
+        cpu.execute1();
+        assert_eq!(5, cpu.dar[3]);
+        cpu.execute1();
+        assert_eq!(0x300, cpu.pc);
 
+        // an unknown control register is an illegal instruction
+        // 0x4e7a,0x0002 is MOVEC CACR, D0 (68020+)
+        let mut cpu = exception_test_core(&[0x4e, 0x7a, 0x00, 0x02]);
+        cpu.set_model(CpuModel::M68010);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_ILLEGAL_INSTRUCTION as u32 * 4, 0x200);
+        cpu.execute1();
+        assert_eq!(0x200, cpu.pc);
+        assert_eq!(0x40, stacked_pc(&cpu));
+    }
 
     #[test]
-    #[allow(unused_must_use)]
-    fn a_read_imm_u16_changes_pc() {
-        let base = 128;
-        let mut cpu = TestCore::new(base);
-        cpu.read_imm_u16();
-        assert_eq!(base+2, cpu.pc);
+    fn address_register_operands_of_invalid_size_or_role_are_illegal() {
+        let opcodes = [
+            (0xd008, "ADD.B A0, D0"),
+            (0x9008, "SUB.B A0, D0"),
+            (0xb008, "CMP.B A0, D0"),
+            (0xc008, "AND.B A0, D0"),
+            (0x8008, "OR.B A0, D0"),
+            (0x1008, "MOVE.B A0, D0"),
+            (0x1040, "MOVEA.B D0, A0"),
+            (0x5208, "ADDQ.B #1, A0"),
+            (0x0608, "ADDI.B #1, A0"),
+            (0x4248, "CLR.W A0"),
+            (0x4488, "NEG.L A0"),
+            (0x4648, "NOT.W A0"),
+            (0x4a08, "TST.B A0"),
+            (0x4a48, "TST.W A0"),
+        ];
+        for &(opcode, text) in opcodes.iter() {
+            // followed by an immediate operand, for those that would take one
+            let mut cpu = exception_test_core(&[(opcode >> 8) as u8, opcode as u8, 0x00, 0x01]);
+            cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_ILLEGAL_INSTRUCTION as u32 * 4, 0x200);
+            cpu.dar[0] = 0x1234_5678;
+            cpu.dar[8] = 0x100;
+            cpu.execute1();
+            assert_eq!(0x200, cpu.pc, "{}", text);
+            assert_eq!(0x40, stacked_pc(&cpu), "{}", text);
+            assert_eq!((0x1234_5678, 0x100), (cpu.dar[0], cpu.dar[8]), "{}", text);
+        }
     }
 
     #[test]
-    fn a_read_imm_u16_reads_from_pc() {
-        let base = 128;
-        let mut cpu = TestCore::new_mem(base, &[2u8, 1u8, 3u8, 4u8]);
-        assert_eq!("-S7-----", cpu.flags());
+    fn move_from_sr_reads_the_whole_status_register() {
+        // (SR set, SR read), with the unimplemented bits reading as zero
+        let cases = [(0x2700, 0x2700), (0x2314, 0x2314), (0xffff, 0xa71f), (0x0015, 0x0015), (0x0000, 0x0000)];
+        for &(sr, expected) in cases.iter() {
+            // MOVE SR, D0
+            let mut cpu = exception_test_core(&[0x40, 0xc0]);
+            cpu.inactive_usp = 0x800;
+            cpu.dar[0] = 0xdead_beef;
+            cpu.sr_to_flags(sr);
+            cpu.execute1();
+            assert_eq!(0xdead_0000 | expected, cpu.dar[0], "SR {:04x}", sr);
+            if sr & 0x8000 == 0 {
+                // in user mode too, as MOVE from SR is unprivileged on the 68000
+                assert_eq!(0x42, cpu.pc, "SR {:04x}", sr);
+            }
+        }
+    }
 
-        let val = cpu.read_imm_u16().unwrap();
-        assert_eq!((2<<8)+(1<<0), val);
-        assert_eq!(Operation::ReadLong(SUPERVISOR_PROGRAM, base, 0x02010304), cpu.mem.logger.ops()[0]);
+    fn line_exception_vector_and_stacked_opcode(opcode: u16) -> (u32, u32) {
+        let mut cpu = exception_test_core(&[(opcode >> 8) as u8, opcode as u8]);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_UNIMPLEMENTED_1010 as u32 * 4, 0x1010);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_UNIMPLEMENTED_1111 as u32 * 4, 0x1111);
+        cpu.execute1();
+        // a handler finds the trapping opcode through the stacked PC
+        let stacked = stacked_pc(&cpu);
+        (cpu.pc, cpu.mem.read_word(SUPERVISOR_PROGRAM, stacked))
     }
 
     #[test]
-    fn an_user_mode_read_imm_u16_is_reflected_in_mem_ops() {
-        let base = 128;
-        let mut cpu = TestCore::new_mem(base, &[2u8, 1u8, 3u8, 4u8]);
-        cpu.s_flag = 0;
-        assert_eq!("-U7-----", cpu.flags());
-
-        let val = cpu.read_imm_u16().unwrap();
-        assert_eq!((2<<8)+(1<<0), val);
-        assert_eq!(Operation::ReadLong(USER_PROGRAM, base, 0x02010304), cpu.mem.logger.ops()[0]);
+    fn line_1010_opcodes_take_vector_10_with_pc_at_instruction() {
+        for opcode in (0xa000..=0xafff).step_by(0x111) {
+            assert_eq!((0x1010, opcode), line_exception_vector_and_stacked_opcode(opcode as u16));
+        }
+        assert_eq!((0x1010, 0xafff), line_exception_vector_and_stacked_opcode(0xafff));
     }
 
     #[test]
-    fn a_reset_reads_sp_and_pc_from_0() {
-        let mut cpu = TestCore::new_mem(0, &[0u8,0u8,1u8,0u8, 0u8,0u8,0u8,128u8]);
-        cpu.reset();
-        assert_eq!(256, cpu.dar[15]);
-        assert_eq!(128, cpu.pc);
-        assert_eq!("-S7-----", cpu.flags());
-        assert_eq!(Operation::ReadLong(SUPERVISOR_PROGRAM, 0, 0x100), cpu.mem.logger.ops()[0]);
+    fn line_1111_opcodes_take_vector_11_with_pc_at_instruction() {
+        for opcode in (0xf000..=0xffff).step_by(0x111) {
+            assert_eq!((0x1111, opcode), line_exception_vector_and_stacked_opcode(opcode as u16));
+        }
+        assert_eq!((0x1111, 0xffff), line_exception_vector_and_stacked_opcode(0xffff));
     }
 
     #[test]
-    fn execute_reads_from_pc_and_does_not_panic_on_illegal_instruction() {
-        let mut cpu = TestCore::new_mem(0xba, &[0xba,0xd1,1u8,0u8, 0u8,0u8,0u8,128u8]);
+    fn move16_takes_line_1111_exception_and_can_be_skipped() {
+        // all MOVE16 forms of the 68040 are in line 1111
+        assert!((0xf600..=0xf63f).all(|opcode| !super::ops::is_legal_opcode(opcode)));
+
+        // MOVE16 (A0)+, (A1)+ ; NOP
+        let mut cpu = exception_test_core(&[0xf6, 0x20, 0x90, 0x00, 0x4e, 0x71]);
+        cpu.dar[8] = 0x1000;
+        cpu.dar[9] = 0x2000;
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x1000, 0x1234_5678);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x2000, 0);
+        // feature detection handler: MOVEQ #-1, D7 ; ADDQ.L #4, 2(A7) ; RTE
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_UNIMPLEMENTED_1111 as u32 * 4, 0x100);
+        for (offset, word) in [0x7eff, 0x58af, 0x0002, 0x4e73].iter().enumerate() {
+            cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x100 + 2 * offset as u32, *word);
+        }
+
         cpu.execute1();
+        assert_eq!(0x100, cpu.pc);
+        assert_eq!(0x40, stacked_pc(&cpu));
+        for _ in 0..3 {
+            cpu.execute1();
+        }
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!(0xffff_ffff, cpu.dar[7]);
+        // nothing was moved
+        assert_eq!((0x1000, 0x2000), (cpu.dar[8], cpu.dar[9]));
+        assert_eq!(0, cpu.mem.read_long(SUPERVISOR_DATA, 0x2000));
     }
+
     #[test]
-    fn execute_does_not_panic_on_odd_pc() {
-        let mut cpu = TestCore::new_mem(0xbd, &[0x00, 0x0a, 0x00, 0x00]);
+    fn illegal_instruction_stacks_address_of_instruction() {
+        let mut cpu = exception_test_core(&[0x4a, 0xfc]); // 0x4afc ILLEGAL
         cpu.execute1();
+        assert_eq!(0x400 - 6, cpu.dar[15]);
+        assert_eq!(0x40, stacked_pc(&cpu));
     }
 
     #[test]
-    fn execute_can_execute_instruction_handler_0a() {
-        let mut cpu = TestCore::new_mem(0xba, &[0x00, 0x0A, 1u8,0u8, 0u8,0u8,0u8,128u8]);
-        cpu.instruction_set = ops::fake::instruction_set();
+    fn illegal_instruction_handler_can_emulate_and_skip_the_instruction() {
+        // 0x4afc ILLEGAL ; 0x7201 MOVEQ #1, D1
+        let mut cpu = exception_test_core(&[0x4a, 0xfc, 0x72, 0x01]);
+        cpu.mem.write_long(SUPERVISOR_DATA, 4 * 4, 0x100);
+        // MOVEA.L (2,A7), A0 ; MOVE.W (A0), D0 ; ADDQ.L #2, (2,A7) ; RTE
+        for (offset, word) in [0x206f, 0x0002, 0x3010, 0x54af, 0x0002, 0x4e73].iter().enumerate() {
+            cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x100 + 2 * offset as u32, *word);
+        }
         cpu.execute1();
-        assert_eq!(0xabcd, cpu.dar[0]);
-        assert_eq!(0x0000, cpu.dar[1]);
+        assert_eq!(0x100, cpu.pc);
+        assert_eq!(0x40, stacked_pc(&cpu));
+        for _ in 0..4 {
+            cpu.execute1();
+        }
+        // the handler found the opcode at the stacked PC and returned past it
+        assert_eq!(0x4afc, cpu.dar[0]);
+        assert_eq!(0x40, cpu.dar[8]);
+        assert_eq!(0x42, cpu.pc);
+        assert_eq!(0x400, cpu.dar[15]);
+        cpu.execute1();
+        assert_eq!(1, cpu.dar[1]);
     }
 
     #[test]
-    fn execute_can_execute_instruction_handler_0b() {
-        let mut cpu = TestCore::new_mem(0xba, &[0x00, 0x0B, 1u8,0u8, 0u8,0u8,0u8,128u8]);
-        cpu.instruction_set = ops::fake::instruction_set();
+    fn unimplemented_instruction_stacks_address_of_instruction() {
+        let mut cpu = exception_test_core(&[0xa1, 0x23]); // Line 1010 emulator
         cpu.execute1();
-        assert_eq!(0x0000, cpu.dar[0]);
-        assert_eq!(0xbcde, cpu.dar[1]);
-    }
+        assert_eq!(0x40, stacked_pc(&cpu));
 
+        let mut cpu = exception_test_core(&[0xf1, 0x23]); // Line 1111 emulator
+        cpu.execute1();
+        assert_eq!(0x40, stacked_pc(&cpu));
+    }
 
     #[test]
-    fn execute_can_execute_set_dx() {
-        // first byte 40 is register D0
-        // 42 == D1
-        // 44 == D2
-        // 46 == D3
-        // 48 == D4
-        // 4a == D5
-        // 4c == D6
-        // 4e == D7
-        let mut cpu = TestCore::new_mem(0x40, &[0x4c, 0x00, 1u8, 0u8]);
-        cpu.instruction_set = ops::fake::instruction_set();
+    fn privilege_violation_stacks_address_of_instruction() {
+        // 0x007c,0x0700 is ORI.W #$0700, SR
+        let mut cpu = exception_test_core(&[0x00, 0x7c, 0x07, 0x00]);
+        cpu.s_flag = super::SFLAG_CLEAR;
+        cpu.inactive_ssp = 0x400;
         cpu.execute1();
-        assert_eq!(0xcdef, cpu.dar[6]);
+        assert_eq!(super::SFLAG_SET, cpu.s_flag);
+        assert_eq!(0x40, stacked_pc(&cpu));
     }
 
     #[test]
-    fn array_elems() {
-        let mut arr = [1, 2, 3, 4];
-        let marr = &mut arr;
-        let elem: &mut i32 = &mut (marr[1]);
-        // let mut elem2: &mut i32 = &mut (arr[2]);
-        assert_eq!(2, *elem);
-        *elem = 200;
-        assert_eq!(200, *elem);
-        // assert_eq!(200, &mut marr[1]);
+    fn privileged_instructions_in_user_mode_take_exception_cycles_only() {
+        // the instruction is not executed, not even its operand fetched, so
+        // every privileged instruction costs the same 34 cycles when trapping
+        let programs: [&[u8]; 8] = [
+            &[0x4e, 0x70],             // RESET
+            &[0x4e, 0x72, 0x27, 0x00], // STOP #$2700
+            &[0x46, 0xfc, 0x27, 0x00], // MOVE #$2700, SR
+            &[0x46, 0xd0],             // MOVE (A0), SR
+            &[0x46, 0xf9, 0x00, 0x00, 0x10, 0x00], // MOVE $1000.L, SR
+            &[0x02, 0x7c, 0xf8, 0xff], // ANDI #$f8ff, SR
+            &[0x4e, 0x60],             // MOVE A0, USP
+            &[0x4e, 0x73],             // RTE
+        ];
+        for program in programs.iter() {
+            let mut cpu = exception_test_core(program);
+            cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_PRIVILEGE_VIOLATION as u32 * 4, 0x200);
+            cpu.s_flag = super::SFLAG_CLEAR;
+            cpu.inactive_ssp = 0x400;
+            cpu.dar[8] = 0x1000;
+            assert_eq!(Cycles(34), cpu.execute1(), "{:02x?}", program);
+            assert_eq!(0x200, cpu.pc);
+            assert_eq!(0x40, stacked_pc(&cpu));
+            assert_eq!(0x0700, cpu.mem.read_word(SUPERVISOR_DATA, cpu.dar[15]));
+            assert_eq!(0x2700, cpu.status_register() & 0xff00);
+        }
     }
+
     #[test]
-    fn cycle_counting() {
-        // 0xc308 = abcd_8_mm taking 18 cycles
-        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x08]);
-        let Cycles(count) = cpu.execute1();
-        assert_eq!(18, count);
+    fn trap_stacks_address_of_next_instruction() {
+        let mut cpu = exception_test_core(&[0x4e, 0x41]); // 0x4e41 TRAP #1
+        cpu.execute1();
+        assert_eq!(0x42, stacked_pc(&cpu));
     }
+
     #[test]
-    fn cycle_counting_exec2() {
-        // 0xc308 = abcd_8_mm taking 18 cycles
-        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x08, 0xc3, 0x08]);
-        let Cycles(count) = cpu.execute(20);
-        assert_eq!(18*2, count);
+    fn rte_from_trap_continues_after_the_trap() {
+        let mut cpu = trap_test_core();
+        cpu.execute1();
+        assert_eq!(0x100, cpu.pc);
+        assert_eq!(0x42, stacked_pc(&cpu));
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!((0x42, 1, 0x400), (cpu.pc, cpu.dar[0], cpu.dar[15]));
+
+        // unlike a fault, which stacks the address of the faulting
+        // instruction so RTE executes it again; 0x4afc is ILLEGAL
+        let mut cpu = exception_test_core(&[0x4a, 0xfc]);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_ILLEGAL_INSTRUCTION as u32 * 4, 0x100);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x100, 0x4e73);
+        cpu.execute1();
+        assert_eq!(0x40, stacked_pc(&cpu));
+        cpu.execute1();
+        assert_eq!(0x40, cpu.pc);
     }
 
     #[test]
-    fn cycle_granularity_rounds_up_to_boundary() {
-        // 0xc308 = abcd_8_mm taking 18 cycles
-        // With granularity 4, should round up to 20
-        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x08]);
-        cpu.set_cycle_granularity(4);
-        let Cycles(count) = cpu.execute1();
-        assert_eq!(20, count); // 18 rounded up to next multiple of 4
+    fn trapv_stacks_address_of_next_instruction() {
+        let mut cpu = exception_test_core(&[0x4e, 0x76]); // 0x4e76 TRAPV
+        cpu.v_flag = super::VFLAG_SET;
+        cpu.execute1();
+        assert_eq!(0x42, stacked_pc(&cpu));
     }
 
     #[test]
-    fn cycle_granularity_no_change_when_already_aligned() {
-        // 0xc300 = abcd_8_rr taking 6 cycles (already aligned to 4)
-        // NOP = 0x4e71 taking 4 cycles (already aligned)
-        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x71]);
-        cpu.set_cycle_granularity(4);
-        let Cycles(count) = cpu.execute1();
-        assert_eq!(4, count); // Already a multiple of 4
+    fn chk_stacks_address_of_next_instruction() {
+        // 0x41bc,0x0010 is CHK.W #16, D0
+        let mut cpu = exception_test_core(&[0x41, 0xbc, 0x00, 0x10]);
+        cpu.dar[0] = 0x20;
+        cpu.execute1();
+        assert_eq!(0x44, stacked_pc(&cpu));
     }
 
     #[test]
-    fn cycle_granularity_atari_st_mode() {
-        // Simulate Atari ST timing with two instructions
-        // 0xc308 = abcd_8_mm taking 18 cycles -> rounds to 20
-        // 0xc300 = abcd_8_rr taking 6 cycles -> rounds to 8
-        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x08, 0xc3, 0x00]);
-        cpu.set_cycle_granularity(4);
-
-        // Execute first instruction - takes 18 cycles, aligned to 20
-        let Cycles(count) = cpu.execute1();
-        assert_eq!(20, count);
-
-        // Execute second instruction - takes 6 cycles, aligned to 8
-        let Cycles(count2) = cpu.execute1();
-        assert_eq!(8, count2);
-
-        // Total: 28 cycles aligned (vs 24 raw) - matching Atari ST bus behavior
-        assert_eq!(28, count + count2);
+    fn zero_divide_stacks_address_of_next_instruction() {
+        // 0x80fc,0x0000 is DIVU.W #0, D0
+        let mut cpu = exception_test_core(&[0x80, 0xfc, 0x00, 0x00]);
+        cpu.execute1();
+        assert_eq!(0x44, stacked_pc(&cpu));
     }
 
     #[test]
-    fn abcd_8_rr() {
-        // opcodes c100 - c107, c300 - c307, etc.
-        // or more generally c[13579bdf]0[0-7]
-        // where [13579bdf] is DX (dest regno) and [0-7] is DY (src regno)
-        // so c300 means D1 = D0 + D1 in BCD
-        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x00]);
-
-        cpu.dar[0] = 0x16;
-        cpu.dar[1] = 0x26;
+    fn interrupt_stacks_address_of_next_instruction() {
+        let mut cpu = exception_test_core(&[0x4e, 0x71]); // 0x4e71 NOP
+        cpu.int_mask = 0;
+        cpu.int_ctrl.request_interrupt(3);
         cpu.execute1();
-
-        // 16 + 26 is 42
-        assert_eq!(0x42, cpu.dar[1]);
+        assert_eq!(0x40, stacked_pc(&cpu));
     }
-    #[test]
-    fn abcd_8_mm() {
-        // opcodes c108 - c10f, c308 - c30f, etc.
-        // or more generally c[13579bdf]0[8-f]
-        // where [13579bdf] is AX (dest regno) and [8-f] is AY (src regno)
-        // so c308 means A1 = A0 + A1 in BCD
-        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x08]);
 
-        cpu.dar[8+0] = 0x160+1;
-        cpu.dar[8+1] = 0x260+1;
-        cpu.mem.write_byte(USER_DATA, 0x160, 0x16);
-        cpu.mem.write_byte(USER_DATA, 0x260, 0x26);
+    #[test]
+    fn address_error_on_fetch_stacks_odd_pc() {
+        let mut cpu = TestCore::new_mem(0x41, &[0x4e, 0x71]);
+        cpu.dar[15] = 0x400;
         cpu.execute1();
-        let res = cpu.mem.read_byte(USER_DATA, 0x260);
+        // Group 0 exception frame has PC at SP+10
+        assert_eq!(0x400 - 14, cpu.dar[15]);
+        assert_eq!(0x41, cpu.mem.read_long(SUPERVISOR_DATA, cpu.dar[15] + 10));
+    }
 
-        // 16 + 26 is 42
-        assert_eq!(0x42, res);
+    #[test]
+    fn rts_to_odd_address_faults_on_next_fetch() {
+        // 0x4e75 is RTS
+        let mut cpu = exception_test_core(&[0x4e, 0x75]);
+        cpu.mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_ADDRESS_ERROR as u32 * 4, 0x200);
+        cpu.dar[15] = 0x3fc;
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x3fc, 0x1235);
+        cpu.sr_to_flags(0x2704);
+
+        // the RTS itself completes and loads the odd PC
+        assert_eq!(Cycles(16), cpu.execute1());
+        assert_eq!(0x1235, cpu.pc);
+        assert_eq!(0x400, cpu.dar[15]);
+
+        // the fault is raised by the following instruction fetch
+        assert_eq!(Cycles(50), cpu.execute1());
+        assert_eq!(0x200, cpu.pc);
+        let sp = cpu.dar[15];
+        assert_eq!(0x400 - 14, sp);
+        // read, instruction processing, supervisor program
+        assert_eq!(0x16, cpu.mem.read_word(SUPERVISOR_DATA, sp));
+        assert_eq!(0x1235, cpu.mem.read_long(SUPERVISOR_DATA, sp + 2));
+        assert_eq!(0x4e75, cpu.mem.read_word(SUPERVISOR_DATA, sp + 6));
+        assert_eq!(0x2704, cpu.mem.read_word(SUPERVISOR_DATA, sp + 8));
+        assert_eq!(0x1235, cpu.mem.read_long(SUPERVISOR_DATA, sp + 10));
     }
 
     #[test]
-    fn add_8_er_d() {
-        // opcodes d000 - d007, d200 - d207, etc.
-        // or more generally d[02468ace]0[0-7]
-        // where [02468ace] is DX (dest regno) and [0-7] is DY (src regno)
+    fn bra_to_odd_target_faults_on_next_fetch() {
+        // 0x6001 is BRA.B *+3
+        let mut cpu = exception_test_core(&[0x60, 0x01]);
+        assert_eq!(Cycles(10), cpu.execute1());
+        assert_eq!(0x43, cpu.pc);
 
-        // opcodes d200 is ADD.B    D0, D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x00]);
+        assert_eq!(Cycles(50), cpu.execute1());
+        assert_eq!(0x43, cpu.mem.read_long(SUPERVISOR_DATA, cpu.dar[15] + 10));
+    }
 
-        cpu.dar[0] = 16;
-        cpu.dar[1] = 26;
+    #[test]
+    fn address_error_on_operand_access_stacks_advanced_pc() {
+        // 0xd250 is ADD.W (A0), D1
+        let mut cpu = exception_test_core(&[0xd2, 0x50]);
+        cpu.dar[8] = 0x101;
         cpu.execute1();
+        assert_eq!(0x101, cpu.mem.read_long(SUPERVISOR_DATA, cpu.dar[15] + 2));
+        assert_eq!(0x42, cpu.mem.read_long(SUPERVISOR_DATA, cpu.dar[15] + 10));
+    }
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
+    use crate::cpu::{Result, Callbacks, Exception, Core};
+
+    struct CustomExceptionHandler
+    {
+        suppress: bool,
+        count: isize,
+        ex: Option<Exception>
     }
 
-    #[test]
-    fn add_8_er_pi() {
-        // opcodes d018 - d01f, d218 - d21f, etc.
-        // or more generally d[02468ace]1[8-f]
-        // where [02468ace] is DX (dest regno) and [8-f] is AY (src regno)
+    impl Callbacks for CustomExceptionHandler {
+        fn exception_callback(&mut self, core: &mut impl Core, ex: Exception) -> Result<Cycles> {
+            self.count += 1;
+            self.ex = Some(ex);
 
-        // opcodes d218 is ADD.B    (A0)+, D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x18]);
-        let addr = 0x100;
-        cpu.dar[8+0] = addr;
-        cpu.mem.write_byte(USER_DATA, addr, 16);
-        cpu.dar[1] = 26;
-        cpu.execute1();
+            if !self.suppress {
+                Err(ex)
+            } else {
+                // correct an odd PC
+                if *core.pc() % 2 == 1 {
+                    *core.pc() += 1;
+                }
+                Ok(Cycles(1000))
+            }
+        }
+    }
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
-        assert_eq!(addr+1, cpu.dar[8+0]);
+    #[derive(Default)]
+    struct BusCycleRecorder {
+        cycles: Vec<(u32, bool)>,
+    }
+
+    impl Callbacks for BusCycleRecorder {
+        fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
+            Err(ex)
+        }
+        fn bus_cycle(&mut self, address: u32, is_write: bool) {
+            self.cycles.push((address, is_write));
+        }
     }
 
     #[test]
-    fn add_8_er_pd() {
-        // opcodes d020 - d027, d220 - d227, etc.
-        // or more generally d[02468ace]2[0-7]
-        // where [02468ace] is DX (dest regno) and [0-7] is AY (src regno)
-
-        // opcodes d220 is ADD.B    -(A0), D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x20]);
-        let addr = 0x100;
-        cpu.dar[8+0] = addr;
-        cpu.mem.write_byte(USER_DATA, addr-1, 16);
-        cpu.dar[1] = 26;
+    fn byte_stack_operations_keep_a7_even() {
+        // 0x1f00 is MOVE.B D0, -(A7), 0x121f is MOVE.B (A7)+, D1
+        let mut cpu = exception_test_core(&[0x1f, 0x00, 0x12, 0x1f]);
+        cpu.dar[0] = 0xab;
         cpu.execute1();
-
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
-        assert_eq!(addr-1, cpu.dar[8+0]);
+        assert_eq!(0x400 - 2, cpu.dar[15]);
+        assert_eq!(0xab, cpu.mem.read_byte(SUPERVISOR_DATA, 0x400 - 2));
+        cpu.execute1();
+        assert_eq!(0x400, cpu.dar[15]);
+        assert_eq!(0xab, cpu.dar[1]);
     }
 
     #[test]
-    fn add_8_er_ai() {
-        // opcodes d010 - d017, d210 - d217, etc.
-        // or more generally d[02468ace]1[0-7]
-        // where [02468ace] is DX (dest regno) and [0-7] is AY (src regno)
-
-        // opcodes d210 is ADD.B    (A0), D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x10]);
-
-        let addr = 0x100;
-        cpu.dar[8+0] = addr;
-        cpu.mem.write_byte(USER_DATA, addr, 16);
-        cpu.dar[1] = 26;
+    fn byte_predecrement_on_other_address_registers_is_one() {
+        // 0x1d00 is MOVE.B D0, -(A6), 0x121e is MOVE.B (A6)+, D1
+        let mut cpu = exception_test_core(&[0x1d, 0x00, 0x12, 0x1e]);
+        cpu.dar[8 + 6] = 0x300;
         cpu.execute1();
-
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
-        assert_eq!(addr, cpu.dar[8+0]);
+        assert_eq!(0x300 - 1, cpu.dar[8 + 6]);
+        cpu.execute1();
+        assert_eq!(0x300, cpu.dar[8 + 6]);
     }
-    #[test]
-    fn add_8_er_di_with_positive_displacement() {
-        // opcodes d028 - d02f, d228 - d22f, etc.
-        // or more generally d[02468ace]2[8-f]
-        // where [02468ace] is DX (dest regno) and [8-f] is AY (src regno)
 
-        // opcodes d228,0108 is ADD.B    (0x108, A0), D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x28, 0x01, 0x08]);
+    #[test]
+    fn execute_until_memory_stops_when_value_is_written() {
+        // 0x5240 is ADDQ.W #1, D0, 0x31c0,0x0100 is MOVE.W D0, $100.W, 0x60f8 is BRA.B back to start
+        let mut cpu = exception_test_core(&[0x52, 0x40, 0x31, 0xc0, 0x01, 0x00, 0x60, 0xf8]);
+        let (_, met) = cpu.execute_until_memory(SUPERVISOR_DATA, 0x100, 2, 3, 10000);
+        assert!(met);
+        assert_eq!(3, cpu.dar[0]);
+        assert_eq!(0x46, cpu.pc);
 
-        let addr = 0x100;
-        cpu.dar[8+0] = addr;
-        let displaced_addr = addr + 0x108;
-        cpu.mem.write_byte(USER_DATA, displaced_addr, 16);
-        cpu.dar[1] = 26;
-        cpu.execute1();
+        let (cycles, met) = cpu.execute_until_memory(SUPERVISOR_DATA, 0x100, 2, 3, 10000);
+        assert!(met);
+        assert_eq!(Cycles(0), cycles);
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
-        assert_eq!(addr, cpu.dar[8+0]);
+        let (cycles, met) = cpu.execute_until_memory(SUPERVISOR_DATA, 0x100, 4, 0xdead, 100);
+        assert!(!met);
+        assert!(cycles.0 >= 100);
     }
-    #[test]
-    fn add_8_er_di_with_negative_displacement() {
-        // opcodes d028 - d02f, d228 - d22f, etc. followed by an extension word
-        // or more generally d[02468ace]2[8-f]
-        // where [02468ace] is DX (dest regno) and [8-f] is AY (src regno)
 
-        // opcodes d228,FFFE is ADD.B    (-2, A0), D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x28, 0xFF, 0xFE]);
+    struct BudgetRecorder {
+        budgets: Vec<i32>,
+    }
 
-        let addr = 0x100;
-        cpu.dar[8+0] = addr;
-        let displaced_addr = addr - 2;
-        cpu.mem.write_byte(USER_DATA, displaced_addr, 16);
-        cpu.dar[1] = 26;
-        cpu.execute1();
+    impl Callbacks for BudgetRecorder {
+        fn exception_callback(&mut self, core: &mut impl Core, ex: Exception) -> Result<Cycles> {
+            self.budgets.push(core.remaining_budget());
+            Err(ex)
+        }
+    }
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
-        assert_eq!(addr, cpu.dar[8+0]);
+    #[test]
+    fn remaining_budget_is_visible_during_execution() {
+        // 0x4e71 is NOP, 0x4e41 is TRAP #1
+        let mut cpu = exception_test_core(&[0x4e, 0x71, 0x4e, 0x41]);
+        let mut recorder = BudgetRecorder { budgets: Vec::new() };
+        cpu.execute_with_state(1000, &mut recorder);
+        // NOP takes 4 cycles, TRAP 38; the (unset) handler then traps again
+        assert_eq!([1000 - 4, 1000 - 4 - 38], recorder.budgets[..2]);
+        assert_eq!(0, cpu.remaining_budget());
+
+        let mut cpu = exception_test_core(&[0x4e, 0x71, 0x4e, 0x71]);
+        let mut seen = Vec::new();
+        cpu.execute_with(100, |core| {
+            seen.push(core.remaining_budget());
+            seen.len() < 2
+        });
+        assert_eq!(vec![96, 92], seen);
+    }
+
+    #[derive(Default)]
+    struct EffectiveAddressRecorder {
+        computed: Vec<(u8, u8, u32)>,
+    }
+
+    impl Callbacks for EffectiveAddressRecorder {
+        fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
+            Err(ex)
+        }
+        fn effective_address_computed(&mut self, mode: u8, reg: u8, resolved: u32) {
+            self.computed.push((mode, reg, resolved));
+        }
     }
+
     #[test]
-    fn add_8_er_ix_with_positive_displacement() {
-        // opcodes d030 - d037, d230 - d237, etc. followed by an extension word
-        // or more generally d[02468ace]3[0-7]
-        // where [02468ace] is DX (dest regno) and [0-7] is AY (src regno)
+    fn effective_address_hook_reports_mode_register_and_address() {
+        // 2559,0010 is MOVE.L (A1)+, ($10,A2), 41fb,0002 is LEA (2,PC,D0.W), A0, 7001 is MOVEQ #1, D0
+        let mut cpu = exception_test_core(&[0x25, 0x59, 0x00, 0x10, 0x41, 0xfb, 0x00, 0x02, 0x70, 0x01]);
+        cpu.dar[0] = 4;
+        cpu.dar[8 + 1] = 0x200;
+        cpu.dar[8 + 2] = 0x300;
+        let mut recorder = EffectiveAddressRecorder::default();
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(vec![(3, 1, 0x200), (5, 2, 0x310)], recorder.computed);
 
-        // opcodes d230,9002 is ADD.B    (2, A0, A1), D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x30, 0x90, 0x02]);
+        let mut recorder = EffectiveAddressRecorder::default();
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(vec![(7, 3, 0x46 + 4 + 2)], recorder.computed);
 
-        let addr = 0x100;
-        let index = 0x10;
-        let displacement = 2;
-        cpu.dar[8+0] = addr;
-        cpu.dar[8+1] = index;
-        let effective_addr = addr + index + displacement;
-        cpu.mem.write_byte(USER_DATA, effective_addr, 16);
-        cpu.dar[1] = 26;
-        cpu.execute1();
+        let mut recorder = EffectiveAddressRecorder::default();
+        cpu.execute_with_state(1, &mut recorder);
+        assert!(recorder.computed.is_empty());
+    }
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
-        assert_eq!(addr, cpu.dar[8+0]);
+    struct IdleOnStop {
+        idle: Option<u64>,
+        stops: Vec<u16>,
     }
-    #[test]
-    fn add_8_er_ix_with_negative_displacement() {
-        // opcodes d030 - d037, d230 - d237, etc. followed by an extension word
-        // or more generally d[02468ace]3[0-7]
-        // where [02468ace] is DX (dest regno) and [0-7] is AY (src regno)
 
-        // opcodes d230,90FE is ADD.B    (-2, A0, A1), D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x30, 0x90, 0xFE]);
+    impl Callbacks for IdleOnStop {
+        fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
+            Err(ex)
+        }
+        fn on_stop(&mut self, sr: u16) -> Option<u64> {
+            self.stops.push(sr);
+            self.idle
+        }
+    }
 
-        let addr = 0x100;
-        let index = 0x10;
-        let displacement = 2;
-        cpu.dar[8+0] = addr;
-        cpu.dar[8+1] = index;
-        let effective_addr = addr + index - displacement;
-        cpu.mem.write_byte(USER_DATA, effective_addr, 16);
-        cpu.dar[1] = 26;
-        cpu.execute1();
+    #[test]
+    fn on_stop_limits_idle_cycles() {
+        // 0x4e72,0x2300 is STOP #$2300
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x72, 0x23, 0x00]);
+        let mut idle = IdleOnStop { idle: Some(100), stops: Vec::new() };
+        assert_eq!(Cycles(4 + 100), cpu.execute_with_state(1000, &mut idle));
+        assert_eq!(vec![0x2300], idle.stops);
+        assert_eq!(super::ProcessingState::Stopped, cpu.processing_state);
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
-        assert_eq!(addr, cpu.dar[8+0]);
+        // already stopped, so the hook is not called again
+        assert_eq!(Cycles(1000), cpu.execute_with_state(1000, &mut idle));
+        assert_eq!(1, idle.stops.len());
     }
+
     #[test]
-    fn add_8_er_aw() {
-        // opcodes d038, d238, d438, etc. followed by an extension word
-        // or more generally d[02468ace]38
-        // where [02468ace] is DX (dest regno) and the extension word is
-        // the 16-bit absolute address
+    fn on_stop_returning_none_consumes_budget() {
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x72, 0x23, 0x00]);
+        let mut idle = IdleOnStop { idle: None, stops: Vec::new() };
+        assert_eq!(Cycles(1000), cpu.execute_with_state(1000, &mut idle));
+        assert_eq!(vec![0x2300], idle.stops);
+    }
 
-        // opcodes d238,0108 is ADD.B    $0108, D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x38, 0x01, 0x08]);
-        cpu.mem.write_byte(USER_DATA, 0x108, 16);
-        cpu.dar[1] = 26;
-        cpu.execute1();
+    #[derive(Default)]
+    struct SkippedIllegalRecorder {
+        skipped: Vec<(u16, u32)>,
+    }
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
+    impl Callbacks for SkippedIllegalRecorder {
+        fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
+            Err(ex)
+        }
+        fn illegal_instruction_skipped(&mut self, opcode: u16, pc: u32) {
+            self.skipped.push((opcode, pc));
+        }
     }
+
     #[test]
-    fn add_8_er_al() {
-        // opcodes d039, d239, d439, etc. followed by two extension words
-        // or more generally d[02468ace]39
+    fn illegal_as_nop_skips_illegal_instructions() {
+        // 0x4afc is ILLEGAL, 0x7001 is MOVEQ #1, D0
+        let mut cpu = exception_test_core(&[0x4a, 0xfc, 0x70, 0x01]);
+        assert!(!cpu.illegal_as_nop());
+        cpu.set_illegal_as_nop(true);
+        let mut recorder = SkippedIllegalRecorder::default();
+
+        assert_eq!(Cycles(4), cpu.execute_with_state(1, &mut recorder));
+        assert_eq!(vec![(0x4afc, 0x40)], recorder.skipped);
+        assert_eq!(0x42, cpu.pc);
+        assert_eq!(0x400, cpu.dar[15]);
+        assert_eq!(0, cpu.exceptions_taken);
 
-        // where [02468ace] is DX (dest regno) and the first extension
-        // word is the high order word of the 32-bit absolute address,
-        // and the second extension word is the low order word.
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(1, cpu.dar[0]);
+    }
 
-        // opcodes d239,0009,0000 is ADD.B    $90000, D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x39, 0x00, 0x09, 0x00, 0x00]);
-        cpu.mem.write_byte(USER_DATA, 0x90000, 16);
-        cpu.dar[1] = 26;
-        cpu.execute1();
+    #[test]
+    fn illegal_as_nop_disabled_takes_exception() {
+        let mut cpu = exception_test_core(&[0x4a, 0xfc]);
+        let mut recorder = SkippedIllegalRecorder::default();
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
+        assert_eq!(Cycles(34), cpu.execute_with_state(1, &mut recorder));
+        assert!(recorder.skipped.is_empty());
+        assert_eq!(0x400 - 6, cpu.dar[15]);
     }
-    #[test]
-    fn add_8_er_pcdi() {
-        // opcodes d03a, d23a, d43a, etc. followed by an extension word
-        // or more generally d[02468ace]3a
 
-        // where [02468ace] is DX (dest regno)
-        // opcodes d23a,0108 is ADD.B    ($0108, PC), D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x3a, 0x01, 0x08]);
-        let addr = 0x40+2+0x0108;
-        cpu.mem.write_byte(USER_DATA, addr, 16);
-        cpu.dar[1] = 26;
-        cpu.execute1();
+    #[test]
+    fn bus_cycle_callback_reports_each_bus_transaction() {
+        // 0x23c0,0x0000,0x0100 is MOVE.L D0, ($00000100).L
+        let mut cpu = TestCore::new_mem(0x40, &[0x23, 0xc0, 0x00, 0x00, 0x01, 0x00]);
+        let mut recorder = BusCycleRecorder::default();
+        let cycles = cpu.execute_with_state(1, &mut recorder);
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
+        // two prefetches of two words each, then the long write as two words
+        assert_eq!(vec![(0x40, false), (0x42, false), (0x44, false), (0x46, false), (0x100, true), (0x102, true)], recorder.cycles);
+        assert_eq!(Cycles(20), cycles);
     }
-    #[test]
-    fn add_8_er_pcix() {
-        // opcodes d03b, d23b, d43b, etc. followed by an extension word
-        // or more generally d[02468ace]3b
 
-        // where [02468ace] is DX (dest regno)
-        // opcodes d23b,9002 is ADD.B    (2, PC, A1), D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x3b, 0x90, 0x02]);
-        let addr = cpu.pc + 2; // will be +2 after reading instruction word
-        let index = 0x10;
-        let displacement = 2;
-        cpu.dar[8+1] = index;
-        let effective_addr = addr + index + displacement;
-        cpu.mem.write_byte(USER_DATA, effective_addr, 16);
-        cpu.dar[1] = 26;
-        cpu.execute1();
+    #[test]
+    fn bus_cycle_callback_leaves_out_internal_cycles() {
+        // 0xc300 is ABCD D0, D1, taking 6 cycles of which 2 are internal
+        let mut cpu = TestCore::new_mem(0x40, &[0xc3, 0x00]);
+        let mut recorder = BusCycleRecorder::default();
+        let cycles = cpu.execute_with_state(1, &mut recorder);
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
+        assert_eq!(vec![(0x40, false), (0x42, false)], recorder.cycles);
+        assert_eq!(Cycles(6), cycles);
     }
+
     #[test]
-    fn add_8_er_imm() {
-        // opcodes d03c, d23c, d43c, etc. followed by an extension word
-        // or more generally d[02468ace]3c
-        // where [02468ace] is DX (dest regno)
+    fn access_capture_lists_accesses_of_the_last_instruction_in_order() {
+        use super::{AccessType, BusAccess};
+        // 0x5250 is ADDQ.W #1, (A0) ; 0x4e71 is NOP
+        let mut cpu = TestCore::new_mem(0x40, &[0x52, 0x50, 0x4e, 0x71]);
+        cpu.dar[8] = 0x100;
+        cpu.set_access_capture(true);
+        cpu.execute1();
+        let access = |address_space, address, size, access_type| BusAccess { address_space, address, size, access_type };
+        assert_eq!(&[access(SUPERVISOR_PROGRAM, 0x40, 4, AccessType::Read),
+                     access(SUPERVISOR_DATA, 0x100, 2, AccessType::Read),
+                     access(SUPERVISOR_DATA, 0x100, 2, AccessType::Write)],
+                   cpu.last_instruction_accesses());
 
-        // opcodes d23c,0010 is ADD.B    #16, D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x3c, 0x00, 0x10]);
+        // the NOP was prefetched along with the ADDQ
+        cpu.execute1();
+        assert!(cpu.last_instruction_accesses().is_empty());
 
-        cpu.dar[1] = 26;
+        // 0xaaaa is a line 1010 opcode, so stacking and the vector fetch follow
         cpu.execute1();
+        let accesses = cpu.last_instruction_accesses();
+        assert_eq!(4, accesses.len());
+        assert_eq!(access(SUPERVISOR_PROGRAM, 0x44, 4, AccessType::Read), accesses[0]);
+        assert_eq!(access(SUPERVISOR_DATA, 10 * 4, 4, AccessType::Read), accesses[3]);
+        cpu.set_access_capture(false);
+        assert!(cpu.last_instruction_accesses().is_empty());
+        cpu.execute1();
+        assert!(cpu.last_instruction_accesses().is_empty());
+    }
 
-        // 16 + 26 is 42
-        assert_eq!(42, cpu.dar[1]);
+    #[derive(Default)]
+    struct FunctionCodeRecorder {
+        fcs: Vec<(u32, u32)>,
+        memory_cycles: usize,
     }
-    #[test]
-    fn add_16_re_pi() { //0xD400, 0xD700
-        let mut cpu = TestCore::new_mem(0x40, &[0xd3, 0x58]);
-        cpu.dar[8+0] = 0x40;
-        cpu.dar[1] = 0xa8;
 
-        cpu.execute1();
-        for op in cpu.mem.logger.ops() {
-            println!("{:?}", op);
+    impl Callbacks for FunctionCodeRecorder {
+        fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
+            Err(ex)
+        }
+        fn bus_cycle(&mut self, _address: u32, _is_write: bool) {
+            self.memory_cycles += 1;
+        }
+        fn bus_cycle_fc(&mut self, address_space: AddressSpace, address: u32, _is_write: bool) {
+            self.fcs.push((address_space.fc(), address));
         }
-        let word = cpu.read_data_word(64).unwrap();
-        assert_eq!(0xd358 + 0xa8, word);
     }
+
     #[test]
-    fn op_with_extension_word_moves_pc_past_extension_word() {
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x30, 0x90, 0xFE]);
-        cpu.execute1();
-        assert_eq!(0x44, cpu.pc);
+    fn bus_cycle_fc_reports_function_codes_and_interrupt_acknowledge() {
+        // 0x3010 is MOVE.W (A0), D0, executed in user mode
+        let mut cpu = TestCore::new_mem(0x40, &[0x30, 0x10]);
+        cpu.reset_to(0x400, 0x40);
+        cpu.sr_to_flags(0x0000);
+        cpu.dar[8] = 0x100;
+        let mut recorder = FunctionCodeRecorder::default();
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(vec![(2, 0x40), (2, 0x42), (1, 0x100)], recorder.fcs);
+
+        cpu.mem.write_long(SUPERVISOR_DATA, 26 * 4, 0x180);
+        cpu.int_ctrl.request_interrupt(2);
+        let mut recorder = FunctionCodeRecorder::default();
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(0x180, cpu.pc);
+        // the acknowledge cycle comes first, in CPU space with the level on A1-A3
+        assert_eq!((7, 0xfffff5), recorder.fcs[0]);
+        assert!(recorder.fcs[1..].iter().all(|&(fc, _)| fc == 5 || fc == 6));
+        // and is left out of the plain bus_cycle reports
+        assert_eq!(recorder.fcs.len() - 1, recorder.memory_cycles);
+    }
+
+    #[derive(Default)]
+    struct SelfModifyingWriteRecorder {
+        addresses: Vec<u32>,
+    }
+
+    impl Callbacks for SelfModifyingWriteRecorder {
+        fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
+            Err(ex)
+        }
+        fn self_modifying_write(&mut self, address: u32) {
+            self.addresses.push(address);
+        }
     }
 
     #[test]
-    fn status_register_roundtrip(){
-        let mut core = TestCore::new(0x40);
-        //Status register bits are:
-        //      TTSM_0iii_000X_NZVC;
-        let f=0b0000_1000_1110_0000; // these bits should always be zero
-        let s=0b0010_0000_0000_0000;
-        let i=0b0000_0111_0000_0000;
-        let x=0b0000_0000_0001_0000;
-        let n=0b0000_0000_0000_1000;
-        let z=0b0000_0000_0000_0100;
-        let v=0b0000_0000_0000_0010;
-        let c=0b0000_0000_0000_0001;
-        let flags = vec![x,n,z,v,c,f,s,i,0];
-        for sf in flags {
-            core.sr_to_flags(sf);
-            let sr = core.status_register();
-            let expected = if sf == f {0} else {sf};
-            assert_eq!(expected, sr);
+    fn writes_to_fetched_code_are_reported_when_enabled() {
+        // 0x3080 is MOVE.W D0, (A0), twice
+        let mut cpu = TestCore::new_mem(0x40, &[0x30, 0x80, 0x30, 0x80]);
+        cpu.reset_to(0x400, 0x40);
+        cpu.dar[8] = 0x42;
+        let mut recorder = SelfModifyingWriteRecorder::default();
+        cpu.execute_with_state(1, &mut recorder);
+        assert!(recorder.addresses.is_empty());
+
+        cpu.reset_to(0x400, 0x40);
+        cpu.enable_smc_detection();
+        cpu.dar[0] = 0x3080;
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(vec![0x42], recorder.addresses);
+
+        cpu.dar[8] = 0x100;
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(vec![0x42], recorder.addresses);
+
+        cpu.disable_smc_detection();
+        cpu.reset_to(0x400, 0x40);
+        cpu.dar[8] = 0x42;
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(vec![0x42], recorder.addresses);
+    }
+
+    #[derive(Default)]
+    struct UninitializedExecutionRecorder {
+        pcs: Vec<u32>,
+    }
+
+    impl Callbacks for UninitializedExecutionRecorder {
+        fn exception_callback(&mut self, _: &mut impl Core, ex: Exception) -> Result<Cycles> {
+            Err(ex)
+        }
+        fn execute_uninitialized(&mut self, pc: u32) {
+            self.pcs.push(pc);
         }
     }
+
     #[test]
-    fn clones_have_independent_registers() {
-        let mut core = TestCore::new(0x40);
-        core.dar[1] = 0x16;
-        let mut clone = core.clone();
-        assert_eq!(0x16, core.dar[1]);
-        assert_eq!(0x16, clone.dar[1]);
-        clone.dar[1] = 0x32;
-        assert_eq!(0x16, core.dar[1]);
-        assert_eq!(0x32, clone.dar[1]);
+    fn execution_of_uninitialized_memory_is_reported_when_enabled() {
+        // JMP $8000.L, where nothing was written
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0xf9, 0x00, 0x00, 0x80, 0x00]);
+        cpu.reset_to(0x400, 0x40);
+        let mut recorder = UninitializedExecutionRecorder::default();
+        cpu.execute_with_state(1, &mut recorder);
+        cpu.execute_with_state(1, &mut recorder);
+        assert!(recorder.pcs.is_empty());
+
+        cpu.reset_to(0x400, 0x40);
+        cpu.enable_uninitialized_execution_check(Vec::new());
+        cpu.execute_with_state(1, &mut recorder);
+        assert!(recorder.pcs.is_empty());
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(vec![0x8000], recorder.pcs);
+
+        recorder.pcs.clear();
+        cpu.reset_to(0x400, 0x40);
+        cpu.enable_uninitialized_execution_check(vec![0x40..0x42, 0xf0_0000..0x100_0000]);
+        cpu.execute_with_state(1, &mut recorder);
+        assert_eq!(vec![0x40], recorder.pcs);
+
+        cpu.disable_uninitialized_execution_check();
+        cpu.reset_to(0x400, 0x40);
+        cpu.execute_with_state(2, &mut recorder);
+        assert_eq!(vec![0x40], recorder.pcs);
+    }
+
+    /// Puts `vector` on the data bus during interrupt acknowledge.
+    struct VectoringBus {
+        mem: crate::ram::PagedMem,
+        vector: Option<u8>,
+        acknowledged: Vec<u8>,
+    }
+
+    impl crate::ram::BusWrapper for VectoringBus {
+        type Inner = crate::ram::PagedMem;
+        fn inner(&self) -> &crate::ram::PagedMem {
+            &self.mem
+        }
+        fn inner_mut(&mut self) -> &mut crate::ram::PagedMem {
+            &mut self.mem
+        }
+        fn interrupt_acknowledge(&mut self, level: u8) -> Option<u8> {
+            self.acknowledged.push(level);
+            self.vector
+        }
+    }
+
+    fn vectoring_core(vector: Option<u8>) -> super::ConfiguredCore<crate::interrupts::AutoInterruptController, VectoringBus> {
+        use super::ConfiguredCore;
+        use crate::interrupts::AutoInterruptController;
+        use crate::ram::PagedMem;
+        let mem = VectoringBus { mem: PagedMem::new(0), vector, acknowledged: Vec::new() };
+        let mut cpu = ConfiguredCore::new_with(0x40, AutoInterruptController::new(), mem);
+        cpu.reset_to(0x400, 0x40);
+        cpu.sr_to_flags(0x2000);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x40, 0x4e71);
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x40 * 4, 0x200);
+        cpu.mem.write_long(SUPERVISOR_DATA, 30 * 4, 0x300);
+        cpu
+    }
+
+    #[test]
+    fn interrupt_acknowledge_takes_vector_from_bus() {
+        let mut cpu = vectoring_core(Some(0x40));
+        cpu.int_ctrl.request_interrupt(6);
+        assert_eq!(Cycles(44), cpu.execute1());
+        assert_eq!(vec![6], cpu.mem.acknowledged);
+        assert_eq!(0x200, cpu.pc);
+        assert_eq!(0x2600, cpu.status_register());
+        // the controller has been acknowledged too, so the request is gone
+        assert_eq!(0, cpu.int_ctrl.highest_priority());
     }
 
     #[test]
-    fn user_mode_chk_16_pd_with_trap_uses_sp_correctly() {
-        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0xa7]); // 0x41a7 CHK.W -(A7), D0
-        cpu.write_data_long(super::EXCEPTION_CHK as u32 * 4, 0x1010).unwrap(); // set up exception vector 6
-        cpu.s_flag = super::SFLAG_CLEAR; // user mode
-        cpu.inactive_ssp = 0x200; // Supervisor stack at 0x200
-        cpu.dar[15] = 0x100; // User stack at 0x100
-        cpu.dar[0] = 0xF123; // negative, will cause a trap (vector 6) and enter supervisor mode
-
+    fn interrupt_acknowledge_without_bus_vector_autovectors() {
+        let mut cpu = vectoring_core(None);
+        cpu.int_ctrl.request_interrupt(6);
         cpu.execute1();
-        assert_eq!(0x1010, cpu.pc);
-        assert_eq!(super::SFLAG_SET, cpu.s_flag);
-        assert_eq!(0x100-2, cpu.inactive_usp); // check USP, decremented by A7 PD
-        assert_eq!(0x200-6, cpu.dar[15]); // check SSP
+        assert_eq!(vec![6], cpu.mem.acknowledged);
+        assert_eq!(0x300, cpu.pc);
     }
 
     #[test]
-    fn sr_to_flags_can_enter_user_mode_and_swap_stackpointers() {
-        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0xa7]); // 0x41a7 CHK.W -(A7), D0
-        cpu.s_flag = super::SFLAG_SET;
-        cpu.inactive_usp = 0x1000;
-        cpu.dar[15] = 0x2000;
-        assert_eq!(super::SFLAG_SET, cpu.s_flag);
-        assert_eq!(0x2000, cpu.ssp());
-        cpu.sr_to_flags(0); // User mode
-        assert_eq!(0x1000, cpu.usp());
-        assert_eq!(0x1000, cpu.dar[15]);
-        assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
-    }
+    fn verify_against_stops_at_first_divergence() {
+        use super::execlog::{Divergence, Field};
+        // MOVEQ #1, D0 ; ADDQ.L #1, D0 ; ADDQ.L #1, D0 ; STOP #$2700
+        let program = [0x70, 0x01, 0x52, 0x80, 0x52, 0x80, 0x4e, 0x72, 0x27, 0x00];
+        let mut cpu = TestCore::new_mem(0x40, &program);
+        let golden = cpu.record_execution(10);
+        assert_eq!(4, golden.len());
+
+        // the first ADDQ now adds 2
+        let mut cpu = TestCore::new_mem(0x40, &program);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x42, 0x5480);
+        assert_eq!(Err(Divergence { index: 1, field: Field::Opcode, expected: 0x5280, actual: 0x5480 }),
+                   cpu.verify_against(&golden));
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!(3, cpu.dar[0]);
 
-    #[test]
-    fn sr_to_flags_can_enter_supervisor_mode_and_swap_stackpointers() {
-        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0xa7]); // 0x41a7 CHK.W -(A7), D0
-        cpu.s_flag = super::SFLAG_CLEAR;
-        cpu.inactive_ssp = 0x1000;
-        cpu.dar[15] = 0x2000;
-        assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
-        assert_eq!(0x2000, cpu.usp());
-        cpu.sr_to_flags(0xffff); // Supa mode
-        assert_eq!(0x1000, cpu.ssp());
-        assert_eq!(0x1000, cpu.dar[15]);
-        assert_eq!(super::SFLAG_SET, cpu.s_flag);
+        // execution restarts from the recorded registers
+        let mut cpu = TestCore::new_mem(0x40, &program);
+        cpu.pc = 0x44;
+        cpu.dar[0] = 7;
+        cpu.sr_to_flags(0x0000);
+        assert_eq!(Ok(()), cpu.verify_against(&golden));
+        assert_eq!((0x4a, 3, 0x2700), (cpu.pc, cpu.dar[0], cpu.status_register()));
+
+        // a log running past the STOP
+        let mut longer = golden.clone();
+        longer.push(golden.entries[0]);
+        let mut cpu = TestCore::new_mem(0x40, &program);
+        assert_eq!(Err(Divergence { index: 4, field: Field::Length, expected: 5, actual: 4 }),
+                   cpu.verify_against(&longer));
     }
 
     #[test]
-    fn core_can_stop() {
-        let initial_pc = 0x40;
-        let mut cpu = TestCore::new_mem_init(initial_pc, &[0x4e, 0x72, 0x00, 0x00], opcodes::OP_NOP);
-        cpu.sr_to_flags(0xffff); // Supa mode
+    fn trampoline_runs_handler_and_returns() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        // 4eb8,2000 is JSR $2000.W, twice
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0xb8, 0x20, 0x00, 0x4e, 0xb8, 0x20, 0x00]);
+        cpu.dar[15] = 0x400;
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        cpu.set_trampoline(0x2000, Box::new(move |core: &mut TestCore| {
+            counter.set(counter.get() + 1);
+            core.dar[0] = core.dar[15];
+            core.mem.write_byte(SUPERVISOR_DATA, 0x3000, 0x55);
+        }));
+
+        assert_eq!(Cycles(18), cpu.execute1());
+        assert_eq!(0x2000, cpu.pc);
+        assert_eq!(Cycles(16), cpu.execute1());
+        assert_eq!(1, calls.get());
+        assert_eq!(0x44, cpu.pc);
+        assert_eq!(0x400, cpu.dar[15]);
+        // the handler saw the return address on the stack
+        assert_eq!(0x400 - 4, cpu.dar[0]);
+        assert_eq!(0x55, cpu.mem.read_byte(SUPERVISOR_DATA, 0x3000));
+
+        // without the trampoline, the code at $2000 runs
+        cpu.remove_trampoline(0x2000);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x2000, 0x4e71);
         cpu.execute1();
-        assert_eq!(0x0000, cpu.status_register());
-        let next_instruction = initial_pc + 2 + 2; // 40 + instruction word + immediate word
-        assert_eq!(next_instruction, cpu.pc);
-        // continue executing some cycles
-        let chunk_of_cycles = 400;
-        let Cycles(consumed) = cpu.execute(chunk_of_cycles);
-        assert_eq!(chunk_of_cycles, consumed);
-        // but as the cpu is stopped, pc should still point
-        // to the next instruction
-        assert_eq!(next_instruction, cpu.pc);
+        cpu.execute1();
+        assert_eq!(0x2002, cpu.pc);
+        assert_eq!(1, calls.get());
     }
 
-    use crate::interrupts::InterruptController;
     #[test]
-    fn reset_calls_interrupt_controller() {
-        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x70]); // 0x4e70 RESET
-        cpu.int_ctrl.request_interrupt(5);
-        cpu.execute1(); // will execute RESET, which will reset all IRQs
-        assert_eq!(0, cpu.int_ctrl.highest_priority());
+    fn trampoline_can_remove_or_replace_itself() {
+        // 4eb8,2000 is JSR $2000.W, three times; 0x7001 is MOVEQ #1, D0 and 0x4e75 is RTS
+        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0xb8, 0x20, 0x00, 0x4e, 0xb8, 0x20, 0x00, 0x4e, 0xb8, 0x20, 0x00]);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x2000, 0x7001);
+        cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x2002, 0x4e75);
+        cpu.dar[15] = 0x400;
+        cpu.set_trampoline(0x2000, Box::new(|core: &mut TestCore| {
+            core.dar[0] = 2;
+            core.set_trampoline(0x2000, Box::new(|core: &mut TestCore| {
+                core.dar[0] = 3;
+                core.remove_trampoline(0x2000);
+            }));
+        }));
+
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!((0x44, 2), (cpu.pc, cpu.dar[0]));
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!((0x48, 3), (cpu.pc, cpu.dar[0]));
+        // the code at $2000 runs once the replacement has removed itself
+        cpu.execute1();
+        cpu.execute1();
+        cpu.execute1();
+        assert_eq!((0x4c, 1), (cpu.pc, cpu.dar[0]));
     }
 
     #[test]
-    fn processing_state_is_known_in_g2_exception_handler() {
-        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0x90]); // 0x4190 CHK.W (A0), D0
-        cpu.write_data_long(super::EXCEPTION_CHK as u32 * 4, 0x1010).unwrap(); // set up exception vector 6
-        cpu.write_data_word(0x1010, opcodes::OP_RTE_32).unwrap(); // handler is just RTE
-        cpu.s_flag = super::SFLAG_CLEAR; // user mode
-        cpu.inactive_ssp = 0x200; // Supervisor stack at 0x200
-        cpu.dar[15] = 0x100; // User stack at 0x100
-        cpu.dar[0] = 0xF123; // negative, will cause a trap (vector 6) and enter the handler in supervisor mode
+    fn opcode_override_can_remove_or_replace_itself() {
+        // 0x5240 is ADDQ.W #1, D0, three times
+        let mut cpu = TestCore::new_mem(0x40, &[0x52, 0x40, 0x52, 0x40, 0x52, 0x40]);
+        cpu.override_opcode(0x5240, Box::new(|core: &mut TestCore| {
+            core.dar[0] += 10;
+            core.override_opcode(0x5240, Box::new(|core: &mut TestCore| {
+                core.dar[0] += 100;
+                core.clear_opcode_overrides();
+                Cycles(4)
+            }));
+            Cycles(4)
+        }));
 
         cpu.execute1();
-        assert_eq!(0x1010, cpu.pc);
-        assert_eq!(super::SFLAG_SET, cpu.s_flag);
-        assert_eq!(super::ProcessingState::Group2Exception, cpu.processing_state);
-        assert_eq!(0x200-6, cpu.dar[15]); // check SSP
+        assert_eq!(10, cpu.dar[0]);
+        cpu.execute1();
+        assert_eq!(110, cpu.dar[0]);
+        cpu.execute1();
+        assert_eq!(111, cpu.dar[0]);
+    }
 
-        cpu.execute1(); // will execute RTE
-        // which should return to user mode, instruction after CHK, normal state
-        assert_eq!(0x42, cpu.pc);
-        assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
-        assert_eq!(super::ProcessingState::Normal, cpu.processing_state);
+    #[test]
+    fn opcode_override_replaces_built_in_handler() {
+        // 0x5240 is ADDQ.W #1, D0, followed by an extension word for the override
+        let mut cpu = TestCore::new_mem(0x40, &[0x52, 0x40, 0x12, 0x34, 0x52, 0x40]);
+        cpu.override_opcode(0x5240, Box::new(|core: &mut TestCore| {
+            assert_eq!(0x5240, core.ir);
+            core.dar[0] = u32::from(core.read_imm_u16().unwrap());
+            Cycles(10)
+        }));
+
+        assert_eq!(Cycles(10), cpu.execute1());
+        assert_eq!(0x1234, cpu.dar[0]);
+        assert_eq!(0x44, cpu.pc);
+
+        cpu.remove_opcode_override(0x5240);
+        assert_eq!(Cycles(4), cpu.execute1());
+        assert_eq!(0x1235, cpu.dar[0]);
     }
 
     #[test]
-    fn processing_state_is_known_in_g1_exception_handler() {
-        // real illegal instruction = 0x4afc, but any illegal instruction should work
-        let mut cpu = TestCore::new_mem(0x40, &[0x4a, 0xfc]);
-        cpu.write_data_long(super::EXCEPTION_ILLEGAL_INSTRUCTION as u32 * 4, 0x1010).unwrap(); // set up exception vector
-        cpu.write_data_word(0x1010, opcodes::OP_RTE_32).unwrap(); // handler is just RTE
-        cpu.s_flag = super::SFLAG_CLEAR; // user mode
-        cpu.inactive_ssp = 0x200; // Supervisor stack at 0x200
-        cpu.dar[15] = 0x100; // User stack at 0x100
+    fn trampoline_lets_pending_interrupt_go_first() {
+        let mut cpu = TestCore::new_mem(0x2000, &[0x4e, 0x71]);
+        cpu.dar[15] = 0x400;
+        cpu.sr_to_flags(0x2000);
+        cpu.mem.write_long(SUPERVISOR_DATA, 27 * 4, 0x180);
+        cpu.set_trampoline(0x2000, Box::new(|core: &mut TestCore| core.dar[0] = 1));
+        cpu.int_ctrl.request_interrupt(3);
+        assert_eq!(Cycles(44), cpu.execute1());
+        assert_eq!(0x180, cpu.pc);
+        assert_eq!(0x2000, stacked_pc(&cpu));
+        assert_eq!(0, cpu.dar[0]);
+    }
 
-        cpu.execute1(); // will execute the illegal instruction and enter the handler in supervisor mode
-        assert_eq!(0x1010, cpu.pc);
-        assert_eq!(super::SFLAG_SET, cpu.s_flag);
-        assert_eq!(super::ProcessingState::Group1Exception, cpu.processing_state);
+    #[test]
+    fn step_back_restores_registers_and_memory_across_snapshots() {
+        use super::Cpu;
+        use super::reverse::ReverseError;
+        let mut cpu = Cpu::new(0);
+        // loop: ADDQ.L #1, D0 ; MOVE.L D0, -(A0) ; BRA.B loop
+        cpu.load_and_run(&[0x52, 0x80, 0x21, 0x00, 0x60, 0xfa], 0x1000, 0x1000, 0);
+        cpu.dar[8] = 0x3000;
+        assert_eq!(Err(ReverseError::NotEnabled), cpu.step_back());
+        cpu.enable_reverse_debugging(4);
+        assert_eq!(Err(ReverseError::NoHistory), cpu.step_back());
+
+        let mut states = vec![(cpu.pc, cpu.dar)];
+        for _ in 0..30 {
+            cpu.execute1();
+            states.push((cpu.pc, cpu.dar));
+        }
+        assert_eq!(10, cpu.dar[0]);
+        for expected in states[..30].iter().rev().take(11) {
+            cpu.step_back().unwrap();
+            assert_eq!(*expected, (cpu.pc, cpu.dar));
+        }
+        // back before the MOVE of the seventh loop, so its store is undone
+        assert_eq!(7, cpu.dar[0]);
+        assert_eq!(6, cpu.mem.read_long(SUPERVISOR_DATA, cpu.dar[8]));
+        assert_eq!(0, cpu.mem.read_long(SUPERVISOR_DATA, cpu.dar[8] - 4));
 
-        cpu.execute1(); // will execute RTE
-        // which should return to user mode, faulting instruction, normal state
-        assert_eq!(0x40, cpu.pc);
-        assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
-        assert_eq!(super::ProcessingState::Normal, cpu.processing_state);
+        // and execution can continue from there
+        cpu.execute1();
+        assert_eq!(states[20], (cpu.pc, cpu.dar));
     }
 
     #[test]
-    fn processing_state_is_known_in_g0_exception_handler() {
-        let mut cpu = TestCore::new_mem(0x40, &[0x41, 0xa0]); // 0x41a0 CHK.W -(A0), D0
-        cpu.write_data_long(super::EXCEPTION_ADDRESS_ERROR as u32 * 4, 0x1010).unwrap(); // set up exception vector
-        cpu.write_data_word(0x1010, 0x504F).unwrap(); // handler is ADD #8, A7
-        cpu.write_data_word(0x1012, opcodes::OP_RTE_32).unwrap(); // followed by RTE
+    fn step_back_restores_exception_nesting() {
+        use super::{Cpu, ProcessingState};
+        // 0x4e40 is TRAP #0, its vector pointing back at it
+        let mut cpu = Cpu::new(0);
+        cpu.load_and_run(&[0x4e, 0x40], 0x1000, 0x1000, 0);
+        cpu.mem.write_long(SUPERVISOR_DATA, 32 * 4, 0x1000);
+        cpu.set_max_exception_nesting(Some(3));
+        cpu.enable_reverse_debugging(2);
+        for _ in 0..3 {
+            cpu.execute1();
+        }
+        let nested = (cpu.dar[15], cpu.exception_nesting);
+        assert_eq!((0x1000 - 3 * 6, 3), nested);
 
-        cpu.s_flag = super::SFLAG_CLEAR; // user mode
-        cpu.inactive_ssp = 0x200; // Supervisor stack at 0x200
-        cpu.dar[15] = 0x100; // User stack at 0x100
-        cpu.dar[8] = 0x0023; // odd, will cause an address error exception and enter the handler in supervisor mode
+        // replaying the first trap from the snapshot taken before it
+        cpu.step_back().unwrap();
+        cpu.step_back().unwrap();
+        assert_eq!((0x1000 - 6, 1), (cpu.dar[15], cpu.exception_nesting));
+        cpu.execute1();
+        cpu.execute1();
+        assert_ne!(ProcessingState::Halted, cpu.processing_state);
+        assert_eq!(nested, (cpu.dar[15], cpu.exception_nesting));
+    }
 
+    #[test]
+    fn step_back_is_limited_to_snapshots_kept() {
+        use super::Cpu;
+        use super::reverse::{ReverseError, SNAPSHOTS_KEPT};
+        let mut cpu = Cpu::new(0);
+        // NOP ; BRA.B back to the NOP
+        cpu.load_and_run(&[0x4e, 0x71, 0x60, 0xfc], 0x1000, 0x1000, 0);
+        cpu.enable_reverse_debugging(1);
+        for _ in 0..SNAPSHOTS_KEPT + 10 {
+            cpu.execute1();
+        }
+        for _ in 0..SNAPSHOTS_KEPT {
+            cpu.step_back().unwrap();
+        }
+        assert_eq!(Err(ReverseError::NoHistory), cpu.step_back());
+        // a failed step back leaves the core where it was
+        assert_eq!(0x1000, cpu.pc);
         cpu.execute1();
-        assert_eq!(0x1010, cpu.pc);
-        assert_eq!(0x200-14, cpu.dar[15]);
-        assert_eq!(super::SFLAG_SET, cpu.s_flag);
-        assert_eq!(super::ProcessingState::Group0Exception, cpu.processing_state);
+        assert_eq!(0x1002, cpu.pc);
+        cpu.step_back().unwrap();
+        assert_eq!(0x1000, cpu.pc);
+    }
 
-        cpu.execute1(); // will execute ADD #8, A7 (to skip g0 stuff in stack frame)
-        assert_eq!(0x1012, cpu.pc);
-        assert_eq!(0x200-6, cpu.dar[15]);
+    #[test]
+    fn x_flag_is_preserved_by_every_instruction_not_writing_it() {
+        use super::Cpu;
+        use super::effects::{register_effects, CCR_X};
+        use crate::ram::PagedMem;
+        let mut cpu = Cpu::new(0x1000);
+        let mut preserving = 0;
+        for opcode in super::ops::implemented_opcodes() {
+            for &(contents, x) in &[(0, 0x10), (0x0002_0002, 0x10), (0x0002_0002, 0)] {
+                cpu.mem = PagedMem::new(contents);
+                cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x1000, u32::from(opcode));
+                if register_effects(&cpu.mem, SUPERVISOR_PROGRAM, 0x1000).ccr_written & CCR_X != 0 {
+                    break;
+                }
+                cpu.dar = [contents; 16];
+                cpu.reset_to(0x4000, 0x1000);
+                // every other flag is the inverse of X
+                cpu.sr_to_flags(0x2700 | x | (!x >> 4 & 0xf));
+                cpu.execute1();
+                assert_eq!(x, cpu.status_register() & 0x10, "opcode {:04x} ({}) changed X", opcode, super::ops::handler_name(opcode));
+                preserving += 1;
+            }
+        }
+        assert!(preserving > 3 * 30_000);
+    }
 
-        cpu.execute1(); // will execute RTE
-        // which should return to user mode, after faulted instruction, normal state
-        assert_eq!(0x42, cpu.pc); // this is what address errors stack in Musashi
-        assert_eq!(super::SFLAG_CLEAR, cpu.s_flag);
-        assert_eq!(super::ProcessingState::Normal, cpu.processing_state);
+    #[test]
+    fn byte_and_word_operations_leave_upper_data_register_bits_alone() {
+        // (program, D0 after), starting from D0 = 0x12345678 and D1 = 1
+        let cases: [(&[u8], u32); 10] = [
+            (&[0x30, 0x3c, 0x00, 0x00], 0x1234_0000), // MOVE.W #0, D0
+            (&[0x10, 0x01], 0x1234_5601),             // MOVE.B D1, D0
+            (&[0xd0, 0x01], 0x1234_5679),             // ADD.B D1, D0
+            (&[0x06, 0x40, 0xa9, 0x88], 0x1234_0000), // ADDI.W #$a988, D0 carries
+            (&[0x42, 0x40], 0x1234_0000),             // CLR.W D0
+            (&[0x46, 0x00], 0x1234_5687),             // NOT.B D0
+            (&[0x44, 0x00], 0x1234_5688),             // NEG.B D0
+            (&[0xe3, 0x48], 0x1234_acf0),             // LSL.W #1, D0
+            (&[0xe3, 0x18], 0x1234_56f0),             // ROL.B #1, D0
+            (&[0x48, 0x80], 0x1234_0078),             // EXT.W D0
+        ];
+        for (program, expected) in cases.iter() {
+            let mut cpu = TestCore::new_mem(0x40, program);
+            cpu.dar[0] = 0x1234_5678;
+            cpu.dar[1] = 1;
+            cpu.execute1();
+            assert_eq!(*expected, cpu.dar[0], "{:02x?}", program);
+        }
     }
 
     #[test]
-    fn stop_changes_processing_state() {
-        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x72]); // 0x4e72 STOP
-        cpu.execute1(); // will execute STOP
-        assert_eq!(super::ProcessingState::Stopped, cpu.processing_state);
+    fn logical_immediates_set_nz_clear_vc_and_keep_x() {
+        const N: u16 = 0b01000;
+        // (program, D0 before, D0 after, CCR after), starting from CCR = X|V|C
+        let cases: [(&[u8], u32, u32, u16); 9] = [
+            (&[0x02, 0x00, 0x00, 0x0f], 0x1234_56f0, 0x1234_5600, X | Z),       // ANDI.B #$0f, D0
+            (&[0x02, 0x40, 0x80, 0x00], 0xffff_ffff, 0xffff_8000, X | N),       // ANDI.W #$8000, D0
+            (&[0x02, 0x80, 0xff, 0xff, 0x00, 0x00], 0x0000_ffff, 0, X | Z),     // ANDI.L #$ffff0000, D0
+            (&[0x00, 0x00, 0x00, 0x80], 0x1234_5600, 0x1234_5680, X | N),       // ORI.B #$80, D0
+            (&[0x00, 0x40, 0x00, 0x00], 0x1234_0000, 0x1234_0000, X | Z),       // ORI.W #0, D0
+            (&[0x00, 0x80, 0x00, 0x00, 0x00, 0x01], 0x8000_0000, 0x8000_0001, X | N), // ORI.L #1, D0
+            (&[0x0a, 0x00, 0x00, 0xff], 0x1234_56ff, 0x1234_5600, X | Z),       // EORI.B #$ff, D0
+            (&[0x0a, 0x40, 0xff, 0xff], 0x1234_7fff, 0x1234_8000, X | N),       // EORI.W #$ffff, D0
+            (&[0x0a, 0x80, 0x12, 0x34, 0x56, 0x78], 0x1234_5678, 0, X | Z),     // EORI.L #$12345678, D0
+        ];
+        for (program, before, after, ccr) in cases.iter() {
+            let mut cpu = TestCore::new_mem(0x40, program);
+            cpu.dar[0] = *before;
+            cpu.sr_to_flags(0x2713);
+            cpu.execute1();
+            assert_eq!(*after, cpu.dar[0], "{:02x?}", program);
+            assert_eq!(*ccr, cpu.condition_code_register(), "{:02x?}", program);
+        }
+
+        // memory destinations are masked to the operand size too
+        // ANDI.B #$0f, (A0) ; ORI.W #$8000, (A0)
+        let mut cpu = TestCore::new_mem(0x40, &[0x02, 0x10, 0x00, 0x0f, 0x00, 0x50, 0x80, 0x00]);
+        cpu.dar[8] = 0x100;
+        cpu.mem.write_long(SUPERVISOR_DATA, 0x100, 0xf0ff_1234);
+        cpu.sr_to_flags(0x2713);
+        cpu.execute1();
+        assert_eq!(0x00ff_1234, cpu.mem.read_long(SUPERVISOR_DATA, 0x100));
+        assert_eq!(X | Z, cpu.condition_code_register());
+        cpu.execute1();
+        assert_eq!(0x80ff_1234, cpu.mem.read_long(SUPERVISOR_DATA, 0x100));
+        assert_eq!(X | N, cpu.condition_code_register());
     }
 
     #[test]
-    fn interrupt_can_trigger_from_stopped_state() {
-        let mut cpu = TestCore::new_mem(0x40, &[0x4e, 0x72]); // 0x4e72 STOP
-        let supervisor_bit = 1 << 13;
-        let irq_mask = 0;
-        cpu.sr_to_flags(supervisor_bit | irq_mask);
-        let vec4handler = 0x2F0000;
-        let autovector_base = 24;
-        let irq = 5;
-        cpu.mem.write_long(SUPERVISOR_PROGRAM, (autovector_base + irq) * 4 , vec4handler);
-        // opcodes d278,0108 is ADD.W    $0108, D1
-        cpu.mem.write_long(SUPERVISOR_PROGRAM, vec4handler, 0xd2780108);
-        cpu.execute1(); // will execute STOP
-        assert_eq!(super::ProcessingState::Stopped, cpu.processing_state);
-        cpu.int_ctrl.request_interrupt(irq as u8);
-        cpu.execute1(); // will trigger interrupt handler
-        assert_eq!(super::ProcessingState::Group1Exception, cpu.processing_state);
-        assert_eq!(vec4handler, cpu.pc);
-        cpu.execute1(); // should execute first instruction of handler, which is 4 bytes
-        assert_eq!(vec4handler + 4, cpu.pc);
+    fn bit_instructions_set_z_from_the_bit_before_changing_it() {
+        const N: u16 = 0b01000;
+        const V: u16 = 0b00010;
+        // (program, D0 before, D0 after, Z set), starting from CCR = X|N|V|C
+        let cases: [(&[u8], u32, u32, bool); 8] = [
+            (&[0x08, 0xc0, 0x00, 0x03], 0x0000_0000, 0x0000_0008, true),   // BSET #3, D0
+            (&[0x08, 0xc0, 0x00, 0x03], 0x0000_0008, 0x0000_0008, false),  // BSET #3, D0
+            (&[0x08, 0x80, 0x00, 0x1f], 0x8000_0001, 0x0000_0001, false),  // BCLR #31, D0
+            (&[0x08, 0x80, 0x00, 0x1f], 0x0000_0001, 0x0000_0001, true),   // BCLR #31, D0
+            (&[0x08, 0x40, 0x00, 0x00], 0x0000_0001, 0x0000_0000, false),  // BCHG #0, D0
+            (&[0x08, 0x40, 0x00, 0x00], 0x0000_0000, 0x0000_0001, true),   // BCHG #0, D0
+            (&[0x08, 0x00, 0x00, 0x23], 0x0000_0008, 0x0000_0008, false),  // BTST #35, D0 (bit 3)
+            (&[0x08, 0x00, 0x00, 0x04], 0x0000_0008, 0x0000_0008, true),   // BTST #4, D0
+        ];
+        for (program, before, after, z) in cases.iter() {
+            let mut cpu = TestCore::new_mem(0x40, program);
+            cpu.dar[0] = *before;
+            cpu.sr_to_flags(0x271b);
+            cpu.execute1();
+            assert_eq!(*after, cpu.dar[0], "{:02x?} {:08x}", program, before);
+            let ccr = X | N | V | C | if *z { Z } else { 0 };
+            assert_eq!(ccr, cpu.condition_code_register(), "{:02x?} {:08x}", program, before);
+        }
+
+        // memory operands are bytes, bit numbers modulo 8
+        // BSET D1, (A0) ; BCHG D1, (A0) ; BTST D1, (A0)
+        let mut cpu = TestCore::new_mem(0x40, &[0x03, 0xd0, 0x03, 0x50, 0x03, 0x10]);
+        cpu.dar[1] = 10;
+        cpu.dar[8] = 0x100;
+        cpu.mem.write_byte(SUPERVISOR_DATA, 0x100, 0x00);
+        cpu.sr_to_flags(0x2700);
+        cpu.execute1();
+        assert_eq!(0x04, cpu.mem.read_byte(SUPERVISOR_DATA, 0x100));
+        assert_eq!(Z, cpu.condition_code_register());
+        cpu.execute1();
+        assert_eq!(0x00, cpu.mem.read_byte(SUPERVISOR_DATA, 0x100));
+        assert_eq!(0, cpu.condition_code_register());
+        cpu.execute1();
+        assert_eq!(Z, cpu.condition_code_register());
     }
 
     #[test]
-    fn pending_interrupt_check_does_not_change_state() {
-        // opcodes d200 is ADD.B    D0, D1
-        let mut cpu = TestCore::new_mem(0x40, &[0xd2, 0x00]);
-        let supervisor_bit = 1 << 13;
-        let irq_mask = 0;
-        cpu.sr_to_flags(supervisor_bit | irq_mask);
-        cpu.int_ctrl.request_interrupt(4);
-        assert_eq!(Some(4), cpu.pending_interrupt());
-        assert_eq!(Some(4), cpu.pending_interrupt());
-        // but is cleared after initiating interrupt handling
-        assert_eq!(Cycles(44), cpu.execute1());
-        assert_eq!(None, cpu.pending_interrupt());
+    fn neg_sets_v_on_the_most_negative_value_and_xc_on_nonzero() {
+        const N: u16 = 0b01000;
+        const V: u16 = 0b00010;
+        // (opcode, D0 before, D0 after, CCR after), starting from CCR = X|C
+        let cases = [
+            (0x4400, 0x1234_5680, 0x1234_5680, X | N | V | C),   // NEG.B D0
+            (0x4400, 0x1234_5600, 0x1234_5600, Z),
+            (0x4400, 0x1234_5601, 0x1234_56ff, X | N | C),
+            (0x4400, 0x1234_56ff, 0x1234_5601, X | C),
+            (0x4440, 0x1234_8000, 0x1234_8000, X | N | V | C),   // NEG.W D0
+            (0x4440, 0x1234_0000, 0x1234_0000, Z),
+            (0x4440, 0x1234_0001, 0x1234_ffff, X | N | C),
+            (0x4440, 0x1234_ffff, 0x1234_0001, X | C),
+            (0x4480, 0x8000_0000, 0x8000_0000, X | N | V | C),   // NEG.L D0
+            (0x4480, 0x0000_0000, 0x0000_0000, Z),
+            (0x4480, 0x0000_0001, 0xffff_ffff, X | N | C),
+            (0x4480, 0x7fff_ffff, 0x8000_0001, X | N | C),
+        ];
+        for &(opcode, before, after, ccr) in cases.iter() {
+            let mut cpu = TestCore::new_mem(0x40, &[(opcode >> 8) as u8, opcode as u8]);
+            cpu.dar[0] = before;
+            cpu.sr_to_flags(0x2711);
+            cpu.execute1();
+            assert_eq!(after, cpu.dar[0], "{:04x} {:08x}", opcode, before);
+            assert_eq!(ccr, cpu.condition_code_register(), "{:04x} {:08x}", opcode, before);
+        }
     }
 
     #[test]
-    fn can_enter_halted_state() {
-        // halted state is entered when a second group 0 exception
-        // (except external reset) occurs in a group 0 exception handler
-        let mut cpu = TestCore::new_mem(0x41, &[0xd2, 0x00]); // d200 is ADD.B D0, D1
-        let address_error_handler = 0x2F0001;
-        cpu.mem.write_long(SUPERVISOR_PROGRAM, super::EXCEPTION_ADDRESS_ERROR as u32 * 4, address_error_handler);
-        // opcodes d278,0108 is ADD.W    $0108, D1
-        cpu.execute1(); // will execute at an odd address, invoking the address error exception handler
-        assert_eq!(super::ProcessingState::Group0Exception, cpu.processing_state);
-        cpu.execute1(); // will execute the handler at an odd address,
-        // this should trigger a second address error, which should halt the processor
-        assert_eq!(super::ProcessingState::Halted, cpu.processing_state);
-        // in the halted state, the only way to win, is not to play.
-        // An external reset is needed.
+    fn asl_sets_v_if_the_sign_changes_at_any_step() {
+        const N: u16 = 0b01000;
+        const V: u16 = 0b00010;
+        // (opcode, D0 before, D0 after, CCR after), starting from CCR = 0, D1 = 2
+        let cases = [
+            (0xe700, 0x1234_5620, 0x1234_5600, X | Z | V | C),   // ASL.B #3, D0: 0 -> 1 -> 0
+            (0xe500, 0x1234_5640, 0x1234_5600, X | Z | V | C),   // ASL.B #2, D0
+            (0xe300, 0x1234_5640, 0x1234_5680, N | V),           // ASL.B #1, D0
+            (0xe500, 0x1234_56e0, 0x1234_5680, X | N | C),       // ASL.B #2, D0: sign stays 1
+            (0xe740, 0x1234_2000, 0x1234_0000, X | Z | V | C),   // ASL.W #3, D0
+            (0xe380, 0x6000_0000, 0xc000_0000, N | V),           // ASL.L #1, D0
+            (0xe3a0, 0x2000_0000, 0x8000_0000, N | V),           // ASL.L D1, D0
+            (0xe3a0, 0xe000_0001, 0x8000_0004, X | N | C),       // ASL.L D1, D0
+            (0xe708, 0x1234_5620, 0x1234_5600, X | Z | C),       // LSL.B #3, D0 never sets V
+        ];
+        for &(opcode, before, after, ccr) in cases.iter() {
+            let mut cpu = TestCore::new_mem(0x40, &[(opcode >> 8) as u8, opcode as u8]);
+            cpu.dar[0] = before;
+            cpu.dar[1] = 2;
+            cpu.sr_to_flags(0x2700);
+            cpu.execute1();
+            assert_eq!(after, cpu.dar[0], "{:04x} {:08x}", opcode, before);
+            assert_eq!(ccr, cpu.condition_code_register(), "{:04x} {:08x}", opcode, before);
+        }
     }
 
     #[test]
-    fn nmi_has_no_effect_in_halted_state() {
-        let mut cpu = TestCore::new_mem(0x41, &[0x4e, 0x72]); // 0x4e72 STOP
-        cpu.processing_state = super::ProcessingState::Halted;
+    fn ext_sign_extends_and_sets_nz_clearing_vc() {
+        const N: u16 = 0b01000;
+        // (opcode, D0 before, D0 after, CCR after), starting from CCR = X|V|C
+        let cases: [(&[u8], u32, u32, u16); 6] = [
+            (&[0x48, 0x80], 0x1234_5680, 0x1234_ff80, X | N), // EXT.W D0
+            (&[0x48, 0x80], 0xffff_ff7f, 0xffff_007f, X),
+            (&[0x48, 0x80], 0x1234_ff00, 0x1234_0000, X | Z),
+            (&[0x48, 0xc0], 0x1234_8000, 0xffff_8000, X | N), // EXT.L D0
+            (&[0x48, 0xc0], 0xffff_7fff, 0x0000_7fff, X),
+            (&[0x48, 0xc0], 0xabcd_0000, 0, X | Z),
+        ];
+        for (program, before, after, ccr) in cases.iter() {
+            let mut cpu = TestCore::new_mem(0x40, program);
+            cpu.dar[0] = *before;
+            cpu.sr_to_flags(0x2713);
+            assert_eq!(Cycles(4), cpu.execute1());
+            assert_eq!(*after, cpu.dar[0], "{:02x?} {:08x}", program, before);
+            assert_eq!(*ccr, cpu.condition_code_register(), "{:02x?} {:08x}", program, before);
+        }
+    }
 
-        // Normally this would trigger a NMI, but should not in halted state
-        cpu.int_ctrl.request_interrupt(7);
-        cpu.execute1(); // will do nothing
-        assert_eq!(super::ProcessingState::Halted, cpu.processing_state);
+    #[test]
+    fn divs_remainder_takes_the_sign_of_the_dividend() {
+        const N: u16 = 0b01000;
+        // (dividend, divisor, remainder:quotient, CCR), from MC68000 results
+        let cases = [
+            (7u32, 2u32, 0x0001_0003u32, 0),
+            (-7i32 as u32, 2, 0xffff_fffd, N),
+            (7, 0xfffe, 0x0001_fffd, N),
+            (-7i32 as u32, 0xfffe, 0xffff_0003, 0),
+            (-8i32 as u32, 2, 0x0000_fffc, N),
+        ];
+        for (dividend, divisor, result, ccr) in cases.iter() {
+            // 0x81c1 is DIVS D1, D0
+            let mut cpu = TestCore::new_mem(0x40, &[0x81, 0xc1]);
+            cpu.dar[0] = *dividend;
+            cpu.dar[1] = *divisor;
+            cpu.sr_to_flags(0x2713);
+            cpu.execute1();
+            assert_eq!(*result, cpu.dar[0], "{} / {}", *dividend as i32, *divisor as i16);
+            // X is kept, V and C are cleared
+            assert_eq!(X | ccr, cpu.condition_code_register(), "{} / {}", *dividend as i32, *divisor as i16);
+        }
     }
 
-    use crate::cpu::{Result, Callbacks, Exception, Core};
+    #[test]
+    fn byte_and_word_instructions_never_touch_upper_data_register_bits() {
+        use super::Cpu;
+        use crate::ram::PagedMem;
+        let mut cpu = Cpu::new(0x1000);
+        let mut checked = 0;
+        for opcode in super::ops::implemented_opcodes() {
+            let name = super::ops::handler_name(opcode);
+            let mut parts = name.split('_');
+            let mnemonic = parts.next().unwrap_or("");
+            // multiplications and divisions have long results, and MOVEM.W
+            // sign-extends what it loads into a register
+            let upper = match (mnemonic, parts.next()) {
+                ("muls" | "mulu" | "divs" | "divu", _) => continue,
+                ("movem", _) => continue,
+                ("ext", Some("bw")) => 0xffff_0000,
+                (_, Some("8")) => 0xffff_ff00,
+                (_, Some("16")) => 0xffff_0000,
+                _ => continue,
+            };
+            for &contents in &[0, 0x0002_0002] {
+                cpu.mem = PagedMem::new(contents);
+                cpu.mem.write_word(SUPERVISOR_PROGRAM, 0x1000, u32::from(opcode));
+                cpu.dar = [contents; 16];
+                for d in 0..8 {
+                    cpu.dar[d] = 0x1234_5678 ^ (d as u32) << 28;
+                }
+                cpu.reset_to(0x4000, 0x1000);
+                cpu.execute1();
+                for d in 0..8 {
+                    assert_eq!((0x1234_5678 ^ (d as u32) << 28) & upper, cpu.dar[d] & upper,
+                               "opcode {:04x} ({}) changed the upper bits of D{}", opcode, name, d);
+                }
+                checked += 1;
+            }
+        }
+        assert!(checked > 2 * 20_000);
+    }
 
-    struct CustomExceptionHandler
-    {
-        suppress: bool,
-        count: isize,
-        ex: Option<Exception>
+    #[test]
+    fn x_flag_is_updated_by_arithmetic_shift_and_extend_instructions() {
+        // (opcode, D0, D1, X after), each run with X set and clear before;
+        // for ADDX, SUBX, NEGX and ABCD the X taken in doesn't change X out
+        let cases: [(&[u8], u32, u32, u16); 14] = [
+            (&[0xd0, 0x81], 0xffff_ffff, 1, X),     // ADD.L D1, D0 carries
+            (&[0xd0, 0x81], 1, 1, 0),               // ADD.L D1, D0
+            (&[0x90, 0x81], 0, 1, X),               // SUB.L D1, D0 borrows
+            (&[0x52, 0x80], 1, 0, 0),               // ADDQ.L #1, D0
+            (&[0x53, 0x80], 0, 0, X),               // SUBQ.L #1, D0 borrows
+            (&[0xd1, 0x81], 0xffff_ffff, 1, X),     // ADDX.L D1, D0
+            (&[0x91, 0x81], 1, 0, 0),               // SUBX.L D1, D0
+            (&[0x44, 0x80], 1, 0, X),               // NEG.L D0
+            (&[0x44, 0x80], 0, 0, 0),               // NEG.L D0 of zero
+            (&[0x40, 0x80], 1, 0, X),               // NEGX.L D0
+            (&[0xe3, 0x88], 0x8000_0000, 0, X),     // LSL.L #1, D0
+            (&[0xe2, 0x80], 2, 0, 0),               // ASR.L #1, D0
+            (&[0xe3, 0x90], 0x8000_0000, 0, X),     // ROXL.L #1, D0
+            (&[0xc1, 0x01], 0x99, 0x01, X),         // ABCD D1, D0 carries
+        ];
+        for (program, d0, d1, expected) in cases.iter() {
+            for &x_before in &[0, X] {
+                let mut cpu = TestCore::new_mem(0x40, program);
+                cpu.dar[0] = *d0;
+                cpu.dar[1] = *d1;
+                cpu.sr_to_flags(0x2700 | x_before);
+                cpu.execute1();
+                assert_eq!(*expected, cpu.status_register() & X, "{:02x?} with D0 {:08x} and X {}", program, d0, x_before);
+            }
+        }
     }
 
-    impl Callbacks for CustomExceptionHandler {
-        fn exception_callback(&mut self, core: &mut impl Core, ex: Exception) -> Result<Cycles> {
-            self.count += 1;
-            self.ex = Some(ex);
+    #[test]
+    fn vector_names_match_exception_constants() {
+        use super::vector_name;
+        assert_eq!("Bus Error", vector_name(super::EXCEPTION_BUS_ERROR));
+        assert_eq!("Address Error", vector_name(super::EXCEPTION_ADDRESS_ERROR));
+        assert_eq!("Illegal Instruction", vector_name(super::EXCEPTION_ILLEGAL_INSTRUCTION));
+        assert_eq!("Zero Divide", vector_name(super::EXCEPTION_ZERO_DIVIDE));
+        assert_eq!("Privilege Violation", vector_name(super::EXCEPTION_PRIVILEGE_VIOLATION));
+        assert_eq!("Line 1111 Emulator", vector_name(super::EXCEPTION_UNIMPLEMENTED_1111));
+        assert_eq!("TRAP #0", vector_name(super::EXCEPTION_TRAP_BASE));
+        assert_eq!("Level 7 Autovector", vector_name(31));
+        assert_eq!("Reserved", vector_name(63));
+        assert_eq!("User Interrupt", vector_name(255));
+    }
 
-            if !self.suppress {
-                Err(ex)
+    #[test]
+    fn trap_dispatches_through_all_sixteen_vectors() {
+        for n in 0..16u32 {
+            // 0x4e4n is TRAP #n, followed by NOP
+            let mut cpu = exception_test_core(&[0x4e, 0x40 | n as u8, 0x4e, 0x71]);
+            let handler = 0x1000 + n * 0x10;
+            cpu.mem.write_long(SUPERVISOR_DATA, (32 + n) * 4, handler);
+            cpu.s_flag = 0;
+            cpu.inactive_ssp = 0x400;
+            cpu.dar[15] = 0x800;
+            cpu.ccr_to_flags(0x1f);
+            let sr = cpu.status_register();
+
+            let mut recorder = CustomExceptionHandler { suppress: false, count: 0, ex: None };
+            let cycles = cpu.execute_with_state(1, &mut recorder);
+
+            assert_eq!(Cycles(38), cycles, "TRAP #{n}");
+            assert_eq!(1, recorder.count);
+            if let Some(Exception::Trap(vector, ea_cycles)) = recorder.ex {
+                assert_eq!(32 + n as u8, vector);
+                assert_eq!(38, ea_cycles);
             } else {
-                // correct an odd PC
-                if *core.pc() % 2 == 1 {
-                    *core.pc() += 1;
-                }
-                Ok(Cycles(1000))
+                panic!("TRAP #{n} did not raise a trap exception");
             }
+            assert_eq!(handler, cpu.pc);
+            assert_eq!(super::ProcessingState::Group2Exception, cpu.processing_state);
+            // the group 2 frame is SR followed by the PC of the next instruction
+            assert_eq!(0x400 - 6, cpu.dar[15]);
+            assert_eq!(sr, cpu.mem.read_word(SUPERVISOR_DATA, cpu.dar[15]) as u16);
+            assert_eq!(0x42, stacked_pc(&cpu));
+            assert_eq!(0x800, cpu.inactive_usp);
+            assert!(cpu.s_flag != 0);
         }
     }
 
@@ -2303,4 +7563,39 @@ mod tests {
             assert!(false);
         }
     }
+
+    #[test]
+    fn injected_bus_error_stacks_group0_frame() {
+        use crate::cpu::{ConfiguredCore, ProcessingState};
+        use crate::interrupts::AutoInterruptController;
+        use crate::ram::PagedMem;
+        use crate::ram::faultinjector::FaultInjector;
+
+        let mut mem = FaultInjector::new(PagedMem::new(0));
+        // 0x3010 is MOVE.W (A0), D0
+        mem.write_long(SUPERVISOR_PROGRAM, 0x1000, 0x3010_3010);
+        mem.write_long(SUPERVISOR_DATA, super::EXCEPTION_BUS_ERROR as u32 * 4, 0x3000);
+        mem.write_word(SUPERVISOR_DATA, 0x2000, 0x1234);
+        mem.fail_nth_access(0x2000, 2);
+        let mut cpu = ConfiguredCore::new_with(0x1000, AutoInterruptController::new(), mem);
+        cpu.processing_state = ProcessingState::Normal;
+        cpu.dar[8] = 0x2000;
+        cpu.dar[15] = 0x800;
+
+        cpu.execute1();
+        assert_eq!(0x1234, cpu.dar[0]);
+        cpu.dar[0] = 0;
+
+        let cycles = cpu.execute1();
+        assert_eq!(Cycles(50), cycles);
+        assert_eq!(0, cpu.dar[0]);
+        assert_eq!(0x3000, cpu.pc);
+        assert_eq!(1, cpu.mem.raised());
+        let sp = cpu.dar[15];
+        assert_eq!(0x7f2, sp);
+        // read, not processing an instruction bit clear, supervisor data
+        assert_eq!(0x15, cpu.mem.read_word(SUPERVISOR_DATA, sp));
+        assert_eq!(0x2000, cpu.mem.read_long(SUPERVISOR_DATA, sp + 2));
+        assert_eq!(0x3010, cpu.mem.read_word(SUPERVISOR_DATA, sp + 6));
+    }
 }
@@ -0,0 +1,212 @@
+//! Effective address decoding.
+//!
+//! [`decode_ea`] turns the 6-bit mode/register field of an instruction,
+//! together with its extension words, into a structured [`Operand`]. It is
+//! the building block for disassemblers and other tools that need to know
+//! what an instruction operates on without executing it.
+
+use std::fmt;
+use crate::ram::{AddressBus, AddressSpace};
+
+/// The index register of an indexed addressing mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Index {
+    /// The register, 0-7 for D0-D7 and 8-15 for A0-A7.
+    pub register: u8,
+    /// True if the whole register is used, false for the sign-extended low word.
+    pub long: bool,
+}
+
+impl fmt::Display for Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.register < 8 { 'D' } else { 'A' };
+        write!(f, "{}{}.{}", kind, self.register & 7, if self.long { 'L' } else { 'W' })
+    }
+}
+
+/// A decoded effective address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    /// `Dn`
+    DataReg(u8),
+    /// `An`
+    AddrReg(u8),
+    /// `(An)`
+    Indirect(u8),
+    /// `(An)+`
+    PostInc(u8),
+    /// `-(An)`
+    PreDec(u8),
+    /// `(d16,An)`
+    Disp16 {
+        /// The address register
+        reg: u8,
+        /// The displacement
+        displacement: i16,
+    },
+    /// `(d8,An,Xn)`
+    IndexDisp8 {
+        /// The address register
+        reg: u8,
+        /// The index register
+        index: Index,
+        /// The displacement
+        displacement: i8,
+    },
+    /// `(xxx).W`, with the address sign-extended to 32 bits
+    AbsShort(u32),
+    /// `(xxx).L`
+    AbsLong(u32),
+    /// `(d16,PC)`
+    PcDisp16 {
+        /// The displacement
+        displacement: i16,
+        /// The address referred to
+        address: u32,
+    },
+    /// `(d8,PC,Xn)`
+    PcIndex {
+        /// The index register
+        index: Index,
+        /// The displacement
+        displacement: i8,
+        /// The address referred to, before adding the index register
+        base: u32,
+    },
+    /// `#<data>`, zero-extended from the operand size
+    Immediate(u32),
+    /// Mode 7 with register 5-7, which is not an addressing mode
+    Invalid,
+}
+
+impl Operand {
+    /// Returns true if the operand is in memory, i.e. has an effective address.
+    pub fn is_memory(&self) -> bool {
+        !matches!(*self, Operand::DataReg(_) | Operand::AddrReg(_) | Operand::Immediate(_) | Operand::Invalid)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Operand::DataReg(reg) => write!(f, "D{reg}"),
+            Operand::AddrReg(reg) => write!(f, "A{reg}"),
+            Operand::Indirect(reg) => write!(f, "(A{reg})"),
+            Operand::PostInc(reg) => write!(f, "(A{reg})+"),
+            Operand::PreDec(reg) => write!(f, "-(A{reg})"),
+            Operand::Disp16 { reg, displacement } => write!(f, "({displacement},A{reg})"),
+            Operand::IndexDisp8 { reg, index, displacement } => write!(f, "({displacement},A{reg},{index})"),
+            Operand::AbsShort(address) => write!(f, "${:04x}.W", address & 0xffff),
+            Operand::AbsLong(address) => write!(f, "${address:08x}.L"),
+            Operand::PcDisp16 { displacement, .. } => write!(f, "({displacement},PC)"),
+            Operand::PcIndex { index, displacement, .. } => write!(f, "({displacement},PC,{index})"),
+            Operand::Immediate(value) => write!(f, "#${value:x}"),
+            Operand::Invalid => write!(f, "<invalid>"),
+        }
+    }
+}
+
+/// Decodes the effective address field `mode`/`reg` of an instruction.
+///
+/// `pc` is the address of the operand's first extension word, if it has
+/// any, in `address_space`; PC relative modes are relative to it. `size`
+/// is the operand size in bytes (1, 2 or 4), which only matters for
+/// immediate data. Returns the operand and the number of extension word
+/// bytes it takes, so that decoding a second operand continues at `pc`
+/// plus that length.
+///
+/// # Example
+///
+/// ```rust
+/// use r68k::cpu::operand::{decode_ea, Index, Operand};
+/// use r68k::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM};
+///
+/// let mut mem = PagedMem::new(0);
+/// // brief extension word of (4,A0,D1.L)
+/// mem.write_word(SUPERVISOR_PROGRAM, 0x1002, 0x1804);
+/// let (operand, length) = decode_ea(&mem, SUPERVISOR_PROGRAM, 0x1002, 6, 0, 2);
+/// assert_eq!(Operand::IndexDisp8 { reg: 0, index: Index { register: 1, long: true }, displacement: 4 }, operand);
+/// assert_eq!(2, length);
+/// assert_eq!("(4,A0,D1.L)", operand.to_string());
+/// ```
+pub fn decode_ea<A: AddressBus>(mem: &A, address_space: AddressSpace, pc: u32, mode: u8, reg: u8, size: u8) -> (Operand, u32) {
+    let reg = reg & 7;
+    let word = || mem.read_word(address_space, pc) as u16;
+    let brief = || {
+        let ext = word();
+        (Index { register: (ext >> 12) as u8, long: ext & 0x800 != 0 }, ext as u8 as i8)
+    };
+    match (mode & 7, reg) {
+        (0, _) => (Operand::DataReg(reg), 0),
+        (1, _) => (Operand::AddrReg(reg), 0),
+        (2, _) => (Operand::Indirect(reg), 0),
+        (3, _) => (Operand::PostInc(reg), 0),
+        (4, _) => (Operand::PreDec(reg), 0),
+        (5, _) => (Operand::Disp16 { reg, displacement: word() as i16 }, 2),
+        (6, _) => {
+            let (index, displacement) = brief();
+            (Operand::IndexDisp8 { reg, index, displacement }, 2)
+        }
+        (7, 0) => (Operand::AbsShort(word() as i16 as u32), 2),
+        (7, 1) => (Operand::AbsLong(mem.read_long(address_space, pc)), 4),
+        (7, 2) => {
+            let displacement = word() as i16;
+            (Operand::PcDisp16 { displacement, address: pc.wrapping_add(displacement as u32) }, 2)
+        }
+        (7, 3) => {
+            let (index, displacement) = brief();
+            (Operand::PcIndex { index, displacement, base: pc.wrapping_add(displacement as u32) }, 2)
+        }
+        (7, 4) => match size {
+            1 => (Operand::Immediate(u32::from(word()) & 0xff), 2),
+            4 => (Operand::Immediate(mem.read_long(address_space, pc)), 4),
+            _ => (Operand::Immediate(u32::from(word())), 2),
+        },
+        _ => (Operand::Invalid, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_ea, Index, Operand};
+    use crate::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM};
+
+    fn decode(words: &[u16], mode: u8, reg: u8, size: u8) -> (Operand, u32) {
+        let mut mem = PagedMem::new(0);
+        for (offset, word) in words.iter().enumerate() {
+            mem.write_word(SUPERVISOR_PROGRAM, 0x1000 + 2 * offset as u32, u32::from(*word));
+        }
+        decode_ea(&mem, SUPERVISOR_PROGRAM, 0x1000, mode, reg, size)
+    }
+
+    #[test]
+    fn register_modes_take_no_extension_words() {
+        assert_eq!((Operand::DataReg(3), 0), decode(&[], 0, 3, 4));
+        assert_eq!((Operand::AddrReg(7), 0), decode(&[], 1, 7, 4));
+        assert_eq!((Operand::Indirect(1), 0), decode(&[], 2, 1, 2));
+        assert_eq!((Operand::PostInc(2), 0), decode(&[], 3, 2, 1));
+        assert_eq!((Operand::PreDec(7), 0), decode(&[], 4, 7, 2));
+        assert_eq!((Operand::Invalid, 0), decode(&[], 7, 5, 2));
+    }
+
+    #[test]
+    fn displacements_are_sign_extended() {
+        assert_eq!((Operand::Disp16 { reg: 2, displacement: -2 }, 2), decode(&[0xfffe], 5, 2, 2));
+        assert_eq!((Operand::IndexDisp8 { reg: 0, index: Index { register: 9, long: false }, displacement: -4 }, 2),
+                   decode(&[0x90fc], 6, 0, 2));
+        assert_eq!((Operand::AbsShort(0xffff_8000), 2), decode(&[0x8000], 7, 0, 2));
+        assert_eq!((Operand::AbsLong(0x0012_3456), 4), decode(&[0x0012, 0x3456], 7, 1, 2));
+        assert_eq!((Operand::PcDisp16 { displacement: -16, address: 0x1000 - 16 }, 2), decode(&[0xfff0], 7, 2, 2));
+        assert_eq!((Operand::PcIndex { index: Index { register: 0, long: true }, displacement: 6, base: 0x1006 }, 2),
+                   decode(&[0x0806], 7, 3, 2));
+    }
+
+    #[test]
+    fn immediate_length_follows_size() {
+        assert_eq!((Operand::Immediate(0x34), 2), decode(&[0x1234], 7, 4, 1));
+        assert_eq!((Operand::Immediate(0x1234), 2), decode(&[0x1234], 7, 4, 2));
+        assert_eq!((Operand::Immediate(0x1234_5678), 4), decode(&[0x1234, 0x5678], 7, 4, 4));
+        assert_eq!("#$12345678", decode(&[0x1234, 0x5678], 7, 4, 4).0.to_string());
+        assert_eq!("$8000.W", decode(&[0x8000], 7, 0, 2).0.to_string());
+    }
+}
@@ -33,6 +33,15 @@ impl<T: Core> InstructionSetGenerator<T> {
         }
     }
 
+    pub fn new_for(model: CpuModel) -> InstructionSetGenerator<T> {
+        let mut optable = generate_optable();
+        if model == CpuModel::M68010 {
+            // later entries win, so these replace the illegal ones
+            optable.extend(generate_optable_68010());
+        }
+        InstructionSetGenerator { optable }
+    }
+
     pub fn generate(&self) -> InstructionSet<T> {
         self.generate_with(illegal, |op| op.handler)
     }
@@ -104,6 +113,45 @@ impl<T: Core> InstructionSetGenerator<T> {
     }
 }
 
+// instructions added by the 68010
+fn generate_optable_68010<T: Core>() -> Vec<OpcodeHandler<T>> {
+    vec![
+        // Put op-entries for MOVE from CCR here
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_DN, move_16_frc_dn),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_AI, move_16_frc_ai),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_PI, move_16_frc_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_PD, move_16_frc_pd),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_DI, move_16_frc_di),
+        op_entry!(MASK_OUT_Y, OP_MOVE_16_FRC_IX, move_16_frc_ix),
+        op_entry!(MASK_EXACT, OP_MOVE_16_FRC_AW, move_16_frc_aw),
+        op_entry!(MASK_EXACT, OP_MOVE_16_FRC_AL, move_16_frc_al),
+        // Put op-entries for MOVEC and MOVES here
+        op_entry!(MASK_EXACT, OP_MOVEC_32_CR, movec_32_cr),
+        op_entry!(MASK_EXACT, OP_MOVEC_32_RC, movec_32_rc),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8_AI, moves_8_ai),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8_PI, moves_8_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8_PD, moves_8_pd),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8_DI, moves_8_di),
+        op_entry!(MASK_OUT_Y, OP_MOVES_8_IX, moves_8_ix),
+        op_entry!(MASK_EXACT, OP_MOVES_8_AW, moves_8_aw),
+        op_entry!(MASK_EXACT, OP_MOVES_8_AL, moves_8_al),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16_AI, moves_16_ai),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16_PI, moves_16_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16_PD, moves_16_pd),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16_DI, moves_16_di),
+        op_entry!(MASK_OUT_Y, OP_MOVES_16_IX, moves_16_ix),
+        op_entry!(MASK_EXACT, OP_MOVES_16_AW, moves_16_aw),
+        op_entry!(MASK_EXACT, OP_MOVES_16_AL, moves_16_al),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32_AI, moves_32_ai),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32_PI, moves_32_pi),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32_PD, moves_32_pd),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32_DI, moves_32_di),
+        op_entry!(MASK_OUT_Y, OP_MOVES_32_IX, moves_32_ix),
+        op_entry!(MASK_EXACT, OP_MOVES_32_AW, moves_32_aw),
+        op_entry!(MASK_EXACT, OP_MOVES_32_AL, moves_32_al),
+    ]
+}
+
 fn generate_optable<T: Core>() -> Vec<OpcodeHandler<T>> {
     // the optable contains opcode mask, matching mask and the corresponding handler + name
     vec![
@@ -1915,4 +1963,53 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn implemented_opcodes_excludes_illegal_and_unimplemented() {
+        let opcodes = crate::cpu::ops::implemented_opcodes();
+
+        assert!(opcodes.windows(2).all(|w| w[0] < w[1]));
+        assert!(opcodes.contains(&0x4e71)); // NOP
+        assert!(opcodes.contains(&0xd200)); // ADD.B D0, D1
+        assert!(!opcodes.contains(&0x4afc)); // ILLEGAL
+        assert!(!opcodes.contains(&0x60ff)); // BRA.L is 68020+
+        assert!(!opcodes.iter().any(|&op| op & 0xf000 == 0xa000 || op & 0xf000 == 0xf000));
+        // guards against decode table entries going missing
+        assert_eq!(45800, opcodes.len());
+    }
+
+    #[test]
+    fn legality_matches_implemented_opcodes() {
+        use crate::cpu::Core;
+        let core = TestCore::new(0);
+        let opcodes = crate::cpu::ops::implemented_opcodes();
+
+        assert_eq!(opcodes.len(), (0..=0xffff).filter(|&op| core.is_legal_opcode(op)).count());
+        assert!(opcodes.iter().all(|&op| core.is_legal_opcode(op)));
+        assert!(core.is_legal_opcode(0x4e75)); // RTS
+        assert!(!core.is_legal_opcode(0x4afc)); // ILLEGAL
+        assert!(!core.is_legal_opcode(0x4e7a)); // MOVEC is 68010+
+        assert!(!core.is_legal_opcode(0xa000)); // Line 1010
+    }
+
+    #[test]
+    fn instruction_length_counts_extension_words() {
+        use crate::cpu::ops::instruction_length;
+        assert_eq!(Some(2), instruction_length(0x4e71)); // NOP
+        assert_eq!(Some(2), instruction_length(0x6702)); // BEQ.B
+        assert_eq!(Some(4), instruction_length(0x6700)); // BEQ.W
+        assert_eq!(Some(4), instruction_length(0x51c8)); // DBF D0
+        assert_eq!(Some(4), instruction_length(0x0640)); // ADDI.W #imm, D0
+        assert_eq!(Some(6), instruction_length(0x0680)); // ADDI.L #imm, D0
+        assert_eq!(Some(10), instruction_length(0x06b9)); // ADDI.L #imm, (abs).L
+        assert_eq!(Some(4), instruction_length(0x023c)); // ANDI #imm, CCR
+        assert_eq!(Some(6), instruction_length(0x083c)); // BTST #n, #imm
+        assert_eq!(Some(10), instruction_length(0x23f9)); // MOVE.L (abs).L, (abs).L
+        assert_eq!(Some(6), instruction_length(0x203c)); // MOVE.L #imm, D0
+        assert_eq!(Some(8), instruction_length(0x48f9)); // MOVEM.L regs, (abs).L
+        assert_eq!(Some(4), instruction_length(0x4e72)); // STOP #imm
+        assert_eq!(Some(4), instruction_length(0x4e56)); // LINK A6, #disp
+        assert_eq!(Some(4), instruction_length(0x4efa)); // JMP (d16, PC)
+        assert_eq!(None, instruction_length(0x4afc)); // ILLEGAL
+    }
 }
@@ -1,5 +1,5 @@
 #![macro_use]
-use super::{Core, Cycles, Result, EXCEPTION_CHK, EXCEPTION_UNIMPLEMENTED_1010, EXCEPTION_UNIMPLEMENTED_1111, EXCEPTION_ZERO_DIVIDE, EXCEPTION_TRAP_BASE, EXCEPTION_TRAPV};
+use super::{Core, CpuModel, Cycles, Result, EXCEPTION_CHK, EXCEPTION_UNIMPLEMENTED_1010, EXCEPTION_UNIMPLEMENTED_1111, EXCEPTION_ZERO_DIVIDE, EXCEPTION_TRAP_BASE, EXCEPTION_TRAPV};
 use super::Exception::*;
 
 mod common;
@@ -175,7 +175,93 @@ use super::InstructionSet;
 pub fn instruction_set<T: Core>() -> InstructionSet<T> {
     handlers::InstructionSetGenerator::new().generate()
 }
+
+/// Returns the instruction set of `model`.
+pub fn instruction_set_for<T: Core>(model: CpuModel) -> InstructionSet<T> {
+    handlers::InstructionSetGenerator::new_for(model).generate()
+}
+
+/// Returns every opcode the decode tables map to a real instruction, in
+/// ascending order.
+///
+/// Opcodes that decode to an Illegal Instruction exception (including the
+/// official `ILLEGAL` opcode and 68020-only forms such as `Bcc.L`) or to
+/// the Line 1010/1111 emulator traps are not included.
+pub fn implemented_opcodes() -> Vec<u16> {
+    (0..=0xffff).filter(|&opcode| is_legal_opcode(opcode)).collect()
+}
+
+/// Returns whether `opcode` is one of the [`implemented_opcodes()`].
+pub fn is_legal_opcode(opcode: u16) -> bool {
+    is_implemented(handler_name(opcode))
+}
+
+/// Returns whether `opcode` is a legal instruction on `model`.
+///
+/// Like [`is_legal_opcode`], which covers the 68000, but including the
+/// instructions the later model adds.
+pub fn is_legal_opcode_for(model: CpuModel, opcode: u16) -> bool {
+    static HANDLER_NAMES_68010: OnceLock<Vec<&'static str>> = OnceLock::new();
+    match model {
+        CpuModel::M68000 => is_legal_opcode(opcode),
+        CpuModel::M68010 => is_implemented(HANDLER_NAMES_68010.get_or_init(|| {
+            handlers::InstructionSetGenerator::<super::Cpu>::new_for(model)
+                .generate_with("illegal", |op| op.name)
+        })[opcode as usize]),
+    }
+}
+
+fn is_implemented(handler_name: &str) -> bool {
+    const NOT_IMPLEMENTED: [&str; 4] = ["illegal", "real_illegal", "unimplemented_1010", "unimplemented_1111"];
+    !NOT_IMPLEMENTED.contains(&handler_name)
+}
+
+/// Returns the length in bytes of the instruction starting with `opcode`,
+/// including all extension words, or `None` if the opcode is not legal.
+///
+/// On the 68000 the length is fully determined by the first word.
+pub fn instruction_length(opcode: u16) -> Option<u32> {
+    if !is_legal_opcode(opcode) {
+        return None;
+    }
+    let mut parts = handler_name(opcode).split('_');
+    let mnemonic = parts.next().unwrap_or("");
+    let operands: Vec<&str> = parts.collect();
+    // operand size in bits, when the name carries one
+    let size = operands.first().and_then(|size| size.parse::<u32>().ok()).unwrap_or(16);
+    let immediate = if size == 32 { 4 } else { 2 };
+    let mut length = 2;
+    length += match mnemonic {
+        "addi" | "andi" | "cmpi" | "eori" | "ori" | "subi" => immediate,
+        "movem" | "movep" | "link" | "stop" => 2,
+        _ if mnemonic.starts_with("db") => 2,
+        // Bcc.W, BRA.W and BSR.W; the .B forms keep the displacement in the opcode
+        _ if mnemonic.starts_with('b') && operands == ["16"] => 2,
+        _ => 0,
+    };
+    for operand in &operands[1.min(operands.len())..] {
+        length += match *operand {
+            // static bit number of BTST/BCHG/BCLR/BSET #n
+            "s" if mnemonic.starts_with('b') => 2,
+            "di" | "ix" | "aw" | "pcdi" | "pcix" => 2,
+            "al" => 4,
+            "imm" => immediate,
+            _ => 0,
+        };
+    }
+    Some(length)
+}
+
+/// Returns the name of the handler the decode tables map `opcode` to.
+pub(crate) fn handler_name(opcode: u16) -> &'static str {
+    static HANDLER_NAMES: OnceLock<Vec<&'static str>> = OnceLock::new();
+    HANDLER_NAMES.get_or_init(|| {
+        handlers::InstructionSetGenerator::<super::Cpu>::new()
+            .generate_with("illegal", |op| op.name)
+    })[opcode as usize]
+}
 use std::num::Wrapping;
+use std::sync::OnceLock;
 use super::operator;
 
 pub fn unimplemented_1010<T: Core>(core: &mut T) -> Result<Cycles> {
@@ -1007,11 +1093,14 @@ chk_16!(chk_16_pi,   ay_pi_16,  10 +  4);
 use crate::cpu::effective_address;
 
 macro_rules! clr {
-    ($name:ident, $dst:ident, $write_op:ident, $cycles:expr) => (
+    ($name:ident, $dst:ident, $read_op:ident, $write_op:ident, $cycles:expr) => (
         pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
             // The MC68000PRM says: In the MC68000 and MC68008 a memory location is read before it is cleared.
-            // We skip this as Musashi doesn't do that either.
+            // Musashi doesn't do that, so we only do when asked to.
             let ea = (effective_address::$dst(core))?;
+            if core.read_before_write() {
+                (core.$read_op(ea))?;
+            }
 
             (core.$write_op(ea, 0))?;
 
@@ -1032,13 +1121,13 @@ pub fn clr_8_dn<T: Core>(core: &mut T) -> Result<Cycles> {
     not_z_flag!(core) = 0;
     Ok(Cycles(4))
 }
-clr!(clr_8_ai, address_indirect_ay, write_data_byte, 8+4);
-clr!(clr_8_pi, postincrement_ay_8,  write_data_byte, 8+4);
-clr!(clr_8_pd, predecrement_ay_8,   write_data_byte, 8+6);
-clr!(clr_8_di, displacement_ay,     write_data_byte, 8+8);
-clr!(clr_8_ix, index_ay,            write_data_byte, 8+10);
-clr!(clr_8_aw, absolute_word,       write_data_byte, 8+8);
-clr!(clr_8_al, absolute_long,       write_data_byte, 8+12);
+clr!(clr_8_ai, address_indirect_ay, read_data_byte, write_data_byte, 8+4);
+clr!(clr_8_pi, postincrement_ay_8,  read_data_byte, write_data_byte, 8+4);
+clr!(clr_8_pd, predecrement_ay_8,   read_data_byte, write_data_byte, 8+6);
+clr!(clr_8_di, displacement_ay,     read_data_byte, write_data_byte, 8+8);
+clr!(clr_8_ix, index_ay,            read_data_byte, write_data_byte, 8+10);
+clr!(clr_8_aw, absolute_word,       read_data_byte, write_data_byte, 8+8);
+clr!(clr_8_al, absolute_long,       read_data_byte, write_data_byte, 8+12);
 
 pub fn clr_16_dn<T: Core>(core: &mut T) -> Result<Cycles> {
     dy!(core) &= 0xffff_0000;
@@ -1049,13 +1138,13 @@ pub fn clr_16_dn<T: Core>(core: &mut T) -> Result<Cycles> {
     not_z_flag!(core) = 0;
     Ok(Cycles(4))
 }
-clr!(clr_16_ai, address_indirect_ay, write_data_word, 8+4);
-clr!(clr_16_pi, postincrement_ay_16, write_data_word, 8+4);
-clr!(clr_16_pd, predecrement_ay_16,  write_data_word, 8+6);
-clr!(clr_16_di, displacement_ay,     write_data_word, 8+8);
-clr!(clr_16_ix, index_ay,            write_data_word, 8+10);
-clr!(clr_16_aw, absolute_word,       write_data_word, 8+8);
-clr!(clr_16_al, absolute_long,       write_data_word, 8+12);
+clr!(clr_16_ai, address_indirect_ay, read_data_word, write_data_word, 8+4);
+clr!(clr_16_pi, postincrement_ay_16, read_data_word, write_data_word, 8+4);
+clr!(clr_16_pd, predecrement_ay_16,  read_data_word, write_data_word, 8+6);
+clr!(clr_16_di, displacement_ay,     read_data_word, write_data_word, 8+8);
+clr!(clr_16_ix, index_ay,            read_data_word, write_data_word, 8+10);
+clr!(clr_16_aw, absolute_word,       read_data_word, write_data_word, 8+8);
+clr!(clr_16_al, absolute_long,       read_data_word, write_data_word, 8+12);
 
 pub fn clr_32_dn<T: Core>(core: &mut T) -> Result<Cycles> {
     dy!(core) = 0;
@@ -1066,13 +1155,13 @@ pub fn clr_32_dn<T: Core>(core: &mut T) -> Result<Cycles> {
     not_z_flag!(core) = 0;
     Ok(Cycles(6))
 }
-clr!(clr_32_ai, address_indirect_ay, write_data_long, 12+8);
-clr!(clr_32_pi, postincrement_ay_32, write_data_long, 12+8);
-clr!(clr_32_pd, predecrement_ay_32,  write_data_long, 12+10);
-clr!(clr_32_di, displacement_ay,     write_data_long, 12+12);
-clr!(clr_32_ix, index_ay,            write_data_long, 12+14);
-clr!(clr_32_aw, absolute_word,       write_data_long, 12+12);
-clr!(clr_32_al, absolute_long,       write_data_long, 12+16);
+clr!(clr_32_ai, address_indirect_ay, read_data_long, write_data_long, 12+8);
+clr!(clr_32_pi, postincrement_ay_32, read_data_long, write_data_long, 12+8);
+clr!(clr_32_pd, predecrement_ay_32,  read_data_long, write_data_long, 12+10);
+clr!(clr_32_di, displacement_ay,     read_data_long, write_data_long, 12+12);
+clr!(clr_32_ix, index_ay,            read_data_long, write_data_long, 12+14);
+clr!(clr_32_aw, absolute_word,       read_data_long, write_data_long, 12+12);
+clr!(clr_32_al, absolute_long,       read_data_long, write_data_long, 12+16);
 
 impl_op!(-, cmp_8, cmp_8_dn,   dy,      dx, 4);
 impl_op!(-, cmp_8, cmp_8_ai,   ay_ai_8, dx, 4+4);
@@ -1996,14 +2085,21 @@ move_toc!(move_16_toc_pcix, pcix_16, 12+10);
 move_toc!(move_16_toc_imm, imm_16, 12+4);
 
 // Put implementation of MOVE from SR ops here
+// (privileged from the 68010 on)
 macro_rules! move_frs {
     ($name:ident, dy, $cycles:expr) => (
         pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            if core.model() != CpuModel::M68000 && s_flag!(core) == 0 {
+                return Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)));
+            }
             dy!(core) = mask_out_below_16!(dy!(core)) | u32::from(core.status_register());
             Ok(Cycles($cycles))
         });
     ($name:ident, $src:ident, $cycles:expr) => (
         pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            if core.model() != CpuModel::M68000 && s_flag!(core) == 0 {
+                return Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)));
+            }
   // unsigned int ea = ((m68ki_cpu.dar+8)[m68ki_cpu.ir & 7]);
   // m68ki_write_16_fc(ea, m68ki_cpu.s_flag | 1, ( m68ki_cpu.t1_flag | m68ki_cpu.t0_flag | (m68ki_cpu.s_flag << 11) | (m68ki_cpu.m_flag << 11) | m68ki_cpu.int_mask | (((m68ki_cpu.x_flag&0x100) >> 4) | ((m68ki_cpu.n_flag&0x80) >> 4) | ((!m68ki_cpu.not_z_flag) << 2) | ((m68ki_cpu.v_flag&0x80) >> 6) | ((m68ki_cpu.c_flag&0x100) >> 8))));
   // return;
@@ -2022,6 +2118,30 @@ move_frs!(move_16_frs_ix, index_ay,            8+10);
 move_frs!(move_16_frs_aw, absolute_word,       8+8);
 move_frs!(move_16_frs_al, absolute_long,       8+12);
 
+// Put implementation of MOVE from CCR ops here (68010+)
+macro_rules! move_frc {
+    ($name:ident, dy, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            dy!(core) = mask_out_below_16!(dy!(core)) | u32::from(core.condition_code_register());
+            Ok(Cycles($cycles))
+        });
+    ($name:ident, $src:ident, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            let ccr = core.condition_code_register();
+            let ea = (effective_address::$src(core))?;
+            (core.write_data_word(ea, u32::from(ccr)))?;
+            Ok(Cycles($cycles))
+        })
+}
+move_frc!(move_16_frc_dn, dy, 4);
+move_frc!(move_16_frc_ai, address_indirect_ay, 8+4);
+move_frc!(move_16_frc_pi, postincrement_ay_16, 8+4);
+move_frc!(move_16_frc_pd, predecrement_ay_16,  8+6);
+move_frc!(move_16_frc_di, displacement_ay,     8+8);
+move_frc!(move_16_frc_ix, index_ay,            8+10);
+move_frc!(move_16_frc_aw, absolute_word,       8+8);
+move_frc!(move_16_frc_al, absolute_long,       8+12);
+
 // Put implementation of MOVE to SR ops here
 macro_rules! move_tos {
     ($name:ident, $src:ident, $cycles:expr) => (
@@ -2064,6 +2184,85 @@ pub fn move_32_fru<T: Core>(core: &mut T) -> Result<Cycles> {
         Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)))
     }
 }
+// Put implementation of MOVEC ops here (68010+)
+pub fn movec_32_cr<T: Core>(core: &mut T) -> Result<Cycles> {
+    if s_flag!(core) == 0 {
+        return Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)));
+    }
+    let extension = (core.read_imm_u16())?;
+    match core.control_register(extension & 0xfff) {
+        Some(value) => {
+            dar!(core)[(extension >> 12) as usize] = value;
+            Ok(Cycles(12))
+        }
+        None => Err(IllegalInstruction(ir!(core), pc!(core).wrapping_sub(4))),
+    }
+}
+pub fn movec_32_rc<T: Core>(core: &mut T) -> Result<Cycles> {
+    if s_flag!(core) == 0 {
+        return Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)));
+    }
+    let extension = (core.read_imm_u16())?;
+    let value = dar!(core)[(extension >> 12) as usize];
+    if core.set_control_register(extension & 0xfff, value) {
+        Ok(Cycles(10))
+    } else {
+        Err(IllegalInstruction(ir!(core), pc!(core).wrapping_sub(4)))
+    }
+}
+
+// Put implementation of MOVES ops here (68010+)
+macro_rules! moves {
+    ($name:ident, $size:expr, $ea:ident, $cycles:expr) => (
+        pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            if s_flag!(core) == 0 {
+                return Err(PrivilegeViolation(ir!(core), pc!(core).wrapping_sub(2)));
+            }
+            let extension = (core.read_imm_u16())?;
+            let register = (extension >> 12) as usize;
+            let ea = (effective_address::$ea(core))?;
+            if extension & 0x800 != 0 {
+                // register to memory, in the DFC address space
+                let value = dar!(core)[register];
+                (core.write_moves(ea, $size, value))?;
+            } else {
+                // memory to register, in the SFC address space
+                let value = (core.read_moves(ea, $size))?;
+                let reg = dar!(core)[register];
+                dar!(core)[register] = match ($size, register >= 8) {
+                    (1, false) => mask_out_below_8!(reg) | value,
+                    (2, false) => mask_out_below_16!(reg) | value,
+                    // address registers take the sign extended value
+                    (1, true) => value as u8 as i8 as u32,
+                    (2, true) => value as u16 as i16 as u32,
+                    _ => value,
+                };
+            }
+            Ok(Cycles($cycles))
+        })
+}
+moves!(moves_8_ai, 1, address_indirect_ay, 18);
+moves!(moves_8_pi, 1, postincrement_ay_8,  18);
+moves!(moves_8_pd, 1, predecrement_ay_8,   20);
+moves!(moves_8_di, 1, displacement_ay,     20);
+moves!(moves_8_ix, 1, index_ay,            24);
+moves!(moves_8_aw, 1, absolute_word,       20);
+moves!(moves_8_al, 1, absolute_long,       24);
+moves!(moves_16_ai, 2, address_indirect_ay, 18);
+moves!(moves_16_pi, 2, postincrement_ay_16, 18);
+moves!(moves_16_pd, 2, predecrement_ay_16,  20);
+moves!(moves_16_di, 2, displacement_ay,     20);
+moves!(moves_16_ix, 2, index_ay,            24);
+moves!(moves_16_aw, 2, absolute_word,       20);
+moves!(moves_16_al, 2, absolute_long,       24);
+moves!(moves_32_ai, 4, address_indirect_ay, 22);
+moves!(moves_32_pi, 4, postincrement_ay_32, 22);
+moves!(moves_32_pd, 4, predecrement_ay_32,  24);
+moves!(moves_32_di, 4, displacement_ay,     26);
+moves!(moves_32_ix, 4, index_ay,            30);
+moves!(moves_32_aw, 4, absolute_word,       26);
+moves!(moves_32_al, 4, absolute_long,       30);
+
 // Put implementation of MOVEM ops here
 macro_rules! movem_16_re {
     ($name:ident, predecrement_ay_16, $cycles:expr) => (
@@ -2859,8 +3058,20 @@ roxr_16!(roxr_16_al, ea_al_16,    20);
 // Put implementation of RTE ops here
 pub fn rte_32<T: Core>(core: &mut T) -> Result<Cycles> {
     if s_flag!(core) != 0 {
+        let sp = dar!(core)[15];
+        // the 68010 format word says how much to unstack
+        let frame_length = if core.model() == CpuModel::M68000 {
+            6
+        } else {
+            match (core.read_data_word(sp.wrapping_add(6)))? >> 12 {
+                0 => 8,
+                8 => 58,
+                _ => return Err(FormatError(ir!(core), pc!(core).wrapping_sub(2))),
+            }
+        };
         let new_sr = core.pop_16();
         let new_pc = core.pop_32();
+        dar!(core)[15] = sp.wrapping_add(frame_length);
         core.jump(new_pc);
         core.sr_to_flags(new_sr);
         core.resume_normal_processing();
@@ -2909,8 +3120,13 @@ macro_rules! sxx_8_dn {
 macro_rules! sxx_8 {
     ($name:ident, $cond:ident, $dst:ident, $cycles:expr) => (
         pub fn $name<T: Core>(core: &mut T) -> Result<Cycles> {
+            // Like CLR, the MC68000 reads the destination before writing it.
+            // Musashi doesn't do that, so we only do when asked to.
             let t = if core.$cond() { 0xffu32 } else { 0u32 };
             let ea = (effective_address::$dst(core))?;
+            if core.read_before_write() {
+                (core.read_data_byte(ea))?;
+            }
             (core.write_data_byte(ea, t))?;
             Ok(Cycles($cycles))
         }
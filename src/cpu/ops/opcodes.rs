@@ -1071,6 +1071,16 @@ pub const OP_MOVE_16_FRS_IX   : u32 = OP_MOVE2 | MOVE_FROM_SR | OPER_IX;
 pub const OP_MOVE_16_FRS_AW   : u32 = OP_MOVE2 | MOVE_FROM_SR | OPER_AW;
 pub const OP_MOVE_16_FRS_AL   : u32 = OP_MOVE2 | MOVE_FROM_SR | OPER_AL;
 
+// Put constants for MOVE from CCR here (68010+)
+pub const OP_MOVE_16_FRC_DN   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_DN;
+pub const OP_MOVE_16_FRC_AI   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_AI;
+pub const OP_MOVE_16_FRC_PI   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_PI;
+pub const OP_MOVE_16_FRC_PD   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_PD;
+pub const OP_MOVE_16_FRC_DI   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_DI;
+pub const OP_MOVE_16_FRC_IX   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_IX;
+pub const OP_MOVE_16_FRC_AW   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_AW;
+pub const OP_MOVE_16_FRC_AL   : u32 = OP_MOVE2 | MOVE_FROM_CCR | OPER_AL;
+
 // Put constants for MOVE to SR here
 pub const OP_MOVE_16_TOS_DN   : u32 = OP_MOVE2 | MOVE_TO_SR | OPER_DN;
 pub const OP_MOVE_16_TOS_AI   : u32 = OP_MOVE2 | MOVE_TO_SR | OPER_AI;
@@ -1087,6 +1097,32 @@ pub const OP_MOVE_16_TOS_IMM  : u32 = OP_MOVE2 | MOVE_TO_SR | OPER_IMM;
 pub const OP_MOVE_32_TOU : u32 = OP_MOVE2 | MOVE_USP | TO_AN;
 pub const OP_MOVE_32_FRU : u32 = OP_MOVE2 | MOVE_USP | FROM_AN;
 
+// Put constants for MOVEC and MOVES here (68010+)
+pub const OP_MOVEC_32_CR : u32 = OP_MOVEC;     // control register to Rn
+pub const OP_MOVEC_32_RC : u32 = OP_MOVEC | 1; // Rn to control register
+
+pub const OP_MOVES_8_AI  : u32 = OP_MOVES | BYTE_SIZED | OPER_AI;
+pub const OP_MOVES_8_PI  : u32 = OP_MOVES | BYTE_SIZED | OPER_PI;
+pub const OP_MOVES_8_PD  : u32 = OP_MOVES | BYTE_SIZED | OPER_PD;
+pub const OP_MOVES_8_DI  : u32 = OP_MOVES | BYTE_SIZED | OPER_DI;
+pub const OP_MOVES_8_IX  : u32 = OP_MOVES | BYTE_SIZED | OPER_IX;
+pub const OP_MOVES_8_AW  : u32 = OP_MOVES | BYTE_SIZED | OPER_AW;
+pub const OP_MOVES_8_AL  : u32 = OP_MOVES | BYTE_SIZED | OPER_AL;
+pub const OP_MOVES_16_AI : u32 = OP_MOVES | WORD_SIZED | OPER_AI;
+pub const OP_MOVES_16_PI : u32 = OP_MOVES | WORD_SIZED | OPER_PI;
+pub const OP_MOVES_16_PD : u32 = OP_MOVES | WORD_SIZED | OPER_PD;
+pub const OP_MOVES_16_DI : u32 = OP_MOVES | WORD_SIZED | OPER_DI;
+pub const OP_MOVES_16_IX : u32 = OP_MOVES | WORD_SIZED | OPER_IX;
+pub const OP_MOVES_16_AW : u32 = OP_MOVES | WORD_SIZED | OPER_AW;
+pub const OP_MOVES_16_AL : u32 = OP_MOVES | WORD_SIZED | OPER_AL;
+pub const OP_MOVES_32_AI : u32 = OP_MOVES | LONG_SIZED | OPER_AI;
+pub const OP_MOVES_32_PI : u32 = OP_MOVES | LONG_SIZED | OPER_PI;
+pub const OP_MOVES_32_PD : u32 = OP_MOVES | LONG_SIZED | OPER_PD;
+pub const OP_MOVES_32_DI : u32 = OP_MOVES | LONG_SIZED | OPER_DI;
+pub const OP_MOVES_32_IX : u32 = OP_MOVES | LONG_SIZED | OPER_IX;
+pub const OP_MOVES_32_AW : u32 = OP_MOVES | LONG_SIZED | OPER_AW;
+pub const OP_MOVES_32_AL : u32 = OP_MOVES | LONG_SIZED | OPER_AL;
+
 pub const OP_MOVEM_16_RE_AI: u32 = OP_MOVEM | REGISTER_TO_MEMORY | WORD_TRANSFER | OPER_AI;
 pub const OP_MOVEM_16_RE_PD: u32 = OP_MOVEM | REGISTER_TO_MEMORY | WORD_TRANSFER | OPER_PD;
 pub const OP_MOVEM_16_RE_DI: u32 = OP_MOVEM | REGISTER_TO_MEMORY | WORD_TRANSFER | OPER_DI;
@@ -2113,6 +2149,18 @@ mod tests {
         assert_eq!(0x4e60, OP_MOVE_32_TOU)
     }
     #[test]
+    fn correctly_defined_op_movec_32_rc() {
+        assert_eq!(0x4e7b, OP_MOVEC_32_RC)
+    }
+    #[test]
+    fn correctly_defined_op_moves_16_pd() {
+        assert_eq!(0x0e60, OP_MOVES_16_PD)
+    }
+    #[test]
+    fn correctly_defined_op_moves_32_al() {
+        assert_eq!(0x0eb9, OP_MOVES_32_AL)
+    }
+    #[test]
     fn correctly_defined_op_movem_16_er_ai() {
         assert_eq!(0x4c90, OP_MOVEM_16_ER_AI)
     }
@@ -0,0 +1,69 @@
+//! Register and memory patches.
+//!
+//! A [`Patch`] collects register overrides and memory writes, for
+//! [`ConfiguredCore::apply_patch`](super::ConfiguredCore::apply_patch) to
+//! apply in one call between instructions, as cheat codes and a debugger's
+//! "set and continue" do.
+//!
+//! ```rust
+//! use r68k::cpu::Cpu;
+//! use r68k::cpu::patch::Patch;
+//! use r68k::ram::{AddressBus, SUPERVISOR_DATA};
+//!
+//! let mut cpu = Cpu::new(0);
+//! let mut patch = Patch::new();
+//! patch.registers[0] = Some(99);
+//! patch.write_word(0x8000, 0x0063);
+//! cpu.apply_patch(&patch);
+//! assert_eq!(99, cpu.dar[0]);
+//! assert_eq!(99, cpu.mem.read_word(SUPERVISOR_DATA, 0x8000));
+//! ```
+
+/// Register overrides and memory writes to apply together.
+///
+/// Registers left at `None` keep their value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Patch {
+    /// New values for D0-D7 and A0-A7, indexed like `dar`. A7 is the
+    /// stack pointer of the mode after `sr` is applied.
+    pub registers: [Option<u32>; 16],
+    /// New program counter
+    pub pc: Option<u32>,
+    /// New status register, switching stack pointers if S changes
+    pub sr: Option<u16>,
+    /// New user stack pointer, overriding A7 in user mode
+    pub usp: Option<u32>,
+    /// New supervisor stack pointer, overriding A7 in supervisor mode
+    pub ssp: Option<u32>,
+    /// Bytes to write, as (address, value), in order
+    pub memory: Vec<(u32, u8)>,
+}
+
+impl Patch {
+    /// Returns a patch that changes nothing.
+    pub fn new() -> Patch {
+        Patch::default()
+    }
+
+    /// Adds writes of `bytes` starting at `address`.
+    pub fn write_bytes(&mut self, address: u32, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.memory.push((address.wrapping_add(offset as u32), *byte));
+        }
+    }
+
+    /// Adds a big-endian word write at `address`.
+    pub fn write_word(&mut self, address: u32, value: u16) {
+        self.write_bytes(address, &value.to_be_bytes());
+    }
+
+    /// Adds a big-endian long word write at `address`.
+    pub fn write_long(&mut self, address: u32, value: u32) {
+        self.write_bytes(address, &value.to_be_bytes());
+    }
+
+    /// Returns true if the patch changes nothing.
+    pub fn is_empty(&self) -> bool {
+        *self == Patch::default()
+    }
+}
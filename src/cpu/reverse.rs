@@ -0,0 +1,152 @@
+//! Stepping backward through snapshots.
+//!
+//! With reverse debugging enabled, the core takes a snapshot of its state,
+//! including memory and the interrupt controller, every few instructions
+//! and keeps the most recent [`SNAPSHOTS_KEPT`] of them.
+//! [`step_back`](super::ConfiguredCore::step_back) restores the nearest
+//! snapshot before the current instruction and executes forward again to
+//! the instruction before it.
+
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use crate::interrupts::InterruptController;
+use crate::ram::AddressBus;
+use super::{ConfiguredCore, Exception, ProcessingState};
+
+/// The number of snapshots kept; older ones are dropped.
+pub const SNAPSHOTS_KEPT: usize = 64;
+
+/// Why stepping back failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReverseError {
+    /// Reverse debugging is not enabled.
+    NotEnabled,
+    /// No snapshot old enough is left, or no instruction has been executed
+    /// since reverse debugging was enabled.
+    NoHistory,
+    /// Executing forward from the snapshot did not get back to the previous
+    /// instruction, e.g. because the CPU was stopped and woken by an
+    /// interrupt requested after the snapshot was taken.
+    Diverged,
+}
+
+impl fmt::Display for ReverseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ReverseError::NotEnabled => write!(f, "reverse debugging is not enabled"),
+            ReverseError::NoHistory => write!(f, "no earlier snapshot to step back from"),
+            ReverseError::Diverged => write!(f, "re-execution did not reach the previous instruction"),
+        }
+    }
+}
+
+impl error::Error for ReverseError {}
+
+/// The state of a core at one point of its history.
+pub(super) struct Snapshot<T: InterruptController, A: AddressBus> {
+    position: u64,
+    pc: u32,
+    inactive_ssp: u32,
+    inactive_usp: u32,
+    ir: u16,
+    dar: [u32; 16],
+    s_flag: u32,
+    irq_level: u8,
+    int_mask: u32,
+    int_ctrl: T,
+    x_flag: u32,
+    c_flag: u32,
+    v_flag: u32,
+    n_flag: u32,
+    not_z_flag: u32,
+    t1_flag: u32,
+    prefetch_addr: u32,
+    prefetch_data: u32,
+    processing_state: ProcessingState,
+    mem: A,
+    exceptions_taken: u64,
+    last_exception: Option<Exception>,
+    exception_nesting: u32,
+}
+
+impl<T: InterruptController + Clone, A: AddressBus + Clone> Snapshot<T, A> {
+    pub(super) fn capture(core: &ConfiguredCore<T, A>, position: u64) -> Snapshot<T, A> {
+        Snapshot {
+            position, pc: core.pc, inactive_ssp: core.inactive_ssp, inactive_usp: core.inactive_usp, ir: core.ir,
+            dar: core.dar, s_flag: core.s_flag, irq_level: core.irq_level, int_mask: core.int_mask,
+            int_ctrl: core.int_ctrl.clone(), x_flag: core.x_flag, c_flag: core.c_flag, v_flag: core.v_flag,
+            n_flag: core.n_flag, not_z_flag: core.not_z_flag, t1_flag: core.t1_flag, prefetch_addr: core.prefetch_addr,
+            prefetch_data: core.prefetch_data, processing_state: core.processing_state, mem: core.mem.clone(),
+            exceptions_taken: core.exceptions_taken, last_exception: core.last_exception,
+            exception_nesting: core.exception_nesting,
+        }
+    }
+
+    pub(super) fn restore(&self, core: &mut ConfiguredCore<T, A>) {
+        core.pc = self.pc;
+        core.inactive_ssp = self.inactive_ssp;
+        core.inactive_usp = self.inactive_usp;
+        core.ir = self.ir;
+        core.dar = self.dar;
+        core.s_flag = self.s_flag;
+        core.irq_level = self.irq_level;
+        core.int_mask = self.int_mask;
+        core.int_ctrl = self.int_ctrl.clone();
+        core.x_flag = self.x_flag;
+        core.c_flag = self.c_flag;
+        core.v_flag = self.v_flag;
+        core.n_flag = self.n_flag;
+        core.not_z_flag = self.not_z_flag;
+        core.t1_flag = self.t1_flag;
+        core.prefetch_addr = self.prefetch_addr;
+        core.prefetch_data = self.prefetch_data;
+        core.processing_state = self.processing_state;
+        core.mem = self.mem.clone();
+        core.exceptions_taken = self.exceptions_taken;
+        core.last_exception = self.last_exception;
+        core.exception_nesting = self.exception_nesting;
+    }
+}
+
+/// The snapshots of a core with reverse debugging enabled.
+pub(super) struct History<T: InterruptController, A: AddressBus> {
+    interval: u64,
+    /// Instructions executed since reverse debugging was enabled
+    pub(super) position: u64,
+    snapshots: VecDeque<Snapshot<T, A>>,
+    /// Takes a snapshot; stored here as the core itself can't require Clone
+    pub(super) capture: fn(&ConfiguredCore<T, A>, u64) -> Snapshot<T, A>,
+}
+
+impl<T: InterruptController, A: AddressBus> History<T, A> {
+    pub(super) fn new(interval: u32, capture: fn(&ConfiguredCore<T, A>, u64) -> Snapshot<T, A>) -> History<T, A> {
+        History { interval: u64::from(interval.max(1)), position: 0, snapshots: VecDeque::new(), capture }
+    }
+
+    /// Returns true if a snapshot should be taken before the next instruction.
+    pub(super) fn snapshot_due(&self) -> bool {
+        self.position.is_multiple_of(self.interval) && self.snapshots.back().is_none_or(|last| last.position < self.position)
+    }
+
+    pub(super) fn push(&mut self, snapshot: Snapshot<T, A>) {
+        if self.snapshots.len() == SNAPSHOTS_KEPT {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Drops the snapshots after `position` and returns the latest one left,
+    /// or returns `None` if there is none at or before `position`.
+    pub(super) fn rewind_to(&mut self, position: u64) -> Option<&Snapshot<T, A>> {
+        if self.snapshots.front().is_none_or(|first| first.position > position) {
+            return None;
+        }
+        while self.snapshots.back().is_some_and(|last| last.position > position) {
+            self.snapshots.pop_back();
+        }
+        let snapshot = self.snapshots.back()?;
+        self.position = snapshot.position;
+        Some(snapshot)
+    }
+}
@@ -0,0 +1,232 @@
+//! 68010 exception stack frames.
+//!
+//! From the 68010 on, every exception stack frame carries a format word
+//! telling RTE how much to unstack. [`read_frame`] decodes a frame from
+//! memory into a [`StackFrame`], for tools and for running 68010 system
+//! code that inspects or builds frames.
+//!
+//! A core set to the 68010 model (see
+//! [`ConfiguredCore::set_model`](super::ConfiguredCore::set_model)) stacks
+//! format 0 and format 8 frames, and its RTE takes a Format Error exception
+//! on any other format, including the 68020 formats 1 and 2. The 68000
+//! group 1 and 2 frame is a [`StackFrame::Short`] without the format word.
+
+use crate::ram::{AddressBus, AddressSpace};
+
+/// A decoded exception stack frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackFrame {
+    /// Format 0, the four word frame of most exceptions.
+    Short {
+        /// The stacked status register
+        sr: u16,
+        /// The stacked program counter
+        pc: u32,
+        /// The exception vector number
+        vector: u8,
+    },
+    /// Format 1, the four word throwaway frame of the 68020 interrupt stack.
+    Throwaway {
+        /// The stacked status register
+        sr: u16,
+        /// The stacked program counter
+        pc: u32,
+        /// The exception vector number
+        vector: u8,
+    },
+    /// Format 2, the six word frame of the 68020 CHK, TRAPV and trace exceptions.
+    SixWord {
+        /// The stacked status register
+        sr: u16,
+        /// The stacked program counter
+        pc: u32,
+        /// The exception vector number
+        vector: u8,
+        /// Address of the instruction that caused the exception
+        instruction_address: u32,
+    },
+    /// Format 8, the 29 word bus or address error frame of the 68010.
+    BusFault {
+        /// The stacked status register
+        sr: u16,
+        /// The stacked program counter
+        pc: u32,
+        /// The exception vector number
+        vector: u8,
+        /// The special status word, describing the faulted access
+        special_status: u16,
+        /// The address of the faulted access
+        fault_address: u32,
+        /// The data output buffer
+        data_output: u16,
+        /// The data input buffer
+        data_input: u16,
+        /// The instruction input buffer
+        instruction_input: u16,
+        /// The version number and internal state, needed to continue the
+        /// faulted instruction
+        internal: [u16; 16],
+    },
+}
+
+impl StackFrame {
+    /// Returns the format code, the upper four bits of the format word.
+    pub fn format(&self) -> u8 {
+        match *self {
+            StackFrame::Short { .. } => 0,
+            StackFrame::Throwaway { .. } => 1,
+            StackFrame::SixWord { .. } => 2,
+            StackFrame::BusFault { .. } => 8,
+        }
+    }
+
+    /// Returns the size of the frame on the stack in bytes, which is what
+    /// RTE adds to the stack pointer.
+    pub fn length(&self) -> u32 {
+        format_length(self.format()).unwrap_or(0)
+    }
+
+    /// Returns the stacked status register.
+    pub fn sr(&self) -> u16 {
+        match *self {
+            StackFrame::Short { sr, .. } | StackFrame::Throwaway { sr, .. } |
+            StackFrame::SixWord { sr, .. } | StackFrame::BusFault { sr, .. } => sr,
+        }
+    }
+
+    /// Returns the stacked program counter.
+    pub fn pc(&self) -> u32 {
+        match *self {
+            StackFrame::Short { pc, .. } | StackFrame::Throwaway { pc, .. } |
+            StackFrame::SixWord { pc, .. } | StackFrame::BusFault { pc, .. } => pc,
+        }
+    }
+
+    /// Returns the exception vector number.
+    pub fn vector(&self) -> u8 {
+        match *self {
+            StackFrame::Short { vector, .. } | StackFrame::Throwaway { vector, .. } |
+            StackFrame::SixWord { vector, .. } | StackFrame::BusFault { vector, .. } => vector,
+        }
+    }
+}
+
+/// Returns the frame length in bytes for a format code, or `None` if the
+/// format is not one of [`StackFrame`]'s.
+pub fn format_length(format: u8) -> Option<u32> {
+    match format {
+        0 | 1 => Some(8),
+        2 => Some(12),
+        8 => Some(58),
+        _ => None,
+    }
+}
+
+/// Reads the exception stack frame at `sp` in `address_space`.
+///
+/// Returns `None` if the format word holds a format [`StackFrame`] does
+/// not define. RTE on a 68010 takes a Format Error exception (vector 14)
+/// for such a frame, without unstacking it.
+///
+/// # Example
+///
+/// ```rust
+/// use r68k::cpu::stackframe::{read_frame, StackFrame};
+/// use r68k::ram::{AddressBus, PagedMem, SUPERVISOR_DATA};
+///
+/// let mut mem = PagedMem::new(0);
+/// // TRAP #0 frame: SR, PC, format 0 with vector offset 32 * 4
+/// mem.write_word(SUPERVISOR_DATA, 0x1000, 0x2000);
+/// mem.write_long(SUPERVISOR_DATA, 0x1002, 0x0000_4002);
+/// mem.write_word(SUPERVISOR_DATA, 0x1006, 0x0080);
+/// let frame = read_frame(&mem, SUPERVISOR_DATA, 0x1000);
+/// assert_eq!(Some(StackFrame::Short { sr: 0x2000, pc: 0x4002, vector: 32 }), frame);
+/// assert_eq!(8, frame.unwrap().length());
+/// ```
+pub fn read_frame<A: AddressBus>(mem: &A, address_space: AddressSpace, sp: u32) -> Option<StackFrame> {
+    let word = |offset: u32| mem.read_word(address_space, sp.wrapping_add(offset)) as u16;
+    let long = |offset: u32| mem.read_long(address_space, sp.wrapping_add(offset));
+    let sr = word(0);
+    let pc = long(2);
+    let format_word = word(6);
+    let vector = ((format_word & 0x0fff) >> 2) as u8;
+    let frame = match format_word >> 12 {
+        0 => StackFrame::Short { sr, pc, vector },
+        1 => StackFrame::Throwaway { sr, pc, vector },
+        2 => StackFrame::SixWord { sr, pc, vector, instruction_address: long(8) },
+        8 => {
+            let mut internal = [0; 16];
+            for (i, slot) in internal.iter_mut().enumerate() {
+                *slot = word(26 + 2 * i as u32);
+            }
+            StackFrame::BusFault {
+                sr, pc, vector,
+                special_status: word(8),
+                fault_address: long(10),
+                data_output: word(16),
+                data_input: word(20),
+                instruction_input: word(24),
+                internal,
+            }
+        }
+        _ => return None,
+    };
+    Some(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_frame, StackFrame};
+    use crate::ram::{AddressBus, PagedMem, SUPERVISOR_DATA};
+
+    fn stack(words: &[u16]) -> PagedMem {
+        let mut mem = PagedMem::new(0);
+        for (offset, word) in words.iter().enumerate() {
+            mem.write_word(SUPERVISOR_DATA, 0x1000 + 2 * offset as u32, u32::from(*word));
+        }
+        mem
+    }
+
+    #[test]
+    fn short_frames_are_decoded() {
+        let mem = stack(&[0x0015, 0x00ff, 0x0042, 0x0080]);
+        let frame = read_frame(&mem, SUPERVISOR_DATA, 0x1000).unwrap();
+        assert_eq!(StackFrame::Short { sr: 0x15, pc: 0xff_0042, vector: 32 }, frame);
+        assert_eq!((0, 8), (frame.format(), frame.length()));
+
+        let mem = stack(&[0x2700, 0x0000, 0x1234, 0x1078]);
+        let frame = read_frame(&mem, SUPERVISOR_DATA, 0x1000).unwrap();
+        assert_eq!(StackFrame::Throwaway { sr: 0x2700, pc: 0x1234, vector: 30 }, frame);
+        assert_eq!((1, 8), (frame.format(), frame.length()));
+
+        let mem = stack(&[0x0004, 0x0001, 0x0000, 0x2018, 0x0000, 0x0ffe]);
+        let frame = read_frame(&mem, SUPERVISOR_DATA, 0x1000).unwrap();
+        assert_eq!(StackFrame::SixWord { sr: 4, pc: 0x10000, vector: 6, instruction_address: 0xffe }, frame);
+        assert_eq!((2, 12), (frame.format(), frame.length()));
+        assert_eq!((4, 0x10000, 6), (frame.sr(), frame.pc(), frame.vector()));
+    }
+
+    #[test]
+    fn bus_fault_frame_is_decoded() {
+        let mut words = vec![0x2000, 0x0000, 0x4000, 0x8008, 0x0105, 0x0012, 0x3456, 0, 0xaaaa, 0, 0x5555, 0, 0x4e71];
+        words.extend(1..=16);
+        let frame = read_frame(&stack(&words), SUPERVISOR_DATA, 0x1000).unwrap();
+        let mut internal = [0; 16];
+        for (i, slot) in internal.iter_mut().enumerate() {
+            *slot = i as u16 + 1;
+        }
+        assert_eq!(StackFrame::BusFault {
+            sr: 0x2000, pc: 0x4000, vector: 2, special_status: 0x0105, fault_address: 0x0012_3456,
+            data_output: 0xaaaa, data_input: 0x5555, instruction_input: 0x4e71, internal,
+        }, frame);
+        assert_eq!(58, frame.length());
+    }
+
+    #[test]
+    fn unknown_formats_are_rejected() {
+        for format in [3u16, 4, 7, 9, 0xa, 0xf].iter() {
+            let mem = stack(&[0x2000, 0, 0x1000, format << 12]);
+            assert_eq!(None, read_frame(&mem, SUPERVISOR_DATA, 0x1000), "format {}", format);
+        }
+    }
+}
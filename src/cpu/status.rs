@@ -0,0 +1,124 @@
+//! Decoded view of the 68000 status register.
+
+/// The status register split into its named fields.
+///
+/// Converting to and from the raw `u16` masks out the bits that are not
+/// implemented on the 68000, so a `StatusRegister` can never carry reserved
+/// bits into the core.
+///
+/// ```text
+/// 15 14 13 12 11 10  9  8  7  6  5  4  3  2  1  0
+///  T  -  S  -  - I2 I1 I0  -  -  -  X  N  Z  V  C
+/// ```
+///
+/// # Example
+///
+/// ```rust
+/// use r68k::cpu::StatusRegister;
+///
+/// let mut sr = StatusRegister::from_bits(0x2700);
+/// assert!(sr.supervisor);
+/// assert_eq!(7, sr.interrupt_mask);
+///
+/// sr.interrupt_mask = 3;
+/// sr.carry = true;
+/// assert_eq!(0x2301, sr.to_bits());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatusRegister {
+    /// Trace mode (T).
+    pub trace: bool,
+    /// Supervisor mode (S).
+    pub supervisor: bool,
+    /// Interrupt priority mask, 0-7.
+    pub interrupt_mask: u8,
+    /// Extend flag (X).
+    pub extend: bool,
+    /// Negative flag (N).
+    pub negative: bool,
+    /// Zero flag (Z).
+    pub zero: bool,
+    /// Overflow flag (V).
+    pub overflow: bool,
+    /// Carry flag (C).
+    pub carry: bool,
+}
+
+impl StatusRegister {
+    /// Bits of the status register implemented on the 68000.
+    pub const VALID_BITS: u16 = 0xa71f;
+
+    /// Decodes a raw status register value, ignoring reserved bits.
+    pub fn from_bits(sr: u16) -> StatusRegister {
+        StatusRegister {
+            trace: sr & 0x8000 != 0,
+            supervisor: sr & 0x2000 != 0,
+            interrupt_mask: ((sr >> 8) & 7) as u8,
+            extend: sr & 0x10 != 0,
+            negative: sr & 0x08 != 0,
+            zero: sr & 0x04 != 0,
+            overflow: sr & 0x02 != 0,
+            carry: sr & 0x01 != 0,
+        }
+    }
+
+    /// Encodes the fields as a raw status register value.
+    ///
+    /// Only the low three bits of `interrupt_mask` are used.
+    pub fn to_bits(&self) -> u16 {
+        u16::from(self.trace) << 15
+            | u16::from(self.supervisor) << 13
+            | u16::from(self.interrupt_mask & 7) << 8
+            | u16::from(self.extend) << 4
+            | u16::from(self.negative) << 3
+            | u16::from(self.zero) << 2
+            | u16::from(self.overflow) << 1
+            | u16::from(self.carry)
+    }
+
+    /// Returns the condition code register (the low byte of the status register).
+    pub fn ccr(&self) -> u16 {
+        self.to_bits() & 0xff
+    }
+}
+
+impl From<u16> for StatusRegister {
+    fn from(sr: u16) -> StatusRegister {
+        StatusRegister::from_bits(sr)
+    }
+}
+
+impl From<StatusRegister> for u16 {
+    fn from(sr: StatusRegister) -> u16 {
+        sr.to_bits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatusRegister;
+
+    #[test]
+    fn round_trip_masks_reserved_bits() {
+        for sr in 0..=0xffffu16 {
+            assert_eq!(sr & StatusRegister::VALID_BITS, StatusRegister::from_bits(sr).to_bits());
+        }
+    }
+
+    #[test]
+    fn decodes_each_field() {
+        let sr = StatusRegister::from_bits(0xa51f);
+        assert_eq!(StatusRegister {
+            trace: true, supervisor: true, interrupt_mask: 5,
+            extend: true, negative: true, zero: true, overflow: true, carry: true,
+        }, sr);
+        assert_eq!(0x1f, sr.ccr());
+        assert_eq!(StatusRegister::default(), StatusRegister::from_bits(0x58e0));
+    }
+
+    #[test]
+    fn to_bits_ignores_out_of_range_mask() {
+        let sr = StatusRegister { interrupt_mask: 0xff, ..StatusRegister::default() };
+        assert_eq!(0x0700, u16::from(sr));
+    }
+}
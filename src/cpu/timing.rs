@@ -0,0 +1,169 @@
+//! Per-opcode cycle counts.
+//!
+//! [`base_cycles`] returns the number of clock cycles the interpreter
+//! charges for an opcode, including effective address calculation, and
+//! [`cycle_variation`] tells which opcodes can take longer depending on
+//! register or flag contents. Together they describe the timing model the
+//! emulator implements, e.g. to cross-check it against the 68000 user's
+//! manual or to estimate the running time of a piece of code statically.
+//!
+//! Wait states added by the [`AddressBus`] are not
+//! included.
+
+use std::sync::OnceLock;
+use crate::ram::{AddressBus, PagedMem, SUPERVISOR_PROGRAM};
+use super::{ops, Cpu};
+
+/// How the cycle count of an opcode can exceed its [`base_cycles`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CycleVariation {
+    /// Shifts and rotates by a register count take 2 more cycles per bit
+    /// shifted.
+    ShiftCount,
+    /// `MOVEM` takes 4 more cycles per word register, or 8 per long
+    /// register, moved.
+    RegisterCount,
+    /// `Bcc`, `DBcc` and `Scc Dn` take a different number of cycles
+    /// depending on the condition and, for `DBcc`, the loop counter. The
+    /// base count is the fastest case: a `Bcc.B` not taken, a `Bcc.W`
+    /// taken, a `DBcc` looping back and an `Scc` with a false condition.
+    Condition,
+    /// `CHK`, `TRAPV`, `DIVU` and `DIVS` take longer when they raise their
+    /// exception.
+    Exception,
+}
+
+/// Returns the fewest cycles the interpreter charges for `opcode`, or
+/// `None` if the opcode is not legal.
+///
+/// For instructions that always raise an exception, like `TRAP #n`, the
+/// count includes exception processing. Where the count depends on data,
+/// see [`cycle_variation`].
+///
+/// The table is built on first use by executing every legal opcode on a
+/// scratch CPU, so it always agrees with the interpreter.
+///
+/// # Example
+///
+/// ```rust
+/// use r68k::cpu::timing::base_cycles;
+///
+/// assert_eq!(Some(4), base_cycles(0x4e71));  // NOP
+/// assert_eq!(Some(8), base_cycles(0x3010));  // MOVE.W (A0), D0
+/// assert_eq!(None, base_cycles(0x4afc));     // ILLEGAL
+/// ```
+pub fn base_cycles(opcode: u16) -> Option<u16> {
+    static BASE_CYCLES: OnceLock<Vec<Option<u16>>> = OnceLock::new();
+    BASE_CYCLES.get_or_init(measure_all)[opcode as usize]
+}
+
+/// Returns how the cycle count of `opcode` depends on data, or `None` if
+/// it is always [`base_cycles`] (or the opcode is not legal).
+pub fn cycle_variation(opcode: u16) -> Option<CycleVariation> {
+    const CONDITIONS: [&str; 14] = ["hi", "ls", "cc", "cs", "ne", "eq", "vc", "vs", "pl", "mi", "ge", "lt", "gt", "le"];
+    if !ops::is_legal_opcode(opcode) {
+        return None;
+    }
+    let mut parts = ops::handler_name(opcode).split('_');
+    let mnemonic = parts.next().unwrap_or("");
+    let operands: Vec<&str> = parts.collect();
+    let shift = ["asl", "asr", "lsl", "lsr", "rol", "ror", "roxl", "roxr"].contains(&mnemonic);
+    let condition = |prefix: &str| mnemonic.strip_prefix(prefix).is_some_and(|cc| CONDITIONS.contains(&cc));
+    match mnemonic {
+        _ if shift && operands.last() == Some(&"r") => Some(CycleVariation::ShiftCount),
+        "movem" => Some(CycleVariation::RegisterCount),
+        "dbf" => Some(CycleVariation::Condition),
+        _ if condition("b") || condition("db") => Some(CycleVariation::Condition),
+        _ if condition("s") && operands.last() == Some(&"dn") => Some(CycleVariation::Condition),
+        "chk" | "trapv" | "divu" | "divs" => Some(CycleVariation::Exception),
+        _ => None,
+    }
+}
+
+const CODE: u32 = 0x1000;
+const STACK: u32 = 0x4000;
+
+// Register, flag and memory contents to execute each opcode with. Zero
+// registers give the shortest shifts, and zero memory an empty MOVEM
+// register list. The others have even, non-zero registers and extension
+// words, so that divisions don't trap and DBcc loops, and between them
+// make every condition but T false at least once.
+const SETUPS: [(u32, u16); 4] = [
+    (0, 0x2700),
+    (0x0002_0002, 0x2700),
+    (0x0002_0002, 0x271f),
+    (0x0002_0002, 0x2708),
+];
+
+fn measure_all() -> Vec<Option<u16>> {
+    let mut cpu = Cpu::new(CODE);
+    (0..=0xffff).map(|opcode| {
+        if !ops::is_legal_opcode(opcode) {
+            return None;
+        }
+        let mut fastest = None;
+        let mut fastest_exception = None;
+        for &(contents, sr) in &SETUPS {
+            let (cycles, exception) = measure(&mut cpu, opcode, contents, sr);
+            let best = if exception { &mut fastest_exception } else { &mut fastest };
+            *best = Some(best.map_or(cycles, |best: u16| best.min(cycles)));
+        }
+        fastest.or(fastest_exception)
+    }).collect()
+}
+
+fn measure(cpu: &mut Cpu, opcode: u16, contents: u32, sr: u16) -> (u16, bool) {
+    cpu.mem = PagedMem::new(contents);
+    cpu.mem.write_word(SUPERVISOR_PROGRAM, CODE, u32::from(opcode));
+    cpu.dar = [contents; 16];
+    cpu.reset_to(STACK, CODE);
+    cpu.sr_to_flags(sr);
+    let exceptions_taken = cpu.exceptions_taken;
+    let cycles = cpu.execute1();
+    (cycles.0 as u16, cpu.exceptions_taken != exceptions_taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base_cycles, cycle_variation, CycleVariation};
+
+    #[test]
+    fn base_cycles_include_effective_address_calculation() {
+        assert_eq!(Some(4), base_cycles(0x3001));  // MOVE.W D1, D0
+        assert_eq!(Some(8), base_cycles(0x3010));  // MOVE.W (A0), D0
+        assert_eq!(Some(12), base_cycles(0x3038)); // MOVE.W $xxxx.W, D0
+        assert_eq!(Some(20), base_cycles(0x2039)); // MOVE.L $xxxxxxxx.L, D0
+        assert_eq!(Some(140), base_cycles(0x80c1)); // DIVU D1, D0
+        assert_eq!(Some(38), base_cycles(0x4e41)); // TRAP #1
+        assert_eq!(None, base_cycles(0xa000));
+    }
+
+    #[test]
+    fn base_cycles_are_the_fastest_case() {
+        assert_eq!(Some(6), base_cycles(0xe268));  // LSR.W D1, D0
+        assert_eq!(Some(8), base_cycles(0x6702));  // BEQ.B, not taken
+        assert_eq!(Some(10), base_cycles(0x6700)); // BEQ.W, taken
+        assert_eq!(Some(10), base_cycles(0x6002)); // BRA.B
+        assert_eq!(Some(10), base_cycles(0x51c8)); // DBF D0, looping
+        assert_eq!(Some(12), base_cycles(0x50c8)); // DBT D0
+        assert_eq!(Some(4), base_cycles(0x57c0));  // SEQ D0, false
+        assert_eq!(Some(6), base_cycles(0x50c0));  // ST D0
+        assert_eq!(Some(12), base_cycles(0x4c90)); // MOVEM.W (A0), no registers
+        assert_eq!(Some(10), base_cycles(0x4181)); // CHK D1, D0
+    }
+
+    #[test]
+    fn variation_is_reported_for_data_dependent_opcodes() {
+        assert_eq!(Some(CycleVariation::ShiftCount), cycle_variation(0xe268)); // LSR.W D1, D0
+        assert_eq!(None, cycle_variation(0xe248));                              // LSR.W #1, D0
+        assert_eq!(Some(CycleVariation::RegisterCount), cycle_variation(0x4c90));
+        assert_eq!(Some(CycleVariation::Condition), cycle_variation(0x6702));
+        assert_eq!(Some(CycleVariation::Condition), cycle_variation(0x51c8));
+        assert_eq!(Some(CycleVariation::Condition), cycle_variation(0x57c0));
+        assert_eq!(None, cycle_variation(0x57d0));                              // SEQ (A0)
+        assert_eq!(None, cycle_variation(0x6002));                              // BRA.B
+        assert_eq!(None, cycle_variation(0x50c8));                              // DBT
+        assert_eq!(Some(CycleVariation::Exception), cycle_variation(0x80c1));
+        assert_eq!(None, cycle_variation(0x4e71));
+    }
+}
@@ -0,0 +1,63 @@
+//! A ready-made system for experiments.
+//!
+//! [`minimal_system`] builds a [`Cpu`] with a [`PagedMem`] holding reset
+//! vectors and a program, and resets it, so trying out a piece of 68000 code
+//! takes two lines. It is a convenience for examples, tests and
+//! exploration; a real system will want its own memory map and interrupt
+//! controller, see [`ConfiguredCore::new_with`](crate::cpu::ConfiguredCore::new_with).
+//!
+//! ```rust
+//! use r68k::demo::minimal_system;
+//!
+//! // MOVEQ #42, D0
+//! let mut cpu = minimal_system(&[0x70, 0x2a]);
+//! cpu.execute1();
+//! assert_eq!(42, cpu.dar[0]);
+//! ```
+
+use crate::cpu::Cpu;
+use crate::interrupts::AutoInterruptController;
+use crate::ram::{AddressBus, PagedMem, SUPERVISOR_DATA, SUPERVISOR_PROGRAM};
+
+/// Address the program is loaded to and started at.
+pub const PROGRAM_ADDRESS: u32 = 0x1000;
+
+/// Initial supervisor stack pointer, the top of the first megabyte.
+pub const STACK_TOP: u32 = 0x0010_0000;
+
+/// Returns a reset CPU about to execute `program`.
+///
+/// Memory is a [`PagedMem`] reading zero where nothing was written, with
+/// the initial SSP at address 0 set to [`STACK_TOP`], the initial PC at
+/// address 4 set to [`PROGRAM_ADDRESS`] and `program` loaded there. Other
+/// exception vectors are zero, so an exception jumps to address 0.
+/// Interrupts come from an [`AutoInterruptController`], and the CPU starts
+/// in supervisor mode with all interrupts masked, as after a hardware reset.
+pub fn minimal_system(program: &[u8]) -> Cpu {
+    let mut mem = PagedMem::new(0);
+    mem.write_long(SUPERVISOR_DATA, 0, STACK_TOP);
+    mem.write_long(SUPERVISOR_DATA, 4, PROGRAM_ADDRESS);
+    for (offset, byte) in program.iter().enumerate() {
+        mem.write_byte(SUPERVISOR_PROGRAM, PROGRAM_ADDRESS.wrapping_add(offset as u32), u32::from(*byte));
+    }
+    let mut cpu = Cpu::new_with(0, AutoInterruptController::new(), mem);
+    cpu.reset();
+    cpu
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{minimal_system, PROGRAM_ADDRESS, STACK_TOP};
+
+    #[test]
+    fn minimal_system_is_reset_to_the_program() {
+        // MOVE.L #$12345678, -(A7) ; STOP #$2700
+        let mut cpu = minimal_system(&[0x2f, 0x3c, 0x12, 0x34, 0x56, 0x78, 0x4e, 0x72, 0x27, 0x00]);
+        assert_eq!(PROGRAM_ADDRESS, cpu.pc);
+        assert_eq!(STACK_TOP, cpu.dar[15]);
+        assert_eq!(0x2700, cpu.status_register());
+        cpu.execute(100);
+        assert_eq!(STACK_TOP - 4, cpu.dar[15]);
+        assert_eq!(PROGRAM_ADDRESS + 10, cpu.pc);
+    }
+}
@@ -88,7 +88,7 @@ const AUTOVECTOR_BASE: u8 = 0x18;
 /// assert_eq!(ctrl.acknowledge_interrupt(5), Some(29));
 /// assert_eq!(ctrl.highest_priority(), 0);
 /// ```
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct AutoInterruptController {
     level: u8
 }
@@ -133,10 +133,108 @@ impl InterruptController for AutoInterruptController {
 }
 
 
+/// Interrupt source that fires at a fixed cycle period, like a timer chip.
+///
+/// Wraps another controller and adds one periodic interrupt at `level`,
+/// acknowledged with `vector`. Requests from the wrapped controller are
+/// passed through unchanged. Time only advances through
+/// [`advance()`](Self::advance); use
+/// [`ConfiguredCore::execute_timed()`](crate::cpu::ConfiguredCore::execute_timed)
+/// to have the core advance it as it executes, so that the interrupt is
+/// requested at the first instruction boundary after each period elapses.
+///
+/// If a period elapses while the previous request is still pending, the
+/// requests are merged, as with a real interrupt line that is already
+/// asserted.
+///
+/// # Example
+///
+/// ```rust
+/// use r68k::interrupts::{AutoInterruptController, InterruptController, PeriodicInterrupt};
+///
+/// // 200 Hz timer at 8 MHz, on level 6 using the level 6 autovector
+/// let mut timer = PeriodicInterrupt::new(AutoInterruptController::new(), 40000, 6, 30);
+///
+/// timer.advance(39996);
+/// assert_eq!(timer.highest_priority(), 0);
+/// timer.advance(4);
+/// assert_eq!(timer.highest_priority(), 6);
+/// assert_eq!(timer.acknowledge_interrupt(6), Some(30));
+/// assert_eq!(timer.highest_priority(), 0);
+/// ```
+#[derive(Clone)]
+pub struct PeriodicInterrupt<C: InterruptController> {
+    /// The wrapped controller, for requesting other interrupts
+    pub inner: C,
+    period: i32,
+    level: u8,
+    vector: u8,
+    countdown: i32,
+    pending: bool,
+}
+
+impl<C: InterruptController> PeriodicInterrupt<C> {
+    /// Creates a periodic interrupt of the given `period` (in cycles),
+    /// priority `level` (1-7) and exception `vector`, in front of `inner`.
+    ///
+    /// The first interrupt is requested once `period` cycles have elapsed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is not positive or `level` is not in the range 1-7.
+    pub fn new(inner: C, period: i32, level: u8, vector: u8) -> PeriodicInterrupt<C> {
+        assert!(period > 0);
+        assert!(level > 0 && level < 8);
+        PeriodicInterrupt { inner, period, level, vector, countdown: period, pending: false }
+    }
+
+    /// Advances time by `cycles`, requesting the interrupt if a period elapsed.
+    pub fn advance(&mut self, cycles: i32) {
+        self.countdown -= cycles;
+        if self.countdown <= 0 {
+            self.pending = true;
+            self.countdown = self.period + self.countdown % self.period;
+        }
+    }
+
+    /// Returns the number of cycles until the interrupt is next requested.
+    pub fn cycles_until_next(&self) -> i32 {
+        self.countdown
+    }
+
+    /// Returns the period in cycles.
+    pub fn period(&self) -> i32 {
+        self.period
+    }
+}
+
+impl<C: InterruptController> InterruptController for PeriodicInterrupt<C> {
+    fn reset_external_devices(&mut self) {
+        self.pending = false;
+        self.countdown = self.period;
+        self.inner.reset_external_devices();
+    }
+
+    fn highest_priority(&self) -> u8 {
+        let own = if self.pending { self.level } else { 0 };
+        own.max(self.inner.highest_priority())
+    }
+
+    fn acknowledge_interrupt(&mut self, priority: u8) -> Option<u8> {
+        if self.pending && priority == self.level {
+            self.pending = false;
+            Some(self.vector)
+        } else {
+            self.inner.acknowledge_interrupt(priority)
+        }
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::{InterruptController, AutoInterruptController,
-        AUTOVECTOR_BASE};
+        AUTOVECTOR_BASE, PeriodicInterrupt};
 
     #[test]
     fn keeps_track_of_priority() {
@@ -161,4 +259,28 @@ mod tests {
         ctrl.reset_external_devices();
         assert_eq!(0, ctrl.highest_priority());
     }
-}
\ No newline at end of file
+    #[test]
+    fn periodic_interrupt_fires_every_period() {
+        let mut timer = PeriodicInterrupt::new(AutoInterruptController::new(), 100, 4, 0x40);
+        timer.advance(250);
+        assert_eq!(4, timer.highest_priority());
+        assert_eq!(50, timer.cycles_until_next());
+        assert_eq!(Some(0x40), timer.acknowledge_interrupt(4));
+        assert_eq!(0, timer.highest_priority());
+        timer.advance(50);
+        assert_eq!(4, timer.highest_priority());
+        assert_eq!(100, timer.cycles_until_next());
+    }
+    #[test]
+    fn periodic_interrupt_passes_through_other_requests() {
+        let mut timer = PeriodicInterrupt::new(AutoInterruptController::new(), 100, 4, 0x40);
+        timer.inner.request_interrupt(6);
+        timer.advance(100);
+        assert_eq!(6, timer.highest_priority());
+        assert_eq!(Some(AUTOVECTOR_BASE + 6), timer.acknowledge_interrupt(6));
+        assert_eq!(4, timer.highest_priority());
+        timer.reset_external_devices();
+        assert_eq!(0, timer.highest_priority());
+        assert_eq!(100, timer.cycles_until_next());
+    }
+}
@@ -17,6 +17,20 @@
 //!
 //! ## Quick Start
 //!
+//! For experiments, [`demo::minimal_system`] sets up memory with reset
+//! vectors and a program, and returns a CPU ready to run it:
+//!
+//! ```rust
+//! use r68k::demo::minimal_system;
+//!
+//! // MOVEQ #42, D0
+//! let mut cpu = minimal_system(&[0x70, 0x2a]);
+//! cpu.execute1();
+//! assert_eq!(42, cpu.dar[0]);
+//! ```
+//!
+//! Building the same system by hand:
+//!
 //! ```rust
 //! use r68k::cpu::ConfiguredCore;
 //! use r68k::ram::PagedMem;
@@ -113,7 +127,7 @@
 //!
 //! ## Exception Handling
 //!
-//! Use [`Callbacks`](cpu::Callbacks) to intercept exceptions:
+//! Use [`Callbacks`] to intercept exceptions:
 //!
 //! ```rust
 //! use r68k::cpu::{Callbacks, Core, Cycles, Exception, Result};
@@ -136,10 +150,11 @@
 //!
 //! ## Architecture
 //!
-//! - [`cpu`] - CPU emulation core with [`ConfiguredCore`](cpu::ConfiguredCore) and [`Core`](cpu::Core) trait
-//! - [`ram`] - Memory interface with [`AddressBus`] trait and [`PagedMem`](ram::PagedMem) implementation
+//! - [`cpu`] - CPU emulation core with [`ConfiguredCore`] and [`Core`] trait
+//! - [`ram`] - Memory interface with [`AddressBus`] trait and [`PagedMem`] implementation
 //! - [`interrupts`] - Interrupt handling with [`InterruptController`] trait
 //! - [`common`] - Shared constants and opcode definitions
+//! - [`demo`] - A minimal ready-to-run system for experiments
 //!
 //! [`AddressBus`]: ram::AddressBus
 //! [`InterruptController`]: interrupts::InterruptController
@@ -148,6 +163,7 @@ pub mod common;
 pub mod cpu;
 pub mod ram;
 pub mod interrupts;
+pub mod demo;
 
 // Re-export commonly used types at crate root for convenience
 pub use cpu::{Cpu, ConfiguredCore, Core, Cycles, Callbacks, Exception, ProcessingState, Result};
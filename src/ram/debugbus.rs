@@ -0,0 +1,174 @@
+//! Byte order checks for custom memory implementations.
+//!
+//! [`DebugBus`] wraps any [`AddressBus`] and verifies that every word and
+//! long word read agrees with the big-endian combination of the byte reads
+//! at the same addresses. A little-endian or half-swapped `read_word` /
+//! `read_long` is the most common mistake in a custom bus, and it usually
+//! shows up much later as mysteriously wrong data.
+//!
+//! ```rust
+//! use r68k::cpu::ConfiguredCore;
+//! use r68k::interrupts::AutoInterruptController;
+//! use r68k::ram::PagedMem;
+//! use r68k::ram::debugbus::DebugBus;
+//!
+//! let mem = DebugBus::new(PagedMem::new(0));
+//! let mut cpu = ConfiguredCore::new_with(0x1000, AutoInterruptController::new(), mem);
+//! ```
+
+use std::cell::RefCell;
+use super::{AddressSpace, AddressBus, BusWrapper};
+
+/// A word or long word read that disagrees with its byte reads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EndianMismatch {
+    /// Address space of the read.
+    pub address_space: AddressSpace,
+    /// Address of the read.
+    pub address: u32,
+    /// Size of the read in bytes, 2 or 4.
+    pub size: u8,
+    /// The value returned by `read_word` or `read_long`.
+    pub value: u32,
+    /// The value assembled from `read_byte`, most significant byte first.
+    pub expected: u32,
+}
+
+/// An [`AddressBus`] wrapper that checks word and long reads for byte order.
+///
+/// Checking is enabled by default in debug builds only. Each checked read
+/// also reads the individual bytes from the wrapped bus, so reads of
+/// memory-mapped registers with side effects should not be checked; turn
+/// checking off with [`set_enabled`](Self::set_enabled) around such code, or
+/// only wrap plain memory. By default a mismatch panics; with
+/// [`set_panic_on_mismatch(false)`](Self::set_panic_on_mismatch) mismatches
+/// are recorded instead, see [`mismatches`](Self::mismatches).
+pub struct DebugBus<M: AddressBus> {
+    /// The wrapped memory.
+    pub mem: M,
+    enabled: bool,
+    panic_on_mismatch: bool,
+    mismatches: RefCell<Vec<EndianMismatch>>,
+}
+
+impl<M: AddressBus> DebugBus<M> {
+    /// Wraps `mem`, checking reads in debug builds and panicking on mismatch.
+    pub fn new(mem: M) -> DebugBus<M> {
+        DebugBus { mem, enabled: cfg!(debug_assertions), panic_on_mismatch: true, mismatches: RefCell::new(Vec::new()) }
+    }
+
+    /// Enables or disables checking.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Returns whether reads are checked.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Sets whether a mismatch panics (the default) or is recorded.
+    pub fn set_panic_on_mismatch(&mut self, panic: bool) {
+        self.panic_on_mismatch = panic;
+    }
+
+    /// Returns the mismatches recorded so far.
+    pub fn mismatches(&self) -> Vec<EndianMismatch> {
+        self.mismatches.borrow().clone()
+    }
+
+    /// Clears the recorded mismatches.
+    pub fn clear_mismatches(&mut self) {
+        self.mismatches.borrow_mut().clear();
+    }
+
+    fn check(&self, address_space: AddressSpace, address: u32, size: u8, value: u32) -> u32 {
+        if !self.enabled {
+            return value;
+        }
+        let expected = (0..u32::from(size)).fold(0, |acc, offset| {
+            (acc << 8) | (self.mem.read_byte(address_space, address.wrapping_add(offset)) & 0xff)
+        });
+        if value != expected {
+            let mismatch = EndianMismatch { address_space, address, size, value, expected };
+            if self.panic_on_mismatch {
+                panic!("inconsistent byte order: read of {} bytes at {:08x} in {:?} returned {:08x}, bytes give {:08x}",
+                    size, address, address_space, value, expected);
+            }
+            self.mismatches.borrow_mut().push(mismatch);
+        }
+        value
+    }
+}
+
+impl<M: AddressBus> BusWrapper for DebugBus<M> {
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.mem
+    }
+
+    fn inner_mut(&mut self) -> &mut M {
+        &mut self.mem
+    }
+
+    fn read_word(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.check(address_space, address, 2, self.mem.read_word(address_space, address))
+    }
+
+    fn read_long(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.check(address_space, address, 4, self.mem.read_long(address_space, address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DebugBus, EndianMismatch};
+    use crate::ram::{AddressBus, AddressSpace, PagedMem, SUPERVISOR_DATA};
+
+    /// Stores bytes correctly, but assembles words little-endian.
+    struct LittleEndianWords(PagedMem);
+
+    impl crate::ram::BusWrapper for LittleEndianWords {
+        type Inner = PagedMem;
+        fn inner(&self) -> &PagedMem {
+            &self.0
+        }
+        fn inner_mut(&mut self) -> &mut PagedMem {
+            &mut self.0
+        }
+        fn read_word(&self, address_space: AddressSpace, address: u32) -> u32 {
+            self.0.read_word(address_space, address).swap_bytes() >> 16
+        }
+    }
+
+    #[test]
+    fn consistent_bus_passes() {
+        let mut mem = DebugBus::new(PagedMem::new(0));
+        mem.set_enabled(true);
+        mem.write_long(SUPERVISOR_DATA, 0x1000, 0x1234_5678);
+        assert_eq!(0x1234_5678, mem.read_long(SUPERVISOR_DATA, 0x1000));
+        assert_eq!(0x5678, mem.read_word(SUPERVISOR_DATA, 0x1002));
+        assert!(mem.mismatches().is_empty());
+    }
+
+    #[test]
+    fn swapped_word_is_recorded() {
+        let mut mem = DebugBus::new(LittleEndianWords(PagedMem::new(0)));
+        mem.set_enabled(true);
+        mem.set_panic_on_mismatch(false);
+        mem.write_word(SUPERVISOR_DATA, 0x1000, 0x1234);
+        assert_eq!(0x3412, mem.read_word(SUPERVISOR_DATA, 0x1000));
+        assert_eq!(vec![EndianMismatch { address_space: SUPERVISOR_DATA, address: 0x1000, size: 2, value: 0x3412, expected: 0x1234 }],
+                   mem.mismatches());
+    }
+
+    #[test]
+    #[should_panic(expected = "inconsistent byte order")]
+    fn swapped_word_panics_by_default() {
+        let mut mem = DebugBus::new(LittleEndianWords(PagedMem::new(0)));
+        mem.set_enabled(true);
+        mem.write_word(SUPERVISOR_DATA, 0x1000, 0x1234);
+        mem.read_word(SUPERVISOR_DATA, 0x1000);
+    }
+}
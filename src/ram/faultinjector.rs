@@ -0,0 +1,192 @@
+//! Scheduled bus errors for testing fault handlers.
+//!
+//! [`FaultInjector`] wraps any [`AddressBus`] and terminates selected accesses
+//! with a bus error, either on the Nth access touching an address or on the
+//! first access once a given cycle count has been reached.
+//!
+//! ```rust
+//! use r68k::cpu::ConfiguredCore;
+//! use r68k::interrupts::AutoInterruptController;
+//! use r68k::ram::PagedMem;
+//! use r68k::ram::faultinjector::FaultInjector;
+//!
+//! let mut mem = FaultInjector::new(PagedMem::new(0));
+//! // the second access to 0x2000 fails
+//! mem.fail_nth_access(0x2000, 2);
+//! let mut cpu = ConfiguredCore::new_with(0x1000, AutoInterruptController::new(), mem);
+//! ```
+
+use std::cell::Cell;
+use super::{AddressSpace, AddressBus, BusWrapper};
+
+/// A scheduled fault.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail the `nth` (1-based) access that touches `address`.
+    NthAccess {
+        /// Address that must be covered by the access.
+        address: u32,
+        /// Which matching access fails.
+        nth: u32,
+    },
+    /// Fail the first access made at or after the given cycle.
+    AtCycle(u64),
+}
+
+/// An [`AddressBus`] wrapper that raises bus errors according to a schedule.
+///
+/// Each fault fires once and is then removed from the schedule. The injector
+/// has no clock of its own; for [`Fault::AtCycle`] the host reports elapsed
+/// time through [`advance`](Self::advance) or [`set_cycle`](Self::set_cycle),
+/// typically between calls to `execute`.
+///
+/// An access fails at most once. When several faults are due on the same
+/// access, the one scheduled first fires and the others are deferred to the
+/// next access they apply to: the next one touching their address, or any
+/// next access for [`Fault::AtCycle`].
+pub struct FaultInjector<M: AddressBus> {
+    /// The wrapped memory.
+    pub mem: M,
+    faults: Vec<(Fault, Cell<u32>, Cell<bool>)>,
+    cycle: u64,
+    raised: Cell<u32>,
+}
+
+impl<M: AddressBus> FaultInjector<M> {
+    /// Wraps `mem` with an empty fault schedule.
+    pub fn new(mem: M) -> FaultInjector<M> {
+        FaultInjector { mem, faults: Vec::new(), cycle: 0, raised: Cell::new(0) }
+    }
+
+    /// Adds a fault to the schedule.
+    pub fn schedule(&mut self, fault: Fault) {
+        self.faults.push((fault, Cell::new(0), Cell::new(false)));
+    }
+
+    /// Schedules a bus error on the `nth` (1-based) access touching `address`.
+    pub fn fail_nth_access(&mut self, address: u32, nth: u32) {
+        self.schedule(Fault::NthAccess { address, nth });
+    }
+
+    /// Schedules a bus error on the first access at or after `cycle`.
+    pub fn fail_at_cycle(&mut self, cycle: u64) {
+        self.schedule(Fault::AtCycle(cycle));
+    }
+
+    /// Removes all pending faults.
+    pub fn clear(&mut self) {
+        self.faults.clear();
+    }
+
+    /// Returns the number of faults that have not fired yet.
+    pub fn pending(&self) -> usize {
+        self.faults.iter().filter(|(_, _, fired)| !fired.get()).count()
+    }
+
+    /// Returns the number of bus errors raised so far.
+    pub fn raised(&self) -> u32 {
+        self.raised.get()
+    }
+
+    /// Returns the current cycle count as reported by the host.
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    /// Sets the current cycle count.
+    pub fn set_cycle(&mut self, cycle: u64) {
+        self.cycle = cycle;
+    }
+
+    /// Advances the current cycle count.
+    pub fn advance(&mut self, cycles: u64) {
+        self.cycle += cycles;
+    }
+}
+
+impl<M: AddressBus> BusWrapper for FaultInjector<M> {
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.mem
+    }
+
+    fn inner_mut(&mut self) -> &mut M {
+        &mut self.mem
+    }
+
+    fn bus_error(&self, address_space: AddressSpace, address: u32, access_size: u8, is_write: bool) -> bool {
+        let mut fail = false;
+        for (fault, hits, fired) in &self.faults {
+            if fired.get() {
+                continue;
+            }
+            let due = match *fault {
+                Fault::NthAccess { address: target, nth } => {
+                    let touches = target.wrapping_sub(address) < u32::from(access_size);
+                    if touches {
+                        hits.set(hits.get() + 1);
+                    }
+                    // >= so that a fault deferred by another one fires later
+                    touches && hits.get() >= nth
+                }
+                Fault::AtCycle(cycle) => self.cycle >= cycle,
+            };
+            if due && !fail {
+                fired.set(true);
+                fail = true;
+            }
+        }
+        if fail {
+            self.raised.set(self.raised.get() + 1);
+            return true;
+        }
+        self.mem.bus_error(address_space, address, access_size, is_write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FaultInjector, Fault};
+    use crate::ram::{AddressBus, PagedMem, SUPERVISOR_DATA};
+
+    #[test]
+    fn nth_access_to_address_fails_once() {
+        let mut mem = FaultInjector::new(PagedMem::new(0));
+        mem.fail_nth_access(0x2002, 2);
+        assert!(!mem.bus_error(SUPERVISOR_DATA, 0x2002, 2, false));
+        // does not touch 0x2002
+        assert!(!mem.bus_error(SUPERVISOR_DATA, 0x2004, 2, false));
+        // a long access at 0x2000 covers 0x2002
+        assert!(mem.bus_error(SUPERVISOR_DATA, 0x2000, 4, true));
+        assert!(!mem.bus_error(SUPERVISOR_DATA, 0x2002, 2, false));
+        assert_eq!(1, mem.raised());
+        assert_eq!(0, mem.pending());
+    }
+
+    #[test]
+    fn faults_due_on_the_same_access_fire_one_access_apart() {
+        let mut mem = FaultInjector::new(PagedMem::new(0));
+        mem.fail_nth_access(0x2000, 1);
+        mem.fail_nth_access(0x2000, 1);
+        assert!(mem.bus_error(SUPERVISOR_DATA, 0x2000, 2, false));
+        assert_eq!(1, mem.pending());
+        // the deferred fault waits for the next access touching its address
+        assert!(!mem.bus_error(SUPERVISOR_DATA, 0x3000, 2, false));
+        assert!(mem.bus_error(SUPERVISOR_DATA, 0x2000, 1, true));
+        assert!(!mem.bus_error(SUPERVISOR_DATA, 0x2000, 2, false));
+        assert_eq!((2, 0), (mem.raised(), mem.pending()));
+    }
+
+    #[test]
+    fn cycle_fault_fires_once_due() {
+        let mut mem = FaultInjector::new(PagedMem::new(0));
+        mem.schedule(Fault::AtCycle(100));
+        assert!(!mem.bus_error(SUPERVISOR_DATA, 0x2000, 1, false));
+        mem.advance(99);
+        assert!(!mem.bus_error(SUPERVISOR_DATA, 0x2000, 1, false));
+        mem.advance(1);
+        assert!(mem.bus_error(SUPERVISOR_DATA, 0x3000, 2, false));
+        assert!(!mem.bus_error(SUPERVISOR_DATA, 0x3000, 2, false));
+    }
+}
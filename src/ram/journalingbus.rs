@@ -0,0 +1,147 @@
+//! Undoable writes for what-if execution.
+//!
+//! [`JournalingBus`] wraps any [`AddressBus`] and records every write with
+//! the value it replaced. Rolling back the journal restores memory to its
+//! state when the journal was last committed, without copying the memory
+//! itself, so code can be executed speculatively and undone.
+//!
+//! ```rust
+//! use r68k::cpu::ConfiguredCore;
+//! use r68k::interrupts::AutoInterruptController;
+//! use r68k::ram::{AddressBus, PagedMem, SUPERVISOR_DATA};
+//! use r68k::ram::journalingbus::JournalingBus;
+//!
+//! let mut cpu = ConfiguredCore::new_with(0x1000, AutoInterruptController::new(), JournalingBus::new(PagedMem::new(0)));
+//! // MOVE.L D0, (A0)
+//! cpu.mem.write_word(SUPERVISOR_DATA, 0x1000, 0x2080);
+//! cpu.mem.commit();
+//! cpu.dar[0] = 0x1234_5678;
+//! cpu.dar[8] = 0x2000;
+//! cpu.execute1();
+//! assert_eq!(0x1234_5678, cpu.mem.read_long(SUPERVISOR_DATA, 0x2000));
+//! cpu.mem.rollback();
+//! assert_eq!(0, cpu.mem.read_long(SUPERVISOR_DATA, 0x2000));
+//! ```
+
+use super::{AddressSpace, AddressBus, BusWrapper};
+
+/// A write recorded by a [`JournalingBus`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// Address space of the write.
+    pub address_space: AddressSpace,
+    /// Address of the write.
+    pub address: u32,
+    /// Size of the write in bytes, 1, 2 or 4.
+    pub size: u8,
+    /// The value read from the address just before the write.
+    pub old_value: u32,
+    /// The value written.
+    pub new_value: u32,
+}
+
+/// An [`AddressBus`] wrapper that journals writes so they can be undone.
+///
+/// Before each write the current value at the address is read from the
+/// wrapped bus, so memory-mapped registers with read side effects should
+/// not be journaled. Writes made directly to [`mem`](Self::mem) bypass the
+/// journal.
+pub struct JournalingBus<M: AddressBus> {
+    /// The wrapped memory.
+    pub mem: M,
+    journal: Vec<JournalEntry>,
+}
+
+impl<M: AddressBus> JournalingBus<M> {
+    /// Wraps `mem` with an empty journal.
+    pub fn new(mem: M) -> JournalingBus<M> {
+        JournalingBus { mem, journal: Vec::new() }
+    }
+
+    /// Returns the writes recorded since the last commit or rollback, oldest first.
+    pub fn journal(&self) -> &[JournalEntry] {
+        &self.journal
+    }
+
+    /// Undoes the recorded writes, newest first, and empties the journal.
+    pub fn rollback(&mut self) {
+        while let Some(entry) = self.journal.pop() {
+            match entry.size {
+                1 => self.mem.write_byte(entry.address_space, entry.address, entry.old_value),
+                2 => self.mem.write_word(entry.address_space, entry.address, entry.old_value),
+                _ => self.mem.write_long(entry.address_space, entry.address, entry.old_value),
+            }
+        }
+    }
+
+    /// Keeps the recorded writes and empties the journal.
+    pub fn commit(&mut self) {
+        self.journal.clear();
+    }
+
+    fn record(&mut self, address_space: AddressSpace, address: u32, size: u8, old_value: u32, new_value: u32) {
+        self.journal.push(JournalEntry { address_space, address, size, old_value, new_value });
+    }
+}
+
+impl<M: AddressBus> BusWrapper for JournalingBus<M> {
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.mem
+    }
+
+    fn inner_mut(&mut self) -> &mut M {
+        &mut self.mem
+    }
+
+    fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        let old_value = self.mem.read_byte(address_space, address);
+        self.record(address_space, address, 1, old_value, value & 0xff);
+        self.mem.write_byte(address_space, address, value)
+    }
+
+    fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        let old_value = self.mem.read_word(address_space, address);
+        self.record(address_space, address, 2, old_value, value & 0xffff);
+        self.mem.write_word(address_space, address, value)
+    }
+
+    fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        let old_value = self.mem.read_long(address_space, address);
+        self.record(address_space, address, 4, old_value, value);
+        self.mem.write_long(address_space, address, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JournalEntry, JournalingBus};
+    use crate::ram::{AddressBus, PagedMem, SUPERVISOR_DATA, USER_DATA};
+
+    #[test]
+    fn writes_are_journaled_with_their_old_values() {
+        let mut mem = JournalingBus::new(PagedMem::new(0x0102_0304));
+        mem.write_word(SUPERVISOR_DATA, 0x1000, 0xabcd);
+        mem.write_byte(USER_DATA, 0x1001, 0x1ff);
+        assert_eq!(&[JournalEntry { address_space: SUPERVISOR_DATA, address: 0x1000, size: 2, old_value: 0x0102, new_value: 0xabcd },
+                     JournalEntry { address_space: USER_DATA, address: 0x1001, size: 1, old_value: 0xcd, new_value: 0xff }],
+                   mem.journal());
+    }
+
+    #[test]
+    fn rollback_restores_overlapping_writes_in_reverse_order() {
+        let mut mem = JournalingBus::new(PagedMem::new(0));
+        mem.write_long(SUPERVISOR_DATA, 0x2000, 0x1111_1111);
+        mem.commit();
+        assert!(mem.journal().is_empty());
+
+        mem.write_long(SUPERVISOR_DATA, 0x2000, 0x2222_2222);
+        mem.write_word(SUPERVISOR_DATA, 0x2002, 0x3333);
+        mem.write_byte(SUPERVISOR_DATA, 0x2001, 0x44);
+        assert_eq!(0x2244_3333, mem.read_long(SUPERVISOR_DATA, 0x2000));
+        mem.rollback();
+        assert_eq!(0x1111_1111, mem.read_long(SUPERVISOR_DATA, 0x2000));
+        assert!(mem.journal().is_empty());
+    }
+}
@@ -27,10 +27,55 @@ impl fmt::Debug for Operation {
     }
 }
 
+/// Destination for the operations recorded by a [`LoggingMem`].
+///
+/// [`OpsLogger`] keeps them in memory, [`StdoutLogger`] prints them and
+/// [`NullLogger`] drops them. Any `Fn(Operation)` closure is a sink too,
+/// which routes operations to a host logger or file. Since bus reads
+/// take `&self`, so does `log`; sinks that store operations need interior
+/// mutability, as [`OpsLogger`] does.
+///
+/// # Example
+///
+/// ```rust
+/// use std::cell::Cell;
+/// use r68k::ram::{AddressBus, SUPERVISOR_DATA};
+/// use r68k::ram::loggingmem::{LoggingMem, Operation};
+///
+/// let writes = Cell::new(0);
+/// let mut mem = LoggingMem::new(0, |op| if let Operation::WriteWord(..) = op { writes.set(writes.get() + 1) });
+/// mem.write_word(SUPERVISOR_DATA, 0x100, 0x1234);
+/// mem.read_word(SUPERVISOR_DATA, 0x100);
+/// assert_eq!(1, writes.get());
+/// ```
 pub trait OpsLogging {
     fn log(&self, op: Operation);
 }
 
+impl<F: Fn(Operation)> OpsLogging for F {
+    fn log(&self, op: Operation) {
+        self(op)
+    }
+}
+
+/// Prints each operation to stdout, one per line.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdoutLogger;
+
+impl OpsLogging for StdoutLogger {
+    fn log(&self, op: Operation) {
+        println!("{:?}", op);
+    }
+}
+
+/// Drops all operations, for a [`LoggingMem`] used as plain memory.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NullLogger;
+
+impl OpsLogging for NullLogger {
+    fn log(&self, _op: Operation) {}
+}
+
 #[derive(Default)]
 pub struct OpsLogger {
     log: RefCell<Vec<Operation>>,
@@ -127,12 +172,30 @@ impl<T: OpsLogging> AddressBus for LoggingMem<T> {
         self.write_u8(address.wrapping_add(2), value >>  8);
         self.write_u8(address.wrapping_add(3), value);
     }
+
+    fn is_initialized(&self, address: u32) -> bool {
+        self.mem.is_initialized(address)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{LoggingMem, AddressBus, OpsLogger, Operation};
+    use super::{LoggingMem, AddressBus, OpsLogger, Operation, NullLogger};
     use crate::ram::{SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA, USER_PROGRAM, ADDRBUS_MASK};
+    use std::cell::RefCell;
+
+    #[test]
+    fn operations_go_to_any_sink() {
+        let ops = RefCell::new(Vec::new());
+        let mut mem = LoggingMem::new(0, |op| ops.borrow_mut().push(op));
+        mem.write_byte(SUPERVISOR_DATA, 0x80, 0x12);
+        assert_eq!(0x12, mem.read_byte(USER_DATA, 0x80));
+        assert_eq!(vec![Operation::WriteByte(SUPERVISOR_DATA, 0x80, 0x12), Operation::ReadByte(USER_DATA, 0x80, 0x12)], *ops.borrow());
+
+        let mut mem = LoggingMem::new(0, NullLogger);
+        mem.write_long(SUPERVISOR_DATA, 0x80, 0x1234_5678);
+        assert_eq!(0x1234_5678, mem.read_long(SUPERVISOR_DATA, 0x80));
+    }
 
     #[test]
     fn read_byte_is_logged() {
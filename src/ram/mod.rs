@@ -56,9 +56,14 @@
 //! }
 //! ```
 
+pub mod debugbus;
+pub mod faultinjector;
+pub mod journalingbus;
 pub mod loggingmem;
 pub mod pagedmem;
-pub use self::pagedmem::PagedMem;
+pub mod protectedbus;
+pub mod rombus;
+pub use self::pagedmem::{PagedMem, MemSnapshot};
 
 /// Mask for the 24-bit address bus (16 MB addressable space).
 pub const ADDRBUS_MASK: u32 = 0x00ff_ffff;
@@ -75,6 +80,7 @@ pub const ADDRBUS_MASK: u32 = 0x00ff_ffff;
 /// - [`SUPERVISOR_DATA`]: Supervisor mode, data access (FC=5)
 /// - [`USER_PROGRAM`]: User mode, program access (FC=2)
 /// - [`USER_DATA`]: User mode, data access (FC=1)
+/// - [`CPU_SPACE`]: CPU space, used for interrupt acknowledge (FC=7)
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
 pub struct AddressSpace(Mode, Segment);
 
@@ -86,12 +92,27 @@ impl AddressSpace {
     /// - 2: User program
     /// - 5: Supervisor data
     /// - 6: Supervisor program
+    /// - 7: CPU space
     pub fn fc(self) -> u32 {
         match self {
             USER_DATA => 1,
             USER_PROGRAM => 2,
             SUPERVISOR_DATA => 5,
             SUPERVISOR_PROGRAM => 6,
+            AddressSpace(_, Segment::Cpu) => 7,
+        }
+    }
+
+    /// Returns the address space of a function code, the inverse of
+    /// [`fc`](Self::fc). The reserved codes 0, 3 and 4 have none.
+    pub fn from_fc(fc: u32) -> Option<AddressSpace> {
+        match fc {
+            1 => Some(USER_DATA),
+            2 => Some(USER_PROGRAM),
+            5 => Some(SUPERVISOR_DATA),
+            6 => Some(SUPERVISOR_PROGRAM),
+            7 => Some(CPU_SPACE),
+            _ => None,
         }
     }
 }
@@ -106,7 +127,7 @@ impl fmt::Debug for AddressSpace {
 
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 enum Segment {
-    Program, Data
+    Program, Data, Cpu
 }
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 enum Mode {
@@ -121,6 +142,12 @@ pub const SUPERVISOR_DATA: AddressSpace = AddressSpace(Mode::Supervisor, Segment
 pub const USER_PROGRAM: AddressSpace = AddressSpace(Mode::User, Segment::Program);
 /// User mode, data access. Function code 1.
 pub const USER_DATA: AddressSpace = AddressSpace(Mode::User, Segment::Data);
+/// CPU space, used by the interrupt acknowledge cycle. Function code 7.
+///
+/// Memory is never read or written in this space; it only qualifies the
+/// bus cycles reported through
+/// [`Callbacks::bus_cycle_fc`](crate::cpu::Callbacks::bus_cycle_fc).
+pub const CPU_SPACE: AddressSpace = AddressSpace(Mode::Supervisor, Segment::Cpu);
 
 /// Trait for implementing the 68000's memory interface.
 ///
@@ -217,5 +244,219 @@ pub trait AddressBus {
     fn wait_cycles(&self, _address: u32, _access_size: u8, _is_write: bool) -> i32 {
         0
     }
+
+    /// Returns true if an access should be terminated with a bus error.
+    ///
+    /// Called before each operand access made by an instruction. Returning
+    /// true aborts the access and raises a Bus Error exception (vector 2)
+    /// instead. Instruction prefetch, stack pushes and pops, and exception
+    /// vector fetches are not checked.
+    ///
+    /// Arguments are the same as for [`wait_cycles`](Self::wait_cycles).
+    /// Default implementation never signals a bus error.
+    fn bus_error(&self, _address_space: AddressSpace, _address: u32, _access_size: u8, _is_write: bool) -> bool {
+        false
+    }
+
+    /// Responds to the interrupt acknowledge (IACK) cycle.
+    ///
+    /// When the CPU takes an interrupt it reads the vector number in
+    /// [`CPU_SPACE`] (FC=7), with `level` (1-7) on address lines A1-A3.
+    /// Return `Some(vector)` to put a vector number on the data bus, as a
+    /// vectored interrupt device does. Return `None` when no device drives
+    /// the bus, and the vector comes from the CPU's
+    /// [`InterruptController`](crate::interrupts::InterruptController) as
+    /// usual, e.g. an autovector for devices asserting VPA.
+    ///
+    /// The interrupt controller is acknowledged either way, so that it
+    /// clears the request. Default implementation returns `None`.
+    fn interrupt_acknowledge(&mut self, _level: u8) -> Option<u8> {
+        None
+    }
+
+    /// Returns false if `address` has never been written.
+    ///
+    /// Used to detect execution of uninitialized memory, see
+    /// [`enable_uninitialized_execution_check`](crate::cpu::ConfiguredCore::enable_uninitialized_execution_check).
+    /// Default implementation considers all memory initialized.
+    fn is_initialized(&self, _address: u32) -> bool {
+        true
+    }
+
+    /// Returns a checksum of the `len` bytes starting at `start`.
+    ///
+    /// The checksum is the 64-bit FNV-1a hash of the bytes in address
+    /// order, as returned by [`read_byte`](Self::read_byte): starting from
+    /// 0xcbf2_9ce4_8422_2325, each byte is XORed into the hash, which is
+    /// then multiplied by 0x0000_0100_0000_01b3, wrapping. It does not
+    /// depend on the host platform, so it can be stored to validate save
+    /// states, or compared between emulators. An empty range hashes to the
+    /// starting value.
+    fn checksum_region(&self, address_space: AddressSpace, start: u32, len: u32) -> u64 {
+        (0..len).fold(0xcbf2_9ce4_8422_2325, |hash, offset| {
+            let byte = self.read_byte(address_space, start.wrapping_add(offset)) as u8;
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+    }
+}
+
+/// An [`AddressBus`] that wraps another bus and adds behaviour to it.
+///
+/// Every method defaults to calling the same method of the wrapped bus, and
+/// [`AddressBus`] is implemented for all `BusWrapper`s through these, so a
+/// wrapper only implements the methods it changes. Methods of [`AddressBus`]
+/// that are computed from others, like
+/// [`checksum_region`](AddressBus::checksum_region), are not forwarded; they
+/// go through the wrapper's own reads.
+///
+/// The methods share their names with those of [`AddressBus`], so calls on
+/// a wrapper are ambiguous where both traits are in scope. Implement the
+/// trait by path, as below, to keep [`AddressBus`] the only one in scope.
+///
+/// ```rust
+/// use r68k::ram::{AddressBus, AddressSpace, PagedMem, SUPERVISOR_DATA};
+///
+/// /// Drops all writes to the first page.
+/// struct ZeroPageGuard(PagedMem);
+///
+/// impl r68k::ram::BusWrapper for ZeroPageGuard {
+///     type Inner = PagedMem;
+///     fn inner(&self) -> &PagedMem {
+///         &self.0
+///     }
+///     fn inner_mut(&mut self) -> &mut PagedMem {
+///         &mut self.0
+///     }
+///     fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+///         if address >= 0x100 {
+///             self.0.write_byte(address_space, address, value)
+///         }
+///     }
+/// }
+///
+/// let mut mem = ZeroPageGuard(PagedMem::new(0));
+/// mem.write_byte(SUPERVISOR_DATA, 0x80, 0x12);
+/// mem.write_byte(SUPERVISOR_DATA, 0x180, 0x34);
+/// assert_eq!(0, mem.read_byte(SUPERVISOR_DATA, 0x80));
+/// assert_eq!(0x34, mem.read_byte(SUPERVISOR_DATA, 0x180));
+/// ```
+pub trait BusWrapper {
+    /// The wrapped bus.
+    type Inner: AddressBus;
+
+    /// Returns the wrapped bus.
+    fn inner(&self) -> &Self::Inner;
+
+    /// Returns the wrapped bus for writing.
+    fn inner_mut(&mut self) -> &mut Self::Inner;
+
+    /// See [`AddressBus::copy_from`].
+    fn copy_from(&mut self, other: &Self) where Self: Sized {
+        self.inner_mut().copy_from(other.inner())
+    }
+
+    /// See [`AddressBus::read_byte`].
+    fn read_byte(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.inner().read_byte(address_space, address)
+    }
+
+    /// See [`AddressBus::read_word`].
+    fn read_word(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.inner().read_word(address_space, address)
+    }
+
+    /// See [`AddressBus::read_long`].
+    fn read_long(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.inner().read_long(address_space, address)
+    }
+
+    /// See [`AddressBus::write_byte`].
+    fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.inner_mut().write_byte(address_space, address, value)
+    }
+
+    /// See [`AddressBus::write_word`].
+    fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.inner_mut().write_word(address_space, address, value)
+    }
+
+    /// See [`AddressBus::write_long`].
+    fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.inner_mut().write_long(address_space, address, value)
+    }
+
+    /// See [`AddressBus::reset_instruction`].
+    fn reset_instruction(&mut self) {
+        self.inner_mut().reset_instruction()
+    }
+
+    /// See [`AddressBus::wait_cycles`].
+    fn wait_cycles(&self, address: u32, access_size: u8, is_write: bool) -> i32 {
+        self.inner().wait_cycles(address, access_size, is_write)
+    }
+
+    /// See [`AddressBus::bus_error`].
+    fn bus_error(&self, address_space: AddressSpace, address: u32, access_size: u8, is_write: bool) -> bool {
+        self.inner().bus_error(address_space, address, access_size, is_write)
+    }
+
+    /// See [`AddressBus::interrupt_acknowledge`].
+    fn interrupt_acknowledge(&mut self, level: u8) -> Option<u8> {
+        self.inner_mut().interrupt_acknowledge(level)
+    }
+
+    /// See [`AddressBus::is_initialized`].
+    fn is_initialized(&self, address: u32) -> bool {
+        self.inner().is_initialized(address)
+    }
 }
 
+impl<W: BusWrapper> AddressBus for W {
+    fn copy_from(&mut self, other: &Self) {
+        BusWrapper::copy_from(self, other)
+    }
+
+    fn read_byte(&self, address_space: AddressSpace, address: u32) -> u32 {
+        BusWrapper::read_byte(self, address_space, address)
+    }
+
+    fn read_word(&self, address_space: AddressSpace, address: u32) -> u32 {
+        BusWrapper::read_word(self, address_space, address)
+    }
+
+    fn read_long(&self, address_space: AddressSpace, address: u32) -> u32 {
+        BusWrapper::read_long(self, address_space, address)
+    }
+
+    fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        BusWrapper::write_byte(self, address_space, address, value)
+    }
+
+    fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        BusWrapper::write_word(self, address_space, address, value)
+    }
+
+    fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        BusWrapper::write_long(self, address_space, address, value)
+    }
+
+    fn reset_instruction(&mut self) {
+        BusWrapper::reset_instruction(self)
+    }
+
+    fn wait_cycles(&self, address: u32, access_size: u8, is_write: bool) -> i32 {
+        BusWrapper::wait_cycles(self, address, access_size, is_write)
+    }
+
+    fn bus_error(&self, address_space: AddressSpace, address: u32, access_size: u8, is_write: bool) -> bool {
+        BusWrapper::bus_error(self, address_space, address, access_size, is_write)
+    }
+
+    fn interrupt_acknowledge(&mut self, level: u8) -> Option<u8> {
+        BusWrapper::interrupt_acknowledge(self, level)
+    }
+
+    fn is_initialized(&self, address: u32) -> bool {
+        BusWrapper::is_initialized(self, address)
+    }
+}
@@ -4,6 +4,7 @@
 //! only allocates pages when written to with non-default values.
 
 use std::collections::HashMap;
+use std::ops::Range;
 use super::{AddressSpace, AddressBus, ADDRBUS_MASK};
 
 const PAGE_SIZE: u32 = 16; // 16 bytes page size
@@ -22,6 +23,7 @@ type Page = Vec<u8>;
 ///
 /// Unwritten memory returns values from a 4-byte initializer pattern that repeats
 /// across the address space. This is useful for detecting uninitialized memory access.
+/// Regions can have a fill byte of their own, see [`set_fill_pattern`](Self::set_fill_pattern).
 ///
 /// # Example
 ///
@@ -41,10 +43,12 @@ type Page = Vec<u8>;
 /// // Read it back
 /// assert_eq!(mem.read_long(SUPERVISOR_DATA, 0x1000), 0x12345678);
 /// ```
+#[derive(Clone)]
 pub struct PagedMem {
     pages: HashMap<u32, Page>,
     /// The 4-byte pattern used for uninitialized memory.
     pub initializer: u32,
+    fill_patterns: Vec<(Range<u32>, u8)>,
 }
 
 impl PagedMem {
@@ -71,12 +75,16 @@ impl PagedMem {
         let mut page = Vec::with_capacity(PAGE_SIZE as usize);
         // initialize page
         for offset in 0..PAGE_SIZE {
-            page.push(self.read_initializer(offset));
+            page.push(self.read_initializer(pageno + offset));
         }
         self.pages.insert(pageno, page);
     }
     // read uninitialized bytes from initializer instead
     fn read_initializer(&self, address: u32) -> u8 {
+        let masked = address & ADDRBUS_MASK;
+        if let Some(&(_, pattern)) = self.fill_patterns.iter().rev().find(|(range, _)| range.contains(&masked)) {
+            return pattern;
+        }
         let shift = match address % 4 {
             0 => 24,
             1 => 16,
@@ -116,6 +124,71 @@ impl PagedMem {
         keys.sort();
         DiffIter { pages: &self.pages, keys, offset: 0 }
     }
+
+    /// Makes untouched bytes in `range` read as `pattern` instead of the
+    /// initializer pattern.
+    ///
+    /// Real systems often have different contents in different regions
+    /// before they are written, e.g. 0xFF in erased flash and 0x00 in
+    /// cleared RAM. Bytes already written keep their value. Where ranges
+    /// overlap, the one set last wins. The fill patterns are configuration
+    /// and are kept by [`clear`](Self::clear) and [`restore`](Self::restore).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use r68k::ram::{PagedMem, AddressBus, SUPERVISOR_DATA};
+    ///
+    /// let mut mem = PagedMem::new(0xDEADBEEF);
+    /// mem.set_fill_pattern(0x10_0000..0x20_0000, 0xFF);
+    /// assert_eq!(0xFFFF_FFFF, mem.read_long(SUPERVISOR_DATA, 0x10_0000));
+    /// assert_eq!(0xFFFF_DEAD, mem.read_long(SUPERVISOR_DATA, 0x1F_FFFE));
+    /// ```
+    pub fn set_fill_pattern(&mut self, range: Range<u32>, pattern: u8) {
+        self.fill_patterns.push((range, pattern));
+    }
+
+    /// Drops all written pages, returning every address to the initializer pattern.
+    pub fn clear(&mut self) {
+        self.pages.clear();
+    }
+
+    /// Captures the allocated pages in a compact [`MemSnapshot`].
+    ///
+    /// Only pages that have been written with non-default values are stored,
+    /// so the snapshot size is proportional to the memory actually in use.
+    pub fn snapshot(&self) -> MemSnapshot {
+        let mut pages: Vec<(u32, Vec<u8>)> = self.pages.iter()
+            .map(|(&base, page)| (base, page.clone()))
+            .collect();
+        pages.sort_by_key(|&(base, _)| base);
+        MemSnapshot { initializer: self.initializer, pages }
+    }
+
+    /// Restores memory to the state captured by [`snapshot`](Self::snapshot).
+    ///
+    /// All current contents are discarded, including pages written after
+    /// the snapshot was taken.
+    pub fn restore(&mut self, snapshot: &MemSnapshot) {
+        self.initializer = snapshot.initializer;
+        self.pages.clear();
+        for (base, page) in &snapshot.pages {
+            assert_eq!(PAGE_SIZE as usize, page.len(), "snapshot page at {base:06x} has the wrong size");
+            self.pages.insert(base & PAGE_MASK, page.clone());
+        }
+    }
+}
+
+/// A compact copy of the contents of a [`PagedMem`].
+///
+/// Holds the initializer pattern and the allocated pages, sorted by base
+/// address. Each page is 16 bytes long and starts at a 16-byte aligned address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemSnapshot {
+    /// The 4-byte pattern used for uninitialized memory.
+    pub initializer: u32,
+    /// Allocated pages as (base address, contents) pairs.
+    pub pages: Vec<(u32, Vec<u8>)>,
 }
 
 impl PagedMem {
@@ -140,7 +213,7 @@ impl PagedMem {
     /// let debug_mem = PagedMem::new(0xDEADBEEF);
     /// ```
     pub fn new(initializer: u32) -> PagedMem {
-        PagedMem { pages: HashMap::new(), initializer }
+        PagedMem { pages: HashMap::new(), initializer, fill_patterns: Vec::new() }
     }
 }
 
@@ -202,6 +275,12 @@ impl AddressBus for PagedMem {
         self.write_u8(address.wrapping_add(2), value >>  8);
         self.write_u8(address.wrapping_add(3), value);
     }
+
+    // tracked per page; writing only initializer values allocates no page,
+    // so such a page still reads as uninitialized
+    fn is_initialized(&self, address: u32) -> bool {
+        self.pages.contains_key(&(address & PAGE_MASK))
+    }
 }
 
 #[cfg(test)]
@@ -235,6 +314,32 @@ mod tests {
         assert_eq!(0x01020304, mem.read_long(SUPERVISOR_DATA, 4*255));
     }
 
+    #[test]
+    fn written_pages_are_initialized() {
+        let mut mem = PagedMem::new(0x01020304);
+        assert!(!mem.is_initialized(128));
+        // writing the initializer value allocates nothing
+        mem.write_byte(SUPERVISOR_DATA, 128, 0x01);
+        assert!(!mem.is_initialized(128));
+        mem.write_byte(SUPERVISOR_DATA, 130, 0x55);
+        assert!(mem.is_initialized(128));
+        assert!(mem.is_initialized(128 + PAGE_SIZE - 1));
+        assert!(mem.is_initialized(0xff00_0080));
+        assert!(!mem.is_initialized(128 + PAGE_SIZE));
+    }
+
+    #[test]
+    fn checksum_is_fnv1a_of_the_region() {
+        let mut mem = PagedMem::new(0);
+        for (offset, byte) in b"foobar".iter().enumerate() {
+            mem.write_byte(SUPERVISOR_DATA, 0x1000 + offset as u32, u32::from(*byte));
+        }
+        assert_eq!(0xcbf2_9ce4_8422_2325, mem.checksum_region(SUPERVISOR_DATA, 0x1000, 0));
+        assert_eq!(0x8594_4171_f739_67e8, mem.checksum_region(SUPERVISOR_DATA, 0x1000, 6));
+        mem.write_byte(SUPERVISOR_DATA, 0x1005, 0);
+        assert_ne!(0x8594_4171_f739_67e8, mem.checksum_region(SUPERVISOR_DATA, 0x1000, 6));
+    }
+
     #[test]
     fn read_your_u32_writes() {
         let mut mem = PagedMem::new(0x01020304);
@@ -367,6 +472,53 @@ mod tests {
         assert_eq!(PAGE_SIZE as usize * mem.allocated_pages(), mem.diffs().count());
     }
 
+    #[test]
+    fn snapshot_restores_scattered_writes()
+    {
+        let mut mem = PagedMem::new(0xDEADBEEF);
+        mem.write_u8(0x10, 0x01);
+        mem.write_u8(0x8002, 0x02);
+        mem.write_u8(0xFF_FFFF, 0x03);
+
+        let snapshot = mem.snapshot();
+        assert_eq!(3, snapshot.pages.len());
+        assert_eq!(vec![0x10, 0x8000, 0xFF_FFF0], snapshot.pages.iter().map(|&(base, _)| base).collect::<Vec<u32>>());
+
+        mem.clear();
+        mem.write_u8(0x4000, 0x04);
+        assert_eq!(0xDE, mem.read_u8(0x10));
+
+        mem.restore(&snapshot);
+        assert_eq!(0x01, mem.read_u8(0x10));
+        assert_eq!(0x02, mem.read_u8(0x8002));
+        assert_eq!(0x03, mem.read_u8(0xFF_FFFF));
+        assert_eq!(0xDE, mem.read_u8(0x4000));
+        assert_eq!(3, mem.allocated_pages());
+        assert_eq!(snapshot, mem.snapshot());
+    }
+
+    #[test]
+    fn fill_pattern_applies_to_untouched_bytes_in_its_region()
+    {
+        let mut mem = PagedMem::new(0x01020304);
+        mem.set_fill_pattern(0x1000..0x1100, 0xFF);
+        mem.set_fill_pattern(0x1080..0x1081, 0x00);
+        assert_eq!(0x0304_FFFF, mem.read_long(SUPERVISOR_DATA, 0x0FFE));
+        assert_eq!(0xFF00_FFFF, mem.read_long(SUPERVISOR_DATA, 0x107F));
+        assert_eq!(0xFFFF_0102, mem.read_long(SUPERVISOR_DATA, 0x10FE));
+
+        // writing the fill byte needs no page, and a new page keeps the
+        // region's pattern in its untouched bytes
+        mem.write_byte(SUPERVISOR_DATA, 0x1010, 0xFF);
+        assert_eq!(0, mem.allocated_pages());
+        mem.write_byte(SUPERVISOR_DATA, 0x1011, 0x42);
+        assert_eq!(1, mem.allocated_pages());
+        assert_eq!(0xFF42_FFFF, mem.read_long(SUPERVISOR_DATA, 0x1010));
+
+        mem.clear();
+        assert_eq!(0xFFFF_FFFF, mem.read_long(SUPERVISOR_DATA, 0x1010));
+    }
+
     #[test]
     fn cross_address_bus_boundary_byte_access() {
         let mut mem = PagedMem::new(0x01020304);
@@ -0,0 +1,170 @@
+//! Memory protection.
+//!
+//! [`ProtectedBus`] wraps any [`AddressBus`] and gives address ranges
+//! [`Permissions`], the coarse protection that glue logic on 68000 boards
+//! provided. Accesses that violate them are terminated with a bus error,
+//! which helps catching operating system bugs that touch forbidden memory.
+//!
+//! ```rust
+//! use r68k::cpu::ConfiguredCore;
+//! use r68k::interrupts::AutoInterruptController;
+//! use r68k::ram::PagedMem;
+//! use r68k::ram::protectedbus::{Permissions, ProtectedBus};
+//!
+//! let mut mem = ProtectedBus::new(PagedMem::new(0));
+//! // system variables, off limits to user programs
+//! mem.protect(0x0000..0x0800, Permissions::READ_WRITE.supervisor_only());
+//! // a memory mapped register that cannot be read back
+//! mem.protect(0xff_8000..0xff_8002, Permissions::WRITE_ONLY);
+//! let mut cpu = ConfiguredCore::new_with(0x1000, AutoInterruptController::new(), mem);
+//! ```
+
+use std::ops::Range;
+use super::{AddressSpace, AddressBus, BusWrapper, ADDRBUS_MASK};
+
+/// The accesses allowed to a region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Permissions {
+    /// Reads are allowed
+    pub read: bool,
+    /// Writes are allowed
+    pub write: bool,
+    /// Only accesses in supervisor address spaces are allowed
+    pub supervisor_only: bool,
+}
+
+impl Permissions {
+    /// Reads and writes are allowed.
+    pub const READ_WRITE: Permissions = Permissions { read: true, write: true, supervisor_only: false };
+    /// Reads are allowed, writes fault.
+    pub const READ_ONLY: Permissions = Permissions { read: true, write: false, supervisor_only: false };
+    /// Writes are allowed, reads fault.
+    pub const WRITE_ONLY: Permissions = Permissions { read: false, write: true, supervisor_only: false };
+    /// All accesses fault.
+    pub const NO_ACCESS: Permissions = Permissions { read: false, write: false, supervisor_only: false };
+
+    /// Returns these permissions, restricted to supervisor accesses.
+    pub fn supervisor_only(self) -> Permissions {
+        Permissions { supervisor_only: true, ..self }
+    }
+
+    /// Returns true if an access in `address_space` is allowed.
+    pub fn allows(&self, address_space: AddressSpace, is_write: bool) -> bool {
+        let supervisor = address_space.fc() & 4 != 0;
+        (supervisor || !self.supervisor_only) && if is_write { self.write } else { self.read }
+    }
+}
+
+/// An [`AddressBus`] wrapper that enforces per-region [`Permissions`].
+///
+/// Ranges are on the 24-bit address bus, and addresses outside of all
+/// regions are unrestricted. Where regions overlap, the one added last
+/// applies. Violations are signalled through [`AddressBus::bus_error`], so,
+/// as for any bus error, only operand accesses of instructions fault;
+/// instruction prefetch, stack pushes and pops and vector fetches go
+/// through to the wrapped memory.
+pub struct ProtectedBus<M: AddressBus> {
+    /// The wrapped memory.
+    pub mem: M,
+    regions: Vec<(Range<u32>, Permissions)>,
+}
+
+impl<M: AddressBus> ProtectedBus<M> {
+    /// Wraps `mem` without any restrictions.
+    pub fn new(mem: M) -> ProtectedBus<M> {
+        ProtectedBus { mem, regions: Vec::new() }
+    }
+
+    /// Gives `range` the `permissions`.
+    pub fn protect(&mut self, range: Range<u32>, permissions: Permissions) {
+        self.regions.push((range, permissions));
+    }
+
+    /// Removes all regions.
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Returns the permissions of `address`, if it is in a region.
+    pub fn permissions(&self, address: u32) -> Option<Permissions> {
+        let address = address & ADDRBUS_MASK;
+        self.regions.iter().rev().find(|(range, _)| range.contains(&address)).map(|&(_, permissions)| permissions)
+    }
+
+    /// Returns true if an access of `size` bytes at `address` violates the
+    /// permissions of any byte it touches.
+    pub fn violates(&self, address_space: AddressSpace, address: u32, size: u8, is_write: bool) -> bool {
+        (0..u32::from(size)).any(|offset| {
+            self.permissions(address.wrapping_add(offset))
+                .is_some_and(|permissions| !permissions.allows(address_space, is_write))
+        })
+    }
+}
+
+impl<M: AddressBus> BusWrapper for ProtectedBus<M> {
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.mem
+    }
+
+    fn inner_mut(&mut self) -> &mut M {
+        &mut self.mem
+    }
+
+    fn bus_error(&self, address_space: AddressSpace, address: u32, access_size: u8, is_write: bool) -> bool {
+        self.violates(address_space, address, access_size, is_write) ||
+            self.mem.bus_error(address_space, address, access_size, is_write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Permissions, ProtectedBus};
+    use crate::cpu::{ConfiguredCore, Exception, ProcessingState};
+    use crate::interrupts::AutoInterruptController;
+    use crate::ram::{AddressBus, PagedMem, SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA};
+
+    #[test]
+    fn permissions_are_checked_per_byte_and_mode() {
+        let mut mem = ProtectedBus::new(PagedMem::new(0));
+        mem.protect(0x1000..0x2000, Permissions::READ_ONLY);
+        mem.protect(0x1800..0x1802, Permissions::NO_ACCESS);
+        mem.protect(0x3000..0x3004, Permissions::WRITE_ONLY.supervisor_only());
+        assert!(!mem.violates(USER_DATA, 0x1000, 4, false));
+        assert!(mem.violates(USER_DATA, 0x1000, 4, true));
+        assert!(mem.violates(SUPERVISOR_DATA, 0x17fe, 4, false));
+        assert!(!mem.violates(SUPERVISOR_DATA, 0x1802, 2, false));
+        assert!(mem.violates(USER_DATA, 0xff00_0fff, 2, true));
+        assert!(!mem.violates(SUPERVISOR_DATA, 0x3000, 4, true));
+        assert!(mem.violates(SUPERVISOR_DATA, 0x3000, 4, false));
+        assert!(mem.violates(USER_DATA, 0x3002, 1, true));
+        assert!(!mem.violates(USER_DATA, 0x3004, 1, false));
+        assert_eq!(None, mem.permissions(0x2000));
+    }
+
+    #[test]
+    fn user_access_to_supervisor_region_raises_bus_error() {
+        let mut mem = ProtectedBus::new(PagedMem::new(0));
+        mem.protect(0x8000..0x9000, Permissions::READ_WRITE.supervisor_only());
+        // MOVE.W (A0), D0 twice
+        mem.write_word(SUPERVISOR_PROGRAM, 0x1000, 0x3010);
+        mem.write_word(SUPERVISOR_PROGRAM, 0x1002, 0x3010);
+        mem.write_word(SUPERVISOR_DATA, 0x8000, 0x1234);
+        mem.write_long(SUPERVISOR_DATA, 2 * 4, 0x2000);
+        let mut cpu = ConfiguredCore::new_with(0x1000, AutoInterruptController::new(), mem);
+        cpu.processing_state = ProcessingState::Normal;
+        cpu.dar[8] = 0x8000;
+        cpu.dar[15] = 0x4000;
+        cpu.execute1();
+        assert_eq!(0x1234, cpu.dar[0]);
+
+        cpu.dar[0] = 0;
+        cpu.inactive_usp = 0x5000;
+        cpu.sr_to_flags(0x0000);
+        cpu.execute1();
+        assert_eq!(0x2000, cpu.pc);
+        assert_eq!(0, cpu.dar[0]);
+        assert!(matches!(cpu.last_exception(), Some(Exception::BusError { address: 0x8000, .. })));
+    }
+}
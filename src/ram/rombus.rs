@@ -0,0 +1,163 @@
+//! Read-only regions.
+//!
+//! [`RomBus`] wraps any [`AddressBus`] and protects address ranges from
+//! writes, the way ROM ignores them. Writes to a protected range are
+//! dropped, or, with [`set_fault_on_write`](RomBus::set_fault_on_write),
+//! terminated with a bus error, as on systems whose ROM decoding asserts
+//! BERR on a write cycle.
+//!
+//! ```rust
+//! use r68k::cpu::ConfiguredCore;
+//! use r68k::interrupts::AutoInterruptController;
+//! use r68k::ram::{AddressBus, PagedMem, SUPERVISOR_DATA};
+//! use r68k::ram::rombus::RomBus;
+//!
+//! let mut mem = RomBus::new(PagedMem::new(0));
+//! mem.add_rom(0xfc_0000..0x100_0000);
+//! mem.write_word(SUPERVISOR_DATA, 0xfc_0000, 0x1234);
+//! assert_eq!(0, mem.read_word(SUPERVISOR_DATA, 0xfc_0000));
+//! let mut cpu = ConfiguredCore::new_with(0x1000, AutoInterruptController::new(), mem);
+//! ```
+
+use std::ops::Range;
+use super::{AddressSpace, AddressBus, BusWrapper, ADDRBUS_MASK};
+
+/// An [`AddressBus`] wrapper that makes address ranges read-only.
+///
+/// Ranges are on the 24-bit address bus. Writes made directly to
+/// [`mem`](Self::mem) bypass the protection, which is how ROM contents are
+/// loaded.
+pub struct RomBus<M: AddressBus> {
+    /// The wrapped memory.
+    pub mem: M,
+    regions: Vec<Range<u32>>,
+    fault_on_write: bool,
+}
+
+impl<M: AddressBus> RomBus<M> {
+    /// Wraps `mem` without any read-only regions.
+    pub fn new(mem: M) -> RomBus<M> {
+        RomBus { mem, regions: Vec::new(), fault_on_write: false }
+    }
+
+    /// Makes `range` read-only.
+    pub fn add_rom(&mut self, range: Range<u32>) {
+        self.regions.push(range);
+    }
+
+    /// Chooses whether writes to ROM raise a bus error (true) or are
+    /// silently dropped (false, the default).
+    ///
+    /// The bus error is signalled through [`AddressBus::bus_error`], so,
+    /// as for any bus error, only operand writes of instructions fault.
+    /// Stack pushes during exception processing are still dropped.
+    pub fn set_fault_on_write(&mut self, fault_on_write: bool) {
+        self.fault_on_write = fault_on_write;
+    }
+
+    /// Returns true if writes to ROM raise a bus error.
+    pub fn fault_on_write(&self) -> bool {
+        self.fault_on_write
+    }
+
+    /// Returns true if `address` is in a read-only region.
+    pub fn is_rom(&self, address: u32) -> bool {
+        let address = address & ADDRBUS_MASK;
+        self.regions.iter().any(|range| range.contains(&address))
+    }
+
+    fn touches_rom(&self, address: u32, size: u32) -> bool {
+        (0..size).any(|offset| self.is_rom(address.wrapping_add(offset)))
+    }
+}
+
+impl<M: AddressBus> BusWrapper for RomBus<M> {
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.mem
+    }
+
+    fn inner_mut(&mut self) -> &mut M {
+        &mut self.mem
+    }
+
+    fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        if !self.is_rom(address) {
+            self.mem.write_byte(address_space, address, value)
+        }
+    }
+
+    fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        if !self.touches_rom(address, 2) {
+            self.mem.write_word(address_space, address, value)
+        }
+    }
+
+    fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        // a long write is two word bus cycles, each going to its own device
+        BusWrapper::write_word(self, address_space, address, value >> 16);
+        BusWrapper::write_word(self, address_space, address.wrapping_add(2), value);
+    }
+
+    fn bus_error(&self, address_space: AddressSpace, address: u32, access_size: u8, is_write: bool) -> bool {
+        if is_write && self.fault_on_write && self.touches_rom(address, u32::from(access_size)) {
+            return true;
+        }
+        self.mem.bus_error(address_space, address, access_size, is_write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RomBus;
+    use crate::cpu::{ConfiguredCore, Exception, ProcessingState};
+    use crate::interrupts::AutoInterruptController;
+    use crate::ram::{AddressBus, PagedMem, SUPERVISOR_DATA, SUPERVISOR_PROGRAM};
+
+    fn rom_writing_core(fault_on_write: bool) -> ConfiguredCore<AutoInterruptController, RomBus<PagedMem>> {
+        let mut mem = RomBus::new(PagedMem::new(0));
+        mem.add_rom(0x8000..0x9000);
+        mem.set_fault_on_write(fault_on_write);
+        // MOVE.W D0, (A0)
+        mem.mem.write_word(SUPERVISOR_PROGRAM, 0x1000, 0x3080);
+        mem.mem.write_long(SUPERVISOR_DATA, 2 * 4, 0x2000);
+        mem.mem.write_word(SUPERVISOR_DATA, 0x8ffe, 0xabcd);
+        let mut cpu = ConfiguredCore::new_with(0x1000, AutoInterruptController::new(), mem);
+        cpu.processing_state = ProcessingState::Normal;
+        cpu.dar[0] = 0x1234;
+        cpu.dar[8] = 0x8ffe;
+        cpu.dar[15] = 0x4000;
+        cpu
+    }
+
+    #[test]
+    fn writes_to_rom_are_dropped_per_bus_cycle() {
+        let mut mem = RomBus::new(PagedMem::new(0));
+        mem.add_rom(0x2002..0x2004);
+        assert!(mem.is_rom(0xff00_2003));
+        mem.write_long(SUPERVISOR_DATA, 0x2000, 0x1122_3344);
+        mem.write_byte(SUPERVISOR_DATA, 0x2003, 0x55);
+        mem.write_byte(SUPERVISOR_DATA, 0x2004, 0x66);
+        assert_eq!(0x1122_0000, mem.read_long(SUPERVISOR_DATA, 0x2000));
+        assert_eq!(0x66, mem.read_byte(SUPERVISOR_DATA, 0x2004));
+    }
+
+    #[test]
+    fn rom_writes_are_ignored_by_default() {
+        let mut cpu = rom_writing_core(false);
+        cpu.execute1();
+        assert_eq!(0x1002, cpu.pc);
+        assert_eq!(0xabcd, cpu.mem.read_word(SUPERVISOR_DATA, 0x8ffe));
+        assert!(cpu.last_exception().is_none());
+    }
+
+    #[test]
+    fn rom_writes_raise_bus_error_when_faulting() {
+        let mut cpu = rom_writing_core(true);
+        cpu.execute1();
+        assert_eq!(0x2000, cpu.pc);
+        assert_eq!(0xabcd, cpu.mem.read_word(SUPERVISOR_DATA, 0x8ffe));
+        assert!(matches!(cpu.last_exception(), Some(Exception::BusError { address: 0x8ffe, .. })));
+    }
+}